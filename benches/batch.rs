@@ -0,0 +1,59 @@
+//! Lock acquisitions and time of validating a 64-segment `writev`, segment
+//! by segment and through the batched iovec import
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use core::{alloc::Layout, hint::black_box};
+use std::time::Instant;
+
+use axuspace::{Access, IoVec, UserConstPtr, UserSpaceAccess, check_region};
+use common::HostUspace;
+use memory_addr::VirtAddr;
+
+const SEGMENTS: usize = 64;
+const SEGMENT_LEN: usize = 64;
+const ITERS: u32 = 10_000;
+
+/// Run `f` `ITERS` times, reporting lock acquisitions per run and the mean
+/// time
+fn bench(name: &str, uspace: &HostUspace, mut f: impl FnMut()) {
+    uspace.locks.set(0);
+    f();
+    let locks = uspace.locks.get();
+    let start = Instant::now();
+    for _ in 0..ITERS {
+        f();
+    }
+    let ns = start.elapsed().as_nanos() / u128::from(ITERS);
+    println!("{name:<12} {locks:>4} locks {ns:>8} ns/iter");
+}
+
+fn main() {
+    let uspace = HostUspace::new(2);
+    let iovs: Vec<IoVec> = (0..SEGMENTS)
+        .map(|i| IoVec {
+            iov_base: uspace.addr(4096 + i * SEGMENT_LEN),
+            iov_len: SEGMENT_LEN,
+        })
+        .collect();
+    let table: Vec<u8> = iovs
+        .iter()
+        .flat_map(|iov| [iov.iov_base, iov.iov_len])
+        .flat_map(usize::to_ne_bytes)
+        .collect();
+    uspace.fill(0, &table);
+    let ptr = UserConstPtr::<IoVec>::from(uspace.addr(0));
+
+    bench("per-segment", &uspace, || {
+        let iovs = uspace.read_vec(ptr, SEGMENTS).unwrap();
+        for iov in &iovs {
+            let layout = Layout::from_size_align(iov.iov_len, 1).unwrap();
+            check_region(&uspace, VirtAddr::from(iov.iov_base), layout, Access::READ).unwrap();
+        }
+        black_box(iovs);
+    });
+    bench("batched", &uspace, || {
+        black_box(uspace.import_iovec(ptr, SEGMENTS, Access::READ).unwrap());
+    });
+}
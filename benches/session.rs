@@ -0,0 +1,45 @@
+//! Lock acquisitions and time of the small same-page reads a `stat`-heavy
+//! workload makes, directly and through a validation session
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use core::hint::black_box;
+use std::time::Instant;
+
+use axuspace::{UserConstPtr, UserSpaceAccess};
+use common::HostUspace;
+
+/// Small objects read per run, all in one page
+const READS: usize = 64;
+const ITERS: u32 = 10_000;
+
+/// Run `f` `ITERS` times, reporting lock acquisitions per run and the mean
+/// time
+fn bench(name: &str, uspace: &HostUspace, mut f: impl FnMut()) {
+    uspace.locks.set(0);
+    f();
+    let locks = uspace.locks.get();
+    let start = Instant::now();
+    for _ in 0..ITERS {
+        f();
+    }
+    let ns = start.elapsed().as_nanos() / u128::from(ITERS);
+    println!("{name:<12} {locks:>4} locks {ns:>8} ns/iter");
+}
+
+/// Read `READS` 16-byte objects from the first page of `uspace`
+fn read_all<A: UserSpaceAccess + ?Sized>(uspace: &A, base: usize) {
+    for i in 0..READS {
+        let ptr = UserConstPtr::<[u64; 2]>::from(base + i * 16);
+        black_box(uspace.read(ptr).unwrap());
+    }
+}
+
+fn main() {
+    let uspace = HostUspace::new(1);
+    let base = uspace.addr(0);
+
+    bench("direct", &uspace, || read_all(&uspace, base));
+    bench("session", &uspace, || read_all(&uspace.session(), base));
+}
@@ -0,0 +1,106 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Permissions a user memory access needs
+    ///
+    /// The bits match `page_table_multiarch::MappingFlags`, which converts to
+    /// and from it with the `page-table-multiarch` feature.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Access: usize {
+        /// Readable
+        const READ = 1 << 0;
+        /// Writable
+        const WRITE = 1 << 1;
+        /// Executable
+        const EXECUTE = 1 << 2;
+        /// Accessible from user mode
+        const USER = 1 << 3;
+    }
+}
+
+bitflags! {
+    /// How a large transfer will touch a range passed to
+    /// [`UserSpaceRaw::prefault`](crate::UserSpaceRaw::prefault)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct PrefaultHint: u8 {
+        /// The range is copied front to back, suiting readahead
+        const SEQUENTIAL = 1 << 0;
+        /// The range is written, so copy-on-write pages will be broken
+        const WILL_WRITE = 1 << 1;
+    }
+}
+
+/// Memory type of a user mapping, reported by
+/// [`UserSpaceRaw::memory_type`](crate::UserSpaceRaw::memory_type)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryType {
+    /// Normal cached memory, copied with the usual routines
+    #[default]
+    Normal,
+    /// Uncached or write-combining memory, such as a framebuffer
+    Uncached,
+    /// Device memory, such as a mapped PCI BAR
+    Device,
+}
+
+#[cfg(feature = "page-table-multiarch")]
+impl From<page_table_multiarch::MappingFlags> for MemoryType {
+    fn from(value: page_table_multiarch::MappingFlags) -> Self {
+        use page_table_multiarch::MappingFlags;
+        if value.contains(MappingFlags::DEVICE) {
+            Self::Device
+        } else if value.contains(MappingFlags::UNCACHED) {
+            Self::Uncached
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+#[cfg(feature = "page-table-multiarch")]
+impl From<Access> for page_table_multiarch::MappingFlags {
+    fn from(value: Access) -> Self {
+        Self::from_bits_truncate(value.bits())
+    }
+}
+
+#[cfg(feature = "page-table-multiarch")]
+impl From<page_table_multiarch::MappingFlags> for Access {
+    /// Keep the permission bits, dropping memory type flags
+    fn from(value: page_table_multiarch::MappingFlags) -> Self {
+        Self::from_bits_truncate(value.bits())
+    }
+}
+
+#[cfg(all(test, feature = "page-table-multiarch"))]
+mod tests {
+    use page_table_multiarch::MappingFlags;
+
+    use super::*;
+
+    #[test]
+    fn converts_to_and_from_mapping_flags() {
+        let all = Access::all();
+        assert_eq!(Access::from(MappingFlags::from(all)), all);
+        let mapping = MappingFlags::READ | MappingFlags::USER | MappingFlags::DEVICE;
+        assert_eq!(Access::from(mapping), Access::READ | Access::USER);
+        assert_eq!(
+            MappingFlags::from(Access::WRITE | Access::EXECUTE),
+            MappingFlags::WRITE | MappingFlags::EXECUTE
+        );
+    }
+
+    #[test]
+    fn memory_type_follows_the_strictest_flag() {
+        let normal = MappingFlags::READ | MappingFlags::USER;
+        assert_eq!(MemoryType::from(normal), MemoryType::Normal);
+        assert_eq!(
+            MemoryType::from(normal | MappingFlags::UNCACHED),
+            MemoryType::Uncached
+        );
+        assert_eq!(
+            MemoryType::from(normal | MappingFlags::DEVICE | MappingFlags::UNCACHED),
+            MemoryType::Device
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use memory_addr::{PhysAddr, VirtAddr};
+
+/// Address type user memory can be named by
+///
+/// Implemented by [`VirtAddr`], which the rest of the crate uses, and by
+/// [`PhysAddr`] for hypervisors whose "user" is a guest addressed by
+/// guest-physical addresses. Accesses still dereference the numeric address
+/// as a host pointer, so such backends must identity-map guest RAM, or
+/// expose it at the same numeric addresses through their own type.
+pub trait UspaceAddr: Copy + Ord {
+    /// Build the address from its numeric value
+    fn from_usize(addr: usize) -> Self;
+
+    /// Numeric value of the address
+    fn as_usize(self) -> usize;
+
+    /// Round down to `align`, a power of two
+    ///
+    /// Named apart from `MemoryAddr::align_down` so both traits can be in
+    /// scope together.
+    fn align_down_to(self, align: usize) -> Self {
+        Self::from_usize(self.as_usize() & !(align - 1))
+    }
+
+    /// Add `offset`, or `None` if the address space wraps
+    fn checked_offset(self, offset: usize) -> Option<Self> {
+        self.as_usize().checked_add(offset).map(Self::from_usize)
+    }
+
+    /// Same address as a [`VirtAddr`], as the backend hooks take
+    fn to_virt(self) -> VirtAddr {
+        VirtAddr::from(self.as_usize())
+    }
+}
+
+impl UspaceAddr for VirtAddr {
+    fn from_usize(addr: usize) -> Self {
+        VirtAddr::from(addr)
+    }
+
+    fn as_usize(self) -> usize {
+        self.into()
+    }
+}
+
+impl UspaceAddr for PhysAddr {
+    fn from_usize(addr: usize) -> Self {
+        PhysAddr::from(addr)
+    }
+
+    fn as_usize(self) -> usize {
+        self.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use memory_addr::VirtAddrRange;
+
+    use super::*;
+    use crate::{
+        Access, Error, UserConstPtr, UserResult, UserSpaceAccess, UserSpaceRaw, mock::MockUspace,
+    };
+
+    /// Guest RAM made of the given regions of host memory, named by
+    /// guest-physical addresses
+    struct GuestMap {
+        ram: MockUspace,
+        /// `(offset, len)` into two pages of RAM
+        regions: &'static [(usize, usize)],
+    }
+
+    impl GuestMap {
+        fn new(regions: &'static [(usize, usize)]) -> Self {
+            Self {
+                ram: MockUspace::new(2),
+                regions,
+            }
+        }
+
+        fn gpa(&self, off: usize) -> PhysAddr {
+            PhysAddr::from_usize(self.ram.addr(off).as_usize())
+        }
+    }
+
+    impl UserSpaceRaw for GuestMap {
+        fn check_region_access(&self, range: VirtAddrRange, _flags: Access) -> UserResult<()> {
+            let start = PhysAddr::from_usize(range.start.as_usize());
+            let mut addr = start;
+            let end = start.checked_offset(range.size()).ok_or(Error::EFAULT)?;
+            while addr < end {
+                let (base, len) = self
+                    .regions
+                    .iter()
+                    .map(|&(off, len)| (self.gpa(off), len))
+                    .find(|&(base, len)| base <= addr && addr.as_usize() < base.as_usize() + len)
+                    .ok_or(Error::EFAULT)?;
+                addr = PhysAddr::from_usize(base.as_usize() + len);
+            }
+            Ok(())
+        }
+
+        fn populate_region(&self, _range: VirtAddrRange, _flags: Access) -> UserResult<()> {
+            Ok(())
+        }
+
+        fn user_addr_range(&self) -> VirtAddrRange {
+            self.ram.user_addr_range()
+        }
+    }
+
+    #[test]
+    fn guest_pointer_addresses() {
+        let guest = GuestMap::new(&[(0, 4096)]);
+        let gpa = guest.gpa(16);
+        let ptr = UserConstPtr::<u8>::from(gpa.as_usize());
+        assert_eq!(ptr.address_as::<PhysAddr>(), gpa);
+        assert_eq!(gpa.align_down_to(4096), guest.gpa(0));
+        assert_eq!(gpa.checked_offset(usize::MAX), None);
+        assert_eq!(gpa.to_virt(), guest.ram.addr(16));
+    }
+
+    #[test]
+    #[allow(unused_unsafe)]
+    fn read_str_from_guest_ram() {
+        // Two adjacent regions, the string crossing from one to the other
+        let guest = GuestMap::new(&[(0, 4096), (4096, 4096)]);
+        guest.ram.fill(4090, b"guest-os\0");
+        let ptr = UserConstPtr::<u8>::from(guest.gpa(4090).as_usize());
+        let mut buf = [0; 16];
+        assert_eq!(guest.read_cstr_into(ptr, &mut buf), Ok(8));
+        assert_eq!(&buf[..8], b"guest-os");
+        assert_eq!(unsafe { ptr.get_as_str(&guest) }, Ok("guest-os"));
+        #[cfg(feature = "alloc")]
+        assert_eq!(guest.read_string(ptr).as_deref(), Ok("guest-os"));
+    }
+
+    #[test]
+    fn guest_ram_holes_fault() {
+        let guest = GuestMap::new(&[(0, 4096)]);
+        guest.ram.fill(4090, b"guest-os\0");
+        let ptr = UserConstPtr::<u8>::from(guest.gpa(4090).as_usize());
+        assert_eq!(guest.read_cstr_into(ptr, &mut [0; 16]), Err(Error::EFAULT));
+    }
+}
@@ -0,0 +1,152 @@
+use core::{
+    cell::UnsafeCell,
+    hint,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use memory_addr::VirtAddrRange;
+
+/// Live mutable borrows tracked at once; further ones are not tracked
+const ALIAS_SLOTS: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Borrow {
+    space: usize,
+    range: VirtAddrRange,
+}
+
+/// Table of the live mutable borrows of user memory
+struct AliasTable {
+    lock: AtomicBool,
+    slots: UnsafeCell<[Option<Borrow>; ALIAS_SLOTS]>,
+}
+
+unsafe impl Sync for AliasTable {}
+
+static ALIASES: AliasTable = AliasTable {
+    lock: AtomicBool::new(false),
+    slots: UnsafeCell::new([None; ALIAS_SLOTS]),
+};
+
+impl AliasTable {
+    /// Run `f` on the slots under the lock, which `f` must not panic with
+    fn with<R>(&self, f: impl FnOnce(&mut [Option<Borrow>; ALIAS_SLOTS]) -> R) -> R {
+        while self.lock.swap(true, Ordering::Acquire) {
+            hint::spin_loop();
+        }
+        let ret = f(unsafe { &mut *self.slots.get() });
+        self.lock.store(false, Ordering::Release);
+        ret
+    }
+}
+
+/// Live mutable borrow overlapping `range` of `space`, if any
+fn find_alias(
+    slots: &[Option<Borrow>],
+    space: usize,
+    range: VirtAddrRange,
+) -> Option<VirtAddrRange> {
+    slots
+        .iter()
+        .flatten()
+        .find(|other| other.space == space && other.range.overlaps(range))
+        .map(|other| other.range)
+}
+
+/// Panic for a mutable borrow of `range` overlapping the live one at `other`
+#[track_caller]
+fn aliased(range: VirtAddrRange, other: VirtAddrRange) -> ! {
+    panic!(
+        "mutable borrow of user memory {:?} overlaps a live one at {:?}",
+        range, other
+    );
+}
+
+/// Entry of a lifetime-bound mutable borrow, removed on drop
+#[derive(Debug)]
+pub(crate) struct AliasGuard {
+    slot: Option<usize>,
+}
+
+impl AliasGuard {
+    /// Record a mutable borrow of `range` of `space`, panicking if it
+    /// overlaps a live one
+    #[track_caller]
+    pub(crate) fn new(space: usize, range: VirtAddrRange) -> Self {
+        if range.is_empty() {
+            return Self { slot: None };
+        }
+        let slot = ALIASES.with(|slots| {
+            if let Some(other) = find_alias(slots, space, range) {
+                return Err(other);
+            }
+            let slot = slots.iter().position(Option::is_none);
+            if let Some(slot) = slot {
+                slots[slot] = Some(Borrow { space, range });
+            }
+            Ok(slot)
+        });
+        match slot {
+            Ok(slot) => Self { slot },
+            Err(other) => aliased(range, other),
+        }
+    }
+}
+
+impl Drop for AliasGuard {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            ALIASES.with(|slots| slots[slot] = None);
+        }
+    }
+}
+
+/// Panic if a `'static` mutable borrow of `range` of `space` overlaps a
+/// live lifetime-bound one
+///
+/// The `'static` borrow itself is not recorded: nothing tells when it ends,
+/// so a lifetime-bound borrow or another `'static` one taken after it is
+/// not checked against it.
+#[track_caller]
+pub(crate) fn check_unaliased(space: usize, range: VirtAddrRange) {
+    if range.is_empty() {
+        return;
+    }
+    if let Some(other) = ALIASES.with(|slots| find_alias(slots, space, range)) {
+        aliased(range, other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{UserReadable, mock::MockUspace};
+
+    #[test]
+    #[should_panic(expected = "overlaps a live one")]
+    fn overlapping_mutable_borrows_panic() {
+        let uspace = MockUspace::new(1);
+        let _slice = uspace.ptr::<u8>(0).get_mut_slice(&uspace, 16).unwrap();
+        let _value = uspace.ptr::<u64>(8).get_mut_ref(&uspace).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps a live one")]
+    fn static_borrows_are_checked_against_live_ones() {
+        let uspace = MockUspace::new(1);
+        let _value = uspace.ptr::<u64>(8).get_mut_ref(&uspace).unwrap();
+        #[allow(deprecated, unused_unsafe)]
+        let _ = unsafe { uspace.ptr::<u8>(0).get_as_mut_slice(&uspace, 12) };
+    }
+
+    #[test]
+    fn disjoint_and_ended_borrows_do_not_alias() {
+        let uspace = MockUspace::new(1);
+        let other = MockUspace::new(1);
+        let value = uspace.ptr::<u64>(8).get_mut_ref(&uspace).unwrap();
+        let _next = uspace.ptr::<u64>(16).get_mut_ref(&uspace).unwrap();
+        let _shared = uspace.cptr::<u64>(8).get_ref(&uspace).unwrap();
+        drop(value);
+        let _again = uspace.ptr::<u64>(8).get_mut_ref(&uspace).unwrap();
+        let _other = other.ptr::<u64>(8).get_mut_ref(&other).unwrap();
+    }
+}
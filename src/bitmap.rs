@@ -0,0 +1,203 @@
+use core::ops::Range;
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    Access, Error, UserPtr, UserReadable, UserResult, UserSpaceAccess, try_access_user_range,
+    user_range_of,
+};
+
+/// A bitmap of `bit_len` bits in user memory
+///
+/// Bit `n` is bit `n % 8` of byte `n / 8`, least significant first, which is
+/// the layout of `unsigned long` bitmaps on little-endian targets. Bits past
+/// `bit_len` in the last byte belong to user space and are never modified.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserBitmap {
+    ptr: UserPtr<u8>,
+    bit_len: usize,
+}
+
+/// Mask of the bits `lo..hi` within one byte
+fn byte_mask(lo: usize, hi: usize) -> u8 {
+    ((0xffu16 << lo) & ((1u16 << hi) - 1)) as u8
+}
+
+impl UserBitmap {
+    /// Describe a bitmap of `bit_len` bits starting at `ptr`
+    pub fn new(ptr: UserPtr<u8>, bit_len: usize) -> Self {
+        Self { ptr, bit_len }
+    }
+
+    /// Number of bits
+    pub fn len(&self) -> usize {
+        self.bit_len
+    }
+
+    /// Whether the bitmap has no bits
+    pub fn is_empty(&self) -> bool {
+        self.bit_len == 0
+    }
+
+    /// Number of bytes spanned in user memory
+    pub fn byte_len(&self) -> usize {
+        self.bit_len.div_ceil(8)
+    }
+
+    fn check_bit(&self, bit: usize) -> UserResult<()> {
+        if bit >= self.bit_len {
+            return Err(Error::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// Read bit `bit`, failing with `EINVAL` if it is out of range
+    pub fn get<A: UserSpaceAccess + ?Sized>(&self, uspace: &A, bit: usize) -> UserResult<bool> {
+        self.check_bit(bit)?;
+        let byte = uspace.read(self.ptr.offset(bit / 8))?;
+        Ok(byte & (1 << (bit % 8)) != 0)
+    }
+
+    /// Set bit `bit` to `val`, failing with `EINVAL` if it is out of range
+    ///
+    /// Only the containing byte is read and written back.
+    pub fn set<A: UserSpaceAccess + ?Sized>(
+        &self,
+        uspace: &A,
+        bit: usize,
+        val: bool,
+    ) -> UserResult<()> {
+        self.check_bit(bit)?;
+        self.set_range(uspace, bit..bit + 1, val)
+    }
+
+    /// Set every bit in `range` to `val`, validating the whole span once
+    ///
+    /// Fails with `EINVAL` if the range is reversed or exceeds the bitmap.
+    /// Bits outside the range in the first and last bytes are preserved.
+    pub fn set_range<A: UserSpaceAccess + ?Sized>(
+        &self,
+        uspace: &A,
+        range: Range<usize>,
+        val: bool,
+    ) -> UserResult<()> {
+        if range.start > range.end || range.end > self.bit_len {
+            return Err(Error::EINVAL);
+        }
+        if range.is_empty() {
+            return Ok(());
+        }
+        let first = range.start / 8;
+        let last = (range.end - 1) / 8;
+        let mut bytes = self
+            .ptr
+            .offset(first)
+            .get_mut_slice(uspace, last - first + 1)?;
+        let user = user_range_of(bytes.as_ptr(), bytes.len());
+        try_access_user_range(user, Access::READ | Access::WRITE, || {
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                let base = (first + i) * 8;
+                let mask = byte_mask(range.start.max(base) - base, range.end.min(base + 8) - base);
+                if val {
+                    *byte |= mask;
+                } else {
+                    *byte &= !mask;
+                }
+            }
+        })
+    }
+
+    /// Number of set bits within `bit_len`
+    pub fn count_ones<A: UserSpaceAccess + ?Sized>(&self, uspace: &A) -> UserResult<usize> {
+        let bytes = self.ptr.get_slice(uspace, self.byte_len())?;
+        let range = user_range_of(bytes.as_ptr(), bytes.len());
+        try_access_user_range(range, Access::READ, || {
+            bytes
+                .iter()
+                .enumerate()
+                .map(|(i, &byte)| {
+                    (byte & byte_mask(0, (self.bit_len - i * 8).min(8))).count_ones() as usize
+                })
+                .sum()
+        })
+    }
+
+    /// Copy the bitmap into kernel words, bits past `bit_len` read as zero
+    #[cfg(feature = "alloc")]
+    pub fn export<A: UserSpaceAccess + ?Sized>(&self, uspace: &A) -> UserResult<Vec<u64>> {
+        let bytes = self.ptr.get_slice(uspace, self.byte_len())?;
+        let mut words = vec![0u64; self.bit_len.div_ceil(64)];
+        let range = user_range_of(bytes.as_ptr(), bytes.len());
+        try_access_user_range(range, Access::READ, || {
+            for (i, &byte) in bytes.iter().enumerate() {
+                let hi = (self.bit_len - i * 8).min(8);
+                words[i / 8] |= ((byte & byte_mask(0, hi)) as u64) << (8 * (i % 8));
+            }
+        })?;
+        Ok(words)
+    }
+
+    /// Overwrite the bitmap from kernel words, validating the whole span once
+    ///
+    /// Fails with `EINVAL` if `words` holds fewer than `bit_len` bits.
+    pub fn import<A: UserSpaceAccess + ?Sized>(&self, uspace: &A, words: &[u64]) -> UserResult<()> {
+        if words.len() < self.bit_len.div_ceil(64) {
+            return Err(Error::EINVAL);
+        }
+        let mut bytes = self.ptr.get_mut_slice(uspace, self.byte_len())?;
+        let range = user_range_of(bytes.as_ptr(), bytes.len());
+        try_access_user_range(range, Access::READ | Access::WRITE, || {
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                let mask = byte_mask(0, (self.bit_len - i * 8).min(8));
+                let val = (words[i / 8] >> (8 * (i % 8))) as u8;
+                *byte = (*byte & !mask) | (val & mask);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockUspace;
+
+    #[test]
+    fn out_of_range_bits_are_rejected() {
+        let uspace = MockUspace::new(1);
+        let bitmap = UserBitmap::new(uspace.ptr(0), 12);
+        assert_eq!(bitmap.get(&uspace, 12), Err(Error::EINVAL));
+        assert_eq!(bitmap.set(&uspace, 12, true), Err(Error::EINVAL));
+        assert_eq!(bitmap.set(&uspace, usize::MAX, true), Err(Error::EINVAL));
+        assert_eq!(bitmap.set_range(&uspace, 4..13, true), Err(Error::EINVAL));
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 5..4;
+        assert_eq!(
+            bitmap.set_range(&uspace, reversed, true),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(bitmap.import(&uspace, &[]), Err(Error::EINVAL));
+        assert_eq!(uspace.load(0, 2), [0, 0]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn writes_keep_neighbouring_bits() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[0b1010_0101, 0xff]);
+        let bitmap = UserBitmap::new(uspace.ptr(0), 12);
+        bitmap.set(&uspace, 1, true).unwrap();
+        bitmap.set(&uspace, 2, false).unwrap();
+        assert_eq!(uspace.load(0, 1), [0b1010_0011]);
+        assert_eq!(bitmap.get(&uspace, 7), Ok(true));
+
+        bitmap.set_range(&uspace, 6..11, false).unwrap();
+        assert_eq!(uspace.load(0, 2), [0b0010_0011, 0b1111_1000]);
+
+        // Bits past `bit_len` are user space's and never read or written
+        assert_eq!(bitmap.export(&uspace), Ok(vec![0x823]));
+        assert_eq!(bitmap.count_ones(&uspace), Ok(4));
+        bitmap.import(&uspace, &[0]).unwrap();
+        assert_eq!(uspace.load(0, 2), [0, 0xf0]);
+    }
+}
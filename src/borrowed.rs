@@ -0,0 +1,246 @@
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "debug-aliasing")]
+use crate::AliasGuard;
+use crate::{Access, UserResult, UserSpaceRaw, try_access_user_range, user_range_of};
+
+/// Validated user `T`, borrowed for as long as the address space it was
+/// checked against
+///
+/// Dereferencing reads user memory directly, outside any access window, so
+/// a fault there is not recovered; [`copy`](Self::copy) reads inside one.
+#[derive(Debug)]
+pub struct UserRef<'a, T> {
+    value: &'a T,
+}
+
+impl<'a, T> UserRef<'a, T> {
+    pub(crate) fn new(value: &'a T) -> Self {
+        Self { value }
+    }
+
+    /// Copy the value out, failing with `EFAULT` if user memory faults
+    pub fn copy(&self) -> UserResult<T>
+    where
+        T: Copy,
+    {
+        try_access_user_range(user_range_of(self.value, 1), Access::READ, || *self.value)
+    }
+}
+
+impl<T> Deref for UserRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+/// Validated user slice, borrowed like a [`UserRef`]
+#[derive(Debug)]
+pub struct UserSliceRef<'a, T> {
+    slice: &'a [T],
+}
+
+impl<'a, T> UserSliceRef<'a, T> {
+    pub(crate) fn new(slice: &'a [T]) -> Self {
+        Self { slice }
+    }
+
+    /// Copy the slice into `buf`, which must have the same length, failing
+    /// with `EFAULT` if user memory faults
+    pub fn copy_to_slice(&self, buf: &mut [T]) -> UserResult<()>
+    where
+        T: Copy,
+    {
+        let range = user_range_of(self.slice.as_ptr(), self.slice.len());
+        try_access_user_range(range, Access::READ, || buf.copy_from_slice(self.slice))
+    }
+
+    /// Copy the slice into a new vector, failing with `EFAULT` if user
+    /// memory faults
+    #[cfg(feature = "alloc")]
+    pub fn copy_to_vec(&self) -> UserResult<Vec<T>>
+    where
+        T: Copy,
+    {
+        let range = user_range_of(self.slice.as_ptr(), self.slice.len());
+        try_access_user_range(range, Access::READ, || self.slice.to_vec())
+    }
+}
+
+impl<T> Deref for UserSliceRef<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.slice
+    }
+}
+
+/// Validated writable user `T`, borrowed like a [`UserRef`]
+///
+/// With the `debug-aliasing` feature, creating one that overlaps another
+/// live mutable borrow of the same address space panics.
+#[derive(Debug)]
+pub struct UserMut<'a, T> {
+    value: &'a mut T,
+    #[cfg(feature = "debug-aliasing")]
+    _alias: AliasGuard,
+}
+
+impl<'a, T> UserMut<'a, T> {
+    #[cfg_attr(feature = "debug-aliasing", track_caller)]
+    pub(crate) fn new<A: UserSpaceRaw + ?Sized>(uspace: &A, value: &'a mut T) -> Self {
+        #[cfg(not(feature = "debug-aliasing"))]
+        let _ = uspace;
+        Self {
+            #[cfg(feature = "debug-aliasing")]
+            _alias: AliasGuard::new(uspace.space_id(), user_range_of(value, 1)),
+            value,
+        }
+    }
+
+    /// Copy the value out, failing with `EFAULT` if user memory faults
+    pub fn copy(&self) -> UserResult<T>
+    where
+        T: Copy,
+    {
+        try_access_user_range(user_range_of(self.value, 1), Access::READ, || *self.value)
+    }
+
+    /// Overwrite the value, failing with `EFAULT` if user memory faults
+    pub fn set(&mut self, val: T) -> UserResult<()>
+    where
+        T: Copy,
+    {
+        let range = user_range_of(self.value, 1);
+        try_access_user_range(range, Access::READ | Access::WRITE, || *self.value = val)
+    }
+}
+
+impl<T> Deref for UserMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for UserMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+/// Validated writable user slice, borrowed like a [`UserMut`]
+#[derive(Debug)]
+pub struct UserSliceMut<'a, T> {
+    slice: &'a mut [T],
+    #[cfg(feature = "debug-aliasing")]
+    _alias: AliasGuard,
+}
+
+impl<'a, T> UserSliceMut<'a, T> {
+    #[cfg_attr(feature = "debug-aliasing", track_caller)]
+    pub(crate) fn new<A: UserSpaceRaw + ?Sized>(uspace: &A, slice: &'a mut [T]) -> Self {
+        #[cfg(not(feature = "debug-aliasing"))]
+        let _ = uspace;
+        Self {
+            #[cfg(feature = "debug-aliasing")]
+            _alias: AliasGuard::new(
+                uspace.space_id(),
+                user_range_of(slice.as_ptr(), slice.len()),
+            ),
+            slice,
+        }
+    }
+
+    /// Copy the slice into a new vector, failing with `EFAULT` if user
+    /// memory faults
+    #[cfg(feature = "alloc")]
+    pub fn copy_to_vec(&self) -> UserResult<Vec<T>>
+    where
+        T: Copy,
+    {
+        let range = user_range_of(self.slice.as_ptr(), self.slice.len());
+        try_access_user_range(range, Access::READ, || self.slice.to_vec())
+    }
+
+    /// Overwrite the slice with `src`, which must have the same length,
+    /// failing with `EFAULT` if user memory faults
+    pub fn copy_from_slice(&mut self, src: &[T]) -> UserResult<()>
+    where
+        T: Copy,
+    {
+        let range = user_range_of(self.slice.as_ptr(), self.slice.len());
+        try_access_user_range(range, Access::READ | Access::WRITE, || {
+            self.slice.copy_from_slice(src)
+        })
+    }
+}
+
+impl<T> Deref for UserSliceMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.slice
+    }
+}
+
+impl<T> DerefMut for UserSliceMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.slice
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, UserReadable, mock::MockUspace};
+
+    #[test]
+    fn borrowed_values_read_and_write_user_memory() {
+        let uspace = MockUspace::new(1);
+        uspace.put(8, 7u64);
+        let value = uspace.cptr::<u64>(8).get_ref(&uspace).unwrap();
+        assert_eq!(*value, 7);
+        assert_eq!(value.copy(), Ok(7));
+
+        let mut value = uspace.ptr::<u64>(8).get_mut_ref(&uspace).unwrap();
+        value.set(9).unwrap();
+        *value += 1;
+        assert_eq!(value.copy(), Ok(10));
+        assert_eq!(uspace.get::<u64>(8), 10);
+    }
+
+    #[test]
+    fn borrowed_slices_copy_out_and_in() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, b"abcd");
+        let slice = uspace.cptr::<u8>(0).get_slice(&uspace, 4).unwrap();
+        assert_eq!(&*slice, b"abcd");
+        let mut buf = [0; 4];
+        slice.copy_to_slice(&mut buf).unwrap();
+        assert_eq!(&buf, b"abcd");
+        #[cfg(feature = "alloc")]
+        assert_eq!(slice.copy_to_vec().unwrap(), b"abcd");
+
+        let mut slice = uspace.ptr::<u8>(0).get_mut_slice(&uspace, 4).unwrap();
+        slice.copy_from_slice(b"wxyz").unwrap();
+        slice[0] = b'W';
+        assert_eq!(uspace.load(0, 4), b"Wxyz");
+
+        // Validation is the same as for the copying helpers
+        uspace.unmap(0);
+        assert_eq!(
+            uspace.cptr::<u8>(0).get_slice(&uspace, 4).err(),
+            Some(Error::EFAULT)
+        );
+        assert_eq!(
+            uspace.ptr::<u64>(0).get_mut_ref(&uspace).err(),
+            Some(Error::EFAULT)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{UserCopy, UserPtr, UserResult, UserSpaceAccess};
+
+/// Kernel copy of a user value, written back on [`commit`](Self::commit)
+///
+/// Created by [`capture`](UserSpaceAccess::capture) for the "copy in,
+/// modify, copy out" pattern of e.g. `termios` or `winsize` updates.
+/// Dropping it discards the changes, which is what a failing syscall wants,
+/// unless [`write_on_drop`](Self::write_on_drop) was chosen; a write-back on
+/// drop cannot report errors.
+pub struct UserCaptured<'a, A: UserSpaceAccess + ?Sized, T: UserCopy> {
+    uspace: &'a A,
+    ptr: UserPtr<T>,
+    val: T,
+    write_on_drop: bool,
+}
+
+impl<'a, A: UserSpaceAccess + ?Sized, T: UserCopy> UserCaptured<'a, A, T> {
+    pub(crate) fn new(uspace: &'a A, ptr: UserPtr<T>) -> UserResult<Self> {
+        Ok(Self {
+            uspace,
+            ptr,
+            val: uspace.read(ptr)?,
+            write_on_drop: false,
+        })
+    }
+
+    /// Write the value back when dropped as well
+    pub fn write_on_drop(mut self) -> Self {
+        self.write_on_drop = true;
+        self
+    }
+
+    /// Write the value back now
+    pub fn commit(mut self) -> UserResult<()> {
+        self.write_on_drop = false;
+        self.uspace.write(self.ptr, self.val)
+    }
+
+    /// Drop the value without writing it back
+    pub fn discard(mut self) {
+        self.write_on_drop = false;
+    }
+}
+
+impl<A: UserSpaceAccess + ?Sized, T: UserCopy> Deref for UserCaptured<'_, A, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.val
+    }
+}
+
+impl<A: UserSpaceAccess + ?Sized, T: UserCopy> DerefMut for UserCaptured<'_, A, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.val
+    }
+}
+
+impl<A: UserSpaceAccess + ?Sized, T: UserCopy> Drop for UserCaptured<'_, A, T> {
+    fn drop(&mut self) {
+        if self.write_on_drop {
+            let _ = self.uspace.write(self.ptr, self.val);
+        }
+    }
+}
+
+/// Kernel copy of a user slice, like [`UserCaptured`]
+///
+/// Created by [`capture_slice`](UserSpaceAccess::capture_slice); byte
+/// buffers are slices of `u8`.
+#[cfg(feature = "alloc")]
+pub struct UserCapturedSlice<'a, A: UserSpaceAccess + ?Sized, T: UserCopy> {
+    uspace: &'a A,
+    ptr: UserPtr<T>,
+    buf: Vec<T>,
+    write_on_drop: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, A: UserSpaceAccess + ?Sized, T: UserCopy> UserCapturedSlice<'a, A, T> {
+    pub(crate) fn new(uspace: &'a A, ptr: UserPtr<T>, len: usize) -> UserResult<Self> {
+        Ok(Self {
+            uspace,
+            ptr,
+            buf: uspace.read_vec(ptr, len)?,
+            write_on_drop: false,
+        })
+    }
+
+    /// Write the slice back when dropped as well
+    pub fn write_on_drop(mut self) -> Self {
+        self.write_on_drop = true;
+        self
+    }
+
+    /// Write the slice back now
+    pub fn commit(mut self) -> UserResult<()> {
+        self.write_on_drop = false;
+        self.uspace.write_slice(self.ptr, &self.buf)
+    }
+
+    /// Drop the slice without writing it back
+    pub fn discard(mut self) {
+        self.write_on_drop = false;
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A: UserSpaceAccess + ?Sized, T: UserCopy> Deref for UserCapturedSlice<'_, A, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.buf
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A: UserSpaceAccess + ?Sized, T: UserCopy> DerefMut for UserCapturedSlice<'_, A, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.buf
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A: UserSpaceAccess + ?Sized, T: UserCopy> Drop for UserCapturedSlice<'_, A, T> {
+    fn drop(&mut self) {
+        if self.write_on_drop {
+            let _ = self.uspace.write_slice(self.ptr, &self.buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, UserSpaceAccess, mock::MockUspace};
+
+    #[test]
+    fn captured_values_are_written_back_on_commit_only() {
+        let uspace = MockUspace::new(1);
+        uspace.put(8, 1u32);
+        let mut val = uspace.capture(uspace.ptr::<u32>(8)).unwrap();
+        *val += 1;
+        drop(val);
+        assert_eq!(uspace.get::<u32>(8), 1);
+
+        let mut val = uspace.capture(uspace.ptr::<u32>(8)).unwrap();
+        *val += 1;
+        val.commit().unwrap();
+        assert_eq!(uspace.get::<u32>(8), 2);
+
+        let mut val = uspace
+            .capture(uspace.ptr::<u32>(8))
+            .unwrap()
+            .write_on_drop();
+        *val = 7;
+        drop(val);
+        assert_eq!(uspace.get::<u32>(8), 7);
+
+        let mut val = uspace
+            .capture(uspace.ptr::<u32>(8))
+            .unwrap()
+            .write_on_drop();
+        *val = 9;
+        val.discard();
+        assert_eq!(uspace.get::<u32>(8), 7);
+
+        uspace.unmap(0);
+        assert!(matches!(
+            uspace.capture(uspace.ptr::<u32>(8)),
+            Err(Error::EFAULT)
+        ));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn captured_slices_follow_the_same_rules() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, b"abcd");
+        let mut buf = uspace.capture_slice(uspace.ptr::<u8>(0), 4).unwrap();
+        buf.make_ascii_uppercase();
+        drop(buf);
+        assert_eq!(uspace.load(0, 4), b"abcd");
+
+        let mut buf = uspace.capture_slice(uspace.ptr::<u8>(0), 4).unwrap();
+        assert_eq!(&*buf, b"abcd");
+        buf[1] = b'B';
+        buf.commit().unwrap();
+        assert_eq!(uspace.load(0, 4), b"aBcd");
+
+        let mut buf = uspace
+            .capture_slice(uspace.ptr::<u8>(0), 4)
+            .unwrap()
+            .write_on_drop();
+        buf[3] = b'D';
+        drop(buf);
+        assert_eq!(uspace.load(0, 4), b"aBcD");
+
+        // The area is made read-only after the capture: the commit fails
+        let buf = uspace.capture_slice(uspace.ptr::<u8>(0), 4).unwrap();
+        uspace.protect(0, crate::Access::READ | crate::Access::USER);
+        assert_eq!(buf.commit(), Err(Error::EFAULT));
+    }
+}
@@ -0,0 +1,242 @@
+use core::{
+    ptr,
+    sync::atomic::{Ordering, compiler_fence},
+};
+
+use crate::BackendSlot;
+
+/// Copy routines able to survive a fault on the user side, installed with
+/// [`set_user_copy_backend`]
+///
+/// A concurrent `munmap` can remove a page between validation and the copy.
+/// Implementations let the trap handler resume after such a fault, e.g.
+/// through an exception table or a per-architecture setjmp, and report how
+/// far the copy got.
+pub trait UserCopyBackend: Sync {
+    /// Copy `len` bytes from user `src` to kernel `dst`
+    ///
+    /// Returns the number of bytes copied before a fault as the error.
+    ///
+    /// # Safety
+    ///
+    /// `dst` must be valid for `len` bytes of writes, and `src` must have
+    /// been validated for `len` bytes of reads.
+    unsafe fn copy_from_user(&self, dst: *mut u8, src: *const u8, len: usize) -> Result<(), usize>;
+
+    /// Copy `len` bytes from kernel `src` to user `dst`
+    ///
+    /// Returns the number of bytes copied before a fault as the error.
+    ///
+    /// # Safety
+    ///
+    /// `src` must be valid for `len` bytes of reads, and `dst` must have been
+    /// validated for `len` bytes of writes.
+    unsafe fn copy_to_user(&self, dst: *mut u8, src: *const u8, len: usize) -> Result<(), usize>;
+}
+
+/// Plain copies that cannot recover from faults, used until a backend is
+/// installed
+struct PlainCopy;
+
+impl UserCopyBackend for PlainCopy {
+    unsafe fn copy_from_user(&self, dst: *mut u8, src: *const u8, len: usize) -> Result<(), usize> {
+        unsafe { ptr::copy_nonoverlapping(src, dst, len) };
+        Ok(())
+    }
+
+    unsafe fn copy_to_user(&self, dst: *mut u8, src: *const u8, len: usize) -> Result<(), usize> {
+        unsafe { ptr::copy_nonoverlapping(src, dst, len) };
+        Ok(())
+    }
+}
+
+/// Plain data that can be copied out of user memory byte for byte
+///
+/// # Safety
+///
+/// Every bit pattern must be a valid value of the type, and the type must
+/// have no padding.
+pub unsafe trait UserCopy: Copy + 'static {}
+
+macro_rules! impl_user_copy {
+    ($($ty:ty),*) => {
+        $(unsafe impl UserCopy for $ty {})*
+    };
+}
+
+impl_user_copy!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+
+unsafe impl<T: UserCopy, const N: usize> UserCopy for [T; N] {}
+
+static COPY_BACKEND: BackendSlot<dyn UserCopyBackend> = BackendSlot::new();
+
+/// Install fault-tolerant copy routines for every user copy of the crate
+///
+/// Must happen before the first user access. Only the first registration
+/// takes effect; later ones return `false`.
+pub fn set_user_copy_backend(backend: &'static dyn UserCopyBackend) -> bool {
+    COPY_BACKEND.set(backend)
+}
+
+/// Whether [`set_user_copy_backend`] installed fault-tolerant copies
+pub fn has_user_copy_backend() -> bool {
+    COPY_BACKEND.get().is_some()
+}
+
+fn copy_backend() -> &'static dyn UserCopyBackend {
+    COPY_BACKEND.get().unwrap_or(&PlainCopy)
+}
+
+/// Copy `len` bytes from user `src` to kernel `dst` through the installed
+/// [`UserCopyBackend`], returning the bytes copied before a fault as the
+/// error
+///
+/// Without a backend this is a plain copy, and a fault is not recoverable.
+///
+/// # Safety
+///
+/// See [`UserCopyBackend::copy_from_user`].
+pub unsafe fn copy_from_user_fallible(
+    dst: *mut u8,
+    src: *const u8,
+    len: usize,
+) -> Result<(), usize> {
+    if len == 0 {
+        return Ok(());
+    }
+    unsafe { copy_backend().copy_from_user(dst, src, len) }
+}
+
+/// Copy `len` bytes from kernel `src` to user `dst` through the installed
+/// [`UserCopyBackend`], returning the bytes copied before a fault as the
+/// error
+///
+/// Without a backend this is a plain copy, and a fault is not recoverable.
+///
+/// # Safety
+///
+/// See [`UserCopyBackend::copy_to_user`].
+pub unsafe fn copy_to_user_fallible(dst: *mut u8, src: *const u8, len: usize) -> Result<(), usize> {
+    if len == 0 {
+        return Ok(());
+    }
+    unsafe { copy_backend().copy_to_user(dst, src, len) }
+}
+
+/// Copy `len` bytes from user device or uncached memory at `src` to kernel
+/// `dst`
+///
+/// Uses aligned, volatile, word-sized reads where possible and bytes
+/// elsewhere, bypassing the [`UserCopyBackend`].
+///
+/// # Safety
+///
+/// See [`UserCopyBackend::copy_from_user`].
+pub(crate) unsafe fn copy_from_device(dst: *mut u8, src: *const u8, len: usize) {
+    let mut off = 0;
+    while off < len {
+        unsafe {
+            let from = src.add(off);
+            if (from as usize).is_multiple_of(WORD) && len - off >= WORD {
+                dst.add(off)
+                    .cast::<usize>()
+                    .write_unaligned(from.cast::<usize>().read_volatile());
+                off += WORD;
+            } else {
+                dst.add(off).write(from.read_volatile());
+                off += 1;
+            }
+        }
+    }
+}
+
+/// Copy `len` bytes from kernel `src` to user device or uncached memory at
+/// `dst`, like [`copy_from_device`]
+///
+/// # Safety
+///
+/// See [`UserCopyBackend::copy_to_user`].
+pub(crate) unsafe fn copy_to_device(dst: *mut u8, src: *const u8, len: usize) {
+    let mut off = 0;
+    while off < len {
+        unsafe {
+            let to = dst.add(off);
+            if (to as usize).is_multiple_of(WORD) && len - off >= WORD {
+                to.cast::<usize>()
+                    .write_volatile(src.add(off).cast::<usize>().read_unaligned());
+                off += WORD;
+            } else {
+                to.write_volatile(src.add(off).read());
+                off += 1;
+            }
+        }
+    }
+}
+
+const WORD: usize = size_of::<usize>();
+
+/// Overwrite `buf` with zeros in a way the compiler cannot elide
+pub(crate) fn zeroize(buf: &mut [u8]) {
+    for byte in buf {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Error, UserSpaceAccess,
+        mock::{self, MockUspace},
+    };
+
+    #[test]
+    fn copies_go_through_the_backend() {
+        let uspace = MockUspace::new(1);
+        let before = mock::copies();
+        uspace
+            .write_slice(uspace.ptr::<u8>(0), b"abcdefgh")
+            .unwrap();
+        let mut buf = [0; 8];
+        uspace
+            .read_slice_to(uspace.cptr::<u8>(0), &mut buf)
+            .unwrap();
+        assert_eq!(&buf, b"abcdefgh");
+        assert_eq!(uspace.read(uspace.cptr::<u32>(0)), Ok(0x6463_6261));
+        assert_eq!(mock::copies() - before, 3);
+
+        // Empty copies never reach the backend
+        uspace.read_slice_to(uspace.cptr::<u8>(0), &mut []).unwrap();
+        assert_eq!(mock::copies() - before, 3);
+    }
+
+    #[test]
+    fn faulting_copies_fail_with_efault() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, b"abcdefgh\0");
+        mock::fault_copies_after(Some(4));
+        let mut buf = [0; 8];
+        assert_eq!(
+            uspace.read_slice_to(uspace.cptr::<u8>(0), &mut buf),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(uspace.read(uspace.cptr::<u64>(0)), Err(Error::EFAULT));
+        assert_eq!(
+            uspace.write_slice(uspace.ptr::<u8>(16), &buf),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(
+            uspace.read_cstr_into(uspace.cptr::<u8>(0), &mut [0; 16]),
+            Err(Error::EFAULT)
+        );
+        // A copy that fits before the fault succeeds
+        assert_eq!(uspace.read(uspace.cptr::<u32>(0)), Ok(0x6463_6261));
+        mock::fault_copies_after(None);
+        assert_eq!(
+            uspace.read_cstr_into(uspace.cptr::<u8>(0), &mut [0; 16]),
+            Ok(8)
+        );
+    }
+}
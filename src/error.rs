@@ -0,0 +1,245 @@
+#[cfg(feature = "track-caller")]
+use core::panic::Location;
+
+use memory_addr::VirtAddr;
+
+use crate::{Access, UserSpaceAccess};
+
+/// Error codes the crate produces, named after their Linux errno
+///
+/// The crate's [`Error`] is this type without the default `linux-errno`
+/// feature; kernels with their own error type convert from it.
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Errno {
+    /// Argument list too long
+    E2BIG,
+    /// Try again
+    EAGAIN,
+    /// Bad address
+    EFAULT,
+    /// Illegal byte sequence
+    EILSEQ,
+    /// Interrupted system call
+    EINTR,
+    /// Invalid argument
+    EINVAL,
+    /// File name too long
+    ENAMETOOLONG,
+    /// Out of memory
+    ENOMEM,
+    /// Operation not supported
+    EOPNOTSUPP,
+    /// Operation not permitted
+    EPERM,
+}
+
+#[cfg(feature = "linux-errno")]
+impl From<Errno> for axerrno::LinuxError {
+    fn from(value: Errno) -> Self {
+        match value {
+            Errno::E2BIG => Self::E2BIG,
+            Errno::EAGAIN => Self::EAGAIN,
+            Errno::EFAULT => Self::EFAULT,
+            Errno::EILSEQ => Self::EILSEQ,
+            Errno::EINTR => Self::EINTR,
+            Errno::EINVAL => Self::EINVAL,
+            Errno::ENAMETOOLONG => Self::ENAMETOOLONG,
+            Errno::ENOMEM => Self::ENOMEM,
+            Errno::EOPNOTSUPP => Self::EOPNOTSUPP,
+            Errno::EPERM => Self::EPERM,
+        }
+    }
+}
+
+/// Error type of the crate and of the [`UserSpaceRaw`] hooks
+///
+/// `axerrno::LinuxError` with the default `linux-errno` feature, [`Errno`]
+/// otherwise.
+///
+/// [`UserSpaceRaw`]: crate::UserSpaceRaw
+#[cfg(feature = "linux-errno")]
+pub type Error = axerrno::LinuxError;
+
+/// Error type of the crate and of the [`UserSpaceRaw`] hooks
+///
+/// `axerrno::LinuxError` with the default `linux-errno` feature, [`Errno`]
+/// otherwise.
+///
+/// [`UserSpaceRaw`]: crate::UserSpaceRaw
+#[cfg(not(feature = "linux-errno"))]
+pub type Error = Errno;
+
+/// Result with the crate's [`Error`], `axerrno::LinuxResult` by default
+pub type UserResult<T = ()> = Result<T, Error>;
+
+/// Cause of a [`UserAccessError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessErrorKind {
+    /// Not mapped, or not user memory at all
+    NotMapped,
+    /// Mapped without the required permissions
+    BadPerms,
+    /// Mapped with the required permissions, but denied by the protection
+    /// key of the page, for `SIGSEGV` with `SEGV_PKUERR`
+    PkeyDenied,
+    /// Not aligned for the accessed type
+    Misaligned,
+    /// The range wraps around the address space
+    Overflow,
+    /// Longer than the access allows
+    TooLong,
+    /// Memory to populate the range could not be allocated
+    NoMemory,
+    /// Any other error reported by the backend
+    Other(Error),
+}
+
+/// Failed user memory access, with the address and flags involved
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserAccessError {
+    /// Start of the failing access, or of the first failing page
+    pub addr: VirtAddr,
+    /// What went wrong
+    pub kind: AccessErrorKind,
+    /// The access flags that were requested
+    pub flags: Access,
+    /// Call site of the outermost crate entry point that failed
+    #[cfg(feature = "track-caller")]
+    pub location: Option<&'static Location<'static>>,
+}
+
+impl UserAccessError {
+    /// Create an error of `kind` at `addr`
+    pub const fn new(addr: VirtAddr, kind: AccessErrorKind, flags: Access) -> Self {
+        Self {
+            addr,
+            kind,
+            flags,
+            #[cfg(feature = "track-caller")]
+            location: None,
+        }
+    }
+
+    /// Wrap an error returned by a backend hook for the access at `addr`
+    pub fn from_backend(addr: VirtAddr, flags: Access, error: Error) -> Self {
+        let kind = match error {
+            Error::EFAULT => AccessErrorKind::NotMapped,
+            Error::ENOMEM => AccessErrorKind::NoMemory,
+            error => AccessErrorKind::Other(error),
+        };
+        Self::new(addr, kind, flags)
+    }
+
+    /// The errno reported to user space
+    pub fn errno(&self) -> Error {
+        match self.kind {
+            AccessErrorKind::NotMapped
+            | AccessErrorKind::BadPerms
+            | AccessErrorKind::PkeyDenied
+            | AccessErrorKind::Misaligned
+            | AccessErrorKind::Overflow => Error::EFAULT,
+            AccessErrorKind::TooLong => Error::EINVAL,
+            AccessErrorKind::NoMemory => Error::ENOMEM,
+            AccessErrorKind::Other(error) => error,
+        }
+    }
+}
+
+impl From<UserAccessError> for Error {
+    fn from(value: UserAccessError) -> Self {
+        value.errno()
+    }
+}
+
+/// Result of the lower-level access APIs
+pub type AccessResult<T> = Result<T, UserAccessError>;
+
+/// Finish a failed check: record the caller location with `track-caller` and
+/// report the error to [`UserSpaceRaw::on_access_error`](crate::UserSpaceRaw::on_access_error)
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub(crate) fn locate<A: UserSpaceAccess + ?Sized, T>(
+    uspace: &A,
+    result: AccessResult<T>,
+) -> AccessResult<T> {
+    match result {
+        Ok(value) => Ok(value),
+        #[allow(unused_mut)]
+        Err(mut error) => {
+            #[cfg(feature = "track-caller")]
+            {
+                error.location = Some(Location::caller());
+            }
+            uspace.on_access_error(&error);
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::Layout;
+
+    use super::*;
+    use crate::{check_region, mock::MockUspace};
+
+    #[test]
+    fn backend_errors_keep_their_errno() {
+        let addr = VirtAddr::from(0x1000);
+        let error = |e| UserAccessError::from_backend(addr, Access::READ, e);
+        assert_eq!(error(Error::EFAULT).kind, AccessErrorKind::NotMapped);
+        assert_eq!(error(Error::ENOMEM).kind, AccessErrorKind::NoMemory);
+        for errno in [Error::EFAULT, Error::ENOMEM, Error::E2BIG] {
+            assert_eq!(Error::from(error(errno)), errno);
+        }
+        let too_long = UserAccessError::new(addr, AccessErrorKind::TooLong, Access::READ);
+        assert_eq!(too_long.errno(), Error::EINVAL);
+    }
+
+    #[cfg(feature = "linux-errno")]
+    #[test]
+    fn errno_converts_to_linux_error() {
+        assert_eq!(axerrno::LinuxError::from(Errno::E2BIG), Error::E2BIG);
+        assert_eq!(axerrno::LinuxError::from(Errno::EILSEQ), Error::EILSEQ);
+        assert_eq!(axerrno::LinuxError::from(Errno::ENOMEM), Error::ENOMEM);
+    }
+
+    #[test]
+    fn errors_name_the_failing_access() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        let layout = Layout::new::<[u64; 2]>();
+        let flags = Access::READ | Access::WRITE;
+        let err = check_region(&uspace, uspace.addr(4088), layout, flags).unwrap_err();
+        assert_eq!(
+            (err.addr, err.kind),
+            (uspace.addr(4088), AccessErrorKind::NotMapped)
+        );
+        assert_eq!(err.errno(), Error::EFAULT);
+
+        uspace.unpopulate(0);
+        uspace.populate_error.set(Some(Error::ENOMEM));
+        let err = check_region(&uspace, uspace.addr(8), layout, flags).unwrap_err();
+        assert_eq!(err.kind, AccessErrorKind::NoMemory);
+        assert_eq!(err.errno(), Error::ENOMEM);
+    }
+
+    #[test]
+    fn failed_checks_reach_the_hook() {
+        let uspace = MockUspace::new(1);
+        let layout = Layout::new::<u64>();
+        check_region(&uspace, uspace.addr(8), layout, Access::READ).unwrap();
+        assert_eq!(uspace.last_error.get(), None);
+
+        uspace.unmap(0);
+        #[cfg(feature = "track-caller")]
+        let line = line!() + 1;
+        let err = check_region(&uspace, uspace.addr(8), layout, Access::READ).unwrap_err();
+        assert_eq!(uspace.last_error.get(), Some(err));
+        #[cfg(feature = "track-caller")]
+        {
+            let location = err.location.unwrap();
+            assert_eq!((location.file(), location.line()), (file!(), line));
+        }
+    }
+}
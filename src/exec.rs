@@ -0,0 +1,341 @@
+#[cfg(feature = "alloc")]
+use core::{ffi::c_char, ops::Range};
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "alloc")]
+use crate::{AllocCharge, Error, UserConstPtr, UserResult, UserSpaceAccess, copy_utf8_str};
+
+/// Budget shared by the `argv` and `envp` of one `execve`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecLimits {
+    /// Total bytes of strings, terminators and pointers, like `ARG_MAX`
+    pub arg_max: usize,
+    /// Bytes of a single string including its terminator, like
+    /// `MAX_ARG_STRLEN`
+    pub max_arg_strlen: usize,
+    /// Number of strings in `argv` and `envp` together, like
+    /// `MAX_ARG_STRINGS`
+    pub max_arg_strings: usize,
+}
+
+impl ExecLimits {
+    /// Linux defaults for an 8 MiB stack limit and 4K pages
+    pub const LINUX: Self = Self {
+        arg_max: 2 * 1024 * 1024,
+        max_arg_strlen: 32 * 4096,
+        max_arg_strings: 0x7fff_ffff,
+    };
+}
+
+impl Default for ExecLimits {
+    fn default() -> Self {
+        Self::LINUX
+    }
+}
+
+/// Kernel copies of the arguments and environment of an `execve`
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExecArgs {
+    /// Argument strings
+    pub argv: Vec<String>,
+    /// Environment strings
+    pub envp: Vec<String>,
+    /// Bytes charged through
+    /// [`charge_kernel_alloc`](crate::UserSpaceRaw::charge_kernel_alloc)
+    /// for the strings, for the caller to uncharge once it frees them
+    pub charged: usize,
+}
+
+/// `execve` strings captured into one shared buffer
+///
+/// Each range indexes the string bytes in `bytes`, and is followed there by
+/// its NUL terminator.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExecArgsBuf {
+    /// Bytes of every string, each followed by a NUL
+    pub bytes: Vec<u8>,
+    /// Argument strings
+    pub argv: Vec<Range<usize>>,
+    /// Environment strings
+    pub envp: Vec<Range<usize>>,
+    /// Bytes charged through
+    /// [`charge_kernel_alloc`](crate::UserSpaceRaw::charge_kernel_alloc)
+    /// for the buffers, for the caller to uncharge once it frees them
+    pub charged: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl ExecArgsBuf {
+    /// Iterate over the argument strings
+    pub fn argv(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.argv.iter().map(|r| &self.bytes[r.clone()])
+    }
+
+    /// Iterate over the environment strings
+    pub fn envp(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.envp.iter().map(|r| &self.bytes[r.clone()])
+    }
+}
+
+/// Remaining budget while capturing `execve` strings
+#[cfg(feature = "alloc")]
+pub(crate) struct ExecBudget {
+    limits: ExecLimits,
+    bytes: usize,
+    strings: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl ExecBudget {
+    pub(crate) fn new(limits: ExecLimits) -> Self {
+        Self {
+            limits,
+            bytes: 0,
+            strings: 0,
+        }
+    }
+
+    /// Account for one string of `len` bytes without its terminator,
+    /// failing with `E2BIG` once any limit is exceeded
+    pub(crate) fn charge(&mut self, len: usize) -> UserResult<()> {
+        let size = len + 1;
+        self.strings += 1;
+        self.bytes = self
+            .bytes
+            .saturating_add(size)
+            .saturating_add(size_of::<usize>());
+        if size > self.limits.max_arg_strlen
+            || self.strings > self.limits.max_arg_strings
+            || self.bytes > self.limits.arg_max
+        {
+            return Err(Error::E2BIG);
+        }
+        Ok(())
+    }
+
+    /// Most bytes a single string may have without its terminator
+    pub(crate) fn max_strlen(&self) -> usize {
+        self.limits.max_arg_strlen.saturating_sub(1)
+    }
+}
+
+/// Errno of a string read under an [`ExecBudget`], with an overlong string
+/// reported as `E2BIG`
+#[cfg(feature = "alloc")]
+fn too_big(error: Error) -> Error {
+    match error {
+        Error::ENAMETOOLONG => Error::E2BIG,
+        e => e,
+    }
+}
+
+/// Append the null-terminated bytes at `ptr` to `buf` without their
+/// terminator, a page at a time, and return their length
+///
+/// Fails with `ENAMETOOLONG` beyond `max` bytes, leaving `buf` extended.
+#[cfg(feature = "alloc")]
+fn append_cstr<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    ptr: UserConstPtr<c_char>,
+    max: usize,
+    buf: &mut Vec<u8>,
+) -> UserResult<usize> {
+    let page_size = uspace.page_size();
+    let start = buf.len();
+    let mut addr = ptr.address().as_usize();
+    loop {
+        let len = buf.len() - start;
+        // One byte past `max` to see the terminator of the longest string
+        let chunk = (page_size - (addr & (page_size - 1))).min(max.saturating_add(1) - len);
+        buf.resize(buf.len() + chunk, 0);
+        match uspace.read_cstr_into(UserConstPtr::<u8>::from(addr), &mut buf[start + len..]) {
+            Ok(n) => {
+                buf.truncate(start + len + n);
+                return Ok(len + n);
+            }
+            Err(Error::ENAMETOOLONG) if len + chunk <= max => addr = addr.wrapping_add(chunk),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Capture a null-terminated array of strings, charging each to `budget`
+/// and its memory to `charge`
+#[cfg(feature = "alloc")]
+pub(crate) fn capture_str_array<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    ptr: UserConstPtr<UserConstPtr<c_char>>,
+    budget: &mut ExecBudget,
+    charge: &mut AllocCharge<'_, A>,
+) -> UserResult<Vec<String>> {
+    let mut strings = Vec::new();
+    if ptr.is_null() {
+        return Ok(strings);
+    }
+    for offset in 0.. {
+        let str_ptr = uspace.read(ptr.offset(offset))?;
+        if str_ptr.is_null() {
+            break;
+        }
+        let s = copy_utf8_str(uspace, str_ptr.address(), budget.max_strlen()).map_err(too_big)?;
+        budget.charge(s.len())?;
+        charge.charge(s.len() + size_of::<String>())?;
+        strings.push(s);
+    }
+    Ok(strings)
+}
+
+/// Append a null-terminated array of strings to `buf`, charging each to
+/// `budget` and its memory to `charge`, and return the range of each string
+///
+/// `buf` is only appended to, and is truncated back on failure.
+#[cfg(feature = "alloc")]
+pub(crate) fn capture_str_array_into<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    ptr: UserConstPtr<UserConstPtr<c_char>>,
+    buf: &mut Vec<u8>,
+    budget: &mut ExecBudget,
+    charge: &mut AllocCharge<'_, A>,
+) -> UserResult<Vec<Range<usize>>> {
+    let start = buf.len();
+    let mut append = || {
+        let mut ranges = Vec::new();
+        if ptr.is_null() {
+            return Ok(ranges);
+        }
+        for offset in 0.. {
+            let str_ptr = uspace.read(ptr.offset(offset))?;
+            if str_ptr.is_null() {
+                break;
+            }
+            let pos = buf.len();
+            let len = append_cstr(uspace, str_ptr, budget.max_strlen(), buf).map_err(too_big)?;
+            budget.charge(len)?;
+            charge.charge(len + 1 + size_of::<Range<usize>>())?;
+            buf.push(0);
+            ranges.push(pos..pos + len);
+        }
+        Ok(ranges)
+    };
+    append().inspect_err(|_| buf.truncate(start))
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockUspace;
+
+    /// Room each string takes: its bytes, the terminator and the pointer
+    const fn cost(len: usize) -> usize {
+        len + 1 + size_of::<usize>()
+    }
+
+    #[test]
+    fn captures_both_arrays() {
+        let uspace = MockUspace::new(1);
+        let end = uspace.put_strs(0, &["sh", "-c", "true"]);
+        uspace.put_strs(end, &["PATH=/bin"]);
+        let args = uspace
+            .capture_exec_args(uspace.cptr(0), uspace.cptr(end), ExecLimits::default())
+            .unwrap();
+        assert_eq!(args.argv, ["sh", "-c", "true"]);
+        assert_eq!(args.envp, ["PATH=/bin"]);
+
+        let args = uspace
+            .capture_exec_args(uspace.cptr(0), 0.into(), ExecLimits::default())
+            .unwrap();
+        assert!(args.envp.is_empty());
+    }
+
+    #[test]
+    fn arrays_share_one_budget() {
+        let uspace = MockUspace::new(1);
+        let end = uspace.put_strs(0, &["ab", "cd"]);
+        uspace.put_strs(end, &["EF"]);
+        let limits = |arg_max, max_arg_strlen, max_arg_strings| ExecLimits {
+            arg_max,
+            max_arg_strlen,
+            max_arg_strings,
+        };
+        let capture = |limits| uspace.capture_exec_args(uspace.cptr(0), uspace.cptr(end), limits);
+
+        assert!(capture(limits(3 * cost(2), 3, 3)).is_ok());
+        // argv alone fits, envp pushes the total over
+        assert_eq!(capture(limits(3 * cost(2) - 1, 3, 3)), Err(Error::E2BIG));
+        assert_eq!(capture(limits(usize::MAX, 3, 2)), Err(Error::E2BIG));
+        // The per-string limit counts the terminator
+        assert_eq!(capture(limits(usize::MAX, 2, 3)), Err(Error::E2BIG));
+    }
+
+    #[test]
+    fn strings_are_copied_from_indirect_spaces() {
+        let uspace = MockUspace::new(1).indirect();
+        let end = uspace.put_strs(0, &["sh", "-c"]);
+        uspace.put_strs(end, &["HOME=/"]);
+        let args = uspace
+            .capture_exec_args(uspace.cptr(0), uspace.cptr(end), ExecLimits::default())
+            .unwrap();
+        assert_eq!(args.argv, ["sh", "-c"]);
+        assert_eq!(args.envp, ["HOME=/"]);
+        let mut buf = Vec::new();
+        let ranges = uspace
+            .read_str_array_into(uspace.cptr(0), &mut buf, ExecLimits::default())
+            .unwrap();
+        assert_eq!(buf, b"sh\0-c\0");
+        assert_eq!(ranges, [0..2, 3..5]);
+    }
+
+    #[test]
+    fn bad_string_pointer_faults() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        uspace.put_strs(0, &["a"]);
+        uspace.put(0, uspace.addr(4096).as_usize());
+        assert_eq!(
+            uspace.capture_exec_args(uspace.cptr(0), 0.into(), ExecLimits::default()),
+            Err(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    fn captures_into_one_buffer() {
+        let uspace = MockUspace::new(1);
+        let end = uspace.put_strs(0, &["sh", "-c"]);
+        uspace.put_strs(end, &["A=1"]);
+        let args = uspace
+            .capture_exec_args_into(uspace.cptr(0), uspace.cptr(end), ExecLimits::default())
+            .unwrap();
+        assert_eq!(args.bytes, b"sh\0-c\0A=1\0");
+        assert!(args.argv().eq([&b"sh"[..], b"-c"]));
+        assert!(args.envp().eq([&b"A=1"[..]]));
+    }
+
+    #[test]
+    fn buffer_is_appended_and_restored() {
+        let uspace = MockUspace::new(1);
+        uspace.put_strs(0, &["x", "y"]);
+        // Strings need not be UTF-8: replace the `y`, after the three
+        // pointers and `x\0`
+        uspace.fill(3 * size_of::<usize>() + 2, &[0xff]);
+        let mut buf = Vec::from(*b"keep");
+        let ranges = uspace
+            .read_str_array_into(uspace.cptr(0), &mut buf, ExecLimits::default())
+            .unwrap();
+        assert_eq!(ranges, [4..5, 6..7]);
+        assert_eq!(buf, b"keepx\0\xff\0");
+
+        let limits = ExecLimits {
+            max_arg_strings: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            uspace.read_str_array_into(uspace.cptr(0), &mut buf, limits),
+            Err(Error::E2BIG)
+        );
+        assert_eq!(buf.len(), 8);
+    }
+}
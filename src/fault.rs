@@ -0,0 +1,120 @@
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange};
+
+use crate::{Access, UserSpaceAccess, access_state, expected_user_fault};
+
+/// What a trap handler should do after [`handle_user_fault`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultDisposition {
+    /// The page was populated; return and retry the faulting access
+    Retry,
+    /// The user access fails; the interrupted access returns `EFAULT`
+    Fail,
+    /// The fault is not part of any expected user access: a kernel bug
+    Unexpected,
+}
+
+/// Resolve a page fault at `addr` for `access`, taken while the kernel was
+/// accessing user memory
+///
+/// Faults outside the range recorded by the current window, or needing
+/// flags it did not ask for, are [`Unexpected`](FaultDisposition::Unexpected).
+/// Faults inside [`read_nofault`](crate::UserSpaceAccess::read_nofault) and
+/// its siblings fail right away. Otherwise the faulting page is populated through
+/// [`populate_region`](crate::UserSpaceRaw::populate_region) and the access
+/// retried; if that fails, the window is marked as faulted so the copy
+/// returns `EFAULT` even if the trap handler lets it complete, e.g. on a
+/// scratch page.
+pub fn handle_user_fault<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    addr: VirtAddr,
+    access: Access,
+) -> FaultDisposition {
+    let Some(flags) = expected_user_fault(addr) else {
+        return FaultDisposition::Unexpected;
+    };
+    if !flags.contains(access - Access::USER) {
+        return FaultDisposition::Unexpected;
+    }
+    let state = access_state();
+    if state.nofault() {
+        state.set_faulted(true);
+        return FaultDisposition::Fail;
+    }
+    let page_size = uspace.page_size();
+    let populated = VirtAddrRange::try_from_start_size(addr.align_down(page_size), page_size)
+        .is_some_and(|page| uspace.populate_region(page, flags).is_ok());
+    if populated {
+        FaultDisposition::Retry
+    } else {
+        state.set_faulted(true);
+        FaultDisposition::Fail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Error, access_user_memory, mock::MockUspace, try_access_user_nofault, try_access_user_range,
+    };
+
+    #[test]
+    fn faults_in_the_expected_range_are_populated() {
+        let uspace = MockUspace::new(2);
+        uspace.unpopulate(1);
+        let range = VirtAddrRange::from_start_size(uspace.addr(4000), 200);
+        let fault = uspace.addr(4100);
+        assert_eq!(
+            handle_user_fault(&uspace, fault, Access::READ),
+            FaultDisposition::Unexpected
+        );
+        let result = try_access_user_range(range, Access::READ, || {
+            // A write fault in a read window is a kernel bug
+            assert_eq!(
+                handle_user_fault(&uspace, fault, Access::WRITE),
+                FaultDisposition::Unexpected
+            );
+            assert_eq!(
+                handle_user_fault(&uspace, uspace.addr(4300), Access::READ),
+                FaultDisposition::Unexpected
+            );
+            handle_user_fault(&uspace, fault, Access::READ | Access::USER)
+        });
+        assert_eq!(result, Ok(FaultDisposition::Retry));
+        assert!(uspace.is_populated(1));
+    }
+
+    #[test]
+    fn unresolved_faults_fail_the_window() {
+        let uspace = MockUspace::new(1);
+        uspace.populate_error.set(Some(Error::ENOMEM));
+        let range = VirtAddrRange::from_start_size(uspace.addr(0), 8);
+        let result = try_access_user_range(range, Access::WRITE, || {
+            handle_user_fault(&uspace, uspace.addr(4), Access::WRITE)
+        });
+        assert_eq!(result, Err(Error::EFAULT));
+
+        // The record does not leak into the next window
+        assert_eq!(try_access_user_range(range, Access::WRITE, || 1), Ok(1));
+        access_user_memory(|| {
+            let inner = try_access_user_range(range, Access::WRITE, || {
+                handle_user_fault(&uspace, uspace.addr(4), Access::WRITE)
+            });
+            assert_eq!(inner, Err(Error::EFAULT));
+            assert!(!access_state().faulted());
+        });
+    }
+
+    #[test]
+    fn nofault_windows_never_populate() {
+        let uspace = MockUspace::new(1);
+        uspace.unpopulate(0);
+        let range = VirtAddrRange::from_start_size(uspace.addr(0), 8);
+        let result = try_access_user_nofault(range, Access::READ, || {
+            handle_user_fault(&uspace, uspace.addr(0), Access::READ)
+        });
+        assert_eq!(result, Err(Error::EFAULT));
+        assert!(!uspace.is_populated(0));
+        assert_eq!(uspace.populates.get(), 0);
+    }
+}
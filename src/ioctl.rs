@@ -0,0 +1,216 @@
+//! Decoding of ioctl arguments from the command encoding.
+//!
+//! Uses the generic `asm-generic/ioctl.h` layout shared by x86, arm, riscv and
+//! loongarch: 8 bits of number, 8 bits of type, 14 bits of size and 2 bits of
+//! direction.
+
+use crate::{Error, UserConstPtr, UserPtr, UserResult, UserSpaceAccess};
+
+const IOC_NRBITS: u32 = 8;
+const IOC_TYPEBITS: u32 = 8;
+const IOC_SIZEBITS: u32 = 14;
+
+const IOC_NRSHIFT: u32 = 0;
+const IOC_TYPESHIFT: u32 = IOC_NRSHIFT + IOC_NRBITS;
+const IOC_SIZESHIFT: u32 = IOC_TYPESHIFT + IOC_TYPEBITS;
+const IOC_DIRSHIFT: u32 = IOC_SIZESHIFT + IOC_SIZEBITS;
+
+/// No data transfer
+pub const IOC_NONE: u32 = 0;
+/// User space writes, the kernel reads the argument
+pub const IOC_WRITE: u32 = 1;
+/// User space reads, the kernel writes the argument
+pub const IOC_READ: u32 = 2;
+
+/// Encode an ioctl command, like `_IOC`
+pub const fn ioc(dir: u32, ty: u8, nr: u8, size: usize) -> u32 {
+    (dir << IOC_DIRSHIFT)
+        | ((ty as u32) << IOC_TYPESHIFT)
+        | ((nr as u32) << IOC_NRSHIFT)
+        | ((size as u32) << IOC_SIZESHIFT)
+}
+
+/// Encode a command without argument data, like `_IO`
+pub const fn io(ty: u8, nr: u8) -> u32 {
+    ioc(IOC_NONE, ty, nr, 0)
+}
+
+/// Encode a command whose `T` argument the kernel writes, like `_IOR`
+pub const fn ior<T>(ty: u8, nr: u8) -> u32 {
+    ioc(IOC_READ, ty, nr, size_of::<T>())
+}
+
+/// Encode a command whose `T` argument the kernel reads, like `_IOW`
+pub const fn iow<T>(ty: u8, nr: u8) -> u32 {
+    ioc(IOC_WRITE, ty, nr, size_of::<T>())
+}
+
+/// Encode a command whose `T` argument is read and written, like `_IOWR`
+pub const fn iowr<T>(ty: u8, nr: u8) -> u32 {
+    ioc(IOC_READ | IOC_WRITE, ty, nr, size_of::<T>())
+}
+
+/// An ioctl `arg` interpreted according to its command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoctlArg {
+    cmd: u32,
+    arg: usize,
+}
+
+impl IoctlArg {
+    /// Pair a command with its raw argument
+    pub fn new(cmd: u32, arg: usize) -> Self {
+        Self { cmd, arg }
+    }
+
+    /// The command
+    pub fn cmd(&self) -> u32 {
+        self.cmd
+    }
+
+    /// Direction bits, a combination of [`IOC_READ`] and [`IOC_WRITE`]
+    pub fn dir(&self) -> u32 {
+        self.cmd >> IOC_DIRSHIFT
+    }
+
+    /// Argument size encoded in the command
+    pub fn size(&self) -> usize {
+        ((self.cmd >> IOC_SIZESHIFT) & ((1 << IOC_SIZEBITS) - 1)) as usize
+    }
+
+    /// Type (driver magic) encoded in the command
+    pub fn ty(&self) -> u8 {
+        (self.cmd >> IOC_TYPESHIFT) as u8
+    }
+
+    /// Number encoded in the command
+    pub fn nr(&self) -> u8 {
+        (self.cmd >> IOC_NRSHIFT) as u8
+    }
+
+    /// The argument as a plain value
+    pub fn as_value(&self) -> usize {
+        self.arg
+    }
+
+    /// Check the command transfers a `T` in direction `dir`
+    fn check<T>(&self, dir: u32) -> UserResult<()> {
+        if self.dir() & dir != dir || self.size() != size_of::<T>() {
+            return Err(Error::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// Read the `T` the argument points to, requires [`IOC_WRITE`]
+    pub fn read_struct<T, A>(&self, uspace: &A) -> UserResult<T>
+    where
+        T: Copy + 'static,
+        A: UserSpaceAccess + ?Sized,
+    {
+        self.check::<T>(IOC_WRITE)?;
+        uspace.read(UserConstPtr::<T>::from(self.arg))
+    }
+
+    /// Write a `T` where the argument points, requires [`IOC_READ`]
+    pub fn write_struct<T, A>(&self, uspace: &A, val: T) -> UserResult<()>
+    where
+        T: 'static,
+        A: UserSpaceAccess + ?Sized,
+    {
+        self.check::<T>(IOC_READ)?;
+        uspace.write(UserPtr::<T>::from(self.arg), val)
+    }
+
+    /// Read the `T` the argument points to, let `f` modify it and write it
+    /// back, requires both directions
+    ///
+    /// Nothing is written back if `f` fails.
+    pub fn update_struct<T, A, R>(
+        &self,
+        uspace: &A,
+        f: impl FnOnce(&mut T) -> UserResult<R>,
+    ) -> UserResult<R>
+    where
+        T: Copy + 'static,
+        A: UserSpaceAccess + ?Sized,
+    {
+        self.check::<T>(IOC_READ | IOC_WRITE)?;
+        let ptr = UserPtr::<T>::from(self.arg);
+        let mut val = uspace.read(ptr)?;
+        let ret = f(&mut val)?;
+        uspace.write(ptr, val)?;
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockUspace;
+
+    // `TIOCGWINSZ`-like and `FIONBIO`-like commands
+    const GET: u32 = ior::<[u16; 4]>(b'T', 0x13);
+    const SET: u32 = iow::<i32>(b'T', 0x21);
+    const SWAP: u32 = iowr::<u32>(b'x', 7);
+
+    #[test]
+    fn command_fields_round_trip() {
+        let arg = IoctlArg::new(GET, 0);
+        assert_eq!(arg.dir(), IOC_READ);
+        assert_eq!(arg.size(), 8);
+        assert_eq!((arg.ty(), arg.nr()), (b'T', 0x13));
+        assert_eq!(IoctlArg::new(io(b'T', 1), 5).as_value(), 5);
+        assert_eq!(IoctlArg::new(SWAP, 0).dir(), IOC_READ | IOC_WRITE);
+    }
+
+    #[test]
+    fn direction_and_size_must_match() {
+        let uspace = MockUspace::new(1);
+        let addr = uspace.addr(0).as_usize();
+        uspace.put(0, 42i32);
+        assert_eq!(
+            IoctlArg::new(SET, addr).read_struct::<i32, _>(&uspace),
+            Ok(42)
+        );
+        assert_eq!(
+            IoctlArg::new(SET, addr).read_struct::<u64, _>(&uspace),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(
+            IoctlArg::new(GET, addr).read_struct::<[u16; 4], _>(&uspace),
+            Err(Error::EINVAL)
+        );
+        IoctlArg::new(GET, addr)
+            .write_struct(&uspace, [1u16, 2, 3, 4])
+            .unwrap();
+        assert_eq!(uspace.get::<[u16; 4]>(0), [1, 2, 3, 4]);
+        assert_eq!(
+            IoctlArg::new(SET, addr).write_struct(&uspace, 0i32),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(uspace.checks.get(), 2);
+    }
+
+    #[test]
+    fn failed_update_writes_nothing_back() {
+        let uspace = MockUspace::new(1);
+        let arg = IoctlArg::new(SWAP, uspace.addr(0).as_usize());
+        uspace.put(0, 1u32);
+        let ret = arg.update_struct(&uspace, |v: &mut u32| {
+            *v += 1;
+            Ok(*v * 10)
+        });
+        assert_eq!(ret, Ok(20));
+        assert_eq!(uspace.get::<u32>(0), 2);
+        let ret = arg.update_struct(&uspace, |v: &mut u32| {
+            *v = 0;
+            Err::<(), _>(Error::E2BIG)
+        });
+        assert_eq!(ret, Err(Error::E2BIG));
+        assert_eq!(uspace.get::<u32>(0), 2);
+        assert_eq!(
+            IoctlArg::new(SET, 0).update_struct(&uspace, |_: &mut i32| Ok(())),
+            Err(Error::EINVAL)
+        );
+    }
+}
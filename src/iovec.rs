@@ -0,0 +1,150 @@
+/// Maximum number of segments accepted by [`import_iovec`], like `UIO_MAXIOV`
+///
+/// [`import_iovec`]: crate::UserSpaceAccess::import_iovec
+pub const UIO_MAXIOV: usize = 1024;
+
+/// `struct iovec`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IoVec {
+    /// Start of the buffer in user space
+    pub iov_base: usize,
+    /// Length of the buffer in bytes
+    pub iov_len: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "alloc")]
+    use crate::Error;
+    use crate::{
+        Access, UserSpaceAccess,
+        mock::{MockUspace, RW},
+    };
+
+    /// Run a regset transfer over a user buffer of `user_len` bytes at offset
+    /// 64, with `f` reporting `ret`; returns the length `f` was given and the
+    /// `iov_len` written back
+    fn regset(
+        uspace: &MockUspace,
+        user_len: usize,
+        kernel_len: usize,
+        ret: usize,
+    ) -> (usize, usize) {
+        let iov = IoVec {
+            iov_base: uspace.addr(64).as_usize(),
+            iov_len: user_len,
+        };
+        uspace.write(uspace.ptr(0), iov).unwrap();
+        let mut given = None;
+        uspace
+            .with_regset_iovec(uspace.ptr(0), kernel_len, Access::WRITE, |_, len| {
+                given = Some(len);
+                Ok(ret)
+            })
+            .unwrap();
+        let back = uspace.read(uspace.cptr::<IoVec>(0)).unwrap();
+        assert_eq!(back.iov_base, iov.iov_base);
+        (given.unwrap(), back.iov_len)
+    }
+
+    #[test]
+    fn user_buffer_longer_than_regset() {
+        let uspace = MockUspace::new(1);
+        assert_eq!(regset(&uspace, 1024, 272, 272), (272, 272));
+    }
+
+    #[test]
+    fn user_buffer_shorter_than_regset() {
+        let uspace = MockUspace::new(1);
+        assert_eq!(regset(&uspace, 100, 272, 100), (100, 100));
+        // A callback overreporting its transfer is clamped
+        assert_eq!(regset(&uspace, 100, 272, 272), (100, 100));
+    }
+
+    #[test]
+    fn zero_length_skips_the_check() {
+        let uspace = MockUspace::new(1);
+        let iov = IoVec {
+            iov_base: 0,
+            iov_len: 0,
+        };
+        uspace.write(uspace.ptr(0), iov).unwrap();
+        uspace
+            .with_regset_iovec(uspace.ptr(0), 272, Access::WRITE, |base, len| {
+                assert!(base.is_null());
+                assert_eq!(len, 0);
+                Ok(0)
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn read_only_buffer_is_rejected() {
+        let uspace = MockUspace::new(2);
+        uspace.protect(1, RW - Access::WRITE);
+        let iov = IoVec {
+            iov_base: uspace.addr(4096).as_usize(),
+            iov_len: 16,
+        };
+        uspace.write(uspace.ptr(0), iov).unwrap();
+        let res = uspace.with_regset_iovec(uspace.ptr(0), 272, Access::WRITE, |_, _| {
+            unreachable!("the buffer is not writable")
+        });
+        assert!(res.is_err());
+        assert_eq!(uspace.read(uspace.cptr::<IoVec>(0)).unwrap(), iov);
+    }
+
+    /// Write an iovec table of `(offset, len)` segments at offset 0
+    #[cfg(feature = "alloc")]
+    fn iovec_table(uspace: &MockUspace, segments: &[(usize, usize)]) {
+        for (i, &(off, len)) in segments.iter().enumerate() {
+            let iov = IoVec {
+                iov_base: uspace.addr(off).as_usize(),
+                iov_len: len,
+            };
+            uspace.put(i * size_of::<IoVec>(), iov);
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn import_checks_every_segment_before_populating() {
+        let uspace = MockUspace::new(3);
+        iovec_table(&uspace, &[(4096, 16), (8192, 0), (8192 + 100, 3900)]);
+        uspace.unpopulate(2);
+        let iovs = uspace
+            .import_iovec(uspace.cptr(0), 3, Access::READ)
+            .unwrap();
+        assert_eq!(iovs.len(), 3);
+        assert!(uspace.is_populated(2));
+        // The table read, then the two non-empty segments
+        assert_eq!(uspace.checks.get(), 3);
+
+        uspace.reset_counts();
+        uspace.unpopulate(1);
+        uspace.unmap(2);
+        assert_eq!(
+            uspace.import_iovec(uspace.cptr(0), 3, Access::READ),
+            Err(Error::EFAULT)
+        );
+        assert!(!uspace.is_populated(1));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn import_limits_count_and_total_length() {
+        let uspace = MockUspace::new(1);
+        assert_eq!(
+            uspace.import_iovec(uspace.cptr(0), UIO_MAXIOV + 1, Access::READ),
+            Err(Error::EINVAL)
+        );
+        iovec_table(&uspace, &[(64, isize::MAX as usize), (64, 1)]);
+        assert_eq!(
+            uspace.import_iovec(uspace.cptr(0), 2, Access::READ),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(uspace.checks.get(), 1);
+    }
+}
@@ -2,10 +2,88 @@
 //! Provides safe abstractions for reading/writing user memory with proper validation.
 
 #![no_std]
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(test)]
+extern crate std;
 
+/// Declare an accessor handing out references that are not tied to the
+/// address space: `unsafe`, unless `legacy-unsafe-refs` keeps it safe
+macro_rules! legacy_ref_fn {
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        #[cfg(feature = "legacy-unsafe-refs")]
+        $(#[$attr])* $vis fn $($rest)*
+        #[cfg(not(feature = "legacy-unsafe-refs"))]
+        $(#[$attr])* $vis unsafe fn $($rest)*
+    };
+}
+
+mod access;
+mod addr;
+#[cfg(feature = "debug-aliasing")]
+mod aliasing;
+mod bitmap;
+mod borrowed;
+mod capture;
+mod copy;
+mod error;
+mod exec;
+mod fault;
+mod ioctl;
+mod iovec;
+mod limits;
+#[cfg(test)]
+mod mock;
+#[cfg(feature = "page-table-uspace")]
+mod page_table;
+mod pin;
 mod ptr;
+mod region;
+mod region_table;
+#[cfg(feature = "alloc")]
+mod secret;
+#[cfg(feature = "alloc")]
+mod session;
+mod stack;
+mod state;
+#[cfg(feature = "struct-helpers")]
+mod structs;
+#[cfg(feature = "alloc")]
+mod transaction;
+#[cfg(feature = "unchecked")]
+mod unchecked;
 mod uspace;
 
+pub use access::*;
+pub use addr::*;
+#[cfg(feature = "debug-aliasing")]
+use aliasing::*;
+pub use bitmap::*;
+pub use borrowed::*;
+pub use capture::*;
+pub use copy::*;
+pub use error::*;
+pub use exec::*;
+pub use fault::*;
+pub use ioctl::*;
+pub use iovec::*;
+pub use limits::*;
+#[cfg(feature = "page-table-uspace")]
+pub use page_table::*;
+pub use pin::*;
 pub use ptr::*;
+pub use region::*;
+pub use region_table::*;
+#[cfg(feature = "alloc")]
+pub use secret::*;
+#[cfg(feature = "alloc")]
+pub use session::*;
+pub use stack::*;
+pub use state::*;
+#[cfg(feature = "struct-helpers")]
+pub use structs::*;
+#[cfg(feature = "alloc")]
+pub use transaction::*;
+#[cfg(feature = "unchecked")]
+pub use unchecked::*;
 pub use uspace::*;
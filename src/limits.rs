@@ -0,0 +1,163 @@
+use memory_addr::PAGE_SIZE_4K;
+
+use crate::{ExecLimits, UIO_MAXIOV, UserResult, UserSpaceAccess};
+
+/// Largest length a single read or write transfers, like Linux's
+/// `MAX_RW_COUNT`
+pub const MAX_RW_COUNT: usize = i32::MAX as usize & !(PAGE_SIZE_4K - 1);
+
+/// Elements a null-terminated scan accepts before its terminator unless
+/// given its own bound, like Linux's `MAX_ARG_STRLEN`
+pub const MAX_SCAN_LEN: usize = 32 * PAGE_SIZE_4K;
+
+/// Policy limits applied by the crate's helpers to user-supplied sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Length a read or write is truncated to, see
+    /// [`clamp_rw_len`](crate::UserSpaceRaw::clamp_rw_len)
+    pub max_rw_count: usize,
+    /// Most segments an iovec table may have, like `UIO_MAXIOV`
+    pub max_iov: usize,
+    /// Limits for string arrays such as `argv` and `envp`
+    pub exec: ExecLimits,
+    /// Pages a bulk copy or scan handles between calls to
+    /// [`relax`](crate::UserSpaceRaw::relax), or 0 to never call it
+    pub relax_pages: usize,
+    /// Elements [`check_null_terminated`](crate::check_null_terminated) and
+    /// the string helpers built on it accept before the terminator
+    pub max_scan_len: usize,
+    /// Bytes from which a copy calls
+    /// [`prefault`](crate::UserSpaceRaw::prefault) on its whole range
+    /// before starting, or 0 to never call it
+    pub prefault_bytes: usize,
+    /// Pages a streaming copy validates and populates ahead of copying, see
+    /// [`copy_from_user_streaming`](crate::UserSpaceAccess::copy_from_user_streaming)
+    pub stream_window_pages: usize,
+}
+
+impl Limits {
+    /// Linux-compatible limits
+    pub const LINUX: Self = Self {
+        max_rw_count: MAX_RW_COUNT,
+        max_iov: UIO_MAXIOV,
+        exec: ExecLimits::LINUX,
+        relax_pages: 16,
+        max_scan_len: MAX_SCAN_LEN,
+        prefault_bytes: 1024 * 1024,
+        stream_window_pages: 64,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::LINUX
+    }
+}
+
+/// Kernel memory charged through [`UserSpaceRaw::charge_kernel_alloc`](crate::UserSpaceRaw::charge_kernel_alloc)
+/// for an allocation sized by user input, uncharged on drop
+///
+/// Helpers hold a charge while they build their result, so early returns
+/// release it; a caller keeping the result charged takes it over with
+/// [`keep`](Self::keep).
+pub struct AllocCharge<'a, A: UserSpaceAccess + ?Sized> {
+    uspace: &'a A,
+    bytes: usize,
+}
+
+impl<'a, A: UserSpaceAccess + ?Sized> AllocCharge<'a, A> {
+    /// Start with nothing charged
+    pub fn new(uspace: &'a A) -> Self {
+        Self { uspace, bytes: 0 }
+    }
+
+    /// Charge `bytes` more, failing as the hook does (typically `ENOMEM`)
+    pub fn charge(&mut self, bytes: usize) -> UserResult<()> {
+        self.uspace.charge_kernel_alloc(bytes)?;
+        self.bytes += bytes;
+        Ok(())
+    }
+
+    /// Bytes currently charged
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Keep the charge, returning its size for the caller to uncharge later
+    pub fn keep(mut self) -> usize {
+        core::mem::take(&mut self.bytes)
+    }
+}
+
+impl<A: UserSpaceAccess + ?Sized> Drop for AllocCharge<'_, A> {
+    fn drop(&mut self) {
+        if self.bytes != 0 {
+            self.uspace.uncharge_kernel_alloc(self.bytes);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::{Access, Error, IoVec, UserSpaceAccess, UserSpaceRaw, mock::MockUspace};
+
+    #[test]
+    fn helpers_follow_the_backend_limits() {
+        let limits = Limits {
+            max_rw_count: 4096,
+            max_iov: 2,
+            exec: ExecLimits {
+                max_arg_strings: 1,
+                ..ExecLimits::LINUX
+            },
+            ..Limits::LINUX
+        };
+        let uspace = MockUspace::new(1).with_limits(limits);
+        assert_eq!(uspace.clamp_rw_len(8192), 4096);
+        assert_eq!(MockUspace::new(1).clamp_rw_len(usize::MAX), MAX_RW_COUNT);
+
+        let end = uspace.put_strs(0, &["a", "b"]);
+        assert_eq!(uspace.read_str_array(uspace.cptr(0)), Err(Error::E2BIG));
+        uspace.put(size_of::<usize>(), 0usize);
+        assert_eq!(uspace.read_str_array(uspace.cptr(0)).unwrap(), ["a"]);
+
+        let iov = uspace.cptr::<IoVec>(end);
+        assert_eq!(
+            uspace.import_iovec(iov, 3, Access::READ).err(),
+            Some(Error::EINVAL)
+        );
+        assert_eq!(
+            uspace.import_iovec(iov, 2, Access::READ).map(|v| v.len()),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn charges_last_as_long_as_the_result() {
+        let uspace = MockUspace::new(1);
+        let end = uspace.put_strs(0, &["abc", "de"]);
+        let argv = uspace.cptr(0);
+        let args = uspace
+            .capture_exec_args(argv, uspace.cptr(end), ExecLimits::LINUX)
+            .unwrap();
+        assert_eq!(args.argv, ["abc", "de"]);
+        assert!(args.charged >= 5);
+        assert_eq!(uspace.charged.get(), args.charged);
+        uspace.uncharge_kernel_alloc(args.charged);
+
+        // Temporary allocations are released before returning
+        uspace
+            .import_iovec(uspace.cptr(end), 2, Access::READ)
+            .unwrap();
+        assert_eq!(uspace.charged.get(), 0);
+
+        // A refused charge fails the call and leaves nothing charged
+        uspace.charge_limit.set(args.charged - 1);
+        assert_eq!(
+            uspace.capture_exec_args(argv, argv, ExecLimits::LINUX),
+            Err(Error::ENOMEM)
+        );
+        assert_eq!(uspace.charged.get(), 0);
+    }
+}
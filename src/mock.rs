@@ -0,0 +1,761 @@
+//! Host backend for the unit tests: "user" memory is a page-aligned heap
+//! allocation whose pages can be unmapped, protected and left unpopulated
+
+#![allow(dead_code)]
+
+use core::cell::{Cell, RefCell};
+use std::{
+    alloc::{Layout, alloc_zeroed, dealloc},
+    sync::Once,
+    thread_local,
+    vec::Vec,
+};
+
+use memory_addr::{PhysAddr, VirtAddr, VirtAddrRange};
+
+#[cfg(feature = "debug-double-fetch")]
+use crate::DoubleFetch;
+use crate::{
+    Access, AccessResult, AccessStateBackend, ArchUserAccess, Error, Limits, MemoryType,
+    PrefaultHint, SyncKind, USER_ADDR_END, UserAccessError, UserConstPtr, UserCopyBackend, UserPtr,
+    UserResult, UserSpaceRaw, check_flags, copy_from_user_fallible, copy_to_user_fallible,
+    set_access_state_backend, set_arch_user_access, set_user_copy_backend,
+};
+
+/// Flags of a fresh mock page
+pub(crate) const RW: Access = Access::READ.union(Access::WRITE).union(Access::USER);
+
+thread_local! {
+    static FLAG: Cell<bool> = const { Cell::new(false) };
+    static EXPECTED: Cell<Option<(VirtAddrRange, Access)>> = const { Cell::new(None) };
+    static FAULTED: Cell<bool> = const { Cell::new(false) };
+    static NOFAULT: Cell<bool> = const { Cell::new(false) };
+    static FLAG_WRITES: Cell<usize> = const { Cell::new(0) };
+    static ABORT: Cell<bool> = const { Cell::new(false) };
+    static COPY_LIMIT: Cell<Option<usize>> = const { Cell::new(None) };
+    static COPIES: Cell<usize> = const { Cell::new(0) };
+    static LAST_COPY: Cell<Option<usize>> = const { Cell::new(None) };
+    static TAG_MASK: Cell<usize> = const { Cell::new(0) };
+    static ARCH_ENABLES: Cell<usize> = const { Cell::new(0) };
+    static ARCH_DISABLES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Access state of the test thread, as each test runs on its own
+struct ThreadState;
+
+impl AccessStateBackend for ThreadState {
+    fn set(&self) {
+        FLAG_WRITES.set(FLAG_WRITES.get() + 1);
+        FLAG.set(true);
+    }
+
+    fn clear(&self) {
+        FLAG_WRITES.set(FLAG_WRITES.get() + 1);
+        FLAG.set(false);
+    }
+
+    fn is_set(&self) -> bool {
+        FLAG.get()
+    }
+
+    fn set_expected(&self, expected: Option<(VirtAddrRange, Access)>) {
+        EXPECTED.set(expected);
+    }
+
+    fn expected(&self) -> Option<(VirtAddrRange, Access)> {
+        EXPECTED.get()
+    }
+
+    fn set_faulted(&self, faulted: bool) {
+        FAULTED.set(faulted);
+    }
+
+    fn faulted(&self) -> bool {
+        FAULTED.get()
+    }
+
+    fn set_nofault(&self, nofault: bool) {
+        NOFAULT.set(nofault);
+    }
+
+    fn nofault(&self) -> bool {
+        NOFAULT.get()
+    }
+
+    fn should_abort(&self) -> bool {
+        ABORT.get()
+    }
+}
+
+/// User copies of the test thread, faulting after the bytes allowed by
+/// [`fault_copies_after`] and ignoring the tag of a [`MockUspace::tagged`]
+/// space like the hardware would
+struct ThreadCopy;
+
+impl ThreadCopy {
+    unsafe fn copy(&self, dst: *mut u8, src: *const u8, len: usize) -> Result<(), usize> {
+        COPIES.set(COPIES.get() + 1);
+        let (dst, src) = (strip_tag(dst as usize), strip_tag(src as usize));
+        let (dst, src) = (dst as *mut u8, src as *const u8);
+        let done = COPY_LIMIT.get().map_or(len, |limit| limit.min(len));
+        unsafe { core::ptr::copy_nonoverlapping(src, dst, done) };
+        if done < len { Err(done) } else { Ok(()) }
+    }
+}
+
+impl UserCopyBackend for ThreadCopy {
+    unsafe fn copy_from_user(&self, dst: *mut u8, src: *const u8, len: usize) -> Result<(), usize> {
+        LAST_COPY.set(Some(src as usize));
+        unsafe { self.copy(dst, src, len) }
+    }
+
+    unsafe fn copy_to_user(&self, dst: *mut u8, src: *const u8, len: usize) -> Result<(), usize> {
+        LAST_COPY.set(Some(dst as usize));
+        unsafe { self.copy(dst, src, len) }
+    }
+}
+
+struct CountingArch;
+
+impl ArchUserAccess for CountingArch {
+    fn enable(&self) {
+        ARCH_ENABLES.set(ARCH_ENABLES.get() + 1);
+    }
+
+    fn disable(&self) {
+        ARCH_DISABLES.set(ARCH_DISABLES.get() + 1);
+    }
+}
+
+/// Install the per-thread access state, copy routines and counting arch hooks
+pub(crate) fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        assert!(set_access_state_backend(&ThreadState));
+        assert!(set_user_copy_backend(&ThreadCopy));
+        assert!(set_arch_user_access(&CountingArch));
+    });
+}
+
+/// Make user copies on this thread fault after `limit` bytes, or never
+pub(crate) fn fault_copies_after(limit: Option<usize>) {
+    COPY_LIMIT.set(limit);
+}
+
+/// User copies on this thread so far
+pub(crate) fn copies() -> usize {
+    COPIES.get()
+}
+
+/// Writes of the access flag on this thread so far
+pub(crate) fn flag_writes() -> usize {
+    FLAG_WRITES.get()
+}
+
+/// `(enable, disable)` calls of the arch hooks on this thread so far
+pub(crate) fn arch_calls() -> (usize, usize) {
+    (ARCH_ENABLES.get(), ARCH_DISABLES.get())
+}
+
+/// User address of the last copy on this thread, as passed in
+pub(crate) fn last_copy() -> Option<VirtAddr> {
+    LAST_COPY.get().map(VirtAddr::from)
+}
+
+fn strip_tag(addr: usize) -> usize {
+    addr & !TAG_MASK.get()
+}
+
+/// Make long accesses on this thread stop early, as for a pending signal
+pub(crate) fn set_abort(abort: bool) {
+    ABORT.set(abort);
+}
+
+/// Size of the mock pages
+pub(crate) const PAGE_SIZE: usize = 4096;
+
+#[derive(Debug)]
+struct Page {
+    flags: Access,
+    populated: bool,
+    pins: usize,
+    phys: Option<usize>,
+}
+
+/// Mock address space over host memory
+#[derive(Debug)]
+pub(crate) struct MockUspace {
+    base: *mut u8,
+    layout: Layout,
+    page_size: usize,
+    pages: RefCell<Vec<Page>>,
+    permissive: bool,
+    combined: bool,
+    direct: bool,
+    tagged: bool,
+    fault_after: Cell<Option<usize>>,
+    generation: Cell<u64>,
+    limits: Limits,
+    /// Calls of `check_region_access`
+    pub(crate) checks: Cell<usize>,
+    /// Calls of `populate_region`
+    pub(crate) populates: Cell<usize>,
+    /// Calls of `populate_region` made inside a user access window
+    pub(crate) window_populates: Cell<usize>,
+    /// Walks of the pages made by an overridden `ensure_region`
+    pub(crate) ensures: Cell<usize>,
+    /// Error `populate_region` fails with, if any
+    pub(crate) populate_error: Cell<Option<Error>>,
+    /// Whether populating would block, failing non-blocking populates
+    pub(crate) would_block: Cell<bool>,
+    /// Error long accesses are interrupted with, if any
+    pub(crate) interrupt: Cell<Option<Error>>,
+    /// Whether the access flag was set, for each call to `relax`
+    pub(crate) relaxes: RefCell<Vec<bool>>,
+    /// Ranges and hints passed to `prefault`
+    pub(crate) prefaults: RefCell<Vec<(VirtAddrRange, PrefaultHint)>>,
+    /// Error `prefault` fails with instead of populating, if any
+    pub(crate) prefault_error: Cell<Option<Error>>,
+    /// Lowest page unmapped pages may be grown down to, like a stack
+    pub(crate) grow_limit: Cell<Option<usize>>,
+    /// Calls to `try_grow_region`
+    pub(crate) grows: Cell<usize>,
+    /// Pages whose protection key denies writes
+    pub(crate) write_denied: RefCell<Vec<usize>>,
+    /// Calls of the raw copy hooks of an [`indirect`](Self::indirect) space
+    pub(crate) raw_copies: Cell<usize>,
+    /// Memory type reported for every range
+    pub(crate) memory: Cell<MemoryType>,
+    /// Ranges and kinds passed to `sync_after_write`
+    pub(crate) syncs: RefCell<Vec<(VirtAddrRange, SyncKind)>>,
+    /// Byte a racing user thread bumps after each copy from user space, and
+    /// how many more times it does
+    racer: Cell<Option<(usize, usize)>>,
+    /// Double fetches passed to `on_double_fetch`
+    #[cfg(feature = "debug-double-fetch")]
+    pub(crate) fetches: RefCell<Vec<DoubleFetch>>,
+    /// Last error passed to `on_access_error`
+    pub(crate) last_error: Cell<Option<UserAccessError>>,
+    /// Bytes currently charged
+    pub(crate) charged: Cell<usize>,
+    /// Most bytes `charge_kernel_alloc` accepts in total
+    pub(crate) charge_limit: Cell<usize>,
+    /// Byte stored at an offset once `checks` reaches a count, like another
+    /// thread writing to the memory meanwhile
+    pub(crate) tamper: Cell<Option<(usize, usize, u8)>>,
+}
+
+impl MockUspace {
+    /// `pages` pages, all mapped read-write and populated
+    pub(crate) fn new(pages: usize) -> Self {
+        Self::with_page_size(pages, PAGE_SIZE)
+    }
+
+    /// `pages` pages of `page_size` bytes, all mapped read-write and
+    /// populated
+    pub(crate) fn with_page_size(pages: usize, page_size: usize) -> Self {
+        init();
+        let layout = Layout::from_size_align(pages * page_size, page_size).unwrap();
+        let base = unsafe { alloc_zeroed(layout) };
+        assert!(!base.is_null());
+        let pages = (0..pages)
+            .map(|i| Page {
+                flags: RW,
+                populated: true,
+                pins: 0,
+                phys: Some(base as usize + i * page_size),
+            })
+            .collect();
+        Self {
+            base,
+            layout,
+            page_size,
+            pages: RefCell::new(pages),
+            permissive: false,
+            combined: false,
+            direct: true,
+            tagged: false,
+            fault_after: Cell::new(None),
+            generation: Cell::new(0),
+            limits: Limits::LINUX,
+            checks: Cell::new(0),
+            populates: Cell::new(0),
+            window_populates: Cell::new(0),
+            ensures: Cell::new(0),
+            populate_error: Cell::new(None),
+            would_block: Cell::new(false),
+            interrupt: Cell::new(None),
+            relaxes: RefCell::new(Vec::new()),
+            prefaults: RefCell::new(Vec::new()),
+            prefault_error: Cell::new(None),
+            grow_limit: Cell::new(None),
+            grows: Cell::new(0),
+            write_denied: RefCell::new(Vec::new()),
+            raw_copies: Cell::new(0),
+            memory: Cell::new(MemoryType::Normal),
+            syncs: RefCell::new(Vec::new()),
+            racer: Cell::new(None),
+            #[cfg(feature = "debug-double-fetch")]
+            fetches: RefCell::new(Vec::new()),
+            last_error: Cell::new(None),
+            charged: Cell::new(0),
+            charge_limit: Cell::new(usize::MAX),
+            tamper: Cell::new(None),
+        }
+    }
+
+    /// Only reachable through the raw copy hooks, like a separate address
+    /// space
+    pub(crate) fn indirect(mut self) -> Self {
+        self.direct = false;
+        self
+    }
+
+    /// Claim the whole address space above the first page and approve every
+    /// check, like a backend trusting the crate to reject bad ranges
+    pub(crate) fn permissive(mut self) -> Self {
+        self.permissive = true;
+        self
+    }
+
+    /// Check and populate in one walk of the pages, overriding
+    /// `ensure_region` instead of going through the separate hooks
+    pub(crate) fn combined(mut self) -> Self {
+        self.combined = true;
+        self
+    }
+
+    /// Ignore the top byte of addresses, like arm64 Top-Byte-Ignore
+    pub(crate) fn tagged(mut self) -> Self {
+        self.tagged = true;
+        TAG_MASK.set(0xff << 56);
+        self
+    }
+
+    /// Use `limits` instead of the Linux ones
+    pub(crate) fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Let the next `checks` checks pass, then fail one with `EFAULT`, like
+    /// a concurrent unmap racing the caller
+    pub(crate) fn fault_after(&self, checks: usize) {
+        self.fault_after.set(Some(checks));
+    }
+
+    /// Address of byte `off`
+    pub(crate) fn addr(&self, off: usize) -> VirtAddr {
+        VirtAddr::from(self.base as usize + off)
+    }
+
+    /// Read-only pointer to byte `off`
+    pub(crate) fn cptr<T>(&self, off: usize) -> UserConstPtr<T> {
+        UserConstPtr::from(self.base as usize + off)
+    }
+
+    /// Writable pointer to byte `off`
+    pub(crate) fn ptr<T>(&self, off: usize) -> UserPtr<T> {
+        UserPtr::from(self.base as usize + off)
+    }
+
+    /// Range of the `len` bytes at byte `off`
+    pub(crate) fn range(&self, off: usize, len: usize) -> VirtAddrRange {
+        VirtAddrRange::from_start_size(self.addr(off), len)
+    }
+
+    /// Copy `data` to byte `off`
+    pub(crate) fn fill(&self, off: usize, data: &[u8]) {
+        assert!(off + data.len() <= self.layout.size());
+        unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), self.base.add(off), data.len()) };
+    }
+
+    /// Copy `val` to byte `off`
+    pub(crate) fn put<T: Copy>(&self, off: usize, val: T) {
+        assert!(off + size_of::<T>() <= self.layout.size());
+        unsafe { self.base.add(off).cast::<T>().write_unaligned(val) };
+    }
+
+    /// Write a null-terminated array of pointers to NUL-terminated copies of
+    /// `strs` at byte `off`, the strings right after it; returns the first
+    /// pointer-aligned offset past them
+    pub(crate) fn put_strs(&self, off: usize, strs: &[&str]) -> usize {
+        let mut at = off + (strs.len() + 1) * size_of::<usize>();
+        for (i, s) in strs.iter().enumerate() {
+            self.put(off + i * size_of::<usize>(), self.addr(at).as_usize());
+            self.fill(at, s.as_bytes());
+            self.put(at + s.len(), 0u8);
+            at += s.len() + 1;
+        }
+        self.put(off + strs.len() * size_of::<usize>(), 0usize);
+        at.next_multiple_of(size_of::<usize>())
+    }
+
+    /// Copy of the `len` bytes at byte `off`
+    pub(crate) fn load(&self, off: usize, len: usize) -> Vec<u8> {
+        assert!(off + len <= self.layout.size());
+        unsafe { core::slice::from_raw_parts(self.base.add(off), len) }.to_vec()
+    }
+
+    /// Copy of the `T` at byte `off`
+    pub(crate) fn get<T: Copy>(&self, off: usize) -> T {
+        assert!(off + size_of::<T>() <= self.layout.size());
+        unsafe { self.base.add(off).cast::<T>().read_unaligned() }
+    }
+
+    /// Set the flags of user page `page`, empty to unmap it
+    pub(crate) fn protect(&self, page: usize, flags: Access) {
+        let flags = if flags.is_empty() {
+            flags
+        } else {
+            flags | Access::USER
+        };
+        self.set_flags(page, flags);
+    }
+
+    /// Make page `page` a supervisor-only mapping with `flags`
+    pub(crate) fn protect_supervisor(&self, page: usize, flags: Access) {
+        self.set_flags(page, flags - Access::USER);
+    }
+
+    fn set_flags(&self, page: usize, flags: Access) {
+        self.pages.borrow_mut()[page].flags = flags;
+        self.generation.set(self.generation.get() + 1);
+    }
+
+    /// Unmap page `page`
+    pub(crate) fn unmap(&self, page: usize) {
+        self.protect(page, Access::empty());
+    }
+
+    /// Leave page `page` mapped but not yet faulted in
+    pub(crate) fn unpopulate(&self, page: usize) {
+        self.pages.borrow_mut()[page].populated = false;
+    }
+
+    /// Bump the byte at `off` after each of the next `times` copies from
+    /// user space, like a racing user thread
+    pub(crate) fn race(&self, off: usize, times: usize) {
+        self.racer.set(Some((off, times)));
+    }
+
+    /// Whether page `page` is populated
+    pub(crate) fn is_populated(&self, page: usize) -> bool {
+        self.pages.borrow()[page].populated
+    }
+
+    /// Pins held on page `page`
+    pub(crate) fn pins(&self, page: usize) -> usize {
+        self.pages.borrow()[page].pins
+    }
+
+    /// Back page `page` by `phys`, or by nothing the backend can translate
+    pub(crate) fn set_phys(&self, page: usize, phys: Option<usize>) {
+        self.pages.borrow_mut()[page].phys = phys;
+    }
+
+    /// Reset the hook counters
+    pub(crate) fn reset_counts(&self) {
+        self.checks.set(0);
+        self.populates.set(0);
+        self.ensures.set(0);
+    }
+
+    /// Indices of the pages of `range`, which must be inside the memory
+    fn page_indices(&self, range: VirtAddrRange) -> UserResult<core::ops::Range<usize>> {
+        let base = self.base as usize;
+        let end = base + self.layout.size();
+        if range.start.as_usize() < base || range.end.as_usize() > end {
+            return Err(Error::EFAULT);
+        }
+        let first = (range.start.as_usize() - base) / self.page_size;
+        let last = (range.end.as_usize() - base).div_ceil(self.page_size);
+        Ok(first..last)
+    }
+}
+
+impl Drop for MockUspace {
+    fn drop(&mut self) {
+        if self.tagged {
+            TAG_MASK.set(0);
+        }
+        unsafe { dealloc(self.base, self.layout) };
+    }
+}
+
+impl UserSpaceRaw for MockUspace {
+    fn check_region_access(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+        self.checks.set(self.checks.get() + 1);
+        if let Some((count, off, byte)) = self.tamper.get()
+            && count == self.checks.get()
+        {
+            self.fill(off, &[byte]);
+        }
+        match self.fault_after.get() {
+            Some(0) => {
+                self.fault_after.set(None);
+                return Err(Error::EFAULT);
+            }
+            Some(n) => self.fault_after.set(Some(n - 1)),
+            None => {}
+        }
+        if self.permissive {
+            return Ok(());
+        }
+        let pages = self.pages.borrow();
+        for page in self.page_indices(range)? {
+            if pages[page].flags.is_empty() || !pages[page].flags.contains(access_flags) {
+                return Err(Error::EFAULT);
+            }
+        }
+        Ok(())
+    }
+
+    fn populate_region(&self, range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        self.populates.set(self.populates.get() + 1);
+        if FLAG.get() {
+            self.window_populates.set(self.window_populates.get() + 1);
+        }
+        if let Some(error) = self.populate_error.get() {
+            return Err(error);
+        }
+        if self.permissive {
+            return Ok(());
+        }
+        let indices = self.page_indices(range)?;
+        let mut pages = self.pages.borrow_mut();
+        for page in indices {
+            pages[page].populated = true;
+        }
+        Ok(())
+    }
+
+    fn ensure_region(&self, range: VirtAddrRange, access_flags: Access) -> AccessResult<()> {
+        let flags = check_flags(access_flags);
+        if !self.combined {
+            self.check_region_access_detailed(range, flags)?;
+            return self.populate_region_detailed(range, flags);
+        }
+        self.ensures.set(self.ensures.get() + 1);
+        if FLAG.get() {
+            self.window_populates.set(self.window_populates.get() + 1);
+        }
+        if self.permissive {
+            return Ok(());
+        }
+        let walk = || {
+            let indices = self.page_indices(range)?;
+            let mut pages = self.pages.borrow_mut();
+            for page in indices.clone() {
+                if pages[page].flags.is_empty() || !pages[page].flags.contains(flags) {
+                    return Err(Error::EFAULT);
+                }
+            }
+            if let Some(error) = self.populate_error.get() {
+                return Err(error);
+            }
+            for page in indices {
+                pages[page].populated = true;
+            }
+            Ok(())
+        };
+        walk().map_err(|e| UserAccessError::from_backend(range.start, access_flags, e))
+    }
+
+    fn prefault(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+        hint: PrefaultHint,
+    ) -> UserResult<()> {
+        self.prefaults.borrow_mut().push((range, hint));
+        if let Some(error) = self.prefault_error.get() {
+            return Err(error);
+        }
+        self.populate_region(range, access_flags)
+    }
+
+    fn populate_region_nonblocking(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<()> {
+        if self.would_block.get() {
+            return Err(Error::EAGAIN);
+        }
+        self.populate_region(range, access_flags)
+    }
+
+    fn check_region_resident(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<bool> {
+        self.check_region_access(range, access_flags)?;
+        if self.permissive {
+            return Ok(true);
+        }
+        let pages = self.pages.borrow();
+        Ok(self.page_indices(range)?.all(|page| pages[page].populated))
+    }
+
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    fn charge_kernel_alloc(&self, bytes: usize) -> UserResult<()> {
+        let charged = self.charged.get() + bytes;
+        if charged > self.charge_limit.get() {
+            return Err(Error::ENOMEM);
+        }
+        self.charged.set(charged);
+        Ok(())
+    }
+
+    fn uncharge_kernel_alloc(&self, bytes: usize) {
+        self.charged.set(self.charged.get() - bytes);
+    }
+
+    fn on_access_error(&self, error: &UserAccessError) {
+        self.last_error.set(Some(*error));
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+
+    fn should_interrupt(&self) -> Option<Error> {
+        self.interrupt.get()
+    }
+
+    fn relax(&self) {
+        self.relaxes.borrow_mut().push(FLAG.get());
+    }
+
+    fn check_pkey(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+        if self.permissive || !access_flags.contains(Access::WRITE) {
+            return Ok(());
+        }
+        let denied = self.write_denied.borrow();
+        match self.page_indices(range)?.any(|page| denied.contains(&page)) {
+            true => Err(Error::EPERM),
+            false => Ok(()),
+        }
+    }
+
+    unsafe fn raw_copy_from_user(&self, dst: *mut u8, src: VirtAddr, len: usize) -> UserResult<()> {
+        if self.direct {
+            unsafe { copy_from_user_fallible(dst, src.as_ptr(), len) }
+                .map_err(|_| Error::EFAULT)?;
+        } else {
+            self.raw_copies.set(self.raw_copies.get() + 1);
+            self.page_indices(VirtAddrRange::from_start_size(src, len))?;
+            unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), dst, len) };
+        }
+        if let Some((off, times)) = self.racer.get()
+            && times > 0
+        {
+            self.racer.set(Some((off, times - 1)));
+            unsafe { *self.base.add(off) += 1 };
+        }
+        Ok(())
+    }
+
+    unsafe fn raw_copy_to_user(&self, dst: VirtAddr, src: *const u8, len: usize) -> UserResult<()> {
+        if self.direct {
+            return unsafe { copy_to_user_fallible(dst.as_mut_ptr(), src, len) }
+                .map_err(|_| Error::EFAULT);
+        }
+        self.raw_copies.set(self.raw_copies.get() + 1);
+        self.page_indices(VirtAddrRange::from_start_size(dst, len))?;
+        unsafe { core::ptr::copy_nonoverlapping(src, dst.as_mut_ptr(), len) };
+        Ok(())
+    }
+
+    fn direct_map(&self) -> bool {
+        self.direct
+    }
+
+    fn memory_type(&self, _range: VirtAddrRange) -> MemoryType {
+        self.memory.get()
+    }
+
+    fn untag_addr(&self, addr: VirtAddr) -> VirtAddr {
+        if self.tagged {
+            VirtAddr::from(strip_tag(addr.as_usize()))
+        } else {
+            addr
+        }
+    }
+
+    fn force_write_region(&self, addr: VirtAddr, data: &[u8]) -> UserResult<()> {
+        let off = addr.as_usize() - self.base as usize;
+        self.fill(off, data);
+        Ok(())
+    }
+
+    fn sync_after_write(&self, range: VirtAddrRange, kind: SyncKind) {
+        self.syncs.borrow_mut().push((range, kind));
+    }
+
+    #[cfg(feature = "debug-double-fetch")]
+    fn on_double_fetch(&self, fetch: &DoubleFetch) {
+        self.fetches.borrow_mut().push(*fetch);
+    }
+
+    fn try_grow_region(&self, range: VirtAddrRange, _access_flags: Access) -> UserResult<bool> {
+        self.grows.set(self.grows.get() + 1);
+        let pages = self.page_indices(range)?;
+        if self
+            .grow_limit
+            .get()
+            .is_none_or(|limit| pages.start < limit)
+        {
+            return Ok(false);
+        }
+        for page in pages {
+            self.protect(page, RW);
+        }
+        Ok(true)
+    }
+
+    fn pin_pages(&self, range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        let indices = self.page_indices(range)?;
+        let mut pages = self.pages.borrow_mut();
+        for page in indices {
+            pages[page].pins += 1;
+        }
+        Ok(())
+    }
+
+    fn unpin_pages(&self, range: VirtAddrRange) {
+        let indices = self.page_indices(range).unwrap();
+        let mut pages = self.pages.borrow_mut();
+        for page in indices {
+            pages[page].pins -= 1;
+        }
+    }
+
+    fn virt_to_phys(&self, vaddr: VirtAddr) -> Option<PhysAddr> {
+        let page = self
+            .page_indices(VirtAddrRange::from_start_size(vaddr, 1))
+            .ok()?
+            .start;
+        let phys = self.pages.borrow()[page].phys?;
+        Some(PhysAddr::from(
+            phys + (vaddr.as_usize() & (self.page_size - 1)),
+        ))
+    }
+
+    fn user_addr_range(&self) -> VirtAddrRange {
+        if self.permissive {
+            let end = usize::MAX & !(self.page_size - 1);
+            return VirtAddrRange::new(VirtAddr::from(self.page_size), VirtAddr::from(end));
+        }
+        VirtAddrRange::new(VirtAddr::from(0), VirtAddr::from(USER_ADDR_END))
+    }
+}
@@ -0,0 +1,108 @@
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange};
+use page_table_multiarch::{GenericPTE, PageTable64, PagingHandler, PagingMetaData};
+
+use crate::{Access, Error, UserResult, UserSpaceRaw};
+
+/// Page table lookups needed by [`PageTableUspace`]
+pub trait UserPageTable {
+    /// Flags and size of the page mapping `vaddr`, or `None` if it is not
+    /// mapped
+    fn query_page(&self, vaddr: VirtAddr) -> Option<(Access, usize)>;
+}
+
+impl<M, PTE, H> UserPageTable for PageTable64<M, PTE, H>
+where
+    M: PagingMetaData<VirtAddr = VirtAddr>,
+    PTE: GenericPTE,
+    H: PagingHandler,
+{
+    fn query_page(&self, vaddr: VirtAddr) -> Option<(Access, usize)> {
+        self.query(vaddr)
+            .ok()
+            .map(|(_, flags, size)| (flags.into(), size as usize))
+    }
+}
+
+/// Handler standing in for the page fault path of a [`PageTableUspace`]
+///
+/// Called with a page missing from the table, or mapped without the needed
+/// flags, it should map the page or fail as the fault would.
+pub type FaultHandler<'a> = &'a dyn Fn(VirtAddr, Access) -> UserResult<()>;
+
+/// Reference backend that checks user accesses by walking a page table
+///
+/// Meant for bringing up a kernel before it has its own memory areas: every
+/// page of a checked range must be mapped with the requested flags and
+/// `USER`, and anything else fails with `EFAULT`. Without a
+/// [fault handler](Self::with_fault_handler) nothing is ever populated;
+/// with one, missing pages are handed to it during the check, as a fault on
+/// touching them would. Real kernels should check their memory areas
+/// instead, as the table alone cannot tell lazily mapped memory from none.
+pub struct PageTableUspace<'a, PT: UserPageTable + ?Sized> {
+    table: &'a PT,
+    fault_handler: Option<FaultHandler<'a>>,
+}
+
+impl<'a, PT: UserPageTable + ?Sized> PageTableUspace<'a, PT> {
+    /// Check accesses against `table`
+    pub fn new(table: &'a PT) -> Self {
+        Self {
+            table,
+            fault_handler: None,
+        }
+    }
+
+    /// Give pages the check finds missing to `handler`
+    pub fn with_fault_handler(mut self, handler: FaultHandler<'a>) -> Self {
+        self.fault_handler = Some(handler);
+        self
+    }
+
+    /// The walked page table
+    pub fn table(&self) -> &'a PT {
+        self.table
+    }
+
+    /// Size of the page at `addr` if it is mapped with `flags`
+    fn mapped_with(&self, addr: VirtAddr, flags: Access) -> Option<usize> {
+        self.table
+            .query_page(addr)
+            .filter(|(mapped, _)| mapped.contains(flags))
+            .map(|(_, size)| size)
+    }
+}
+
+impl<PT: UserPageTable + ?Sized> UserSpaceRaw for PageTableUspace<'_, PT> {
+    fn check_region_access(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+        let flags = access_flags | Access::USER;
+        let mut addr = range.start;
+        while addr < range.end {
+            let size = match (self.mapped_with(addr, flags), self.fault_handler) {
+                (Some(size), _) => size,
+                (None, Some(handler)) => {
+                    handler(addr.align_down_4k(), flags)?;
+                    self.mapped_with(addr, flags).ok_or(Error::EFAULT)?
+                }
+                (None, None) => return Err(Error::EFAULT),
+            };
+            match addr.align_down(size).checked_add(size) {
+                Some(next) => addr = next,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn populate_region(&self, _range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        Ok(())
+    }
+
+    fn check_region_resident(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<bool> {
+        self.check_region_access(range, access_flags)?;
+        Ok(true)
+    }
+}
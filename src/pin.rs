@@ -0,0 +1,257 @@
+use core::{iter, slice};
+
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, VirtAddrRange};
+
+use crate::{Access, Error, UserResult, UserSpaceAccess};
+
+/// Segment of a [`PinnedRegion`] beyond a device's DMA address width,
+/// returned by [`PinnedRegion::dma_segments`]
+///
+/// Callers typically fall back to a bounce buffer below the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmaOutOfRange {
+    /// Start of the offending segment
+    pub phys: PhysAddr,
+    /// Length of the offending segment
+    pub len: usize,
+}
+
+/// User pages pinned by [`pin_region`](UserSpaceAccess::pin_region)
+///
+/// The backend keeps the pages mapped and in place until the region is
+/// dropped, so unlike the other accessors its slices stay valid across a
+/// sleep, e.g. for an AIO completion or a driver filling the buffer later.
+/// Dropping unpins the pages.
+pub struct PinnedRegion<'a, A: UserSpaceAccess + ?Sized> {
+    uspace: &'a A,
+    range: VirtAddrRange,
+    flags: Access,
+}
+
+impl<'a, A: UserSpaceAccess + ?Sized> PinnedRegion<'a, A> {
+    pub(crate) fn new(uspace: &'a A, range: VirtAddrRange, flags: Access) -> Self {
+        Self {
+            uspace,
+            range,
+            flags,
+        }
+    }
+
+    /// The pinned range
+    pub fn range(&self) -> VirtAddrRange {
+        self.range
+    }
+
+    /// The access flags the range was pinned for
+    pub fn flags(&self) -> Access {
+        self.flags
+    }
+
+    /// Untagged range handed to the backend
+    fn untagged(&self) -> VirtAddrRange {
+        VirtAddrRange::from_start_size(self.uspace.untag_addr(self.range.start), self.range.size())
+    }
+
+    /// The pinned bytes
+    pub fn as_slice(&self) -> &[u8] {
+        if self.range.is_empty() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.range.start.as_ptr(), self.range.size()) }
+    }
+
+    /// The pinned bytes, mutably
+    ///
+    /// Panics unless the region was pinned for [`Access::WRITE`].
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        assert!(
+            self.flags.contains(Access::WRITE),
+            "user region was not pinned for writing"
+        );
+        if self.range.is_empty() {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.range.start.as_mut_ptr(), self.range.size()) }
+    }
+
+    /// Physical segments backing the region, in order, as `(start, len)`
+    ///
+    /// Pages translated by [`virt_to_phys`](crate::UserSpaceRaw::virt_to_phys)
+    /// are merged while physically contiguous; the first and last segments
+    /// start and end where the region does, not on page boundaries. A page
+    /// the backend cannot translate yields `EFAULT` and ends the iteration.
+    pub fn phys_segments(&self) -> impl Iterator<Item = UserResult<(PhysAddr, usize)>> + '_ {
+        let end = self.untagged().end;
+        let mut addr = self.untagged().start;
+        iter::from_fn(move || {
+            if addr >= end {
+                return None;
+            }
+            let Some((phys, mut len)) = self.phys_chunk(addr, end) else {
+                addr = end;
+                return Some(Err(Error::EFAULT));
+            };
+            while addr + len < end {
+                match self.phys_chunk(addr + len, end) {
+                    Some((next, next_len)) if next == phys + len => len += next_len,
+                    _ => break,
+                }
+            }
+            addr += len;
+            Some(Ok((phys, len)))
+        })
+    }
+
+    /// Like [`phys_segments`](Self::phys_segments), for a device that can
+    /// only address physical memory up to `dma_limit`, inclusive
+    ///
+    /// Fails with the first segment reaching past the limit, before anything
+    /// is handed out. Pages that cannot be translated are left to fail in the
+    /// returned iterator.
+    pub fn dma_segments(
+        &self,
+        dma_limit: u64,
+    ) -> Result<impl Iterator<Item = UserResult<(PhysAddr, usize)>> + '_, DmaOutOfRange> {
+        match self
+            .phys_segments()
+            .map_while(Result::ok)
+            .find(|&(phys, len)| {
+                phys.as_usize()
+                    .checked_add(len - 1)
+                    .is_none_or(|last| last as u64 > dma_limit)
+            }) {
+            Some((phys, len)) => Err(DmaOutOfRange { phys, len }),
+            None => Ok(self.phys_segments()),
+        }
+    }
+
+    /// Physical address of `addr` and the bytes until its page or the
+    /// region ends, or `None` if the backend cannot translate it
+    fn phys_chunk(&self, addr: VirtAddr, end: VirtAddr) -> Option<(PhysAddr, usize)> {
+        let page_end = addr.align_down(self.uspace.page_size()) + self.uspace.page_size();
+        let phys = self.uspace.virt_to_phys(addr)?;
+        Some((phys, page_end.min(end) - addr))
+    }
+}
+
+impl<A: UserSpaceAccess + ?Sized> Drop for PinnedRegion<'_, A> {
+    fn drop(&mut self) {
+        if !self.range.is_empty() {
+            self.uspace.unpin_pages(self.untagged());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::mock::MockUspace;
+
+    #[test]
+    fn pins_nest_and_drop() {
+        let uspace = MockUspace::new(3);
+        let range = uspace.range(4000, 200);
+        let outer = uspace.pin_region(range, Access::READ).unwrap();
+        {
+            let _inner = uspace.pin_region(range, Access::READ).unwrap();
+            assert_eq!([uspace.pins(0), uspace.pins(1), uspace.pins(2)], [2, 2, 0]);
+        }
+        assert_eq!([uspace.pins(0), uspace.pins(1)], [1, 1]);
+        drop(outer);
+        assert_eq!([uspace.pins(0), uspace.pins(1)], [0, 0]);
+    }
+
+    #[test]
+    fn failed_pin_leaves_nothing_pinned() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        assert!(
+            uspace
+                .pin_region(uspace.range(0, 8192), Access::READ)
+                .is_err()
+        );
+        assert_eq!(uspace.pins(0), 0);
+        assert!(uspace.pin_region(uspace.range(0, 0), Access::READ).is_ok());
+    }
+
+    #[test]
+    fn pinned_bytes_are_shared() {
+        let uspace = MockUspace::new(1);
+        let mut region = uspace
+            .pin_region(uspace.range(16, 4), Access::READ | Access::WRITE)
+            .unwrap();
+        region.as_mut_slice().copy_from_slice(b"pin!");
+        assert_eq!(region.as_slice(), b"pin!");
+        assert_eq!(uspace.load(16, 4), b"pin!");
+    }
+
+    #[test]
+    #[should_panic(expected = "not pinned for writing")]
+    fn read_only_pin_is_not_writable() {
+        let uspace = MockUspace::new(1);
+        let mut region = uspace.pin_region(uspace.range(0, 4), Access::READ).unwrap();
+        region.as_mut_slice();
+    }
+
+    #[test]
+    fn phys_segments_merge_contiguous_pages() {
+        let uspace = MockUspace::new(3);
+        let region = uspace
+            .pin_region(uspace.range(100, 8192), Access::READ)
+            .unwrap();
+        let base = uspace.addr(0).as_usize();
+        let segments: Vec<_> = region.phys_segments().collect();
+        assert_eq!(segments, [Ok((PhysAddr::from(base + 100), 8192))]);
+
+        uspace.set_phys(1, Some(0x10_0000));
+        let segments: Vec<_> = region.phys_segments().collect();
+        assert_eq!(
+            segments,
+            [
+                Ok((PhysAddr::from(base + 100), 3996)),
+                Ok((PhysAddr::from(0x10_0000), 4096)),
+                Ok((PhysAddr::from(base + 8192), 100)),
+            ]
+        );
+        drop(region);
+
+        uspace.set_phys(0, Some(0x8_0000));
+        uspace.set_phys(2, Some(0x10_1000));
+        let region = uspace
+            .pin_region(uspace.range(0, 3 * 4096), Access::READ)
+            .unwrap();
+        let segments: Vec<_> = region.dma_segments(0x10_1fff).unwrap().collect();
+        assert_eq!(
+            segments,
+            [
+                Ok((PhysAddr::from(0x8_0000), 4096)),
+                Ok((PhysAddr::from(0x10_0000), 8192)),
+            ]
+        );
+        assert_eq!(
+            region.dma_segments(0xf_ffff).err(),
+            Some(DmaOutOfRange {
+                phys: PhysAddr::from(0x10_0000),
+                len: 8192,
+            })
+        );
+    }
+
+    #[test]
+    fn untranslatable_page_ends_the_segments() {
+        let uspace = MockUspace::new(2);
+        uspace.set_phys(1, None);
+        let region = uspace
+            .pin_region(uspace.range(0, 8192), Access::READ)
+            .unwrap();
+        let segments: Vec<_> = region.phys_segments().collect();
+        let base = uspace.addr(0).as_usize();
+        assert_eq!(
+            segments,
+            [Ok((PhysAddr::from(base), 4096)), Err(Error::EFAULT)]
+        );
+        assert!(region.dma_segments(u64::MAX).is_ok());
+    }
+}
@@ -1,10 +1,145 @@
-use core::{alloc::Layout, ffi::c_char, mem::transmute, ptr, slice, str};
+use core::{
+    alloc::Layout,
+    ptr::{self, NonNull},
+    slice, str,
+};
 
-use axerrno::{LinuxError, LinuxResult};
-use memory_addr::VirtAddr;
-use page_table_multiarch::MappingFlags;
+use memory_addr::{VirtAddr, VirtAddrRange};
 
-use crate::{UserSpaceAccess, check_null_terminated, check_region};
+use crate::{
+    Access, Error, UserMut, UserRef, UserResult, UserSliceMut, UserSliceRef, UserSpaceAccess,
+    UspaceAddr, check_null_terminated_bounded, check_region, scan_utf8_str, too_long_as,
+};
+#[cfg(feature = "debug-aliasing")]
+use crate::{check_unaliased, user_range_of};
+
+/// Build a reference to a validated user `T`
+///
+/// Zero-sized values are never backed by user memory, which may be null,
+/// unaligned or unmapped for them, so a dangling pointer is used instead.
+pub(crate) unsafe fn user_ref<'a, T>(ptr: *mut T) -> &'a mut T {
+    if size_of::<T>() == 0 {
+        unsafe { &mut *NonNull::dangling().as_ptr() }
+    } else {
+        unsafe { &mut *ptr }
+    }
+}
+
+/// Build a slice over `len` validated user `T`s
+///
+/// Empty and zero-sized slices are not backed by user memory, see
+/// [`user_ref`].
+pub(crate) unsafe fn user_slice<'a, T>(ptr: *mut T, len: usize) -> &'a mut [T] {
+    if len == 0 || size_of::<T>() == 0 {
+        unsafe { slice::from_raw_parts_mut(NonNull::dangling().as_ptr(), len) }
+    } else {
+        unsafe { slice::from_raw_parts_mut(ptr, len) }
+    }
+}
+
+/// Panic unless `uspace` can hand out references into user memory
+#[track_caller]
+pub(crate) fn assert_direct_map<A: UserSpaceAccess + ?Sized>(uspace: &A) {
+    assert!(
+        uspace.direct_map(),
+        "user memory is not directly mapped, use the copying helpers instead"
+    );
+}
+
+/// Layout of a user slice of `len` elements
+///
+/// Fails with `EINVAL` unless the total size fits in `isize::MAX` bytes as
+/// `slice::from_raw_parts` requires; `len` itself is capped the same way so
+/// zero-sized elements cannot produce absurd lengths.
+pub(crate) fn slice_layout<T>(len: usize) -> UserResult<Layout> {
+    if len > isize::MAX as usize {
+        return Err(Error::EINVAL);
+    }
+    Layout::array::<T>(len)
+        .ok()
+        .filter(|layout| layout.size() <= isize::MAX as usize)
+        .ok_or(Error::EINVAL)
+}
+
+/// Borrow the null-terminated UTF-8 string at `addr`, accepting at most
+/// `max` bytes
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub(crate) fn user_str<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    addr: VirtAddr,
+    max: usize,
+) -> UserResult<&'static str> {
+    let len = scan_utf8_str(uspace, addr, max, |_| {})?;
+    assert_direct_map(uspace);
+    let slice = unsafe { user_slice(addr.as_mut_ptr(), len) };
+    Ok(unsafe { str::from_utf8_unchecked(slice) })
+}
+
+/// User pointer to a null-terminated string
+///
+/// Implemented for `u8` pointers and for `c_char` ones, whichever signedness
+/// `c_char` has on the target, so string helpers such as
+/// [`read_string`](crate::UserSpaceAccess::read_string) take both without
+/// casts.
+pub trait UserStrPtr: Copy {
+    /// Address of the first byte
+    fn str_address(self) -> VirtAddr;
+}
+
+/// Macro to generate the string operations of a user space pointer type for
+/// one byte type
+macro_rules! impl_user_str {
+    ($ptr_type:ident, $byte:ty) => {
+        /// String reading implementation for byte pointers
+        impl $ptr_type<$byte> {
+            legacy_ref_fn! {
+                /// Get a null-terminated string from user space
+                ///
+                /// The string is checked to be UTF-8 while its terminator is
+                /// searched for, failing with `EILSEQ` otherwise. The slice stays
+                /// in user memory, which can change after the check; use
+                /// [`read_string`](crate::UserSpaceAccess::read_string) for a copy
+                /// of exactly the bytes checked. Accepts at most
+                /// [`max_scan_len`](crate::Limits::max_scan_len) bytes, failing with
+                /// `ENAMETOOLONG` beyond them.
+                ///
+                /// # Safety
+                ///
+                /// See [`UserReadable::get_as_ref`].
+                #[cfg_attr(feature = "track-caller", track_caller)]
+                pub fn get_as_str<A: UserSpaceAccess + ?Sized>(
+                    self,
+                    uspace: &A,
+                ) -> UserResult<&'static str> {
+                    user_str(uspace, self.address(), uspace.limits().max_scan_len)
+                }
+            }
+
+            legacy_ref_fn! {
+                /// Like [`get_as_str`](Self::get_as_str), accepting at most `max`
+                /// bytes
+                ///
+                /// # Safety
+                ///
+                /// See [`UserReadable::get_as_ref`].
+                #[cfg_attr(feature = "track-caller", track_caller)]
+                pub fn get_as_str_bounded<A: UserSpaceAccess + ?Sized>(
+                    self,
+                    uspace: &A,
+                    max: usize,
+                ) -> UserResult<&'static str> {
+                    user_str(uspace, self.address(), max)
+                }
+            }
+        }
+
+        impl UserStrPtr for $ptr_type<$byte> {
+            fn str_address(self) -> VirtAddr {
+                self.address()
+            }
+        }
+    };
+}
 
 /// Macro to generate common pointer operations for user space pointer types
 macro_rules! impl_user_pointer {
@@ -15,6 +150,11 @@ macro_rules! impl_user_pointer {
                 VirtAddr::from_ptr_of(self.0)
             }
 
+            /// Get the address of this pointer as another address type
+            pub fn address_as<Addr: UspaceAddr>(&self) -> Addr {
+                Addr::from_usize(self.address().as_usize())
+            }
+
             /// Check if this pointer is null
             pub fn is_null(&self) -> bool {
                 self.0.is_null()
@@ -25,75 +165,193 @@ macro_rules! impl_user_pointer {
                 $ptr_type(self.0 as *const U as $raw_ptr)
             }
 
-            /// Add an offset to this pointer
+            /// Add an offset of `offset` elements to this pointer
+            ///
+            /// The address wraps around rather than overflowing, and a wrapped
+            /// pointer fails every later check.
             pub fn offset(self, offset: usize) -> Self {
-                $ptr_type(unsafe { self.0.add(offset) })
+                $ptr_type(self.0.wrapping_add(offset))
             }
         }
 
         impl<T> UserReadable<T> for $ptr_type<T> {
-            /// Get a reference to data in user space with validation
-            fn get_as_ref<A: UserSpaceAccess>(self, uspace: &A) -> LinuxResult<&'static T> {
-                check_region(
-                    uspace,
-                    self.address(),
-                    Layout::new::<T>(),
-                    MappingFlags::READ,
-                )?;
-                Ok(unsafe { &*self.0 })
+            fn user_address(&self) -> VirtAddr {
+                self.address()
             }
 
-            /// Get a slice from user space with validation
-            fn get_as_slice<A: UserSpaceAccess>(
+            /// Borrow data in user space with validation
+            #[cfg_attr(feature = "track-caller", track_caller)]
+            fn get_ref<'a, A: UserSpaceAccess + ?Sized>(
                 self,
-                uspace: &A,
+                uspace: &'a A,
+            ) -> UserResult<UserRef<'a, T>> {
+                check_region(uspace, self.address(), Layout::new::<T>(), Access::READ)?;
+                assert_direct_map(uspace);
+                Ok(UserRef::new(unsafe { user_ref(self.0 as *mut T) }))
+            }
+
+            /// Borrow a slice from user space with validation
+            #[cfg_attr(feature = "track-caller", track_caller)]
+            fn get_slice<'a, A: UserSpaceAccess + ?Sized>(
+                self,
+                uspace: &'a A,
                 len: usize,
-            ) -> LinuxResult<&'static [T]> {
+            ) -> UserResult<UserSliceRef<'a, T>> {
                 check_region(
                     uspace,
                     self.address(),
-                    Layout::array::<T>(len).map_err(|_| LinuxError::EINVAL)?,
-                    MappingFlags::READ,
+                    slice_layout::<T>(len)?,
+                    Access::READ,
                 )?;
-                Ok(unsafe { slice::from_raw_parts(self.0, len) })
+                assert_direct_map(uspace);
+                Ok(UserSliceRef::new(unsafe {
+                    user_slice(self.0 as *mut T, len)
+                }))
             }
 
-            /// Get a null-terminated slice from user space with validation
-            fn get_as_null_terminated<A: UserSpaceAccess>(
-                self,
-                uspace: &A,
-            ) -> LinuxResult<&'static [T]>
-            where
-                T: PartialEq + Default,
-            {
-                let len =
-                    check_null_terminated::<T, A>(uspace, self.address(), MappingFlags::READ)?;
-                Ok(unsafe { slice::from_raw_parts(self.0, len) })
+            legacy_ref_fn! {
+                /// Get a reference to data in user space with validation
+                #[cfg_attr(feature = "track-caller", track_caller)]
+                fn get_as_ref<A: UserSpaceAccess + ?Sized>(
+                    self,
+                    uspace: &A,
+                ) -> UserResult<&'static T> {
+                    check_region(uspace, self.address(), Layout::new::<T>(), Access::READ)?;
+                    assert_direct_map(uspace);
+                    Ok(unsafe { user_ref(self.0 as *mut T) })
+                }
             }
-        }
 
-        /// String reading implementation for c_char pointers
-        impl $ptr_type<c_char> {
-            /// Get a null-terminated string from user space
-            pub fn get_as_str<A: UserSpaceAccess>(self, uspace: &A) -> LinuxResult<&'static str> {
-                let slice = self.get_as_null_terminated(uspace)?;
-                let slice = unsafe { transmute::<&[c_char], &[u8]>(slice) };
-                str::from_utf8(slice).map_err(|_| LinuxError::EILSEQ)
+            legacy_ref_fn! {
+                /// Get a slice from user space with validation
+                #[cfg_attr(feature = "track-caller", track_caller)]
+                fn get_as_slice<A: UserSpaceAccess + ?Sized>(
+                    self,
+                    uspace: &A,
+                    len: usize,
+                ) -> UserResult<&'static [T]> {
+                    check_region(
+                        uspace,
+                        self.address(),
+                        slice_layout::<T>(len)?,
+                        Access::READ,
+                    )?;
+                    assert_direct_map(uspace);
+                    Ok(unsafe { user_slice(self.0 as *mut T, len) })
+                }
+            }
+
+            legacy_ref_fn! {
+                /// Get a null-terminated slice of at most `max` elements from user
+                /// space with validation
+                #[cfg_attr(feature = "track-caller", track_caller)]
+                fn get_as_null_terminated_bounded<A: UserSpaceAccess + ?Sized>(
+                    self,
+                    uspace: &A,
+                    max: usize,
+                ) -> UserResult<&'static [T]>
+                where
+                    T: PartialEq + Default,
+                {
+                    let len = check_null_terminated_bounded::<T, A>(
+                        uspace,
+                        self.address(),
+                        Access::READ,
+                        max,
+                    )
+                    .map_err(|e| too_long_as(e, Error::ENAMETOOLONG))?;
+                    slice_layout::<T>(len)?;
+                    assert_direct_map(uspace);
+                    Ok(unsafe { user_slice(self.0 as *mut T, len) })
+                }
             }
         }
+
+        impl_user_str!($ptr_type, u8);
+        impl_user_str!($ptr_type, i8);
     };
 }
 
 /// Trait for reading data from user space pointers
+///
+/// The reference-returning methods panic for backends without a
+/// [`direct_map`](crate::UserSpaceRaw::direct_map).
 pub trait UserReadable<T> {
-    /// Get a reference to data in user space
-    fn get_as_ref<A: UserSpaceAccess>(self, uspace: &A) -> LinuxResult<&'static T>;
-    /// Get a slice from user space
-    fn get_as_slice<A: UserSpaceAccess>(self, uspace: &A, len: usize) -> LinuxResult<&'static [T]>;
-    /// Get a null-terminated slice from user space
-    fn get_as_null_terminated<A: UserSpaceAccess>(self, uspace: &A) -> LinuxResult<&'static [T]>
-    where
-        T: PartialEq + Default;
+    /// Address the pointer points to
+    fn user_address(&self) -> VirtAddr;
+
+    /// Borrow data in user space for as long as `uspace`
+    fn get_ref<'a, A: UserSpaceAccess + ?Sized>(self, uspace: &'a A) -> UserResult<UserRef<'a, T>>;
+    /// Borrow a slice from user space for as long as `uspace`
+    fn get_slice<'a, A: UserSpaceAccess + ?Sized>(
+        self,
+        uspace: &'a A,
+        len: usize,
+    ) -> UserResult<UserSliceRef<'a, T>>;
+    legacy_ref_fn! {
+        /// Get a reference to data in user space
+        ///
+        /// # Safety
+        ///
+        /// The returned reference is not tied to `uspace`: the caller must stop
+        /// using it before the memory can be unmapped or remapped, must not
+        /// create another mutable reference to the same memory while it lives, and
+        /// must only touch it inside an access window such as
+        /// [`try_access_user_range`](crate::try_access_user_range) for faults
+        /// to be recovered.
+        #[deprecated(note = "the reference outlives the address space, use `get_ref`")]
+        fn get_as_ref<A: UserSpaceAccess + ?Sized>(self, uspace: &A) -> UserResult<&'static T>;
+    }
+    legacy_ref_fn! {
+        /// Get a slice from user space
+        ///
+        /// # Safety
+        ///
+        /// See [`get_as_ref`](Self::get_as_ref).
+        #[deprecated(note = "the slice outlives the address space, use `get_slice`")]
+        fn get_as_slice<A: UserSpaceAccess + ?Sized>(
+            self,
+            uspace: &A,
+            len: usize,
+        ) -> UserResult<&'static [T]>;
+    }
+    legacy_ref_fn! {
+        /// Get a null-terminated slice from user space
+        ///
+        /// Accepts at most [`max_scan_len`](crate::Limits::max_scan_len)
+        /// elements, failing with `ENAMETOOLONG` beyond them.
+        ///
+        /// # Safety
+        ///
+        /// See [`get_as_ref`](Self::get_as_ref).
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        #[allow(unused_unsafe)]
+        fn get_as_null_terminated<A: UserSpaceAccess + ?Sized>(
+            self,
+            uspace: &A,
+        ) -> UserResult<&'static [T]>
+        where
+            T: PartialEq + Default,
+            Self: Sized,
+        {
+            unsafe { self.get_as_null_terminated_bounded(uspace, uspace.limits().max_scan_len) }
+        }
+    }
+    legacy_ref_fn! {
+        /// Like [`get_as_null_terminated`](Self::get_as_null_terminated),
+        /// accepting at most `max` elements
+        ///
+        /// # Safety
+        ///
+        /// See [`get_as_ref`](Self::get_as_ref).
+        fn get_as_null_terminated_bounded<A: UserSpaceAccess + ?Sized>(
+            self,
+            uspace: &A,
+            max: usize,
+        ) -> UserResult<&'static [T]>
+        where
+            T: PartialEq + Default;
+    }
 }
 
 /// Mutable user space pointer wrapper
@@ -131,46 +389,146 @@ impl<T> Default for UserPtr<T> {
 impl_user_pointer!(UserPtr, *mut U);
 
 impl<T> UserPtr<T> {
-    /// Get mutable reference to data in user space
-    pub fn get_as_mut<A: UserSpaceAccess>(self, uspace: &A) -> LinuxResult<&'static mut T> {
+    /// Mutably borrow data in user space for as long as `uspace`
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    pub fn get_mut_ref<'a, A: UserSpaceAccess + ?Sized>(
+        self,
+        uspace: &'a A,
+    ) -> UserResult<UserMut<'a, T>> {
         check_region(
             uspace,
             self.address(),
             Layout::new::<T>(),
-            MappingFlags::READ.union(MappingFlags::WRITE),
+            Access::READ.union(Access::WRITE),
         )?;
-        Ok(unsafe { &mut *self.0 })
+        assert_direct_map(uspace);
+        Ok(UserMut::new(uspace, unsafe { user_ref(self.0) }))
     }
 
-    /// Get mutable slice from user space
-    pub fn get_as_mut_slice<A: UserSpaceAccess>(
+    /// Mutably borrow a slice from user space for as long as `uspace`
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    pub fn get_mut_slice<'a, A: UserSpaceAccess + ?Sized>(
         self,
-        uspace: &A,
+        uspace: &'a A,
         len: usize,
-    ) -> LinuxResult<&'static mut [T]> {
+    ) -> UserResult<UserSliceMut<'a, T>> {
         check_region(
             uspace,
             self.address(),
-            Layout::array::<T>(len).map_err(|_| LinuxError::EINVAL)?,
-            MappingFlags::READ.union(MappingFlags::WRITE),
+            slice_layout::<T>(len)?,
+            Access::READ.union(Access::WRITE),
         )?;
-        Ok(unsafe { slice::from_raw_parts_mut(self.0, len) })
+        assert_direct_map(uspace);
+        Ok(UserSliceMut::new(uspace, unsafe {
+            user_slice(self.0, len)
+        }))
     }
 
-    /// Get a mutable null-terminated slice from user space
-    pub fn get_as_mut_null_terminated<A: UserSpaceAccess>(
-        self,
-        uspace: &A,
-    ) -> LinuxResult<&'static mut [T]>
-    where
-        T: PartialEq + Default,
-    {
-        let len = check_null_terminated::<T, A>(
-            uspace,
-            self.address(),
-            MappingFlags::READ.union(MappingFlags::WRITE),
-        )?;
-        Ok(unsafe { slice::from_raw_parts_mut(self.0, len) })
+    legacy_ref_fn! {
+        /// Get mutable reference to data in user space
+        ///
+        /// # Safety
+        ///
+        /// The returned reference is not tied to `uspace`: the caller must stop
+        /// using it before the memory can be unmapped or remapped, must not
+        /// create another reference to the same memory while it lives, and
+        /// must only touch it inside an access window such as
+        /// [`try_access_user_range`](crate::try_access_user_range) for faults
+        /// to be recovered.
+        #[deprecated(note = "the reference outlives the address space, use `get_mut_ref`")]
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        pub fn get_as_mut<A: UserSpaceAccess + ?Sized>(
+            self,
+            uspace: &A,
+        ) -> UserResult<&'static mut T> {
+            check_region(
+                uspace,
+                self.address(),
+                Layout::new::<T>(),
+                Access::READ.union(Access::WRITE),
+            )?;
+            assert_direct_map(uspace);
+            #[cfg(feature = "debug-aliasing")]
+            check_unaliased(uspace.space_id(), user_range_of(self.0, 1));
+            Ok(unsafe { user_ref(self.0) })
+        }
+    }
+
+    legacy_ref_fn! {
+        /// Get mutable slice from user space
+        ///
+        /// # Safety
+        ///
+        /// See [`get_as_mut`](Self::get_as_mut).
+        #[deprecated(note = "the slice outlives the address space, use `get_mut_slice`")]
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        pub fn get_as_mut_slice<A: UserSpaceAccess + ?Sized>(
+            self,
+            uspace: &A,
+            len: usize,
+        ) -> UserResult<&'static mut [T]> {
+            check_region(
+                uspace,
+                self.address(),
+                slice_layout::<T>(len)?,
+                Access::READ.union(Access::WRITE),
+            )?;
+            assert_direct_map(uspace);
+            #[cfg(feature = "debug-aliasing")]
+            check_unaliased(uspace.space_id(), user_range_of(self.0, len));
+            Ok(unsafe { user_slice(self.0, len) })
+        }
+    }
+
+    legacy_ref_fn! {
+        /// Get a mutable null-terminated slice from user space
+        ///
+        /// Accepts at most [`max_scan_len`](crate::Limits::max_scan_len)
+        /// elements, failing with `ENAMETOOLONG` beyond them.
+        ///
+        /// # Safety
+        ///
+        /// See [`get_as_mut`](Self::get_as_mut).
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        #[allow(unused_unsafe)]
+        pub fn get_as_mut_null_terminated<A: UserSpaceAccess + ?Sized>(
+            self,
+            uspace: &A,
+        ) -> UserResult<&'static mut [T]>
+        where
+            T: PartialEq + Default,
+        {
+            unsafe { self.get_as_mut_null_terminated_bounded(uspace, uspace.limits().max_scan_len) }
+        }
+    }
+
+    legacy_ref_fn! {
+        /// Like [`get_as_mut_null_terminated`](Self::get_as_mut_null_terminated),
+        /// accepting at most `max` elements
+        ///
+        /// # Safety
+        ///
+        /// See [`get_as_mut`](Self::get_as_mut).
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        pub fn get_as_mut_null_terminated_bounded<A: UserSpaceAccess + ?Sized>(
+            self,
+            uspace: &A,
+            max: usize,
+        ) -> UserResult<&'static mut [T]>
+        where
+            T: PartialEq + Default,
+        {
+            let len = check_null_terminated_bounded::<T, A>(
+                uspace,
+                self.address(),
+                Access::READ.union(Access::WRITE),
+                max,
+            )
+            .map_err(|e| too_long_as(e, Error::ENAMETOOLONG))?;
+            slice_layout::<T>(len)?;
+            assert_direct_map(uspace);
+            Ok(unsafe { user_slice(self.0, len) })
+        }
     }
 }
 
@@ -201,3 +559,290 @@ impl<T> Default for UserConstPtr<T> {
 
 impl_user_pointer!(UserConstPtr, *const U);
 
+/// User pointer to a value one call both reads and writes back, such as the
+/// `old_value` of `timer_settime` or the `old` of `setitimer`
+///
+/// A null pointer stands for an argument the caller left out: reading it
+/// gives `None` and writing back to it does nothing, so callers need no null
+/// checks of their own.
+#[repr(transparent)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct UserInOutPtr<T>(UserPtr<T>);
+
+impl<T> From<usize> for UserInOutPtr<T> {
+    /// Create UserInOutPtr from a numeric address
+    fn from(value: usize) -> Self {
+        Self(UserPtr::from(value))
+    }
+}
+
+impl<T> From<UserPtr<T>> for UserInOutPtr<T> {
+    /// Create UserInOutPtr from a mutable user pointer
+    fn from(value: UserPtr<T>) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Default for UserInOutPtr<T> {
+    /// Create a null UserInOutPtr
+    fn default() -> Self {
+        Self(UserPtr::default())
+    }
+}
+
+impl<T> UserInOutPtr<T> {
+    /// Get the underlying mutable pointer
+    pub fn as_ptr(self) -> UserPtr<T> {
+        self.0
+    }
+
+    /// Check if the pointer is null
+    pub fn is_null(&self) -> bool {
+        self.0.is_null()
+    }
+
+    /// Read the value passed in, or `None` for a null pointer
+    pub fn read_in<A: UserSpaceAccess>(self, uspace: &A) -> UserResult<Option<T>>
+    where
+        T: Copy + 'static,
+    {
+        if self.is_null() {
+            return Ok(None);
+        }
+        uspace.read(self.0).map(Some)
+    }
+
+    /// Write `val` back, doing nothing for a null pointer
+    pub fn write_back<A: UserSpaceAccess>(self, uspace: &A, val: T) -> UserResult<()>
+    where
+        T: 'static,
+    {
+        if self.is_null() {
+            return Ok(());
+        }
+        uspace.write(self.0, val)
+    }
+}
+
+/// User space code address, such as a signal trampoline or a new thread PC
+///
+/// It can only be checked for executability, never dereferenced by the
+/// kernel.
+#[repr(transparent)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct UserCodePtr(usize);
+
+impl From<usize> for UserCodePtr {
+    /// Create UserCodePtr from a numeric address
+    fn from(value: usize) -> Self {
+        UserCodePtr(value)
+    }
+}
+
+impl UserCodePtr {
+    /// Get the virtual address of this pointer
+    pub fn address(&self) -> VirtAddr {
+        VirtAddr::from(self.0)
+    }
+
+    /// Check if this pointer is null
+    pub fn is_null(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check that the `len` bytes starting at this address are executable
+    /// user memory
+    pub fn check_executable<A: UserSpaceAccess + ?Sized>(
+        self,
+        uspace: &A,
+        len: usize,
+    ) -> UserResult<()> {
+        let range =
+            VirtAddrRange::try_from_start_size(self.address(), len.max(1)).ok_or(Error::EFAULT)?;
+        uspace.check_executable(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ffi::c_char;
+
+    use super::*;
+    use crate::{USER_ADDR_END, mock::MockUspace};
+
+    const MAX: usize = isize::MAX as usize;
+
+    #[test]
+    fn slice_size_boundaries() {
+        assert_eq!(slice_layout::<u8>(MAX).map(|l| l.size()), Ok(MAX));
+        assert_eq!(slice_layout::<u8>(MAX + 1), Err(Error::EINVAL));
+        assert_eq!(
+            slice_layout::<u64>(MAX / 8).map(|l| l.size()),
+            Ok(MAX / 8 * 8)
+        );
+        assert_eq!(slice_layout::<u64>(MAX / 8 + 1), Err(Error::EINVAL));
+        assert_eq!(slice_layout::<[u8; 3]>(MAX / 3 + 1), Err(Error::EINVAL));
+    }
+
+    #[test]
+    fn zero_sized_length_is_capped() {
+        assert_eq!(slice_layout::<()>(MAX).map(|l| l.size()), Ok(0));
+        assert_eq!(slice_layout::<()>(MAX + 1), Err(Error::EINVAL));
+        assert_eq!(slice_layout::<()>(usize::MAX), Err(Error::EINVAL));
+    }
+
+    #[test]
+    #[allow(deprecated, unused_unsafe)]
+    fn slice_constructors_reject_oversized_lengths() {
+        let uspace = MockUspace::new(1).permissive();
+        let ptr = UserPtr::<u64>::from(0x1000);
+        let cptr = UserConstPtr::<u64>::from(0x1000);
+        let zst = UserPtr::<()>::from(0x1000);
+        assert_eq!(
+            ptr.get_mut_slice(&uspace, MAX / 8 + 1).err(),
+            Some(Error::EINVAL)
+        );
+        assert_eq!(
+            cptr.get_slice(&uspace, MAX / 8 + 1).err(),
+            Some(Error::EINVAL)
+        );
+        assert_eq!(
+            zst.get_mut_slice(&uspace, MAX + 1).err(),
+            Some(Error::EINVAL)
+        );
+        unsafe {
+            assert_eq!(
+                ptr.get_as_mut_slice(&uspace, MAX / 8 + 1),
+                Err(Error::EINVAL)
+            );
+            assert_eq!(cptr.get_as_slice(&uspace, MAX / 8 + 1), Err(Error::EINVAL));
+            assert_eq!(zst.get_as_mut_slice(&uspace, MAX + 1), Err(Error::EINVAL));
+        }
+        assert_eq!(uspace.checks.get(), 0);
+    }
+
+    #[test]
+    fn code_pointers_need_executable_pages() {
+        let uspace = MockUspace::new(2);
+        uspace.protect(0, Access::READ | Access::EXECUTE);
+        let code = UserCodePtr::from(uspace.addr(4000).as_usize());
+        assert_eq!(code.check_executable(&uspace, 96), Ok(()));
+        assert_eq!(code.check_executable(&uspace, 0), Ok(()));
+        assert_eq!(code.check_executable(&uspace, 97), Err(Error::EFAULT));
+        assert_eq!(uspace.populates.get(), 0);
+
+        uspace.reset_counts();
+        let kernel = UserCodePtr::from(USER_ADDR_END);
+        assert_eq!(kernel.check_executable(&uspace, 1), Err(Error::EFAULT));
+        let wrapping = UserCodePtr::from(usize::MAX);
+        assert_eq!(wrapping.check_executable(&uspace, 2), Err(Error::EFAULT));
+        assert_eq!(uspace.checks.get(), 0);
+    }
+
+    #[test]
+    #[allow(unused_unsafe)]
+    fn unterminated_mapping_fails_fast() {
+        // 16 MiB without a single NUL
+        let pages = 4096;
+        let uspace = MockUspace::new(pages);
+        for page in 0..pages {
+            uspace.fill(page * 4096, &[b'a'; 4096]);
+        }
+        let cptr = uspace.cptr::<u8>(0);
+        let ptr = uspace.ptr::<u8>(0);
+        let default_pages = crate::MAX_SCAN_LEN / 4096 + 1;
+
+        unsafe {
+            assert_eq!(
+                cptr.cast::<c_char>().get_as_str(&uspace),
+                Err(Error::ENAMETOOLONG)
+            );
+            assert!(uspace.checks.get() <= default_pages);
+            uspace.reset_counts();
+            assert_eq!(
+                cptr.get_as_null_terminated(&uspace).err(),
+                Some(Error::ENAMETOOLONG)
+            );
+            assert!(uspace.checks.get() <= default_pages);
+            uspace.reset_counts();
+            assert_eq!(
+                ptr.get_as_mut_null_terminated(&uspace).err(),
+                Some(Error::ENAMETOOLONG)
+            );
+            assert!(uspace.checks.get() <= default_pages);
+
+            // Explicit bounds stop sooner
+            uspace.reset_counts();
+            assert_eq!(
+                cptr.get_as_null_terminated_bounded(&uspace, 100).err(),
+                Some(Error::ENAMETOOLONG)
+            );
+            assert_eq!(
+                ptr.get_as_mut_null_terminated_bounded(&uspace, 100).err(),
+                Some(Error::ENAMETOOLONG)
+            );
+            assert_eq!(uspace.checks.get(), 2);
+        }
+
+        #[cfg(feature = "alloc")]
+        {
+            uspace.reset_counts();
+            assert_eq!(uspace.read_string(cptr), Err(Error::ENAMETOOLONG));
+            assert!(uspace.checks.get() <= default_pages);
+
+            // Argument strings go over the per-string limit
+            let last = (pages - 1) * 4096;
+            uspace.fill(last, &uspace.addr(0).as_usize().to_ne_bytes());
+            uspace.fill(last + 8, &[0; 8]);
+            uspace.reset_counts();
+            let argv = UserConstPtr::<UserConstPtr<c_char>>::from(uspace.addr(last).as_usize());
+            assert_eq!(uspace.read_str_array(argv), Err(Error::E2BIG));
+            assert!(uspace.checks.get() <= default_pages + 2);
+        }
+    }
+
+    /// Length of the user string at `ptr`, for either element type
+    fn cstr_len<P: UserStrPtr>(uspace: &MockUspace, ptr: P) -> UserResult<usize> {
+        uspace.read_cstr_into(ptr, &mut [0; 64])
+    }
+
+    #[test]
+    #[allow(unused_unsafe)]
+    fn byte_and_char_pointers_share_the_string_helpers() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, b"path\0\xff\0");
+        let bytes = uspace.cptr::<u8>(0);
+        let chars = uspace.cptr::<c_char>(0);
+        let mut_bytes = uspace.ptr::<u8>(0);
+        let mut_chars = uspace.ptr::<c_char>(0);
+
+        assert_eq!(cstr_len(&uspace, bytes), Ok(4));
+        assert_eq!(cstr_len(&uspace, chars), Ok(4));
+        assert_eq!(cstr_len(&uspace, mut_bytes), Ok(4));
+        assert_eq!(cstr_len(&uspace, mut_chars), Ok(4));
+        assert_eq!(uspace.str_eq(bytes, "path"), uspace.str_eq(chars, "path"));
+        unsafe {
+            assert_eq!(bytes.get_as_str(&uspace), Ok("path"));
+            assert_eq!(chars.get_as_str(&uspace), Ok("path"));
+            assert_eq!(mut_bytes.get_as_str(&uspace), Ok("path"));
+            assert_eq!(mut_chars.get_as_str_bounded(&uspace, 4), Ok("path"));
+            assert_eq!(
+                bytes.get_as_str_bounded(&uspace, 3),
+                chars.get_as_str_bounded(&uspace, 3)
+            );
+            // The same errors, whichever the element type
+            let bad = bytes.offset(5);
+            assert_eq!(bad.get_as_str(&uspace), Err(Error::EILSEQ));
+            assert_eq!(chars.offset(5).get_as_str(&uspace), Err(Error::EILSEQ));
+        }
+        #[cfg(feature = "alloc")]
+        {
+            assert_eq!(uspace.read_string(bytes).as_deref(), Ok("path"));
+            assert_eq!(uspace.read_string(mut_chars).as_deref(), Ok("path"));
+            assert_eq!(
+                uspace.read_string(bytes.offset(5)),
+                uspace.read_string(chars.offset(5))
+            );
+        }
+    }
+}
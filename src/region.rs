@@ -0,0 +1,344 @@
+use core::{alloc::Layout, marker::PhantomData};
+
+use memory_addr::{VirtAddr, VirtAddrRange};
+
+#[cfg(feature = "debug-aliasing")]
+use crate::AliasGuard;
+use crate::{
+    Access, Error, UserConstPtr, UserPtr, UserResult, UserSpaceAccess, assert_direct_map,
+    check_region, slice_layout, try_access_user_range, user_range_of, user_slice,
+};
+
+/// Proof that a user region passed [`check_region`](crate::check_region) for
+/// some access flags
+///
+/// Accesses inside the region through [`read_in`], [`write_in`] and
+/// [`slice_in`] only repeat the cheap bounds, alignment and flag checks
+/// against the token. The token is bound to the borrow of the uspace that
+/// produced it and records its [`generation`]; once the generation changes
+/// the token is no longer [current](Self::is_current), and accesses through
+/// it validate the accessed range again from scratch.
+///
+/// [`generation`]: crate::UserSpaceRaw::generation
+///
+/// [`read_in`]: crate::UserSpaceAccess::read_in
+/// [`write_in`]: crate::UserSpaceAccess::write_in
+/// [`slice_in`]: crate::UserSpaceAccess::slice_in
+#[derive(Debug)]
+pub struct ValidatedRegion<'a, A: ?Sized> {
+    uspace: &'a A,
+    range: VirtAddrRange,
+    flags: Access,
+    generation: u64,
+}
+
+impl<A: ?Sized> Clone for ValidatedRegion<'_, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A: ?Sized> Copy for ValidatedRegion<'_, A> {}
+
+impl<'a, A: UserSpaceAccess + ?Sized> ValidatedRegion<'a, A> {
+    pub(crate) fn new(uspace: &'a A, range: VirtAddrRange, flags: Access, generation: u64) -> Self {
+        Self {
+            uspace,
+            range,
+            flags,
+            generation,
+        }
+    }
+
+    /// Whether the token still vouches for its region in `uspace`, i.e. it
+    /// was produced by `uspace` and no mapping change happened since
+    pub fn is_current(&self, uspace: &A) -> bool {
+        core::ptr::eq(self.uspace, uspace) && uspace.generation() == self.generation
+    }
+
+    /// The validated range, empty for a zero-sized check
+    pub fn range(&self) -> VirtAddrRange {
+        self.range
+    }
+
+    /// The access flags the range was validated for
+    pub fn flags(&self) -> Access {
+        self.flags
+    }
+
+    /// Check that an access of `layout` at `start` with `flags` through
+    /// `uspace` is covered by this region
+    ///
+    /// Zero-sized accesses always pass, as they do in `check_region`. A token
+    /// that is no longer current falls back to a full `check_region`.
+    pub(crate) fn check(
+        &self,
+        uspace: &A,
+        start: VirtAddr,
+        layout: Layout,
+        flags: Access,
+    ) -> UserResult<()> {
+        if !core::ptr::eq(self.uspace, uspace) {
+            return Err(Error::EFAULT);
+        }
+        if layout.size() == 0 {
+            return Ok(());
+        }
+        if uspace.generation() != self.generation {
+            check_region(uspace, start, layout, flags)?;
+            return Ok(());
+        }
+        if start.as_usize() & (layout.align() - 1) != 0 || !self.flags.contains(flags) {
+            return Err(Error::EFAULT);
+        }
+        VirtAddrRange::try_from_start_size(start, layout.size())
+            .filter(|&range| self.range.contains_range(range))
+            .map(|_| ())
+            .ok_or(Error::EFAULT)
+    }
+}
+
+/// A user slice description that can be kept across syscalls, such as for
+/// futex waiters or long-lived buffer registrations
+///
+/// Only the address, length and required flags are stored, never a
+/// reference: every access through [`with`](Self::with) or
+/// [`with_mut`](Self::with_mut) validates the region again, and fails
+/// cleanly once it is no longer mapped as required.
+#[derive(Debug)]
+pub struct VerifiedUserSlice<T> {
+    addr: usize,
+    len: usize,
+    flags: Access,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for VerifiedUserSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for VerifiedUserSlice<T> {}
+
+impl<T> VerifiedUserSlice<T> {
+    /// Validate `len` elements at `ptr` for `flags` and remember them
+    pub fn new<A: UserSpaceAccess + ?Sized>(
+        uspace: &A,
+        ptr: UserConstPtr<T>,
+        len: usize,
+        flags: Access,
+    ) -> UserResult<Self> {
+        check_region(uspace, ptr.address(), slice_layout::<T>(len)?, flags)?;
+        Ok(Self {
+            addr: ptr.address().as_usize(),
+            len,
+            flags,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Validate a read-only slice
+    pub fn readable<A: UserSpaceAccess + ?Sized>(
+        uspace: &A,
+        ptr: UserConstPtr<T>,
+        len: usize,
+    ) -> UserResult<Self> {
+        Self::new(uspace, ptr, len, Access::READ)
+    }
+
+    /// Validate a readable and writable slice
+    pub fn writable<A: UserSpaceAccess + ?Sized>(
+        uspace: &A,
+        ptr: UserPtr<T>,
+        len: usize,
+    ) -> UserResult<Self> {
+        Self::new(
+            uspace,
+            UserConstPtr::from(ptr.address().as_usize()),
+            len,
+            Access::READ | Access::WRITE,
+        )
+    }
+
+    /// The user pointer to the first element
+    pub fn as_ptr(&self) -> UserConstPtr<T> {
+        UserConstPtr::from(self.addr)
+    }
+
+    /// The mutable user pointer to the first element
+    pub fn as_mut_ptr(&self) -> UserPtr<T> {
+        UserPtr::from(self.addr)
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the slice is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The flags required on every access
+    pub fn flags(&self) -> Access {
+        self.flags
+    }
+
+    /// Validate the slice again and run `f` on its contents
+    pub fn with<A: UserSpaceAccess + ?Sized, R>(
+        &self,
+        uspace: &A,
+        f: impl FnOnce(&[T]) -> R,
+    ) -> UserResult<R> {
+        self.revalidate(uspace, self.flags | Access::READ)?;
+        assert_direct_map(uspace);
+        let slice = unsafe { user_slice(self.addr as *mut T, self.len) };
+        let range = user_range_of(slice.as_ptr(), slice.len());
+        try_access_user_range(range, Access::READ, || f(slice))
+    }
+
+    /// Validate the slice again and run `f` on its contents mutably
+    ///
+    /// Fails with `EFAULT` unless the slice was created writable.
+    pub fn with_mut<A: UserSpaceAccess + ?Sized, R>(
+        &self,
+        uspace: &A,
+        f: impl FnOnce(&mut [T]) -> R,
+    ) -> UserResult<R> {
+        if !self.flags.contains(Access::WRITE) {
+            return Err(Error::EFAULT);
+        }
+        self.revalidate(uspace, self.flags | Access::READ)?;
+        assert_direct_map(uspace);
+        let slice = unsafe { user_slice(self.addr as *mut T, self.len) };
+        let range = user_range_of(slice.as_ptr(), slice.len());
+        #[cfg(feature = "debug-aliasing")]
+        let _alias = AliasGuard::new(uspace.space_id(), range);
+        try_access_user_range(range, Access::READ | Access::WRITE, || f(slice))
+    }
+
+    fn revalidate<A: UserSpaceAccess + ?Sized>(&self, uspace: &A, flags: Access) -> UserResult<()> {
+        check_region(
+            uspace,
+            VirtAddr::from(self.addr),
+            slice_layout::<T>(self.len)?,
+            flags,
+        )?;
+        Ok(())
+    }
+}
+
+impl<T> From<VerifiedUserSlice<T>> for (UserConstPtr<T>, usize) {
+    fn from(slice: VerifiedUserSlice<T>) -> Self {
+        (slice.as_ptr(), slice.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserSpaceAccess, mock::MockUspace};
+
+    #[test]
+    fn accesses_inside_the_region_skip_the_backend() {
+        let uspace = MockUspace::new(1);
+        uspace.put(16, 7u32);
+        let region = uspace
+            .validate(uspace.range(16, 32), Access::READ | Access::WRITE)
+            .unwrap();
+        assert_eq!(region.range(), uspace.range(16, 32));
+        assert_eq!(uspace.read_in(&region, uspace.cptr::<u32>(16)), Ok(7));
+        uspace.write_in(&region, uspace.ptr::<u64>(40), 9).unwrap();
+        assert_eq!(uspace.get::<u64>(40), 9);
+        assert_eq!(
+            uspace
+                .slice_in(&region, uspace.cptr::<u8>(16), 32)
+                .map(|slice| slice.len()),
+            Ok(32)
+        );
+        assert_eq!(uspace.checks.get(), 1);
+    }
+
+    #[test]
+    fn accesses_outside_the_token_fail() {
+        let uspace = MockUspace::new(1);
+        let region = uspace.validate(uspace.range(16, 32), Access::READ).unwrap();
+        let fault = Some(Error::EFAULT);
+        assert_eq!(uspace.read_in(&region, uspace.cptr::<u64>(8)).err(), fault);
+        assert_eq!(uspace.read_in(&region, uspace.cptr::<u64>(44)).err(), fault);
+        assert_eq!(uspace.read_in(&region, uspace.cptr::<u32>(18)).err(), fault);
+        assert_eq!(
+            uspace.write_in(&region, uspace.ptr::<u8>(16), 0).err(),
+            fault
+        );
+        assert_eq!(
+            uspace.slice_in(&region, uspace.cptr::<u8>(16), 33).err(),
+            Some(Error::EFAULT)
+        );
+        // Zero-sized accesses pass anywhere, like in `check_region`
+        assert_eq!(uspace.read_in(&region, uspace.cptr::<()>(0)), Ok(()));
+
+        // A token only vouches for the uspace that produced it
+        let other = MockUspace::new(1);
+        assert_eq!(other.read_in(&region, uspace.cptr::<u8>(16)).err(), fault);
+        assert_eq!(uspace.checks.get(), 1);
+    }
+
+    #[test]
+    fn handle_is_send_and_sync() {
+        fn check<T: Send + Sync>() {}
+        check::<VerifiedUserSlice<u32>>();
+    }
+
+    #[test]
+    fn handle_revalidates_on_use() {
+        let uspace = MockUspace::new(2);
+        uspace.fill(4096, &[1, 2, 3, 4]);
+        let slice = VerifiedUserSlice::writable(&uspace, uspace.ptr::<u8>(4096), 4).unwrap();
+        assert_eq!(slice.with(&uspace, |s| s.iter().sum::<u8>()), Ok(10));
+        slice.with_mut(&uspace, |s| s[0] = 9).unwrap();
+        assert_eq!(uspace.load(4096, 1), [9]);
+
+        uspace.unmap(1);
+        assert_eq!(slice.with(&uspace, |_| unreachable!()), Err(Error::EFAULT));
+        assert_eq!(
+            slice.with_mut(&uspace, |_| unreachable!()),
+            Err(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    fn readable_handle_is_not_writable() {
+        let uspace = MockUspace::new(1);
+        let slice = VerifiedUserSlice::readable(&uspace, uspace.cptr::<u8>(0), 4).unwrap();
+        assert_eq!(
+            slice.with_mut(&uspace, |_| unreachable!()),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(
+            <(UserConstPtr<u8>, usize)>::from(slice),
+            (uspace.cptr(0), 4)
+        );
+    }
+
+    #[test]
+    fn token_goes_stale_on_munmap() {
+        let uspace = MockUspace::new(2);
+        uspace.fill(4096, &7u32.to_ne_bytes());
+        let region = uspace
+            .validate(uspace.range(4096, 8), Access::READ)
+            .unwrap();
+        assert!(region.is_current(&uspace));
+        assert_eq!(uspace.read_in(&region, uspace.cptr::<u32>(4096)), Ok(7));
+        assert_eq!(uspace.checks.get(), 1);
+
+        uspace.unmap(1);
+        assert!(!region.is_current(&uspace));
+        assert_eq!(
+            uspace.read_in(&region, uspace.cptr::<u32>(4096)),
+            Err(Error::EFAULT)
+        );
+    }
+}
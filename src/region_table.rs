@@ -0,0 +1,248 @@
+use memory_addr::{PAGE_SIZE_4K, VirtAddr, VirtAddrRange};
+
+use crate::{Access, Error, UserResult, UserSpaceRaw};
+
+/// Backend for targets without an MMU, where user memory is a fixed set of
+/// windows guarded by an MPU
+///
+/// A range is accessible if windows granting the requested flags cover all
+/// of it, and nothing is ever populated. Holds at most `N` windows, built
+/// with [`RegionTableBuilder`].
+#[derive(Debug, Clone)]
+pub struct RegionTableUspace<const N: usize> {
+    windows: [(VirtAddrRange, Access); N],
+    len: usize,
+    granule: usize,
+}
+
+impl<const N: usize> RegionTableUspace<N> {
+    /// The configured windows
+    pub fn windows(&self) -> &[(VirtAddrRange, Access)] {
+        &self.windows[..self.len]
+    }
+
+    /// Window containing `addr`
+    fn window_of(&self, addr: VirtAddr) -> Option<(VirtAddrRange, Access)> {
+        self.windows()
+            .iter()
+            .copied()
+            .find(|(range, _)| range.contains(addr))
+    }
+}
+
+impl<const N: usize> UserSpaceRaw for RegionTableUspace<N> {
+    fn check_region_access(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+        let mut addr = range.start;
+        while addr < range.end {
+            let (window, _) = self
+                .window_of(addr)
+                .filter(|(_, flags)| flags.contains(access_flags))
+                .ok_or(Error::EFAULT)?;
+            addr = window.end;
+        }
+        Ok(())
+    }
+
+    fn populate_region(&self, _range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        Ok(())
+    }
+
+    fn check_region_resident(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<bool> {
+        self.check_region_access(range, access_flags)?;
+        Ok(true)
+    }
+
+    fn page_size(&self) -> usize {
+        self.granule
+    }
+
+    /// Smallest range covering every window
+    fn user_addr_range(&self) -> VirtAddrRange {
+        let windows = self.windows().iter().map(|(range, _)| range);
+        match (
+            windows.clone().map(|range| range.start).min(),
+            windows.map(|range| range.end).max(),
+        ) {
+            (Some(start), Some(end)) => VirtAddrRange::new(start, end),
+            _ => VirtAddrRange::default(),
+        }
+    }
+}
+
+/// Builder of a [`RegionTableUspace`], typically filled at task load time
+#[derive(Debug, Clone)]
+pub struct RegionTableBuilder<const N: usize> {
+    table: RegionTableUspace<N>,
+}
+
+impl<const N: usize> RegionTableBuilder<N> {
+    /// Start with no windows and a 4K granule
+    pub fn new() -> Self {
+        Self {
+            table: RegionTableUspace {
+                windows: [(VirtAddrRange::default(), Access::empty()); N],
+                len: 0,
+                granule: PAGE_SIZE_4K,
+            },
+        }
+    }
+
+    /// Add a window of user memory accessible with `flags`
+    ///
+    /// Fails with `EINVAL` for an empty window or one overlapping an earlier
+    /// window, and with `ENOMEM` once `N` windows are configured.
+    pub fn window(mut self, range: VirtAddrRange, flags: Access) -> UserResult<Self> {
+        if range.is_empty()
+            || self
+                .table
+                .windows()
+                .iter()
+                .any(|(other, _)| other.overlaps(range))
+        {
+            return Err(Error::EINVAL);
+        }
+        let slot = self
+            .table
+            .windows
+            .get_mut(self.table.len)
+            .ok_or(Error::ENOMEM)?;
+        *slot = (range, flags);
+        self.table.len += 1;
+        Ok(self)
+    }
+
+    /// Set the protection granule, a power of two, used as the page size of
+    /// scans
+    ///
+    /// Windows should be aligned to it: scans validate one granule at a
+    /// time, so a granule reaching past a window fails.
+    pub fn granule(mut self, granule: usize) -> Self {
+        debug_assert!(granule.is_power_of_two());
+        self.table.granule = granule;
+        self
+    }
+
+    /// Finish the table
+    pub fn build(self) -> RegionTableUspace<N> {
+        self.table
+    }
+}
+
+impl<const N: usize> Default for RegionTableBuilder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::Layout;
+
+    use super::*;
+    use crate::{AccessErrorKind, check_region};
+
+    const RO: Access = Access::READ.union(Access::USER);
+    const RW: Access = RO.union(Access::WRITE);
+
+    fn range(start: usize, end: usize) -> VirtAddrRange {
+        VirtAddrRange::from_start_size(VirtAddr::from(start), end - start)
+    }
+
+    /// Read-write text at 0x10000..0x12000, read-only data right after it,
+    /// and a gap before a read-write stack at 0x20000..0x21000
+    fn table() -> RegionTableUspace<4> {
+        RegionTableBuilder::new()
+            .window(range(0x10000, 0x12000), RW)
+            .unwrap()
+            .window(range(0x12000, 0x13000), RO)
+            .unwrap()
+            .window(range(0x20000, 0x21000), RW)
+            .unwrap()
+            .build()
+    }
+
+    #[test]
+    fn accesses_must_stay_inside_matching_windows() {
+        let table = table();
+        assert_eq!(table.windows().len(), 3);
+        assert_eq!(table.user_addr_range(), range(0x10000, 0x21000));
+        assert_eq!(
+            table.check_region_access(range(0x10000, 0x12000), RW),
+            Ok(())
+        );
+        // Adjacent windows cover a range together
+        let across = range(0x11000, 0x13000);
+        assert_eq!(table.check_region_access(across, Access::READ), Ok(()));
+        assert_eq!(table.check_region_access(across, RW), Err(Error::EFAULT));
+        // The gap between the data and the stack is not user memory
+        let into_gap = range(0x12f00, 0x13100);
+        assert_eq!(
+            table.check_region_access(into_gap, Access::READ),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(
+            table.check_region_access(range(0x20f00, 0x21100), RW),
+            Err(Error::EFAULT)
+        );
+
+        let layout = Layout::new::<[u64; 4]>();
+        let write = |addr: usize| {
+            check_region(&table, VirtAddr::from(addr), layout, Access::WRITE)
+                .map(|_| ())
+                .map_err(|e| e.kind)
+        };
+        assert_eq!(write(0x20fe0), Ok(()));
+        assert_eq!(write(0x12000), Err(AccessErrorKind::NotMapped));
+        assert_eq!(write(0x20ff0), Err(AccessErrorKind::NotMapped));
+    }
+
+    #[test]
+    fn nothing_is_ever_populated() {
+        let table = table();
+        // Populating is a no-op, so checks alone decide the outcome
+        let gap = range(0x14000, 0x15000);
+        assert_eq!(table.populate_region(gap, RW), Ok(()));
+        assert_eq!(
+            table.check_region_access(gap, Access::READ),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(
+            table.check_region_resident(range(0x20000, 0x20100), RW),
+            Ok(true)
+        );
+        assert_eq!(
+            table.check_region_resident(gap, Access::READ),
+            Err(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    fn builder_rejects_bad_windows() {
+        let builder = RegionTableBuilder::<2>::new()
+            .window(range(0x10000, 0x11000), RW)
+            .unwrap();
+        let empty = VirtAddrRange::from_start_size(VirtAddr::from(0x30000), 0);
+        assert!(matches!(
+            builder.clone().window(empty, RW),
+            Err(Error::EINVAL)
+        ));
+        assert!(matches!(
+            builder.clone().window(range(0x10800, 0x11800), RW),
+            Err(Error::EINVAL)
+        ));
+        let full = builder.window(range(0x11000, 0x12000), RW).unwrap();
+        assert!(matches!(
+            full.clone().window(range(0x20000, 0x21000), RW),
+            Err(Error::ENOMEM)
+        ));
+        assert_eq!(full.granule(0x400).build().page_size(), 0x400);
+        assert_eq!(
+            RegionTableBuilder::<1>::new().build().user_addr_range(),
+            VirtAddrRange::default()
+        );
+    }
+}
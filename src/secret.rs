@@ -0,0 +1,99 @@
+use core::ops::{Deref, DerefMut};
+
+use alloc::{vec, vec::Vec};
+
+use crate::zeroize;
+
+/// Kernel copy of sensitive user data, such as key material, zeroed when
+/// dropped
+///
+/// Returned by [`read_sensitive`](crate::UserSpaceAccess::read_sensitive).
+/// The buffer is allocated once at its final size, so no stale copy is left
+/// behind by a reallocation.
+pub struct SecretBuf {
+    buf: Vec<u8>,
+}
+
+impl SecretBuf {
+    /// A zeroed buffer of `len` bytes
+    pub(crate) fn zeroed(len: usize) -> Self {
+        Self { buf: vec![0; len] }
+    }
+}
+
+impl Deref for SecretBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl DerefMut for SecretBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl core::fmt::Debug for SecretBuf {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SecretBuf")
+            .field("len", &self.buf.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for SecretBuf {
+    fn drop(&mut self) {
+        zeroize(&mut self.buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::format;
+
+    use crate::{Error, UserSpaceAccess, mock::MockUspace, zeroize};
+
+    #[test]
+    fn sensitive_reads_are_bounded_and_hidden() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(8, b"hunter2");
+        let secret = uspace.read_sensitive(uspace.cptr(8), 7, 16).unwrap();
+        assert_eq!(&*secret, b"hunter2");
+        assert!(!format!("{secret:?}").contains("hunter2"));
+
+        assert_eq!(
+            uspace.read_sensitive(uspace.cptr(8), 17, 16).err(),
+            Some(Error::EINVAL)
+        );
+        assert_eq!(
+            uspace.read_sensitive(uspace.cptr(4090), 16, 16).err(),
+            Some(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    fn sensitive_writes_scrub_the_source() {
+        let uspace = MockUspace::new(1);
+        let mut data = *b"key";
+        uspace.write_sensitive(uspace.ptr(0), &mut data).unwrap();
+        assert_eq!(uspace.load(0, 3), b"key");
+        assert_eq!(data, [0; 3]);
+
+        // Even when the write fails
+        let mut data = *b"key";
+        assert_eq!(
+            uspace.write_sensitive(uspace.ptr(4094), &mut data),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(data, [0; 3]);
+    }
+
+    #[test]
+    fn zeroize_clears_every_byte() {
+        let mut buf = [0xa5u8; 33];
+        zeroize(&mut buf);
+        assert_eq!(buf, [0; 33]);
+    }
+}
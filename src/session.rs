@@ -0,0 +1,658 @@
+use core::cell::{Cell, RefCell};
+#[cfg(feature = "debug-double-fetch")]
+use core::panic::Location;
+
+use core::alloc::Layout;
+
+use alloc::vec::Vec;
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, VirtAddrRange};
+
+use crate::{
+    Access, AccessHint, AccessResult, Error, Limits, MemoryType, PrefaultHint, SyncKind,
+    UserAccessError, UserResult, UserSpaceAccess, UserSpaceRaw, check_flags, effective_user_range,
+};
+
+/// Maximum number of pages remembered by a [`ValidationSession`]
+pub const SESSION_CACHE_PAGES: usize = 32;
+
+/// Reads remembered by a [`ValidationSession`] for double-fetch detection
+#[cfg(feature = "debug-double-fetch")]
+const SESSION_READS: usize = 64;
+
+/// Double fetches a [`ValidationSession`] reports at most
+#[cfg(feature = "debug-double-fetch")]
+const SESSION_DOUBLE_FETCH_REPORTS: usize = 4;
+
+/// A read overlapping an earlier read of the same [`ValidationSession`],
+/// passed to [`UserSpaceRaw::on_double_fetch`]
+#[cfg(feature = "debug-double-fetch")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoubleFetch {
+    /// The earlier read
+    pub first: VirtAddrRange,
+    /// Call site of the earlier read
+    pub first_location: &'static Location<'static>,
+    /// The overlapping read
+    pub second: VirtAddrRange,
+    /// Call site of the overlapping read
+    pub second_location: &'static Location<'static>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedPage {
+    page: VirtAddr,
+    checked: Access,
+    populated: Access,
+}
+
+/// A [`UserSpaceAccess`] wrapper that remembers which pages it has already
+/// checked and populated, skipping repeated backend calls for them
+///
+/// Intended to live for one syscall: every helper of the crate works through
+/// it, and calls touching the same few pages hit the backend once. Objects
+/// within the page last validated by [`check_region`](crate::check_region)
+/// skip even the cache lookup.
+///
+/// The cache is tied to the backend's
+/// [`generation`](UserSpaceRaw::generation) and is dropped whenever it
+/// changes, so backends keeping the default generation get no caching at
+/// all. [`invalidate`](Self::invalidate) drops it explicitly. Dropping the
+/// session discards the cache.
+///
+/// With the `debug-double-fetch` feature, the session also remembers the
+/// ranges read through it and reports reads overlapping an earlier one to
+/// [`UserSpaceRaw::on_double_fetch`], except for the intentional re-reads of
+/// [`read_stable`](crate::UserSpaceAccess::read_stable).
+pub struct ValidationSession<'a, A: UserSpaceAccess + ?Sized> {
+    uspace: &'a A,
+    cache: RefCell<Vec<CachedPage>>,
+    /// Page last checked and populated by `check_region`, with its flags
+    last: Cell<Option<(VirtAddr, Access)>>,
+    generation: Cell<u64>,
+    #[cfg(feature = "debug-double-fetch")]
+    reads: RefCell<Vec<(VirtAddrRange, &'static Location<'static>)>>,
+    #[cfg(feature = "debug-double-fetch")]
+    reports: Cell<usize>,
+    #[cfg(feature = "debug-double-fetch")]
+    reread: Cell<Option<VirtAddrRange>>,
+}
+
+impl<'a, A: UserSpaceAccess + ?Sized> ValidationSession<'a, A> {
+    /// Start a session with an empty cache
+    pub fn new(uspace: &'a A) -> Self {
+        Self {
+            uspace,
+            cache: RefCell::new(Vec::with_capacity(SESSION_CACHE_PAGES)),
+            last: Cell::new(None),
+            generation: Cell::new(uspace.generation()),
+            #[cfg(feature = "debug-double-fetch")]
+            reads: RefCell::new(Vec::new()),
+            #[cfg(feature = "debug-double-fetch")]
+            reports: Cell::new(0),
+            #[cfg(feature = "debug-double-fetch")]
+            reread: Cell::new(None),
+        }
+    }
+
+    /// The wrapped backend
+    pub fn inner(&self) -> &'a A {
+        self.uspace
+    }
+
+    /// Forget everything validated so far
+    pub fn invalidate(&self) {
+        self.cache.borrow_mut().clear();
+        self.last.set(None);
+    }
+
+    /// Drop the cache if the backend's generation moved on
+    fn sync_generation(&self) {
+        let generation = self.uspace.generation();
+        if self.generation.replace(generation) != generation {
+            self.invalidate();
+        }
+    }
+
+    /// Start of every page overlapping `range`, or `None` if there are too
+    /// many to cache
+    fn pages(&self, range: VirtAddrRange) -> Option<impl Iterator<Item = VirtAddr>> {
+        let page_size = self.uspace.page_size();
+        let first = range.start.align_down(page_size);
+        let count = (range.end.as_usize() - first.as_usize()).div_ceil(page_size);
+        (count <= SESSION_CACHE_PAGES).then(|| (0..count).map(move |i| first + i * page_size))
+    }
+
+    /// Whether every page of `range` was validated for `flags`
+    fn is_cached(&self, range: VirtAddrRange, flags: Access, populated: bool) -> bool {
+        self.sync_generation();
+        let cache = self.cache.borrow();
+        self.pages(range).is_some_and(|mut pages| {
+            pages.all(|page| {
+                cache.iter().any(|entry| {
+                    entry.page == page
+                        && if populated {
+                            entry.populated.contains(flags)
+                        } else {
+                            entry.checked.contains(flags)
+                        }
+                })
+            })
+        })
+    }
+
+    /// Remember every page of `range` as validated for `flags`
+    fn record(&self, range: VirtAddrRange, flags: Access, populated: bool) {
+        let Some(pages) = self.pages(range) else {
+            return;
+        };
+        let mut cache = self.cache.borrow_mut();
+        for page in pages {
+            let idx = match cache.iter().position(|entry| entry.page == page) {
+                Some(idx) => idx,
+                None => {
+                    if cache.len() == SESSION_CACHE_PAGES {
+                        cache.remove(0);
+                    }
+                    cache.push(CachedPage {
+                        page,
+                        checked: Access::empty(),
+                        populated: Access::empty(),
+                    });
+                    cache.len() - 1
+                }
+            };
+            let entry = &mut cache[idx];
+            entry.checked |= flags;
+            if populated {
+                entry.populated |= flags;
+            }
+        }
+    }
+}
+
+impl<A: UserSpaceAccess + ?Sized> UserSpaceRaw for ValidationSession<'_, A> {
+    fn check_region(
+        &self,
+        start: VirtAddr,
+        layout: Layout,
+        access_flags: Access,
+        hint: AccessHint,
+    ) -> AccessResult<()> {
+        if layout.size() == 0 {
+            return Ok(());
+        }
+        let page_size = self.uspace.page_size();
+        let addr = self.uspace.untag_addr(start);
+        let page = addr.align_down(page_size);
+        let one_page = addr.is_aligned(layout.align())
+            && layout.size() <= page.as_usize() + page_size - addr.as_usize();
+
+        self.sync_generation();
+        if one_page
+            && self
+                .last
+                .get()
+                .is_some_and(|(last, flags)| last == page && flags.contains(access_flags))
+        {
+            return Ok(());
+        }
+        // Only well-formed ranges inside user space are looked up and
+        // recorded; the backend reports everything else
+        let range = VirtAddrRange::try_from_start_size(addr, layout.size()).filter(|&range| {
+            addr.is_aligned(layout.align()) && effective_user_range(self).contains_range(range)
+        });
+        let flags = check_flags(access_flags);
+        let populated = hint != AccessHint::NoPopulate;
+        let hit = range.is_some_and(|range| {
+            self.is_cached(range, flags, populated)
+                && self.uspace.check_pkey(range, access_flags).is_ok()
+        });
+        if !hit {
+            let generation = self.generation.get();
+            self.uspace
+                .check_region(start, layout, access_flags, hint)?;
+            if self.uspace.generation() != generation {
+                return Ok(());
+            }
+            if let Some(range) = range {
+                self.record(range, flags, populated);
+            }
+        }
+        // Only a populated page whole inside user space stands for other
+        // objects in it
+        if one_page
+            && populated
+            && VirtAddrRange::try_from_start_size(page, page_size)
+                .is_some_and(|range| effective_user_range(self).contains_range(range))
+        {
+            let flags = match self.last.get() {
+                Some((last, flags)) if last == page => flags | access_flags,
+                _ => access_flags,
+            };
+            self.last.set(Some((page, flags)));
+        }
+        Ok(())
+    }
+
+    fn scan_null_terminated(
+        &self,
+        start: VirtAddr,
+        layout: Layout,
+        access_flags: Access,
+        max: usize,
+        hint: AccessHint,
+    ) -> AccessResult<usize> {
+        self.uspace
+            .scan_null_terminated(start, layout, access_flags, max, hint)
+    }
+
+    fn clamp_rw_len(&self, len: usize) -> usize {
+        self.uspace.clamp_rw_len(len)
+    }
+
+    fn query_region(&self, range: VirtAddrRange, f: &mut dyn FnMut(VirtAddr, Option<Access>)) {
+        self.uspace.query_region(range, f)
+    }
+
+    fn check_region_access(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+        Ok(self.check_region_access_detailed(range, access_flags)?)
+    }
+
+    fn populate_region(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+        Ok(self.populate_region_detailed(range, access_flags)?)
+    }
+
+    fn populate_region_nonblocking(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<()> {
+        if !self.is_cached(range, access_flags, true) {
+            self.uspace
+                .populate_region_nonblocking(range, access_flags)?;
+            self.record(range, access_flags, true);
+        }
+        Ok(())
+    }
+
+    fn check_region_access_detailed(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> AccessResult<()> {
+        if !self.is_cached(range, access_flags, false) {
+            self.uspace
+                .check_region_access_detailed(range, access_flags)?;
+            self.record(range, access_flags, false);
+        }
+        Ok(())
+    }
+
+    fn populate_region_detailed(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> AccessResult<()> {
+        if !self.is_cached(range, access_flags, true) {
+            self.uspace.populate_region_detailed(range, access_flags)?;
+            self.record(range, access_flags, true);
+        }
+        Ok(())
+    }
+
+    fn ensure_region(&self, range: VirtAddrRange, access_flags: Access) -> AccessResult<()> {
+        let flags = check_flags(access_flags);
+        if !self.is_cached(range, flags, true) {
+            self.uspace.ensure_region(range, access_flags)?;
+            self.record(range, flags, true);
+        }
+        Ok(())
+    }
+
+    fn prefault(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+        hint: PrefaultHint,
+    ) -> UserResult<()> {
+        self.uspace.prefault(range, access_flags, hint)
+    }
+
+    fn check_region_resident(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<bool> {
+        if self.is_cached(range, access_flags, true) {
+            return Ok(true);
+        }
+        let resident = self.uspace.check_region_resident(range, access_flags)?;
+        self.record(range, access_flags, resident);
+        Ok(resident)
+    }
+
+    fn check_regions(&self, regions: &[(VirtAddrRange, Access)]) -> UserResult<()> {
+        let missing = regions
+            .iter()
+            .copied()
+            .filter(|&(range, flags)| !self.is_cached(range, flags, true))
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        self.uspace.check_regions(&missing)?;
+        for (range, flags) in missing {
+            self.record(range, flags, true);
+        }
+        Ok(())
+    }
+
+    fn page_size(&self) -> usize {
+        self.uspace.page_size()
+    }
+
+    fn user_addr_range(&self) -> VirtAddrRange {
+        self.uspace.user_addr_range()
+    }
+
+    fn charge_kernel_alloc(&self, bytes: usize) -> UserResult<()> {
+        self.uspace.charge_kernel_alloc(bytes)
+    }
+
+    fn uncharge_kernel_alloc(&self, bytes: usize) {
+        self.uspace.uncharge_kernel_alloc(bytes);
+    }
+
+    fn limits(&self) -> &Limits {
+        self.uspace.limits()
+    }
+
+    fn on_access_error(&self, error: &UserAccessError) {
+        self.uspace.on_access_error(error);
+    }
+
+    #[cfg(feature = "debug-double-fetch")]
+    fn record_user_read(&self, range: VirtAddrRange, location: &'static Location<'static>) {
+        if self.reread.take() == Some(range) {
+            return;
+        }
+        let mut reads = self.reads.borrow_mut();
+        if let Some(&(first, first_location)) =
+            reads.iter().find(|(other, _)| other.overlaps(range))
+            && self.reports.get() < SESSION_DOUBLE_FETCH_REPORTS
+        {
+            self.reports.set(self.reports.get() + 1);
+            self.uspace.on_double_fetch(&DoubleFetch {
+                first,
+                first_location,
+                second: range,
+                second_location: location,
+            });
+        }
+        if reads.len() == SESSION_READS {
+            reads.remove(0);
+        }
+        reads.push((range, location));
+    }
+
+    #[cfg(feature = "debug-double-fetch")]
+    fn on_double_fetch(&self, fetch: &DoubleFetch) {
+        self.uspace.on_double_fetch(fetch);
+    }
+
+    #[cfg(feature = "debug-double-fetch")]
+    fn expect_user_reread(&self, range: VirtAddrRange) {
+        self.reread.set(Some(range));
+    }
+
+    fn min_user_addr(&self) -> usize {
+        self.uspace.min_user_addr()
+    }
+
+    fn generation(&self) -> u64 {
+        self.uspace.generation()
+    }
+
+    fn space_id(&self) -> usize {
+        self.uspace.space_id()
+    }
+
+    fn should_interrupt(&self) -> Option<Error> {
+        self.uspace.should_interrupt()
+    }
+
+    fn relax(&self) {
+        self.uspace.relax();
+    }
+
+    fn memory_type(&self, range: VirtAddrRange) -> MemoryType {
+        self.uspace.memory_type(range)
+    }
+
+    unsafe fn raw_copy_from_user(&self, dst: *mut u8, src: VirtAddr, len: usize) -> UserResult<()> {
+        unsafe { self.uspace.raw_copy_from_user(dst, src, len) }
+    }
+
+    unsafe fn raw_copy_to_user(&self, dst: VirtAddr, src: *const u8, len: usize) -> UserResult<()> {
+        unsafe { self.uspace.raw_copy_to_user(dst, src, len) }
+    }
+
+    fn direct_map(&self) -> bool {
+        self.uspace.direct_map()
+    }
+
+    fn pin_pages(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+        self.uspace.pin_pages(range, access_flags)
+    }
+
+    fn unpin_pages(&self, range: VirtAddrRange) {
+        self.uspace.unpin_pages(range);
+    }
+
+    fn virt_to_phys(&self, vaddr: VirtAddr) -> Option<PhysAddr> {
+        self.uspace.virt_to_phys(vaddr)
+    }
+
+    fn untag_addr(&self, addr: VirtAddr) -> VirtAddr {
+        self.uspace.untag_addr(addr)
+    }
+
+    fn force_write_region(&self, addr: VirtAddr, data: &[u8]) -> UserResult<()> {
+        self.uspace.force_write_region(addr, data)
+    }
+
+    fn sync_after_write(&self, range: VirtAddrRange, kind: SyncKind) {
+        self.uspace.sync_after_write(range, kind);
+    }
+
+    fn check_pkey(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+        self.uspace.check_pkey(range, access_flags)
+    }
+
+    fn try_grow_region(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<bool> {
+        self.uspace.try_grow_region(range, access_flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Error, check_region_batch,
+        mock::{MockUspace, RW},
+    };
+
+    #[test]
+    fn repeated_accesses_hit_the_backend_once() {
+        let uspace = MockUspace::new(2);
+        let session = uspace.session();
+        for off in (0..4096).step_by(64) {
+            assert_eq!(session.read(uspace.cptr::<[u64; 2]>(off)), Ok([0; 2]));
+        }
+        assert_eq!((uspace.checks.get(), uspace.populates.get()), (1, 1));
+
+        // New flags, and objects reaching into the next page, go to the
+        // backend
+        session.write(uspace.ptr::<u64>(8), 1).unwrap();
+        assert_eq!(uspace.checks.get(), 2);
+        assert_eq!(session.read(uspace.cptr::<[u64; 2]>(4088)), Ok([0; 2]));
+        assert_eq!(uspace.checks.get(), 3);
+    }
+
+    #[test]
+    fn invalidate_revalidates() {
+        let uspace = MockUspace::new(1);
+        let session = uspace.session();
+        assert_eq!(session.read(uspace.cptr::<u64>(0)), Ok(0));
+        assert_eq!(session.read(uspace.cptr::<u64>(0)), Ok(0));
+        assert_eq!(uspace.checks.get(), 1);
+        session.invalidate();
+        assert_eq!(session.read(uspace.cptr::<u64>(0)), Ok(0));
+        assert_eq!(uspace.checks.get(), 2);
+    }
+
+    #[test]
+    fn large_ranges_and_evicted_pages_are_not_cached() {
+        let pages = SESSION_CACHE_PAGES + 1;
+        let uspace = MockUspace::new(pages);
+        let session = uspace.session();
+        let mut buf = [0u8; (SESSION_CACHE_PAGES + 1) * 4096];
+        session
+            .read_slice_to(uspace.cptr::<u8>(0), &mut buf)
+            .unwrap();
+        session
+            .read_slice_to(uspace.cptr::<u8>(0), &mut buf)
+            .unwrap();
+        assert_eq!(uspace.checks.get(), 2);
+
+        // Touching one page more than the cache holds evicts the first
+        for page in 0..pages {
+            session.read(uspace.cptr::<u8>(page * 4096)).unwrap();
+        }
+        uspace.reset_counts();
+        session.read(uspace.cptr::<u8>(4096)).unwrap();
+        assert_eq!(uspace.checks.get(), 0);
+        session.read(uspace.cptr::<u8>(0)).unwrap();
+        assert_eq!(uspace.checks.get(), 1);
+    }
+
+    #[test]
+    fn batches_only_forward_missing_regions() {
+        let uspace = MockUspace::new(2);
+        let session = uspace.session();
+        session.read(uspace.cptr::<u64>(0)).unwrap();
+        uspace.reset_counts();
+        let regions = [
+            (uspace.addr(0), 8, Access::READ),
+            (uspace.addr(4096), 8, Access::READ),
+        ];
+        check_region_batch(&session, &regions).unwrap();
+        assert_eq!((uspace.checks.get(), uspace.populates.get()), (1, 1));
+        check_region_batch(&session, &regions).unwrap();
+        assert_eq!(uspace.checks.get(), 1);
+    }
+
+    #[test]
+    fn munmap_between_reads_revalidates() {
+        let uspace = MockUspace::new(2);
+        let session = uspace.session();
+        assert_eq!(session.read(uspace.cptr::<u64>(4096)), Ok(0));
+        assert_eq!(session.read(uspace.cptr::<u64>(4104)), Ok(0));
+        assert_eq!(uspace.checks.get(), 1);
+
+        uspace.unmap(1);
+        assert_eq!(session.read(uspace.cptr::<u64>(4096)), Err(Error::EFAULT));
+        assert_eq!(uspace.checks.get(), 2);
+    }
+
+    #[test]
+    fn nonblocking_populates_are_cached() {
+        let uspace = MockUspace::new(1);
+        uspace.unpopulate(0);
+        let session = uspace.session();
+        let word = uspace.cptr::<u32>(64);
+        uspace.would_block.set(true);
+        assert_eq!(session.read_futex_value(word), Err(Error::EAGAIN));
+        uspace.would_block.set(false);
+        assert_eq!(session.read_futex_value(word), Ok(0));
+        let populates = uspace.populates.get();
+        uspace.would_block.set(true);
+        assert_eq!(session.read_futex_value(word), Ok(0));
+        assert_eq!(uspace.populates.get(), populates);
+    }
+
+    #[cfg(feature = "debug-double-fetch")]
+    #[test]
+    fn overlapping_reads_are_reported_once_per_pair() {
+        let uspace = MockUspace::new(1);
+        let session = uspace.session();
+        let first = line!() + 1;
+        session.read(uspace.cptr::<u64>(0)).unwrap();
+        session.read(uspace.cptr::<u64>(8)).unwrap();
+        // Writes never count as fetches
+        session.write(uspace.ptr::<u64>(0), 1).unwrap();
+        assert!(uspace.fetches.borrow().is_empty());
+
+        let second = line!() + 1;
+        session.read(uspace.cptr::<u32>(4)).unwrap();
+        let fetches = uspace.fetches.borrow();
+        assert_eq!(fetches.len(), 1);
+        assert_eq!(fetches[0].first, uspace.range(0, 8));
+        assert_eq!(fetches[0].second, uspace.range(4, 4));
+        assert_eq!(fetches[0].first_location.line(), first);
+        assert_eq!(fetches[0].second_location.line(), second);
+        drop(fetches);
+
+        // A fresh session starts over
+        let session = uspace.session();
+        session.read(uspace.cptr::<u64>(0)).unwrap();
+        assert_eq!(uspace.fetches.borrow().len(), 1);
+    }
+
+    #[cfg(feature = "debug-double-fetch")]
+    #[test]
+    fn double_fetch_reports_are_capped() {
+        let uspace = MockUspace::new(1);
+        let session = uspace.session();
+        for _ in 0..10 {
+            session.read(uspace.cptr::<u64>(0)).unwrap();
+        }
+        assert_eq!(uspace.fetches.borrow().len(), SESSION_DOUBLE_FETCH_REPORTS);
+        // Reads through the backend itself are not tracked
+        uspace.read(uspace.cptr::<u64>(0)).unwrap();
+        uspace.read(uspace.cptr::<u64>(0)).unwrap();
+        assert_eq!(uspace.fetches.borrow().len(), SESSION_DOUBLE_FETCH_REPORTS);
+    }
+
+    #[test]
+    fn small_objects_in_a_checked_page_skip_the_backend() {
+        let uspace = MockUspace::new(2);
+        let session = uspace.session();
+        for off in (0..4096).step_by(64) {
+            assert_eq!(session.read(uspace.cptr::<[u64; 2]>(off)), Ok([0; 2]));
+        }
+        assert_eq!(uspace.checks.get(), 1);
+        assert_eq!(uspace.populates.get(), 1);
+
+        // New flags, and objects reaching into the next page, go to the
+        // backend
+        assert_eq!(session.write(uspace.ptr::<u64>(8), 1), Ok(()));
+        assert_eq!(uspace.checks.get(), 2);
+        assert_eq!(session.read(uspace.cptr::<u64>(16)), Ok(0));
+        assert_eq!(session.write(uspace.ptr::<u64>(16), 2), Ok(()));
+        assert_eq!(uspace.checks.get(), 2);
+        assert_eq!(session.read(uspace.cptr::<[u64; 2]>(4088)), Ok([0; 2]));
+        assert_eq!(uspace.checks.get(), 3);
+    }
+
+    #[test]
+    fn protection_change_drops_the_fast_path() {
+        let uspace = MockUspace::new(1);
+        let session = uspace.session();
+        assert_eq!(session.read(uspace.cptr::<u64>(0)), Ok(0));
+        uspace.protect(0, RW - Access::WRITE);
+        assert_eq!(session.write(uspace.ptr::<u64>(0), 1), Err(Error::EFAULT));
+        assert_eq!(session.read(uspace.cptr::<u64>(8)), Ok(0));
+        assert_eq!(uspace.checks.get(), 3);
+    }
+}
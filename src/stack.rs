@@ -0,0 +1,385 @@
+#[cfg(feature = "struct-helpers")]
+use core::alloc::Layout;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use memory_addr::VirtAddr;
+
+#[cfg(feature = "struct-helpers")]
+use crate::{Access, SIGINFO_SIZE, SigInfo, check_region};
+use crate::{Error, UserPtr, UserResult, UserSpaceAccess};
+
+/// `AT_NULL`, the auxiliary vector terminator
+pub const AT_NULL: usize = 0;
+
+/// Stack alignment required at process entry
+#[cfg(feature = "alloc")]
+const STACK_ALIGN: usize = 16;
+
+/// Writer that fills a user stack downward from its top
+///
+/// Every push is validated and written immediately; going more than `budget`
+/// bytes below the top fails with `E2BIG`, as `execve` reports an oversized
+/// argument area.
+pub struct UserStackWriter<'a, A: UserSpaceAccess + ?Sized> {
+    uspace: &'a A,
+    sp: usize,
+    limit: usize,
+}
+
+impl<'a, A: UserSpaceAccess + ?Sized> UserStackWriter<'a, A> {
+    /// Start writing below `top`, using at most `budget` bytes
+    pub fn new(uspace: &'a A, top: VirtAddr, budget: usize) -> Self {
+        Self {
+            uspace,
+            sp: top.as_usize(),
+            limit: top.as_usize().saturating_sub(budget),
+        }
+    }
+
+    /// Current stack pointer
+    pub fn sp(&self) -> VirtAddr {
+        VirtAddr::from(self.sp)
+    }
+
+    /// Move the stack pointer down by `len` bytes and align it to `align`,
+    /// which must be a power of two
+    pub(crate) fn reserve(&mut self, len: usize, align: usize) -> UserResult<usize> {
+        let sp = self
+            .sp
+            .checked_sub(len)
+            .map(|sp| sp & !(align - 1))
+            .filter(|&sp| sp >= self.limit)
+            .ok_or(Error::E2BIG)?;
+        self.sp = sp;
+        Ok(sp)
+    }
+
+    /// Align the stack pointer down to `align`, which must be a power of two
+    pub fn align(&mut self, align: usize) -> UserResult<()> {
+        self.reserve(0, align).map(|_| ())
+    }
+
+    /// Push raw bytes, returning their address
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> UserResult<VirtAddr> {
+        let sp = self.reserve(bytes.len(), 1)?;
+        self.uspace.write_slice(UserPtr::from(sp), bytes)?;
+        Ok(VirtAddr::from(sp))
+    }
+
+    /// Push a NUL-terminated copy of `s`, returning its address
+    pub fn push_cstr(&mut self, s: &[u8]) -> UserResult<VirtAddr> {
+        self.push_bytes(&[0])?;
+        self.push_bytes(s)
+    }
+
+    /// Push a NUL-terminated copy of `s`, returning its address
+    pub fn push_str(&mut self, s: &str) -> UserResult<VirtAddr> {
+        self.push_cstr(s.as_bytes())
+    }
+
+    /// Push a word aligned to its natural alignment, returning its address
+    pub fn push_usize(&mut self, val: usize) -> UserResult<VirtAddr> {
+        let sp = self.reserve(size_of::<usize>(), align_of::<usize>())?;
+        self.uspace.write(UserPtr::from(sp), val)?;
+        Ok(VirtAddr::from(sp))
+    }
+
+    /// Push consecutive words so the first one is at an address aligned to
+    /// `align`, returning that address
+    #[cfg(feature = "alloc")]
+    fn push_words(&mut self, words: &[usize], align: usize) -> UserResult<usize> {
+        let sp = self.reserve(size_of_val(words), align)?;
+        self.uspace.write_slice(UserPtr::from(sp), words)?;
+        Ok(sp)
+    }
+
+    /// Finish writing, returning the final stack pointer
+    pub fn finish(self) -> VirtAddr {
+        self.sp()
+    }
+
+    /// Lay out the initial process stack and return the entry stack pointer
+    ///
+    /// Pushes the `envp` and `argv` strings, then, from the 16-byte aligned
+    /// entry stack pointer upward: `argc`, the `argv` pointers, a null, the
+    /// `envp` pointers, a null and the `auxv` pairs terminated by `AT_NULL`.
+    /// Data referenced by `auxv` (e.g. `AT_RANDOM` bytes) should be pushed
+    /// before calling this.
+    #[cfg(feature = "alloc")]
+    pub fn build_initial_stack<S: AsRef<[u8]>>(
+        mut self,
+        argv: &[S],
+        envp: &[S],
+        auxv: &[(usize, usize)],
+    ) -> UserResult<VirtAddr> {
+        let mut push_all = |strs: &[S]| -> UserResult<Vec<usize>> {
+            let mut ptrs = strs
+                .iter()
+                .rev()
+                .map(|s| self.push_cstr(s.as_ref()).map(VirtAddr::as_usize))
+                .collect::<UserResult<Vec<_>>>()?;
+            ptrs.reverse();
+            Ok(ptrs)
+        };
+        let envp = push_all(envp)?;
+        let argv = push_all(argv)?;
+
+        let mut words = Vec::with_capacity(argv.len() + envp.len() + 2 * auxv.len() + 5);
+        words.push(argv.len());
+        words.extend(argv);
+        words.push(0);
+        words.extend(envp);
+        words.push(0);
+        for &(key, val) in auxv {
+            words.extend([key, val]);
+        }
+        words.extend([AT_NULL, 0]);
+
+        let sp = self.push_words(&words, STACK_ALIGN)?;
+        Ok(VirtAddr::from(sp))
+    }
+}
+
+/// Bytes below the stack pointer that leaf code may use without moving it,
+/// which a signal frame must skip
+pub const RED_ZONE: usize = if cfg!(target_arch = "x86_64") { 128 } else { 0 };
+
+/// Addresses of a signal frame written by [`SignalFrameWriter`]
+#[cfg(feature = "struct-helpers")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalFrame {
+    /// User stack pointer to enter the handler with
+    pub sp: VirtAddr,
+    /// Address of the `siginfo_t`, the handler's second argument
+    pub siginfo: VirtAddr,
+    /// Address of the `ucontext_t`, the handler's third argument
+    pub ucontext: VirtAddr,
+}
+
+/// Writer carving a signal frame out of the user stack or alternate stack
+///
+/// The crate owns the frame layout and validation; the architecture-specific
+/// `ucontext_t` (saved registers included) is supplied pre-encoded by the
+/// caller.
+#[cfg(feature = "struct-helpers")]
+pub struct SignalFrameWriter<'a, A: UserSpaceAccess + ?Sized> {
+    stack: UserStackWriter<'a, A>,
+    top: usize,
+}
+
+#[cfg(feature = "struct-helpers")]
+impl<'a, A: UserSpaceAccess + ?Sized> SignalFrameWriter<'a, A> {
+    /// Place the frame below `user_sp`, skipping the red zone
+    pub fn new(uspace: &'a A, user_sp: VirtAddr) -> Self {
+        let top = user_sp.as_usize().saturating_sub(RED_ZONE);
+        Self {
+            stack: UserStackWriter::new(uspace, VirtAddr::from(top), top),
+            top,
+        }
+    }
+
+    /// Place the frame at the top of the alternate stack `[base, base + size)`
+    ///
+    /// [`write`](Self::write) fails with `EFAULT` if the frame does not fit.
+    pub fn on_altstack(uspace: &'a A, base: VirtAddr, size: usize) -> Self {
+        let top = base.as_usize().saturating_add(size);
+        Self {
+            stack: UserStackWriter::new(uspace, VirtAddr::from(top), size),
+            top,
+        }
+    }
+
+    /// Write the frame: the `ucontext_t` bytes aligned to `ucontext_align`,
+    /// then the `siginfo_t`, then on x86_64 the `restorer` return address
+    ///
+    /// The whole frame is validated as one region before anything is
+    /// written. The resulting stack pointer is 16-byte aligned, minus the
+    /// pushed return address on x86_64 as at a function entry; elsewhere the
+    /// caller puts `restorer` in the link register.
+    pub fn write(
+        mut self,
+        info: &SigInfo,
+        ucontext: &[u8],
+        ucontext_align: usize,
+        restorer: Option<usize>,
+    ) -> UserResult<SignalFrame> {
+        let frame_err = |_| Error::EFAULT;
+        let uc = self
+            .stack
+            .reserve(ucontext.len(), ucontext_align.max(STACK_ALIGN))
+            .map_err(frame_err)?;
+        let si = self
+            .stack
+            .reserve(SIGINFO_SIZE, align_of::<usize>())
+            .map_err(frame_err)?;
+        self.stack.align(STACK_ALIGN).map_err(frame_err)?;
+        let ret_slot = match restorer {
+            Some(restorer) if cfg!(target_arch = "x86_64") => Some((
+                self.stack
+                    .reserve(size_of::<usize>(), align_of::<usize>())
+                    .map_err(frame_err)?,
+                restorer,
+            )),
+            _ => None,
+        };
+        let sp = self.stack.sp;
+
+        let uspace = self.stack.uspace;
+        check_region(
+            uspace,
+            VirtAddr::from(sp),
+            Layout::from_size_align(self.top - sp, 1).map_err(|_| Error::EFAULT)?,
+            Access::READ | Access::WRITE,
+        )?;
+        uspace.write_slice(UserPtr::from(uc), ucontext)?;
+        info.write_to(uspace, UserPtr::from(si))?;
+        if let Some((slot, restorer)) = ret_slot {
+            uspace.write(UserPtr::from(slot), restorer)?;
+        }
+
+        Ok(SignalFrame {
+            sp: VirtAddr::from(sp),
+            siginfo: VirtAddr::from(si),
+            ucontext: VirtAddr::from(uc),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockUspace;
+
+    const WORD: usize = size_of::<usize>();
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn initial_stack_layout() {
+        let uspace = MockUspace::new(1);
+        let base = uspace.addr(0).as_usize();
+        let word = |addr: usize| uspace.get::<usize>(addr - base);
+        let cstr = |addr: usize| {
+            let off = addr - base;
+            let len = (off..4096).position(|i| uspace.load(i, 1) == [0]).unwrap();
+            uspace.load(off, len)
+        };
+
+        let mut writer = UserStackWriter::new(&uspace, uspace.addr(4096), 4096);
+        let random = writer.push_bytes(&[7; 16]).unwrap().as_usize();
+        let sp = writer
+            .build_initial_stack(&["sh", "-c"], &["HOME=/"], &[(25, random)])
+            .unwrap()
+            .as_usize();
+
+        assert_eq!(sp % STACK_ALIGN, 0);
+        assert_eq!(word(sp), 2);
+        assert_eq!(cstr(word(sp + WORD)), b"sh");
+        assert_eq!(cstr(word(sp + 2 * WORD)), b"-c");
+        assert_eq!(word(sp + 3 * WORD), 0);
+        assert_eq!(cstr(word(sp + 4 * WORD)), b"HOME=/");
+        assert_eq!(word(sp + 5 * WORD), 0);
+        assert_eq!((word(sp + 6 * WORD), word(sp + 7 * WORD)), (25, random));
+        assert_eq!((word(sp + 8 * WORD), word(sp + 9 * WORD)), (AT_NULL, 0));
+        // Strings are pushed in order, argv below envp
+        assert!(word(sp + WORD) < word(sp + 2 * WORD));
+        assert!(word(sp + 2 * WORD) < word(sp + 4 * WORD));
+    }
+
+    #[test]
+    fn pushes_are_aligned_and_bounded() {
+        let uspace = MockUspace::new(1);
+        let top = uspace.addr(4096);
+        let mut writer = UserStackWriter::new(&uspace, top, 32);
+        writer.push_str("abc").unwrap();
+        let word = writer.push_usize(5).unwrap();
+        assert_eq!(word.as_usize() % align_of::<usize>(), 0);
+        assert_eq!(word, top - 4 - WORD - (top.as_usize() - 4) % WORD);
+        assert_eq!(writer.push_bytes(&[0; 32]), Err(Error::E2BIG));
+        // A failed push leaves the stack pointer where it was
+        assert_eq!(writer.sp(), word);
+        writer.align(16).unwrap();
+        assert_eq!(writer.finish().as_usize() % 16, 0);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn oversized_argument_area_fails() {
+        let uspace = MockUspace::new(1);
+        let writer = UserStackWriter::new(&uspace, uspace.addr(4096), 64);
+        let arg = [b'x'; 60];
+        assert_eq!(
+            writer.build_initial_stack(&[&arg[..]], &[], &[]),
+            Err(Error::E2BIG)
+        );
+    }
+
+    #[cfg(feature = "struct-helpers")]
+    #[test]
+    fn signal_frame_layout() {
+        let uspace = MockUspace::new(2);
+        let user_sp = uspace.addr(8192 - 8);
+        let info = SigInfo::kill(10, 42, 0);
+        let frame = SignalFrameWriter::new(&uspace, user_sp)
+            .write(&info, &[0xcc; 40], 64, Some(0x1234))
+            .unwrap();
+        let off = |addr: VirtAddr| addr.as_usize() - uspace.addr(0).as_usize();
+
+        assert!(frame.ucontext.as_usize() + 40 <= user_sp.as_usize() - RED_ZONE);
+        assert_eq!(frame.ucontext.as_usize() % 64, 0);
+        assert_eq!(
+            frame.siginfo.as_usize() + SIGINFO_SIZE,
+            frame.ucontext.as_usize()
+        );
+        assert_eq!(uspace.load(off(frame.ucontext), 40), [0xcc; 40]);
+        assert_eq!(
+            uspace.load(off(frame.siginfo), SIGINFO_SIZE),
+            info.to_bytes()
+        );
+        if cfg!(target_arch = "x86_64") {
+            assert_eq!(frame.sp.as_usize() % 16, 16 - WORD);
+            assert_eq!(uspace.get::<usize>(off(frame.sp)), 0x1234);
+        } else {
+            assert_eq!(frame.sp.as_usize() % 16, 0);
+        }
+        // One check for the whole frame, then one per write
+        let writes = if cfg!(target_arch = "x86_64") { 3 } else { 2 };
+        assert_eq!(uspace.checks.get(), 1 + writes);
+    }
+
+    #[cfg(feature = "struct-helpers")]
+    #[test]
+    fn frame_must_fit_the_altstack() {
+        let uspace = MockUspace::new(1);
+        let info = SigInfo::fault(11, 1, 0);
+        let res = SignalFrameWriter::on_altstack(&uspace, uspace.addr(0), SIGINFO_SIZE).write(
+            &info,
+            &[0xcc; 64],
+            16,
+            None,
+        );
+        assert_eq!(res, Err(Error::EFAULT));
+        assert_eq!(uspace.checks.get(), 0);
+
+        let frame = SignalFrameWriter::on_altstack(&uspace, uspace.addr(0), 1024)
+            .write(&info, &[0xcc; 64], 16, None)
+            .unwrap();
+        assert_eq!(frame.ucontext, uspace.addr(1024 - 64));
+    }
+
+    #[cfg(feature = "struct-helpers")]
+    #[test]
+    fn frame_is_checked_before_writing() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(0);
+        let info = SigInfo::kill(10, 42, 0);
+        let res = SignalFrameWriter::new(&uspace, uspace.addr(4096 + 64 + RED_ZONE)).write(
+            &info,
+            &[0xcc; 64],
+            16,
+            None,
+        );
+        assert_eq!(res, Err(Error::EFAULT));
+        assert_eq!(uspace.load(4096, 64), [0; 64]);
+    }
+}
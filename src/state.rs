@@ -0,0 +1,977 @@
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering, compiler_fence},
+};
+
+use memory_addr::{VirtAddr, VirtAddrRange};
+
+use crate::{Access, Error, UserResult};
+
+/// Storage of the "accessing user memory" flag of the current execution
+/// context, behind [`access_user_memory`] and [`is_accessing_user_memory`]
+///
+/// The state is only read by the same context, or by the fault handler
+/// interrupting it, so relaxed atomics are enough; the crate puts compiler
+/// fences at window boundaries so user accesses are not moved outside.
+/// Nested windows do not touch the flag, and fault records are only written
+/// when they change.
+pub trait AccessStateBackend: Sync {
+    /// Mark the current context as accessing user memory
+    fn set(&self);
+    /// Mark the current context as no longer accessing user memory
+    fn clear(&self);
+    /// Whether the current context is accessing user memory
+    fn is_set(&self) -> bool;
+
+    /// Record the range and flags of the user access in progress, or that
+    /// none is known
+    ///
+    /// Read back by [`expected_user_fault`]. The default records nothing.
+    fn set_expected(&self, _expected: Option<(VirtAddrRange, Access)>) {}
+
+    /// The range and flags last recorded by
+    /// [`set_expected`](Self::set_expected)
+    fn expected(&self) -> Option<(VirtAddrRange, Access)> {
+        None
+    }
+
+    /// Record whether a fault inside the current window could not be
+    /// resolved
+    ///
+    /// Set by [`handle_user_fault`](crate::handle_user_fault) and checked by
+    /// [`try_access_user_range`]. The default records nothing.
+    fn set_faulted(&self, _faulted: bool) {}
+
+    /// Whether a fault was recorded by [`set_faulted`](Self::set_faulted)
+    fn faulted(&self) -> bool {
+        false
+    }
+
+    /// Record whether faults inside the current window must fail instead of
+    /// populating, as for [`read_nofault`](crate::UserSpaceAccess::read_nofault)
+    ///
+    /// The default records nothing.
+    fn set_nofault(&self, _nofault: bool) {}
+
+    /// Whether recorded by [`set_nofault`](Self::set_nofault)
+    fn nofault(&self) -> bool {
+        false
+    }
+
+    /// Whether long accesses should stop early, e.g. for a pending signal
+    ///
+    /// Polled through [`AccessWindow::should_abort`]. Defaults to `false`.
+    fn should_abort(&self) -> bool {
+        false
+    }
+}
+
+/// Expected access range kept by the built-in backends, empty for none
+#[derive(Debug, Default)]
+struct ExpectedSlot {
+    start: AtomicUsize,
+    end: AtomicUsize,
+    flags: AtomicUsize,
+}
+
+impl ExpectedSlot {
+    const fn new() -> Self {
+        Self {
+            start: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+            flags: AtomicUsize::new(0),
+        }
+    }
+
+    fn store(&self, expected: Option<(VirtAddrRange, Access)>) {
+        let (range, flags) = expected.unwrap_or((VirtAddrRange::default(), Access::empty()));
+        self.start.store(range.start.as_usize(), Ordering::Relaxed);
+        self.end.store(range.end.as_usize(), Ordering::Relaxed);
+        self.flags.store(flags.bits(), Ordering::Relaxed);
+    }
+
+    fn load(&self) -> Option<(VirtAddrRange, Access)> {
+        let start = self.start.load(Ordering::Relaxed);
+        let end = self.end.load(Ordering::Relaxed);
+        let flags = Access::from_bits_truncate(self.flags.load(Ordering::Relaxed));
+        (start < end).then(|| {
+            (
+                VirtAddrRange::new(VirtAddr::from(start), VirtAddr::from(end)),
+                flags,
+            )
+        })
+    }
+}
+
+#[cfg(feature = "percpu")]
+#[percpu::def_percpu]
+static ACCESSING_USER_MEM: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "percpu")]
+#[percpu::def_percpu]
+static EXPECTED_USER_ACCESS: ExpectedSlot = ExpectedSlot::new();
+
+#[cfg(feature = "percpu")]
+#[percpu::def_percpu]
+static USER_ACCESS_FAULTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "percpu")]
+#[percpu::def_percpu]
+static USER_ACCESS_NOFAULT: AtomicBool = AtomicBool::new(false);
+
+/// Per-CPU flag from the `percpu` crate, the default with the `percpu`
+/// feature
+///
+/// Requires per-CPU areas to be initialized before the first user access.
+#[cfg(feature = "percpu")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PercpuAccessState;
+
+#[cfg(feature = "percpu")]
+impl AccessStateBackend for PercpuAccessState {
+    fn set(&self) {
+        ACCESSING_USER_MEM.with_current(|v| v.store(true, Ordering::Relaxed));
+    }
+
+    fn clear(&self) {
+        ACCESSING_USER_MEM.with_current(|v| v.store(false, Ordering::Relaxed));
+    }
+
+    fn is_set(&self) -> bool {
+        ACCESSING_USER_MEM.with_current(|v| v.load(Ordering::Relaxed))
+    }
+
+    fn set_expected(&self, expected: Option<(VirtAddrRange, Access)>) {
+        EXPECTED_USER_ACCESS.with_current(|slot| slot.store(expected));
+    }
+
+    fn expected(&self) -> Option<(VirtAddrRange, Access)> {
+        EXPECTED_USER_ACCESS.with_current(|slot| slot.load())
+    }
+
+    fn set_faulted(&self, faulted: bool) {
+        USER_ACCESS_FAULTED.with_current(|v| v.store(faulted, Ordering::Relaxed));
+    }
+
+    fn faulted(&self) -> bool {
+        USER_ACCESS_FAULTED.with_current(|v| v.load(Ordering::Relaxed))
+    }
+
+    fn set_nofault(&self, nofault: bool) {
+        USER_ACCESS_NOFAULT.with_current(|v| v.store(nofault, Ordering::Relaxed));
+    }
+
+    fn nofault(&self) -> bool {
+        USER_ACCESS_NOFAULT.with_current(|v| v.load(Ordering::Relaxed))
+    }
+}
+
+/// One global flag, for single-CPU kernels and early boot, and the default
+/// without the `percpu` feature
+#[derive(Debug, Default)]
+pub struct GlobalAccessState {
+    flag: AtomicBool,
+    expected: ExpectedSlot,
+    faulted: AtomicBool,
+    nofault: AtomicBool,
+}
+
+impl GlobalAccessState {
+    /// Create a cleared flag
+    pub const fn new() -> Self {
+        Self {
+            flag: AtomicBool::new(false),
+            expected: ExpectedSlot::new(),
+            faulted: AtomicBool::new(false),
+            nofault: AtomicBool::new(false),
+        }
+    }
+}
+
+impl AccessStateBackend for GlobalAccessState {
+    fn set(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+    fn is_set(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    fn set_expected(&self, expected: Option<(VirtAddrRange, Access)>) {
+        self.expected.store(expected);
+    }
+
+    fn expected(&self) -> Option<(VirtAddrRange, Access)> {
+        self.expected.load()
+    }
+
+    fn set_faulted(&self, faulted: bool) {
+        self.faulted.store(faulted, Ordering::Relaxed);
+    }
+
+    fn faulted(&self) -> bool {
+        self.faulted.load(Ordering::Relaxed)
+    }
+
+    fn set_nofault(&self, nofault: bool) {
+        self.nofault.store(nofault, Ordering::Relaxed);
+    }
+
+    fn nofault(&self) -> bool {
+        self.nofault.load(Ordering::Relaxed)
+    }
+}
+
+const UNSET: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+
+/// Backend registered once at boot, e.g. with [`set_access_state_backend`]
+pub(crate) struct BackendSlot<T: ?Sized + 'static> {
+    state: AtomicU8,
+    backend: UnsafeCell<Option<&'static T>>,
+}
+
+// SAFETY: `backend` is written once while `state` is `WRITING`, and only
+// read after `state` is `READY`
+unsafe impl<T: ?Sized + Sync> Sync for BackendSlot<T> {}
+
+impl<T: ?Sized + 'static> BackendSlot<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(UNSET),
+            backend: UnsafeCell::new(None),
+        }
+    }
+
+    /// Register `backend`, returning `false` if one already was
+    pub(crate) fn set(&self, backend: &'static T) -> bool {
+        if self
+            .state
+            .compare_exchange(UNSET, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        unsafe { *self.backend.get() = Some(backend) };
+        self.state.store(READY, Ordering::Release);
+        true
+    }
+
+    /// The registered backend, if any
+    pub(crate) fn get(&self) -> Option<&'static T> {
+        if self.state.load(Ordering::Acquire) == READY {
+            unsafe { *self.backend.get() }
+        } else {
+            None
+        }
+    }
+}
+
+/// Access state kept in a task, for the backend installed with
+/// [`set_current_task_hooks`]
+///
+/// The per-CPU and global backends are only correct while nothing inside a
+/// user access window sleeps: a task blocking in `populate_region` (e.g. on
+/// swap-in) leaves its flag to whichever task runs next on the CPU, and
+/// loses it when it resumes elsewhere. Kernels whose populate path can sleep
+/// keep one of these in every task instead.
+#[cfg(feature = "per-task")]
+#[derive(Debug, Default)]
+pub struct TaskAccessState {
+    state: GlobalAccessState,
+}
+
+#[cfg(feature = "per-task")]
+impl TaskAccessState {
+    /// Create a cleared state
+    pub const fn new() -> Self {
+        Self {
+            state: GlobalAccessState::new(),
+        }
+    }
+}
+
+/// Scheduler hooks giving the crate the state of the running task
+#[cfg(feature = "per-task")]
+pub trait CurrentTaskHooks: Sync {
+    /// Access state of the task running on this CPU
+    fn current_access_state(&self) -> &TaskAccessState;
+}
+
+#[cfg(feature = "per-task")]
+static TASK_HOOKS: BackendSlot<dyn CurrentTaskHooks> = BackendSlot::new();
+
+/// Keep the access state in the running task, found through `hooks`
+///
+/// Takes precedence over the default backend, but not over one installed
+/// with [`set_access_state_backend`]. Must happen before the first user
+/// access. Only the first registration takes effect; later ones return
+/// `false`.
+#[cfg(feature = "per-task")]
+pub fn set_current_task_hooks(hooks: &'static dyn CurrentTaskHooks) -> bool {
+    TASK_HOOKS.set(hooks)
+}
+
+/// Backend reaching the [`TaskAccessState`] of the running task
+#[cfg(feature = "per-task")]
+struct PerTaskAccessState;
+
+#[cfg(feature = "per-task")]
+impl PerTaskAccessState {
+    fn current(&self) -> &GlobalAccessState {
+        let hooks = TASK_HOOKS.get().expect("no current task hooks");
+        &hooks.current_access_state().state
+    }
+}
+
+#[cfg(feature = "per-task")]
+impl AccessStateBackend for PerTaskAccessState {
+    fn set(&self) {
+        self.current().set();
+    }
+
+    fn clear(&self) {
+        self.current().clear();
+    }
+
+    fn is_set(&self) -> bool {
+        self.current().is_set()
+    }
+
+    fn set_expected(&self, expected: Option<(VirtAddrRange, Access)>) {
+        self.current().set_expected(expected);
+    }
+
+    fn expected(&self) -> Option<(VirtAddrRange, Access)> {
+        self.current().expected()
+    }
+
+    fn set_faulted(&self, faulted: bool) {
+        self.current().set_faulted(faulted);
+    }
+
+    fn faulted(&self) -> bool {
+        self.current().faulted()
+    }
+
+    fn set_nofault(&self, nofault: bool) {
+        self.current().set_nofault(nofault);
+    }
+
+    fn nofault(&self) -> bool {
+        self.current().nofault()
+    }
+}
+
+#[cfg(feature = "per-task")]
+static PER_TASK_BACKEND: PerTaskAccessState = PerTaskAccessState;
+
+static BACKEND: BackendSlot<dyn AccessStateBackend> = BackendSlot::new();
+
+#[cfg(feature = "percpu")]
+static DEFAULT_BACKEND: PercpuAccessState = PercpuAccessState;
+#[cfg(not(feature = "percpu"))]
+static DEFAULT_BACKEND: GlobalAccessState = GlobalAccessState::new();
+
+/// Replace the default backend of the access flag
+///
+/// Must happen before the first user access. Only the first registration
+/// takes effect; later ones return `false`.
+pub fn set_access_state_backend(backend: &'static dyn AccessStateBackend) -> bool {
+    BACKEND.set(backend)
+}
+
+/// The registered backend, or the default one
+pub(crate) fn access_state() -> &'static dyn AccessStateBackend {
+    if let Some(backend) = BACKEND.get() {
+        return backend;
+    }
+    #[cfg(feature = "per-task")]
+    if TASK_HOOKS.get().is_some() {
+        return &PER_TASK_BACKEND;
+    }
+    &DEFAULT_BACKEND
+}
+
+/// Architecture hooks opening the hardware user access window, installed with
+/// [`set_arch_user_access`]
+///
+/// Kernels running with SMAP (x86), without `SSTATUS.SUM` (RISC-V) or with
+/// PAN (arm64) fault on any user access outside such a window. The crate
+/// calls [`enable`](Self::enable) when the outermost user access window
+/// opens and [`disable`](Self::disable) when it closes, including while
+/// unwinding, so calls are always balanced.
+pub trait ArchUserAccess: Sync {
+    /// Allow kernel accesses to user memory, e.g. `stac` or setting `SUM`
+    fn enable(&self);
+
+    /// Forbid kernel accesses to user memory again, e.g. `clac` or clearing
+    /// `SUM`
+    fn disable(&self);
+}
+
+static ARCH: BackendSlot<dyn ArchUserAccess> = BackendSlot::new();
+
+/// Install the hooks opening and closing hardware user access windows
+///
+/// Must happen before the first user access. Only the first registration
+/// takes effect; later ones return `false`.
+pub fn set_arch_user_access(arch: &'static dyn ArchUserAccess) -> bool {
+    ARCH.set(arch)
+}
+
+fn arch_enable() {
+    if let Some(arch) = ARCH.get() {
+        arch.enable();
+    }
+}
+
+fn arch_disable() {
+    if let Some(arch) = ARCH.get() {
+        arch.disable();
+    }
+}
+
+/// Check if the current thread is accessing user memory
+pub fn is_accessing_user_memory() -> bool {
+    access_state().is_set()
+}
+
+/// User access state of a switched-out task, taken by
+/// [`save_user_access_state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use = "the state is lost unless restored"]
+pub struct UserAccessState {
+    active: bool,
+    expected: Option<(VirtAddrRange, Access)>,
+    faulted: bool,
+    nofault: bool,
+}
+
+impl UserAccessState {
+    /// Whether the task was inside a user access window
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// Take the user access state of the current context and clear it, for the
+/// context-switch path of kernels using the per-CPU or global backend
+///
+/// Those backends are only sound across a switch in the middle of a user
+/// access, as happens when `populate_region` blocks, if the scheduler parks
+/// the outgoing task's state with this and hands it to
+/// [`restore_user_access_state`] when the task runs again, on whichever CPU.
+/// Nesting needs no separate record, as each open guard remembers its own.
+/// An active window is also closed with [`ArchUserAccess::disable`].
+pub fn save_user_access_state() -> UserAccessState {
+    let state = access_state();
+    let saved = UserAccessState {
+        active: state.is_set(),
+        expected: state.expected(),
+        faulted: state.faulted(),
+        nofault: state.nofault(),
+    };
+    state.set_expected(None);
+    state.set_faulted(false);
+    state.set_nofault(false);
+    if saved.active {
+        state.clear();
+        arch_disable();
+    }
+    saved
+}
+
+/// Put back a state taken by [`save_user_access_state`], so faults of the
+/// resumed task are again recognized as user accesses
+pub fn restore_user_access_state(saved: UserAccessState) {
+    let state = access_state();
+    state.set_expected(saved.expected);
+    state.set_faulted(saved.faulted);
+    state.set_nofault(saved.nofault);
+    if saved.active {
+        arch_enable();
+        state.set();
+    } else {
+        state.clear();
+    }
+}
+
+/// Open user access window, closed when dropped
+///
+/// Windows nest: dropping restores the flag, the expected range and the
+/// fault records as they were when the guard was created, so only the
+/// outermost window clears the flag and calls [`ArchUserAccess::disable`].
+/// This also happens while unwinding, so a panic inside the window cannot
+/// leave it set.
+#[must_use = "the window closes as soon as the guard is dropped"]
+pub struct UserAccessGuard {
+    state: &'static dyn AccessStateBackend,
+    nested: bool,
+    prev_expected: Option<(VirtAddrRange, Access)>,
+    prev_faulted: bool,
+    prev_nofault: bool,
+}
+
+impl Drop for UserAccessGuard {
+    fn drop(&mut self) {
+        debug_assert!(
+            self.state.is_set(),
+            "user access window closed more than once"
+        );
+        compiler_fence(Ordering::SeqCst);
+        self.state.set_expected(self.prev_expected);
+        if self.state.faulted() != self.prev_faulted {
+            self.state.set_faulted(self.prev_faulted);
+        }
+        if self.state.nofault() != self.prev_nofault {
+            self.state.set_nofault(self.prev_nofault);
+        }
+        if !self.nested {
+            self.state.clear();
+            arch_disable();
+        }
+    }
+}
+
+/// Mark the current context as accessing user memory until the returned
+/// guard is dropped
+///
+/// No range is recorded; see [`begin_user_access_to`].
+pub fn begin_user_access() -> UserAccessGuard {
+    let state = access_state();
+    let nested = state.is_set();
+    let prev_expected = state.expected();
+    let prev_faulted = state.faulted();
+    let prev_nofault = state.nofault();
+    if prev_faulted {
+        state.set_faulted(false);
+    }
+    if !nested {
+        arch_enable();
+        state.set();
+    }
+    compiler_fence(Ordering::SeqCst);
+    UserAccessGuard {
+        state,
+        nested,
+        prev_expected,
+        prev_faulted,
+        prev_nofault,
+    }
+}
+
+/// Like [`begin_user_access`], also recording that the window accesses
+/// `range` with `flags`
+pub fn begin_user_access_to(range: VirtAddrRange, flags: Access) -> UserAccessGuard {
+    let guard = begin_user_access();
+    guard.state.set_expected(Some((range, flags)));
+    guard
+}
+
+/// Flags the interrupted user access needs if a fault at `addr` is one the
+/// current window expects, or `None` for a fault outside any user access
+///
+/// Fault handlers use this to tell legitimate user accesses from wild kernel
+/// pointers that happen to land in user memory.
+pub fn expected_user_fault(addr: VirtAddr) -> Option<Access> {
+    let state = access_state();
+    if !state.is_set() {
+        return None;
+    }
+    state
+        .expected()
+        .filter(|(range, _)| range.contains(addr))
+        .map(|(_, flags)| flags)
+}
+
+/// User access window handed to [`try_access_user_memory`]
+pub struct AccessWindow {
+    guard: UserAccessGuard,
+}
+
+impl AccessWindow {
+    /// Whether the access should stop early, as
+    /// [`AccessStateBackend::should_abort`] says
+    pub fn should_abort(&self) -> bool {
+        self.guard.state.should_abort()
+    }
+
+    /// Whether a fault inside the window could not be resolved by
+    /// [`handle_user_fault`](crate::handle_user_fault), so whatever was read
+    /// must be discarded and the access fail with `EFAULT`
+    pub fn faulted(&self) -> bool {
+        self.guard.state.faulted()
+    }
+
+    /// Record that the window now accesses `range` with `flags`
+    pub fn expect(&self, range: VirtAddrRange, flags: Access) {
+        self.guard.state.set_expected(Some((range, flags)));
+    }
+}
+
+/// Like [`access_user_memory`], for closures that may fail or stop early
+///
+/// `f` can poll [`AccessWindow::should_abort`] between chunks of a long
+/// access; the crate's own page-stepping loops fail with `EINTR` when it
+/// says so.
+pub fn try_access_user_memory<R, E>(f: impl FnOnce(&AccessWindow) -> Result<R, E>) -> Result<R, E> {
+    let window = AccessWindow {
+        guard: begin_user_access(),
+    };
+    f(&window)
+}
+
+/// Enable safe access to user memory within the closure
+///
+/// Every load and store of user memory made by the crate runs inside it, so
+/// a fault handler can tell them from kernel bugs. Nested calls leave the
+/// flag set for the rest of the outer closure, and it is cleared even if `f`
+/// panics.
+pub fn access_user_memory<R>(f: impl FnOnce() -> R) -> R {
+    let _guard = begin_user_access();
+    f()
+}
+
+/// Like [`access_user_memory`], recording that `f` accesses `range` with
+/// `flags`
+pub fn access_user_range<R>(range: VirtAddrRange, flags: Access, f: impl FnOnce() -> R) -> R {
+    let _guard = begin_user_access_to(range, flags);
+    f()
+}
+
+/// Like [`access_user_range`], failing with `EFAULT` if a fault inside `f`
+/// could not be resolved by [`handle_user_fault`](crate::handle_user_fault)
+///
+/// The trap handler may let the access complete on a scratch page after
+/// such a fault; this makes sure the result is not used.
+pub fn try_access_user_range<R>(
+    range: VirtAddrRange,
+    flags: Access,
+    f: impl FnOnce() -> R,
+) -> UserResult<R> {
+    let guard = begin_user_access_to(range, flags);
+    let result = f();
+    if guard.state.faulted() {
+        return Err(Error::EFAULT);
+    }
+    Ok(result)
+}
+
+/// Like [`try_access_user_range`], but faults inside `f` fail right away
+/// instead of populating
+pub(crate) fn try_access_user_nofault<R>(
+    range: VirtAddrRange,
+    flags: Access,
+    f: impl FnOnce() -> R,
+) -> UserResult<R> {
+    let guard = begin_user_access_to(range, flags);
+    guard.state.set_nofault(true);
+    let result = f();
+    if guard.state.faulted() {
+        return Err(Error::EFAULT);
+    }
+    Ok(result)
+}
+
+/// Run `f` with user access suspended, as it must be around a yield
+///
+/// Inside a window the flag, expected range and fault record are cleared for
+/// `f` and restored after, so faults taken by whatever runs meanwhile are
+/// not mistaken for user accesses. Windows that must not block, such as
+/// nofault reads, skip `f`.
+pub(crate) fn relax_user_access(f: impl FnOnce()) {
+    let state = access_state();
+    if !state.is_set() {
+        f();
+        return;
+    }
+    if state.nofault() {
+        return;
+    }
+    let expected = state.expected();
+    let faulted = state.faulted();
+    state.set_expected(None);
+    state.set_faulted(false);
+    state.clear();
+    arch_disable();
+    f();
+    arch_enable();
+    state.set();
+    state.set_expected(expected);
+    state.set_faulted(faulted);
+}
+
+/// Byte range of `len` user `T`s at `ptr`, which must have been validated
+pub(crate) fn user_range_of<T>(ptr: *const T, len: usize) -> VirtAddrRange {
+    VirtAddrRange::from_start_size(VirtAddr::from_ptr_of(ptr), len * size_of::<T>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Error, UserSpaceAccess, VerifiedUserSlice,
+        mock::{self, MockUspace},
+    };
+
+    #[test]
+    fn flag_is_set_in_the_callback_only() {
+        mock::init();
+        assert!(!is_accessing_user_memory());
+        access_user_memory(|| assert!(is_accessing_user_memory()));
+        assert!(!is_accessing_user_memory());
+    }
+
+    #[test]
+    fn only_the_first_backend_is_registered() {
+        static OTHER: GlobalAccessState = GlobalAccessState::new();
+        mock::init();
+        assert!(!set_access_state_backend(&OTHER));
+        access_user_memory(|| assert!(!OTHER.is_set()));
+    }
+
+    #[test]
+    fn global_state_toggles() {
+        let state = GlobalAccessState::new();
+        assert!(!state.is_set());
+        state.set();
+        assert!(state.is_set());
+        state.clear();
+        assert!(!state.is_set());
+    }
+
+    #[test]
+    fn copies_run_inside_a_window() {
+        let uspace = MockUspace::new(1);
+        uspace.write(uspace.ptr::<u64>(0), 1).unwrap();
+        assert_eq!(uspace.read(uspace.cptr::<u64>(0)), Ok(1));
+        uspace.write_slice(uspace.ptr::<u8>(8), &[2; 8]).unwrap();
+        uspace
+            .read_slice_to(uspace.cptr::<u8>(8), &mut [0; 8])
+            .unwrap();
+        assert!(!is_accessing_user_memory());
+
+        let slice = VerifiedUserSlice::readable(&uspace, uspace.cptr::<u8>(0), 8).unwrap();
+        assert_eq!(
+            slice.with(&uspace, |_| is_accessing_user_memory()),
+            Ok(true)
+        );
+        assert!(!is_accessing_user_memory());
+    }
+
+    #[test]
+    fn panic_clears_the_flag() {
+        mock::init();
+        let result = std::panic::catch_unwind(|| {
+            access_user_memory(|| {
+                assert!(is_accessing_user_memory());
+                panic!("fault in the window");
+            })
+        });
+        assert!(result.is_err());
+        assert!(!is_accessing_user_memory());
+
+        let result = std::panic::catch_unwind(|| {
+            let _guard = begin_user_access_to(
+                VirtAddrRange::from_start_size(0x1000.into(), 8),
+                Access::READ,
+            );
+            assert!(is_accessing_user_memory());
+            panic!("fault in the window");
+        });
+        assert!(result.is_err());
+        assert!(!is_accessing_user_memory());
+        assert_eq!(expected_user_fault(0x1000.into()), None);
+    }
+
+    /// Inner helper leaving its window early
+    fn inner(bail: bool) -> Option<u32> {
+        access_user_memory(|| {
+            if bail {
+                return None;
+            }
+            assert!(is_accessing_user_memory());
+            Some(1)
+        })
+    }
+
+    #[test]
+    fn nested_windows_keep_the_flag() {
+        mock::init();
+        access_user_memory(|| {
+            assert_eq!(inner(true), None);
+            assert!(is_accessing_user_memory());
+            let guard = begin_user_access();
+            assert_eq!(inner(false), Some(1));
+            drop(guard);
+            assert!(is_accessing_user_memory());
+        });
+        assert!(!is_accessing_user_memory());
+    }
+
+    #[test]
+    #[allow(unused_unsafe)]
+    fn aborted_scans_fail_with_eintr() {
+        let uspace = MockUspace::new(2);
+        uspace.fill(4090, b"abcdefgh\0");
+        let ptr = uspace.cptr::<u8>(4090);
+        let mut buf = [0; 16];
+        mock::set_abort(true);
+        assert_eq!(uspace.read_cstr_into(ptr, &mut buf), Err(Error::EINTR));
+        assert_eq!(unsafe { ptr.get_as_str(&uspace) }, Err(Error::EINTR));
+        assert_eq!(
+            try_access_user_memory(|window| match window.should_abort() {
+                true => Err(Error::EINTR),
+                false => Ok(()),
+            }),
+            Err(Error::EINTR)
+        );
+        assert!(!is_accessing_user_memory());
+        mock::set_abort(false);
+        assert_eq!(uspace.read_cstr_into(ptr, &mut buf), Ok(8));
+        assert_eq!(unsafe { ptr.get_as_str(&uspace) }, Ok("abcdefgh"));
+    }
+
+    #[test]
+    fn faults_are_expected_inside_the_recorded_range_only() {
+        mock::init();
+        let range = VirtAddrRange::from_start_size(0x4000.into(), 16);
+        assert_eq!(expected_user_fault(0x4000.into()), None);
+        access_user_range(range, Access::WRITE, || {
+            assert_eq!(expected_user_fault(0x400f.into()), Some(Access::WRITE));
+            assert_eq!(expected_user_fault(0x4010.into()), None);
+            access_user_range(
+                VirtAddrRange::from_start_size(0x8000.into(), 8),
+                Access::READ,
+                || assert_eq!(expected_user_fault(0x4000.into()), None),
+            );
+            // The inner window restored the outer range
+            assert_eq!(expected_user_fault(0x4000.into()), Some(Access::WRITE));
+            // A window without a range keeps the one it is nested in
+            access_user_memory(|| {
+                assert_eq!(expected_user_fault(0x4000.into()), Some(Access::WRITE))
+            });
+        });
+        assert_eq!(expected_user_fault(0x4000.into()), None);
+
+        let uspace = MockUspace::new(1);
+        let slice = VerifiedUserSlice::readable(&uspace, uspace.cptr::<u8>(64), 8).unwrap();
+        let expected = |off| expected_user_fault(uspace.addr(off));
+        assert_eq!(
+            slice.with(&uspace, |_| (expected(64), expected(71), expected(72))),
+            Ok((Some(Access::READ), Some(Access::READ), None))
+        );
+    }
+
+    /// `(enable, disable)` arch calls made by `f`
+    fn arch_calls_of(f: impl FnOnce()) -> (usize, usize) {
+        let (enables, disables) = crate::mock::arch_calls();
+        f();
+        let (e, d) = crate::mock::arch_calls();
+        (e - enables, d - disables)
+    }
+
+    #[test]
+    fn arch_hooks_are_balanced() {
+        crate::mock::init();
+        assert_eq!(arch_calls_of(|| access_user_memory(|| {})), (1, 1));
+        let nested = arch_calls_of(|| {
+            access_user_memory(|| {
+                access_user_memory(|| assert!(is_accessing_user_memory()));
+                assert_eq!(crate::mock::arch_calls().0, crate::mock::arch_calls().1 + 1);
+            })
+        });
+        assert_eq!(nested, (1, 1));
+        let panicked = arch_calls_of(|| {
+            let _ =
+                std::panic::catch_unwind(|| access_user_memory(|| panic!("fault in the window")));
+        });
+        assert_eq!(panicked, (1, 1));
+
+        let uspace = MockUspace::new(1);
+        let copies = arch_calls_of(|| {
+            uspace.write(uspace.ptr::<u64>(0), 1).unwrap();
+            uspace.read(uspace.cptr::<u64>(0)).unwrap();
+        });
+        assert!(copies.0 > 0);
+        assert_eq!(copies.0, copies.1);
+    }
+
+    /// Scheduler switching away from the current task and back, running
+    /// `other` in between
+    fn switch_around(other: impl FnOnce()) {
+        let saved = save_user_access_state();
+        other();
+        restore_user_access_state(saved);
+    }
+
+    #[test]
+    fn state_survives_a_context_switch() {
+        crate::mock::init();
+        let range = VirtAddrRange::from_start_size(0x1000.into(), 0x100);
+        let flags = Access::READ | Access::WRITE;
+        access_user_range(range, flags, || {
+            access_user_memory(|| {
+                // `populate_region` blocks and another task runs
+                switch_around(|| {
+                    assert!(!is_accessing_user_memory());
+                    assert_eq!(expected_user_fault(0x1010.into()), None);
+                    access_user_memory(|| {});
+                });
+                assert!(is_accessing_user_memory());
+                assert_eq!(expected_user_fault(0x1010.into()), Some(flags));
+            });
+            assert_eq!(expected_user_fault(0x1010.into()), Some(flags));
+        });
+        assert!(!is_accessing_user_memory());
+        assert_eq!(expected_user_fault(0x1010.into()), None);
+    }
+
+    #[test]
+    fn idle_state_is_restored_idle() {
+        crate::mock::init();
+        let saved = save_user_access_state();
+        assert!(!saved.is_active());
+        let calls = arch_calls_of(|| restore_user_access_state(saved));
+        assert_eq!(calls, (0, 0));
+        assert!(!is_accessing_user_memory());
+    }
+
+    /// Writes of the access flag made by `f`
+    fn flag_writes_of(f: impl FnOnce()) -> usize {
+        let before = crate::mock::flag_writes();
+        f();
+        crate::mock::flag_writes() - before
+    }
+
+    #[test]
+    fn nested_windows_do_not_write_the_flag() {
+        crate::mock::init();
+        assert_eq!(flag_writes_of(|| access_user_memory(|| {})), 2);
+        let nested = flag_writes_of(|| {
+            access_user_memory(|| {
+                for _ in 0..100 {
+                    access_user_memory(|| {});
+                }
+            })
+        });
+        assert_eq!(nested, 2);
+
+        // Many small reads inside one window cost no flag traffic each
+        let uspace = MockUspace::new(1);
+        let reads = flag_writes_of(|| {
+            access_user_memory(|| {
+                for off in (0..4096).step_by(8) {
+                    uspace.read(uspace.cptr::<u64>(off)).unwrap();
+                }
+            })
+        });
+        assert_eq!(reads, 2);
+    }
+}
@@ -0,0 +1,213 @@
+use core::slice;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{Error, UserResult};
+
+/// Size of the first published `struct clone_args`
+pub const CLONE_ARGS_SIZE_VER0: usize = 64;
+/// Size of `struct clone_args` with `set_tid`
+pub const CLONE_ARGS_SIZE_VER1: usize = 80;
+/// Size of `struct clone_args` with `cgroup`
+pub const CLONE_ARGS_SIZE_VER2: usize = 88;
+
+/// `CLONE_INTO_CGROUP`: create the child in the cgroup given by `cgroup`
+pub const CLONE_INTO_CGROUP: u64 = 0x2_0000_0000;
+
+/// Maximum number of PID namespace levels, bounding `set_tid_size`
+pub const MAX_PID_NS_LEVEL: usize = 32;
+
+/// Highest valid signal number
+const SIGRTMAX: u64 = 64;
+
+/// `struct clone_args` in its latest known layout
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RawCloneArgs {
+    /// `CLONE_*` flags
+    pub flags: u64,
+    /// Where to store the pidfd with `CLONE_PIDFD`
+    pub pidfd: u64,
+    /// Where to store the child TID in the child's memory
+    pub child_tid: u64,
+    /// Where to store the child TID in the parent's memory
+    pub parent_tid: u64,
+    /// Signal sent to the parent when the child exits
+    pub exit_signal: u64,
+    /// Lowest address of the child stack
+    pub stack: u64,
+    /// Size of the child stack
+    pub stack_size: u64,
+    /// TLS value for the child
+    pub tls: u64,
+    /// Pointer to an array of TIDs to use, one per PID namespace level
+    pub set_tid: u64,
+    /// Number of elements in `set_tid`
+    pub set_tid_size: u64,
+    /// File descriptor of the target cgroup
+    pub cgroup: u64,
+}
+
+impl RawCloneArgs {
+    pub(crate) fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: any bytes are a valid `RawCloneArgs`
+        unsafe { slice::from_raw_parts_mut((self as *mut Self).cast(), size_of::<Self>()) }
+    }
+
+    /// Apply the cross-field rules of `clone3`, `size` being the size of the
+    /// user struct
+    pub fn validate(&self, size: usize) -> UserResult<()> {
+        if self.set_tid_size > MAX_PID_NS_LEVEL as u64
+            || (self.set_tid == 0) != (self.set_tid_size == 0)
+        {
+            return Err(Error::EINVAL);
+        }
+        if self.exit_signal > SIGRTMAX {
+            return Err(Error::EINVAL);
+        }
+        if self.flags & CLONE_INTO_CGROUP != 0 {
+            if self.cgroup > i32::MAX as u64 || size < CLONE_ARGS_SIZE_VER2 {
+                return Err(Error::EINVAL);
+            }
+        } else if self.cgroup != 0 {
+            return Err(Error::EINVAL);
+        }
+        if (self.stack == 0) != (self.stack_size == 0) {
+            return Err(Error::EINVAL);
+        }
+        Ok(())
+    }
+}
+
+/// Validated, fully kernel-owned `clone3` arguments
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CloneArgs {
+    /// `CLONE_*` flags
+    pub flags: u64,
+    /// Where to store the pidfd with `CLONE_PIDFD`
+    pub pidfd: usize,
+    /// Where to store the child TID in the child's memory
+    pub child_tid: usize,
+    /// Where to store the child TID in the parent's memory
+    pub parent_tid: usize,
+    /// Signal sent to the parent when the child exits, zero for none
+    pub exit_signal: u32,
+    /// Child stack as `(lowest address, size)`
+    pub stack: Option<(usize, usize)>,
+    /// TLS value for the child
+    pub tls: usize,
+    /// TIDs to use, copied in from user space
+    pub set_tid: Vec<i32>,
+    /// Target cgroup file descriptor with `CLONE_INTO_CGROUP`
+    pub cgroup: Option<u32>,
+}
+
+impl CloneArgs {
+    /// Convert validated raw arguments, `set_tid` is left empty with room for
+    /// `set_tid_size` elements to be read in
+    pub(crate) fn from_raw(raw: &RawCloneArgs) -> Self {
+        Self {
+            flags: raw.flags,
+            pidfd: raw.pidfd as usize,
+            child_tid: raw.child_tid as usize,
+            parent_tid: raw.parent_tid as usize,
+            exit_signal: raw.exit_signal as u32,
+            stack: (raw.stack != 0).then_some((raw.stack as usize, raw.stack_size as usize)),
+            tls: raw.tls as usize,
+            set_tid: vec![0; raw.set_tid_size as usize],
+            cgroup: (raw.flags & CLONE_INTO_CGROUP != 0).then_some(raw.cgroup as u32),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserSpaceAccess, mock::MockUspace};
+
+    fn store(uspace: &MockUspace, off: usize, raw: RawCloneArgs) {
+        let mut raw = raw;
+        uspace.fill(off, raw.as_bytes_mut());
+    }
+
+    #[test]
+    fn nested_set_tid_is_copied() {
+        let uspace = MockUspace::new(1);
+        let tids: Vec<u8> = [7i32, 8, 9].iter().flat_map(|t| t.to_ne_bytes()).collect();
+        uspace.fill(512, &tids);
+        let raw = RawCloneArgs {
+            exit_signal: 17,
+            set_tid: uspace.addr(512).as_usize() as u64,
+            set_tid_size: 3,
+            ..Default::default()
+        };
+        store(&uspace, 0, raw);
+        let args = uspace
+            .read_clone_args(uspace.cptr(0), CLONE_ARGS_SIZE_VER1)
+            .unwrap();
+        assert_eq!(args.set_tid, [7, 8, 9]);
+        assert_eq!(args.exit_signal, 17);
+
+        // The copy does not follow later changes
+        uspace.fill(512, &[0; 12]);
+        assert_eq!(args.set_tid, [7, 8, 9]);
+    }
+
+    #[test]
+    fn bad_set_tid_faults() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        let raw = RawCloneArgs {
+            set_tid: uspace.addr(4096).as_usize() as u64,
+            set_tid_size: 1,
+            ..Default::default()
+        };
+        store(&uspace, 0, raw);
+        assert_eq!(
+            uspace.read_clone_args(uspace.cptr(0), CLONE_ARGS_SIZE_VER1),
+            Err(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    fn set_tid_rules() {
+        let too_many = RawCloneArgs {
+            set_tid: 0x1000,
+            set_tid_size: MAX_PID_NS_LEVEL as u64 + 1,
+            ..Default::default()
+        };
+        assert_eq!(too_many.validate(CLONE_ARGS_SIZE_VER1), Err(Error::EINVAL));
+        let no_pointer = RawCloneArgs {
+            set_tid_size: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            no_pointer.validate(CLONE_ARGS_SIZE_VER1),
+            Err(Error::EINVAL)
+        );
+    }
+
+    #[test]
+    fn struct_sizes() {
+        let uspace = MockUspace::new(2);
+        store(&uspace, 0, RawCloneArgs::default());
+        assert_eq!(
+            uspace.read_clone_args(uspace.cptr(0), CLONE_ARGS_SIZE_VER0 - 8),
+            Err(Error::EINVAL)
+        );
+        assert!(
+            uspace
+                .read_clone_args(uspace.cptr(0), CLONE_ARGS_SIZE_VER0)
+                .is_ok()
+        );
+        assert_eq!(
+            uspace.read_clone_args(uspace.cptr(0), 4097),
+            Err(Error::E2BIG)
+        );
+        uspace.fill(CLONE_ARGS_SIZE_VER2, &[1]);
+        assert_eq!(
+            uspace.read_clone_args(uspace.cptr(0), CLONE_ARGS_SIZE_VER2 + 8),
+            Err(Error::E2BIG)
+        );
+    }
+}
@@ -0,0 +1,20 @@
+//! Kernel-side definitions of common Linux ABI structures and the validation
+//! rules syscalls apply to them.
+
+mod clone;
+mod poll;
+mod resource;
+mod sched;
+mod select;
+mod siginfo;
+mod signal;
+mod time;
+
+pub use clone::*;
+pub use poll::*;
+pub use resource::*;
+pub use sched::*;
+pub use select::*;
+pub use siginfo::*;
+pub use signal::*;
+pub use time::*;
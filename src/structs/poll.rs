@@ -0,0 +1,169 @@
+use core::mem::offset_of;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{AllocCharge, Error, PartialCopy, UserPtr, UserResult, UserSpaceAccess};
+
+/// `struct pollfd`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PollFd {
+    /// File descriptor, negative entries are ignored
+    pub fd: i32,
+    /// Requested events
+    pub events: i16,
+    /// Returned events
+    pub revents: i16,
+}
+
+/// Kernel copy of a `poll` array that writes back only `revents`
+///
+/// User space may legitimately keep modifying `fd` and `events` while the
+/// syscall runs, so the write-back never touches those fields.
+pub struct PollFdTable {
+    ptr: UserPtr<PollFd>,
+    entries: Vec<PollFd>,
+}
+
+impl PollFdTable {
+    /// Copy in `nfds` entries, failing with `EINVAL` above `max_nfds`
+    pub fn read<A: UserSpaceAccess + ?Sized>(
+        uspace: &A,
+        ptr: UserPtr<PollFd>,
+        nfds: usize,
+        max_nfds: usize,
+    ) -> UserResult<Self> {
+        if nfds > max_nfds {
+            return Err(Error::EINVAL);
+        }
+        let mut charge = AllocCharge::new(uspace);
+        charge.charge(nfds * size_of::<PollFd>())?;
+        let mut entries = vec![PollFd::default(); nfds];
+        uspace.read_slice_to(ptr, &mut entries)?;
+        for entry in &mut entries {
+            entry.revents = 0;
+        }
+        Ok(Self { ptr, entries })
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the table has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the `(fd, events)` pairs in order
+    pub fn iter(&self) -> impl Iterator<Item = (i32, i16)> + '_ {
+        self.entries.iter().map(|e| (e.fd, e.events))
+    }
+
+    /// Record the returned events of entry `index`, ignored out of range
+    pub fn set_revents(&mut self, index: usize, revents: i16) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.revents = revents;
+        }
+    }
+
+    /// Number of entries with non-zero `revents`, the `poll` return value
+    pub fn ready_count(&self) -> usize {
+        self.entries.iter().filter(|e| e.revents != 0).count()
+    }
+
+    /// Write each entry's `revents` back, stopping at the first fault
+    ///
+    /// On failure [`PartialCopy::done`] is the number of entries updated.
+    pub fn write_back<A: UserSpaceAccess + ?Sized>(&self, uspace: &A) -> Result<(), PartialCopy> {
+        for (i, entry) in self.entries.iter().enumerate() {
+            let revents = self
+                .ptr
+                .offset(i)
+                .cast::<u8>()
+                .offset(offset_of!(PollFd, revents))
+                .cast::<i16>();
+            uspace
+                .write(revents, entry.revents)
+                .map_err(|error| PartialCopy { done: i, error })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Access, mock::MockUspace};
+
+    const ENTRY: usize = size_of::<PollFd>();
+
+    fn entry(fd: i32) -> PollFd {
+        PollFd {
+            fd,
+            events: 1,
+            revents: -1,
+        }
+    }
+
+    #[test]
+    fn read_clears_revents_and_caps_nfds() {
+        let uspace = MockUspace::new(1);
+        uspace.put(0, entry(3));
+        uspace.put(ENTRY, entry(-1));
+        assert!(matches!(
+            PollFdTable::read(&uspace, uspace.ptr(0), 3, 2),
+            Err(Error::EINVAL)
+        ));
+        let table = PollFdTable::read(&uspace, uspace.ptr(0), 2, 2).unwrap();
+        assert_eq!(table.len(), 2);
+        assert!(table.iter().eq([(3, 1), (-1, 1)]));
+        assert_eq!(table.ready_count(), 0);
+    }
+
+    #[test]
+    fn write_back_leaves_fd_and_events() {
+        let uspace = MockUspace::new(1);
+        uspace.put(0, entry(3));
+        let mut table = PollFdTable::read(&uspace, uspace.ptr(0), 1, 8).unwrap();
+        table.set_revents(0, 4);
+        table.set_revents(1, 4);
+        assert_eq!(table.ready_count(), 1);
+        // User space rewrote the entry while the syscall ran
+        uspace.put(0, entry(7));
+        table.write_back(&uspace).unwrap();
+        assert_eq!(
+            uspace.get::<PollFd>(0),
+            PollFd {
+                fd: 7,
+                events: 1,
+                revents: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn write_back_reports_the_faulting_entry() {
+        // The third entry starts the second page, which turns read-only
+        let uspace = MockUspace::new(2);
+        let off = 4096 - 2 * ENTRY;
+        for i in 0..4 {
+            uspace.put(off + i * ENTRY, entry(i as i32));
+        }
+        let mut table = PollFdTable::read(&uspace, uspace.ptr(off), 4, 8).unwrap();
+        for i in 0..4 {
+            table.set_revents(i, 1);
+        }
+        uspace.protect(1, Access::READ);
+        assert_eq!(
+            table.write_back(&uspace),
+            Err(PartialCopy {
+                done: 2,
+                error: Error::EFAULT,
+            })
+        );
+        assert_eq!(uspace.get::<PollFd>(off + ENTRY).revents, 1);
+        assert_eq!(uspace.get::<PollFd>(off + 2 * ENTRY).revents, -1);
+    }
+}
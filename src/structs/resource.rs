@@ -0,0 +1,123 @@
+use crate::{Error, UserResult};
+
+/// Unlimited resource value
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// `struct rlimit64`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RLimit64 {
+    /// Soft limit
+    pub rlim_cur: u64,
+    /// Hard limit, the ceiling for the soft limit
+    pub rlim_max: u64,
+}
+
+impl RLimit64 {
+    /// An unlimited soft and hard limit
+    pub const INFINITY: Self = Self {
+        rlim_cur: RLIM_INFINITY,
+        rlim_max: RLIM_INFINITY,
+    };
+
+    /// Reject a soft limit above the hard limit with `EINVAL`
+    pub fn validate(&self) -> UserResult<()> {
+        if self.rlim_cur > self.rlim_max {
+            return Err(Error::EINVAL);
+        }
+        Ok(())
+    }
+}
+
+/// Unlimited resource value in the 32-bit `struct rlimit`
+#[cfg(feature = "compat")]
+pub const COMPAT_RLIM_INFINITY: u32 = u32::MAX;
+
+/// 32-bit `struct rlimit`
+#[cfg(feature = "compat")]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompatRLimit {
+    /// Soft limit
+    pub rlim_cur: u32,
+    /// Hard limit
+    pub rlim_max: u32,
+}
+
+#[cfg(feature = "compat")]
+impl From<CompatRLimit> for RLimit64 {
+    /// Widen, mapping the 32-bit infinity to [`RLIM_INFINITY`]
+    fn from(value: CompatRLimit) -> Self {
+        let widen = |v| match v {
+            COMPAT_RLIM_INFINITY => RLIM_INFINITY,
+            v => v as u64,
+        };
+        Self {
+            rlim_cur: widen(value.rlim_cur),
+            rlim_max: widen(value.rlim_max),
+        }
+    }
+}
+
+#[cfg(feature = "compat")]
+impl From<RLimit64> for CompatRLimit {
+    /// Narrow, clamping values that do not fit to the 32-bit infinity as
+    /// Linux's compat `getrlimit` does
+    fn from(value: RLimit64) -> Self {
+        let narrow = |v: u64| v.try_into().unwrap_or(COMPAT_RLIM_INFINITY);
+        Self {
+            rlim_cur: narrow(value.rlim_cur),
+            rlim_max: narrow(value.rlim_max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserPtr, UserSpaceAccess, mock::MockUspace};
+
+    #[test]
+    fn soft_limit_above_hard_is_rejected() {
+        let uspace = MockUspace::new(1);
+        let rlim = RLimit64 {
+            rlim_cur: 8,
+            rlim_max: 16,
+        };
+        uspace.write_rlimit64(uspace.ptr(0), rlim).unwrap();
+        assert_eq!(uspace.read_rlimit64(uspace.cptr(0)), Ok(rlim));
+        uspace.put(0, 32u64);
+        assert_eq!(uspace.read_rlimit64(uspace.cptr(0)), Err(Error::EINVAL));
+        assert_eq!(RLimit64::INFINITY.validate(), Ok(()));
+        assert_eq!(uspace.write_rlimit64_opt(UserPtr::from(0), rlim), Ok(()));
+    }
+
+    #[cfg(feature = "compat")]
+    #[test]
+    fn compat_limits_map_infinity() {
+        let wide = RLimit64::from(CompatRLimit {
+            rlim_cur: 4,
+            rlim_max: COMPAT_RLIM_INFINITY,
+        });
+        assert_eq!(wide.rlim_cur, 4);
+        assert_eq!(wide.rlim_max, RLIM_INFINITY);
+
+        let uspace = MockUspace::new(1);
+        let big = RLimit64 {
+            rlim_cur: 1 << 32,
+            rlim_max: RLIM_INFINITY,
+        };
+        uspace.write_compat_rlimit(uspace.ptr(0), big).unwrap();
+        assert_eq!(
+            uspace.get::<CompatRLimit>(0),
+            CompatRLimit {
+                rlim_cur: COMPAT_RLIM_INFINITY,
+                rlim_max: COMPAT_RLIM_INFINITY,
+            }
+        );
+        assert_eq!(
+            uspace.read_compat_rlimit(uspace.cptr(0)),
+            Ok(RLimit64::INFINITY)
+        );
+    }
+}
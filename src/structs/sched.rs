@@ -0,0 +1,306 @@
+use core::slice;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{Error, UserResult};
+
+const BITS_PER_WORD: usize = usize::BITS as usize;
+
+/// Kernel CPU affinity mask of `max_cpus` bits, stored as `unsigned long`s
+/// like `cpu_set_t`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuMaskBuf {
+    words: Vec<usize>,
+    max_cpus: usize,
+}
+
+impl CpuMaskBuf {
+    /// An empty mask for `max_cpus` CPUs
+    pub fn new(max_cpus: usize) -> Self {
+        Self {
+            words: vec![0; max_cpus.div_ceil(BITS_PER_WORD)],
+            max_cpus,
+        }
+    }
+
+    /// Number of CPUs covered
+    pub fn max_cpus(&self) -> usize {
+        self.max_cpus
+    }
+
+    /// Size in bytes of the kernel mask, what `sched_getaffinity` reports
+    pub fn kernel_size(&self) -> usize {
+        self.words.len() * size_of::<usize>()
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        // SAFETY: any `usize` is valid as bytes
+        unsafe { slice::from_raw_parts(self.words.as_ptr().cast(), self.kernel_size()) }
+    }
+
+    pub(crate) fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: any bytes are a valid `usize`
+        unsafe { slice::from_raw_parts_mut(self.words.as_mut_ptr().cast(), self.kernel_size()) }
+    }
+
+    /// Clear the bits of CPUs at or beyond `max_cpus`
+    pub(crate) fn mask_tail(&mut self) {
+        let tail = self.max_cpus % BITS_PER_WORD;
+        if tail != 0 {
+            self.words[self.max_cpus / BITS_PER_WORD] &= (1 << tail) - 1;
+        }
+    }
+
+    /// Whether `cpu` is in the mask, always false for `cpu >= max_cpus`
+    pub fn get(&self, cpu: usize) -> bool {
+        cpu < self.max_cpus && self.words[cpu / BITS_PER_WORD] & (1 << (cpu % BITS_PER_WORD)) != 0
+    }
+
+    /// Add or remove `cpu`, ignored for `cpu >= max_cpus`
+    pub fn set(&mut self, cpu: usize, val: bool) {
+        if cpu >= self.max_cpus {
+            return;
+        }
+        let bit = 1 << (cpu % BITS_PER_WORD);
+        if val {
+            self.words[cpu / BITS_PER_WORD] |= bit;
+        } else {
+            self.words[cpu / BITS_PER_WORD] &= !bit;
+        }
+    }
+
+    /// Number of CPUs in the mask
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Iterate over the CPUs in the mask in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.max_cpus).filter(|&cpu| self.get(cpu))
+    }
+}
+
+/// Check a `sched_getaffinity` size: it must cover every kernel CPU and be a
+/// whole number of `unsigned long`s
+pub fn check_cpu_set_out_size(size: usize, max_cpus: usize) -> UserResult<()> {
+    if size.saturating_mul(8) < max_cpus || !size.is_multiple_of(size_of::<usize>()) {
+        return Err(Error::EINVAL);
+    }
+    Ok(())
+}
+
+/// Size of the first published `struct sched_attr`
+pub const SCHED_ATTR_SIZE_VER0: u32 = 48;
+/// Size of `struct sched_attr` with the utilization clamps
+pub const SCHED_ATTR_SIZE_VER1: u32 = 56;
+
+/// `SCHED_FLAG_RESET_ON_FORK`
+pub const SCHED_FLAG_RESET_ON_FORK: u64 = 0x01;
+/// `SCHED_FLAG_RECLAIM`
+pub const SCHED_FLAG_RECLAIM: u64 = 0x02;
+/// `SCHED_FLAG_DL_OVERRUN`
+pub const SCHED_FLAG_DL_OVERRUN: u64 = 0x04;
+/// `SCHED_FLAG_KEEP_POLICY`
+pub const SCHED_FLAG_KEEP_POLICY: u64 = 0x08;
+/// `SCHED_FLAG_KEEP_PARAMS`
+pub const SCHED_FLAG_KEEP_PARAMS: u64 = 0x10;
+/// `SCHED_FLAG_UTIL_CLAMP_MIN`
+pub const SCHED_FLAG_UTIL_CLAMP_MIN: u64 = 0x20;
+/// `SCHED_FLAG_UTIL_CLAMP_MAX`
+pub const SCHED_FLAG_UTIL_CLAMP_MAX: u64 = 0x40;
+/// Both utilization clamp flags, which need a [`SCHED_ATTR_SIZE_VER1`] struct
+pub const SCHED_FLAG_UTIL_CLAMP: u64 = SCHED_FLAG_UTIL_CLAMP_MIN | SCHED_FLAG_UTIL_CLAMP_MAX;
+/// Every known `sched_flags` bit
+pub const SCHED_FLAG_ALL: u64 = SCHED_FLAG_RESET_ON_FORK
+    | SCHED_FLAG_RECLAIM
+    | SCHED_FLAG_DL_OVERRUN
+    | SCHED_FLAG_KEEP_POLICY
+    | SCHED_FLAG_KEEP_PARAMS
+    | SCHED_FLAG_UTIL_CLAMP;
+
+/// `struct sched_attr`, in its latest known layout
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SchedAttr {
+    /// Size of the structure as known by its writer
+    pub size: u32,
+    /// Scheduling policy
+    pub sched_policy: u32,
+    /// `SCHED_FLAG_*` bits
+    pub sched_flags: u64,
+    /// Nice value for `SCHED_NORMAL` and `SCHED_BATCH`
+    pub sched_nice: i32,
+    /// Static priority for `SCHED_FIFO` and `SCHED_RR`
+    pub sched_priority: u32,
+    /// `SCHED_DEADLINE` runtime in nanoseconds
+    pub sched_runtime: u64,
+    /// `SCHED_DEADLINE` deadline in nanoseconds
+    pub sched_deadline: u64,
+    /// `SCHED_DEADLINE` period in nanoseconds
+    pub sched_period: u64,
+    /// Minimum utilization clamp
+    pub sched_util_min: u32,
+    /// Maximum utilization clamp
+    pub sched_util_max: u32,
+}
+
+impl SchedAttr {
+    /// Size of the layout known to the kernel
+    pub const KERNEL_SIZE: u32 = size_of::<Self>() as u32;
+
+    /// Check the flags of an attribute read from a `size`-byte struct
+    pub fn validate(&self, size: u32) -> UserResult<()> {
+        if self.sched_flags & !SCHED_FLAG_ALL != 0 {
+            return Err(Error::EINVAL);
+        }
+        if self.sched_flags & SCHED_FLAG_UTIL_CLAMP != 0 && size < SCHED_ATTR_SIZE_VER1 {
+            return Err(Error::EINVAL);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `SchedAttr` is plain integers without padding
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+
+    pub(crate) fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: any bytes are a valid `SchedAttr`
+        unsafe { slice::from_raw_parts_mut((self as *mut Self).cast(), size_of::<Self>()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserSpaceAccess, mock::MockUspace};
+
+    #[test]
+    fn long_user_mask_is_truncated() {
+        // A 1024-bit user mask against 128 kernel CPUs: only the first 16
+        // bytes are read, the rest lies on an unmapped page
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        let off = 4096 - 16;
+        uspace.fill(off, &[0xff; 16]);
+        let mask = uspace.read_cpu_set(uspace.cptr(off), 128, 128).unwrap();
+        assert_eq!(mask.count(), 128);
+    }
+
+    #[test]
+    fn short_user_mask_is_zero_extended() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[0xff; 8]);
+        let mask = uspace.read_cpu_set(uspace.cptr(0), 8, 128).unwrap();
+        assert_eq!(mask.iter().collect::<Vec<_>>(), (0..64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tail_bits_are_dropped() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[0xff; 8]);
+        let mask = uspace.read_cpu_set(uspace.cptr(0), 8, 4).unwrap();
+        assert_eq!(mask.count(), 4);
+    }
+
+    #[test]
+    fn write_reports_kernel_size() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[0xaa; 128]);
+        let mut mask = CpuMaskBuf::new(128);
+        mask.set(0, true);
+        mask.set(127, true);
+        assert_eq!(uspace.write_cpu_set(uspace.ptr(0), 128, &mask), Ok(16));
+        assert_eq!(uspace.load(0, 16), mask.as_bytes());
+        assert!(uspace.load(16, 112).iter().all(|&b| b == 0xaa));
+    }
+
+    #[test]
+    fn write_rejects_bad_sizes() {
+        let uspace = MockUspace::new(1);
+        let mask = CpuMaskBuf::new(128);
+        assert_eq!(
+            uspace.write_cpu_set(uspace.ptr(0), 8, &mask),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(
+            uspace.write_cpu_set(uspace.ptr(0), 17, &mask),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(check_cpu_set_out_size(16, 128), Ok(()));
+        assert_eq!(check_cpu_set_out_size(size_of::<usize>() * 3, 1), Ok(()));
+    }
+
+    #[test]
+    fn sched_attr_versions() {
+        let uspace = MockUspace::new(1);
+        // A VER0 struct leaves the clamps zero, whatever follows it
+        uspace.fill(0, &[0xff; 64]);
+        uspace.fill(0, &[0; 48]);
+        uspace.put(0, SCHED_ATTR_SIZE_VER0);
+        uspace.put(24, 7u64);
+        let attr = uspace.read_sched_attr(uspace.cptr(0), 48).unwrap();
+        assert_eq!(attr.sched_runtime, 7);
+        assert_eq!((attr.sched_util_min, attr.sched_util_max), (0, 0));
+        // A zero size means VER0
+        uspace.put(0, 0u32);
+        assert_eq!(
+            uspace.read_sched_attr(uspace.cptr(0), 48).map(|a| a.size),
+            Ok(SCHED_ATTR_SIZE_VER0)
+        );
+        assert_eq!(
+            uspace.read_sched_attr(uspace.cptr(0), 56),
+            Err(Error::EINVAL)
+        );
+        // Clamps need a VER1 struct
+        uspace.put(8, SCHED_FLAG_UTIL_CLAMP_MIN);
+        assert_eq!(
+            uspace.read_sched_attr(uspace.cptr(0), 48),
+            Err(Error::EINVAL)
+        );
+        uspace.put(8, 0x80u64);
+        uspace.put(0, SCHED_ATTR_SIZE_VER1);
+        assert_eq!(
+            uspace.read_sched_attr(uspace.cptr(0), 56),
+            Err(Error::EINVAL)
+        );
+    }
+
+    #[test]
+    fn longer_sched_attr_needs_a_zero_tail() {
+        let uspace = MockUspace::new(1);
+        uspace.put(0, 64u32);
+        uspace.put(SchedAttr::KERNEL_SIZE as usize, 0u64);
+        assert!(uspace.read_sched_attr(uspace.cptr(0), 64).is_ok());
+        uspace.put(60, 1u32);
+        assert_eq!(
+            uspace.read_sched_attr(uspace.cptr(0), 64),
+            Err(Error::E2BIG)
+        );
+        uspace.put(0, 40u32);
+        assert_eq!(
+            uspace.read_sched_attr(uspace.cptr(0), 40),
+            Err(Error::E2BIG)
+        );
+    }
+
+    #[test]
+    fn sched_attr_is_truncated_to_the_user_size() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[0xaa; 64]);
+        let attr = SchedAttr {
+            sched_priority: 5,
+            sched_util_max: 1024,
+            ..Default::default()
+        };
+        assert_eq!(uspace.write_sched_attr(uspace.ptr(0), 48, &attr), Ok(48));
+        assert_eq!(uspace.get::<u32>(0), 48);
+        assert_eq!(uspace.get::<u32>(20), 5);
+        assert_eq!(uspace.load(48, 16), [0xaa; 16]);
+        assert_eq!(uspace.write_sched_attr(uspace.ptr(0), 128, &attr), Ok(56));
+        assert_eq!(
+            uspace.write_sched_attr(uspace.ptr(0), 32, &attr),
+            Err(Error::EINVAL)
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{Error, UserResult};
+
+/// Number of descriptors in an `fd_set`
+pub const FD_SETSIZE: usize = 1024;
+
+const BITS_PER_WORD: usize = u64::BITS as usize;
+const FD_SET_WORDS: usize = FD_SETSIZE / BITS_PER_WORD;
+
+/// Kernel copy of the first `nfds` bits of a `select` `fd_set`
+///
+/// Bits at or beyond `nfds` are never set: they are dropped when reading and
+/// written back as zero, matching Linux.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FdSetBuf {
+    words: [u64; FD_SET_WORDS],
+    nfds: usize,
+}
+
+impl FdSetBuf {
+    /// An empty set covering descriptors `0..nfds`, failing with `EINVAL` for
+    /// more than [`FD_SETSIZE`] descriptors
+    pub fn new(nfds: usize) -> UserResult<Self> {
+        if nfds > FD_SETSIZE {
+            return Err(Error::EINVAL);
+        }
+        Ok(Self {
+            words: [0; FD_SET_WORDS],
+            nfds,
+        })
+    }
+
+    /// Number of descriptors covered
+    pub fn nfds(&self) -> usize {
+        self.nfds
+    }
+
+    /// Number of 64-bit words transferred to or from user space
+    pub fn word_len(&self) -> usize {
+        self.nfds.div_ceil(BITS_PER_WORD)
+    }
+
+    /// The words transferred to or from user space
+    pub fn words(&self) -> &[u64] {
+        &self.words[..self.word_len()]
+    }
+
+    pub(crate) fn words_mut(&mut self) -> &mut [u64] {
+        let len = self.word_len();
+        &mut self.words[..len]
+    }
+
+    /// Clear the bits at or beyond `nfds` in the last word
+    pub(crate) fn mask_tail(&mut self) {
+        let tail = self.nfds % BITS_PER_WORD;
+        if tail != 0 {
+            self.words[self.nfds / BITS_PER_WORD] &= (1 << tail) - 1;
+        }
+    }
+
+    /// Whether `fd` is in the set, always false for `fd >= nfds`
+    pub fn get(&self, fd: usize) -> bool {
+        fd < self.nfds && self.words[fd / BITS_PER_WORD] & (1 << (fd % BITS_PER_WORD)) != 0
+    }
+
+    /// Add or remove `fd`, ignored for `fd >= nfds`
+    pub fn set(&mut self, fd: usize, val: bool) {
+        if fd >= self.nfds {
+            return;
+        }
+        let bit = 1 << (fd % BITS_PER_WORD);
+        if val {
+            self.words[fd / BITS_PER_WORD] |= bit;
+        } else {
+            self.words[fd / BITS_PER_WORD] &= !bit;
+        }
+    }
+
+    /// Remove every descriptor
+    pub fn clear(&mut self) {
+        self.words = [0; FD_SET_WORDS];
+    }
+
+    /// Iterate over the descriptors in the set in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nfds).filter(|&fd| self.get(fd))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserSpaceAccess, mock::MockUspace};
+
+    #[test]
+    fn rejects_oversized_sets() {
+        assert_eq!(FdSetBuf::new(FD_SETSIZE + 1), Err(Error::EINVAL));
+        assert_eq!(FdSetBuf::new(FD_SETSIZE).unwrap().word_len(), FD_SET_WORDS);
+    }
+
+    #[test]
+    fn ignores_fds_past_nfds() {
+        let mut set = FdSetBuf::new(3).unwrap();
+        set.set(2, true);
+        set.set(3, true);
+        assert!(set.get(2));
+        assert!(!set.get(3));
+        assert_eq!(set.words(), &[0b100]);
+    }
+
+    #[test]
+    fn partial_word_is_masked() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &u64::MAX.to_ne_bytes());
+        let set = uspace.read_fd_set(uspace.cptr(0), 5).unwrap();
+        assert_eq!(set.words(), &[0b1_1111]);
+        assert_eq!(set.iter().count(), 5);
+    }
+
+    #[test]
+    fn reads_only_covered_words() {
+        // 65 descriptors need two words, a third would lie on the unmapped page
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        let off = 4096 - 2 * size_of::<u64>();
+        uspace.fill(off, &[0xff; 16]);
+        let set = uspace.read_fd_set(uspace.cptr(off), 65).unwrap();
+        assert_eq!(set.words(), &[u64::MAX, 1]);
+        assert!(uspace.read_fd_set(uspace.cptr(off), 129).is_err());
+    }
+
+    #[test]
+    fn writes_only_covered_words() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[0xaa; 24]);
+        let mut set = FdSetBuf::new(70).unwrap();
+        set.set(0, true);
+        set.set(69, true);
+        uspace.write_fd_set(uspace.ptr(0), &set).unwrap();
+        let mut expected = [0xaa; 24];
+        expected[..8].copy_from_slice(&1u64.to_ne_bytes());
+        expected[8..16].copy_from_slice(&(1u64 << 5).to_ne_bytes());
+        assert_eq!(uspace.load(0, 24), expected);
+    }
+
+    #[test]
+    fn null_sets_are_empty() {
+        let uspace = MockUspace::new(1);
+        let set = uspace.read_fd_set(0.into(), 64).unwrap();
+        assert_eq!(set.iter().next(), None);
+        uspace.write_fd_set(0.into(), &set).unwrap();
+    }
+}
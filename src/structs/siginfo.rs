@@ -0,0 +1,267 @@
+use crate::{UserPtr, UserResult, UserSpaceAccess};
+
+/// Size in bytes of `siginfo_t`
+pub const SIGINFO_SIZE: usize = 128;
+
+/// `SIGCHLD`
+pub const SIGCHLD: u32 = 17;
+
+/// `si_code`: sent by `kill`
+pub const SI_USER: i32 = 0;
+/// `si_code`: sent by the kernel
+pub const SI_KERNEL: i32 = 0x80;
+/// `si_code`: sent by `sigqueue`
+pub const SI_QUEUE: i32 = -1;
+/// `si_code`: a POSIX timer expired
+pub const SI_TIMER: i32 = -2;
+
+/// `si_code` for `SIGCHLD`: the child exited
+pub const CLD_EXITED: i32 = 1;
+/// `si_code` for `SIGCHLD`: the child was killed
+pub const CLD_KILLED: i32 = 2;
+/// `si_code` for `SIGCHLD`: the child was killed and dumped core
+pub const CLD_DUMPED: i32 = 3;
+/// `si_code` for `SIGCHLD`: a traced child trapped
+pub const CLD_TRAPPED: i32 = 4;
+/// `si_code` for `SIGCHLD`: the child stopped
+pub const CLD_STOPPED: i32 = 5;
+/// `si_code` for `SIGCHLD`: a stopped child continued
+pub const CLD_CONTINUED: i32 = 6;
+
+const OFF_SIGNO: usize = 0;
+const OFF_ERRNO: usize = 4;
+const OFF_CODE: usize = 8;
+/// Start of the `_sifields` union, which is pointer-aligned
+const OFF_FIELDS: usize = (OFF_CODE + 4).next_multiple_of(align_of::<usize>());
+
+/// The active member of the `_sifields` union
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigInfoFields {
+    /// `_kill`, also used for `sigqueue` with a value
+    Kill {
+        /// Sending process
+        pid: i32,
+        /// Real user ID of the sender
+        uid: u32,
+        /// `si_value`, only meaningful for `SI_QUEUE`
+        value: usize,
+    },
+    /// `_timer`
+    Timer {
+        /// Kernel timer ID
+        tid: i32,
+        /// Overrun count
+        overrun: i32,
+        /// `si_value` from the `sigevent`
+        value: usize,
+    },
+    /// `_sigchld`
+    Child {
+        /// Child process
+        pid: i32,
+        /// Real user ID of the child
+        uid: u32,
+        /// Exit status or signal
+        status: i32,
+        /// User time consumed, in clock ticks
+        utime: isize,
+        /// System time consumed, in clock ticks
+        stime: isize,
+    },
+    /// `_sigfault`
+    Fault {
+        /// Faulting address
+        addr: usize,
+    },
+}
+
+/// Builder for a `siginfo_t` delivered by `SA_SIGINFO` handlers and `waitid`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigInfo {
+    /// `si_signo`
+    pub signo: u32,
+    /// `si_errno`
+    pub errno: i32,
+    /// `si_code`
+    pub code: i32,
+    /// Source-specific fields
+    pub fields: SigInfoFields,
+}
+
+impl SigInfo {
+    /// A signal sent by `kill` from `pid`
+    pub fn kill(signo: u32, pid: i32, uid: u32) -> Self {
+        Self {
+            signo,
+            errno: 0,
+            code: SI_USER,
+            fields: SigInfoFields::Kill { pid, uid, value: 0 },
+        }
+    }
+
+    /// A signal sent by `sigqueue` from `pid` carrying `value`
+    pub fn queue(signo: u32, pid: i32, uid: u32, value: usize) -> Self {
+        Self {
+            signo,
+            errno: 0,
+            code: SI_QUEUE,
+            fields: SigInfoFields::Kill { pid, uid, value },
+        }
+    }
+
+    /// Expiration of a POSIX timer
+    pub fn timer(signo: u32, tid: i32, overrun: i32, value: usize) -> Self {
+        Self {
+            signo,
+            errno: 0,
+            code: SI_TIMER,
+            fields: SigInfoFields::Timer {
+                tid,
+                overrun,
+                value,
+            },
+        }
+    }
+
+    /// A `SIGCHLD` state change, `code` is one of the `CLD_*` constants
+    pub fn child(code: i32, pid: i32, uid: u32, status: i32, utime: isize, stime: isize) -> Self {
+        Self {
+            signo: SIGCHLD,
+            errno: 0,
+            code,
+            fields: SigInfoFields::Child {
+                pid,
+                uid,
+                status,
+                utime,
+                stime,
+            },
+        }
+    }
+
+    /// A synchronous fault at `addr`, e.g. `SIGSEGV` with `SEGV_MAPERR`
+    pub fn fault(signo: u32, code: i32, addr: usize) -> Self {
+        Self {
+            signo,
+            errno: 0,
+            code,
+            fields: SigInfoFields::Fault { addr },
+        }
+    }
+
+    /// Encode into the ABI layout, every byte not part of the active union
+    /// member is zero
+    pub fn to_bytes(&self) -> [u8; SIGINFO_SIZE] {
+        let mut buf = [0; SIGINFO_SIZE];
+        let mut put = |off: usize, bytes: &[u8]| buf[off..off + bytes.len()].copy_from_slice(bytes);
+        put(OFF_SIGNO, &self.signo.to_ne_bytes());
+        put(OFF_ERRNO, &self.errno.to_ne_bytes());
+        put(OFF_CODE, &self.code.to_ne_bytes());
+
+        let f = OFF_FIELDS;
+        let word = size_of::<usize>();
+        match self.fields {
+            SigInfoFields::Kill { pid, uid, value } => {
+                put(f, &pid.to_ne_bytes());
+                put(f + 4, &uid.to_ne_bytes());
+                put(f + 8, &value.to_ne_bytes());
+            }
+            SigInfoFields::Timer {
+                tid,
+                overrun,
+                value,
+            } => {
+                put(f, &tid.to_ne_bytes());
+                put(f + 4, &overrun.to_ne_bytes());
+                put(f + 8, &value.to_ne_bytes());
+            }
+            SigInfoFields::Child {
+                pid,
+                uid,
+                status,
+                utime,
+                stime,
+            } => {
+                put(f, &pid.to_ne_bytes());
+                put(f + 4, &uid.to_ne_bytes());
+                put(f + 8, &status.to_ne_bytes());
+                // `si_utime` is a `long`, aligned after `si_status`
+                let utime_off = (f + 12).next_multiple_of(word);
+                put(utime_off, &utime.to_ne_bytes());
+                put(utime_off + word, &stime.to_ne_bytes());
+            }
+            SigInfoFields::Fault { addr } => {
+                put(f, &addr.to_ne_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Write the full 128-byte `siginfo_t` to user space
+    pub fn write_to<A: UserSpaceAccess + ?Sized>(
+        &self,
+        uspace: &A,
+        ptr: UserPtr<u8>,
+    ) -> UserResult<()> {
+        uspace.write(ptr.cast::<[u8; SIGINFO_SIZE]>(), self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockUspace;
+
+    /// Expected `siginfo_t` of a 64-bit little-endian target, with `fields`
+    /// at their offsets
+    fn layout(fields: &[(usize, &[u8])]) -> [u8; SIGINFO_SIZE] {
+        let mut buf = [0; SIGINFO_SIZE];
+        for &(off, bytes) in fields {
+            buf[off..off + bytes.len()].copy_from_slice(bytes);
+        }
+        buf
+    }
+
+    #[test]
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    fn child_layout() {
+        let info = SigInfo::child(CLD_EXITED, 42, 1000, 3, 7, 9);
+        let expected = layout(&[
+            (0, &[17, 0, 0, 0]),
+            (8, &[1, 0, 0, 0]),
+            (16, &[42, 0, 0, 0]),
+            (20, &[0xe8, 0x03, 0, 0]),
+            (24, &[3, 0, 0, 0]),
+            (32, &[7, 0, 0, 0, 0, 0, 0, 0]),
+            (40, &[9, 0, 0, 0, 0, 0, 0, 0]),
+        ]);
+        assert_eq!(info.to_bytes(), expected);
+    }
+
+    #[test]
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    fn fault_layout() {
+        let info = SigInfo::fault(11, 1, 0xdead_beef_0000);
+        let expected = layout(&[
+            (0, &[11, 0, 0, 0]),
+            (8, &[1, 0, 0, 0]),
+            (16, &[0, 0, 0xef, 0xbe, 0xad, 0xde, 0, 0]),
+        ]);
+        assert_eq!(info.to_bytes(), expected);
+    }
+
+    #[test]
+    fn write_to_zeroes_padding() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[0xaa; SIGINFO_SIZE]);
+        let info = SigInfo::fault(11, 1, 0x1000);
+        info.write_to(&uspace, uspace.ptr(0)).unwrap();
+        assert_eq!(uspace.load(0, SIGINFO_SIZE), info.to_bytes());
+        assert!(
+            uspace
+                .load(OFF_FIELDS + size_of::<usize>(), 64)
+                .iter()
+                .all(|&b| b == 0)
+        );
+    }
+}
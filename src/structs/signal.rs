@@ -0,0 +1,500 @@
+use core::ffi::c_ulong;
+
+use crate::{Error, UserResult};
+
+/// Size in bytes of the kernel `sigset_t`, the only `sigsetsize` accepted by
+/// the `rt_sig*` syscalls
+pub const SIGSET_SIZE: usize = size_of::<u64>();
+
+/// `SIGKILL`
+pub const SIGKILL: u32 = 9;
+/// `SIGSTOP`
+pub const SIGSTOP: u32 = 19;
+
+/// Number of signals a signal set holds, numbered from 1
+pub const NSIG: u32 = 64;
+
+/// Signals that can never be blocked, ignored or caught
+pub const SIG_UNBLOCKABLE: u64 = (1 << (SIGKILL - 1)) | (1 << (SIGSTOP - 1));
+
+/// The bit of `sig` in a signal set, or `None` outside `1..=NSIG`
+pub const fn sigmask(sig: u32) -> Option<u64> {
+    match sig {
+        1..=NSIG => Some(1 << (sig - 1)),
+        _ => None,
+    }
+}
+
+/// Check the `sigsetsize` argument of an `rt_sig*` syscall
+pub fn check_sigsetsize(sigsetsize: usize) -> UserResult<()> {
+    if sigsetsize != SIGSET_SIZE {
+        return Err(Error::EINVAL);
+    }
+    Ok(())
+}
+
+/// `SA_NOCLDSTOP`: no `SIGCHLD` when children stop
+pub const SA_NOCLDSTOP: u64 = 0x0000_0001;
+/// `SA_NOCLDWAIT`: do not create zombies
+pub const SA_NOCLDWAIT: u64 = 0x0000_0002;
+/// `SA_SIGINFO`: the handler takes `siginfo_t` and `ucontext_t` arguments
+pub const SA_SIGINFO: u64 = 0x0000_0004;
+/// `SA_RESTORER`: `sa_restorer` holds the signal return trampoline
+pub const SA_RESTORER: u64 = 0x0400_0000;
+/// `SA_ONSTACK`: run the handler on the alternate signal stack
+pub const SA_ONSTACK: u64 = 0x0800_0000;
+/// `SA_RESTART`: restart interrupted syscalls
+pub const SA_RESTART: u64 = 0x1000_0000;
+/// `SA_NODEFER`: do not block the signal while its handler runs
+pub const SA_NODEFER: u64 = 0x4000_0000;
+/// `SA_RESETHAND`: reset the handler to `SIG_DFL` once delivered
+pub const SA_RESETHAND: u64 = 0x8000_0000;
+
+/// Every `sa_flags` bit the helpers accept, others fail with `EINVAL`
+pub const SA_SUPPORTED: u64 = SA_NOCLDSTOP
+    | SA_NOCLDWAIT
+    | SA_SIGINFO
+    | SA_ONSTACK
+    | SA_RESTART
+    | SA_NODEFER
+    | SA_RESETHAND
+    | if HAS_SA_RESTORER { SA_RESTORER } else { 0 };
+
+/// Whether the native `struct sigaction` has an `sa_restorer` field
+const HAS_SA_RESTORER: bool = !cfg!(any(target_arch = "riscv64", target_arch = "loongarch64"));
+
+/// `SIG_DFL`
+pub const SIG_DFL: usize = 0;
+/// `SIG_IGN`
+pub const SIG_IGN: usize = 1;
+
+/// Disposition of a signal
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SigHandler {
+    /// `SIG_DFL`: take the default action
+    #[default]
+    Default,
+    /// `SIG_IGN`: ignore the signal
+    Ignore,
+    /// Run the user handler at this address
+    Handler(usize),
+}
+
+impl From<usize> for SigHandler {
+    fn from(value: usize) -> Self {
+        match value {
+            SIG_DFL => Self::Default,
+            SIG_IGN => Self::Ignore,
+            addr => Self::Handler(addr),
+        }
+    }
+}
+
+impl From<SigHandler> for usize {
+    fn from(value: SigHandler) -> Self {
+        match value {
+            SigHandler::Default => SIG_DFL,
+            SigHandler::Ignore => SIG_IGN,
+            SigHandler::Handler(addr) => addr,
+        }
+    }
+}
+
+/// Validated kernel-side view of a `struct sigaction`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SigAction {
+    /// What to do when the signal is delivered
+    pub handler: SigHandler,
+    /// `SA_*` flags, a subset of [`SA_SUPPORTED`]
+    pub flags: u64,
+    /// Signal return trampoline, only present with `SA_RESTORER`
+    pub restorer: Option<usize>,
+    /// Signals blocked while the handler runs, never `SIGKILL` or `SIGSTOP`
+    pub mask: u64,
+}
+
+impl SigAction {
+    fn new(handler: usize, flags: u64, restorer: usize, mask: u64) -> UserResult<Self> {
+        if flags & !SA_SUPPORTED != 0 {
+            return Err(Error::EINVAL);
+        }
+        Ok(Self {
+            handler: handler.into(),
+            flags,
+            restorer: (flags & SA_RESTORER != 0).then_some(restorer),
+            mask: mask & !SIG_UNBLOCKABLE,
+        })
+    }
+}
+
+/// `unsigned long` words of a native `sigset_t`
+pub const SIGSET_WORDS: usize = SIGSET_SIZE / size_of::<c_ulong>();
+
+/// Split a signal set into the words of a native `sigset_t`, the lowest
+/// signals in the first word
+pub fn sigset_to_words(set: u64) -> [c_ulong; SIGSET_WORDS] {
+    core::array::from_fn(|i| (set >> (i as u32 * c_ulong::BITS)) as c_ulong)
+}
+
+/// Join the words of a native `sigset_t` into a signal set
+#[allow(clippy::unnecessary_cast)] // `c_ulong` is `u32` on 32-bit targets
+pub fn sigset_from_words(words: [c_ulong; SIGSET_WORDS]) -> u64 {
+    (0..SIGSET_WORDS).fold(0, |set, i| {
+        set | (words[i] as u64) << (i as u32 * c_ulong::BITS)
+    })
+}
+
+/// Native `struct sigaction` layout as seen by `rt_sigaction`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RawSigAction {
+    /// `sa_handler` or `sa_sigaction`
+    pub handler: usize,
+    /// `sa_flags`
+    pub flags: c_ulong,
+    /// `sa_restorer`
+    #[cfg(not(any(target_arch = "riscv64", target_arch = "loongarch64")))]
+    pub restorer: usize,
+    /// `sa_mask`, see [`sigset_from_words`]
+    pub mask: [c_ulong; SIGSET_WORDS],
+}
+
+impl TryFrom<RawSigAction> for SigAction {
+    type Error = crate::Error;
+
+    #[allow(clippy::unnecessary_cast)]
+    fn try_from(raw: RawSigAction) -> UserResult<Self> {
+        #[cfg(not(any(target_arch = "riscv64", target_arch = "loongarch64")))]
+        let restorer = raw.restorer;
+        #[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
+        let restorer = 0;
+        Self::new(
+            raw.handler,
+            raw.flags as u64,
+            restorer,
+            sigset_from_words(raw.mask),
+        )
+    }
+}
+
+impl From<SigAction> for RawSigAction {
+    fn from(act: SigAction) -> Self {
+        Self {
+            handler: act.handler.into(),
+            flags: act.flags as c_ulong,
+            #[cfg(not(any(target_arch = "riscv64", target_arch = "loongarch64")))]
+            restorer: act.restorer.unwrap_or(0),
+            mask: sigset_to_words(act.mask),
+        }
+    }
+}
+
+/// 32-bit `struct compat_sigaction` layout
+#[cfg(feature = "compat")]
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CompatSigAction {
+    /// `sa_handler` or `sa_sigaction`
+    pub handler: u32,
+    /// `sa_flags`
+    pub flags: u32,
+    /// `sa_restorer`
+    pub restorer: u32,
+    /// `sa_mask`, as two 32-bit words
+    pub mask: [u32; 2],
+}
+
+#[cfg(feature = "compat")]
+impl TryFrom<CompatSigAction> for SigAction {
+    type Error = crate::Error;
+
+    fn try_from(raw: CompatSigAction) -> UserResult<Self> {
+        let mask = raw.mask[0] as u64 | (raw.mask[1] as u64) << 32;
+        Self::new(
+            raw.handler as usize,
+            u64::from(raw.flags),
+            raw.restorer as usize,
+            mask,
+        )
+    }
+}
+
+#[cfg(feature = "compat")]
+impl TryFrom<SigAction> for CompatSigAction {
+    type Error = crate::Error;
+
+    /// Fails with `EFAULT` if an address does not fit in 32 bits
+    fn try_from(act: SigAction) -> UserResult<Self> {
+        let handler: usize = act.handler.into();
+        Ok(Self {
+            handler: handler.try_into().map_err(|_| Error::EFAULT)?,
+            flags: act.flags as u32,
+            restorer: act
+                .restorer
+                .unwrap_or(0)
+                .try_into()
+                .map_err(|_| Error::EFAULT)?,
+            mask: [act.mask as u32, (act.mask >> 32) as u32],
+        })
+    }
+}
+
+/// `SS_ONSTACK`: the thread is currently running on the alternate stack
+pub const SS_ONSTACK: i32 = 1;
+/// `SS_DISABLE`: the alternate stack is disabled
+pub const SS_DISABLE: i32 = 2;
+/// `SS_AUTODISARM`: disable the alternate stack while a handler runs on it
+pub const SS_AUTODISARM: i32 = 1 << 31;
+
+/// Smallest alternate signal stack accepted by `sigaltstack`
+pub const MINSIGSTKSZ: usize = if cfg!(target_arch = "aarch64") {
+    5120
+} else {
+    2048
+};
+
+/// `stack_t` layout as seen by `sigaltstack`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RawStack {
+    /// `ss_sp`
+    pub sp: usize,
+    /// `ss_flags`
+    pub flags: i32,
+    /// `ss_size`
+    pub size: usize,
+}
+
+/// Validated alternate signal stack configuration
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SigStack {
+    /// No alternate stack
+    #[default]
+    Disable,
+    /// Handlers with `SA_ONSTACK` run on `[base, base + size)`
+    Enable {
+        /// Lowest address of the stack
+        base: usize,
+        /// Size in bytes, at least [`MINSIGSTKSZ`]
+        size: usize,
+        /// Whether `SS_AUTODISARM` was requested
+        autodisarm: bool,
+    },
+}
+
+impl TryFrom<RawStack> for SigStack {
+    type Error = crate::Error;
+
+    /// Apply the `sigaltstack` rules: unknown modes fail with `EINVAL`,
+    /// stacks below [`MINSIGSTKSZ`] with `ENOMEM`
+    fn try_from(raw: RawStack) -> UserResult<Self> {
+        match raw.flags & !SS_AUTODISARM {
+            SS_DISABLE => Ok(Self::Disable),
+            0 | SS_ONSTACK if raw.size < MINSIGSTKSZ => Err(Error::ENOMEM),
+            0 | SS_ONSTACK => Ok(Self::Enable {
+                base: raw.sp,
+                size: raw.size,
+                autodisarm: raw.flags & SS_AUTODISARM != 0,
+            }),
+            _ => Err(Error::EINVAL),
+        }
+    }
+}
+
+impl SigStack {
+    /// Encode as a `stack_t`, `on_stack` tells whether the thread is
+    /// currently running on it
+    pub fn to_raw(self, on_stack: bool) -> RawStack {
+        match self {
+            Self::Disable => RawStack {
+                sp: 0,
+                flags: SS_DISABLE,
+                size: 0,
+            },
+            Self::Enable {
+                base,
+                size,
+                autodisarm,
+            } => RawStack {
+                sp: base,
+                flags: if on_stack { SS_ONSTACK } else { 0 }
+                    | if autodisarm { SS_AUTODISARM } else { 0 },
+                size,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserConstPtr, UserPtr, UserSpaceAccess, mock::MockUspace, nullable};
+
+    #[test]
+    fn sigmask_range() {
+        assert_eq!(sigmask(1), Some(1));
+        assert_eq!(sigmask(NSIG), Some(1 << 63));
+        assert_eq!(sigmask(0), None);
+        assert_eq!(sigmask(NSIG + 1), None);
+        assert_eq!(
+            Some(SIG_UNBLOCKABLE),
+            sigmask(SIGKILL).zip(sigmask(SIGSTOP)).map(|(k, s)| k | s)
+        );
+    }
+
+    #[test]
+    fn sigsetsize_must_match() {
+        let uspace = MockUspace::new(1);
+        uspace.put(0, u64::MAX);
+        assert_eq!(uspace.read_sigset(uspace.cptr(0), 8), Ok(u64::MAX));
+        for size in [0, 4, 16] {
+            assert_eq!(uspace.read_sigset(uspace.cptr(0), size), Err(Error::EINVAL));
+            assert_eq!(
+                uspace.write_sigset(uspace.ptr(0), size, 0),
+                Err(Error::EINVAL)
+            );
+            // Even a null pointer has its size checked
+            assert_eq!(
+                uspace.read_sigset_opt(UserConstPtr::from(0), size),
+                Err(Error::EINVAL)
+            );
+            assert_eq!(
+                uspace.write_sigset_opt(UserPtr::from(0), size, 0),
+                Err(Error::EINVAL)
+            );
+        }
+        assert_eq!(uspace.checks.get(), 1);
+    }
+
+    #[test]
+    fn sigmask_reads_drop_unblockable_signals() {
+        let uspace = MockUspace::new(1);
+        uspace.put(0, u64::MAX);
+        assert_eq!(
+            uspace.read_sigmask(uspace.cptr(0), SIGSET_SIZE),
+            Ok(!SIG_UNBLOCKABLE)
+        );
+        assert_eq!(
+            uspace.read_sigset_opt(UserConstPtr::from(0), SIGSET_SIZE),
+            Ok(None)
+        );
+        assert_eq!(
+            uspace.write_sigset_opt(UserPtr::from(0), SIGSET_SIZE, 1),
+            Ok(())
+        );
+        uspace
+            .write_sigset(uspace.ptr(8), SIGSET_SIZE, 0x5a)
+            .unwrap();
+        assert_eq!(
+            uspace.read_sigset_opt(uspace.cptr(8), SIGSET_SIZE),
+            Ok(Some(0x5a))
+        );
+    }
+
+    #[test]
+    fn raw_sigaction_matches_the_kernel_layout() {
+        let word = size_of::<c_ulong>();
+        let fields = if HAS_SA_RESTORER { 3 } else { 2 };
+        assert_eq!(size_of::<RawSigAction>(), fields * word + SIGSET_SIZE);
+        assert_eq!(align_of::<RawSigAction>(), word);
+        let set = (1 << 63) | (1 << 31) | 1;
+        assert_eq!(sigset_from_words(sigset_to_words(set)), set);
+        assert_eq!(sigset_to_words(set)[0] & 1, 1);
+    }
+
+    #[test]
+    fn query_only_sigaction() {
+        let uspace = MockUspace::new(1);
+        let current = SigAction {
+            handler: SigHandler::Handler(0x4000),
+            flags: SA_SIGINFO | SA_RESTART,
+            restorer: None,
+            mask: sigmask(2).unwrap(),
+        };
+        let act = UserConstPtr::<RawSigAction>::from(0);
+        let oldact = uspace.ptr::<RawSigAction>(0);
+
+        assert_eq!(nullable!(uspace.read_sigaction(act)), Ok(None));
+        uspace.write_sigaction_opt(oldact, current).unwrap();
+        assert_eq!(uspace.read_sigaction(uspace.cptr(0)), Ok(current));
+        assert_eq!(
+            uspace.write_sigaction_opt(UserPtr::from(0), current),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn sigaction_is_normalized() {
+        let uspace = MockUspace::new(1);
+        uspace.put(
+            0,
+            RawSigAction {
+                handler: SIG_IGN,
+                mask: sigset_to_words(SIG_UNBLOCKABLE | sigmask(10).unwrap()),
+                ..Default::default()
+            },
+        );
+        let act = uspace.read_sigaction(uspace.cptr(0)).unwrap();
+        assert_eq!(act.handler, SigHandler::Ignore);
+        assert_eq!(Some(act.mask), sigmask(10));
+        assert_eq!(act.restorer, None);
+
+        uspace.put(
+            0,
+            RawSigAction {
+                flags: 0x10,
+                ..Default::default()
+            },
+        );
+        assert_eq!(uspace.read_sigaction(uspace.cptr(0)), Err(Error::EINVAL));
+    }
+
+    #[test]
+    fn sigaltstack_rules() {
+        let stack = |flags, size| RawStack {
+            sp: 0x8000,
+            flags,
+            size,
+        };
+        assert_eq!(
+            SigStack::try_from(stack(SS_DISABLE, 0)),
+            Ok(SigStack::Disable)
+        );
+        assert_eq!(
+            SigStack::try_from(stack(0, MINSIGSTKSZ - 1)),
+            Err(Error::ENOMEM)
+        );
+        assert_eq!(
+            SigStack::try_from(stack(0x10, MINSIGSTKSZ)),
+            Err(Error::EINVAL)
+        );
+        let enabled = SigStack::Enable {
+            base: 0x8000,
+            size: MINSIGSTKSZ,
+            autodisarm: true,
+        };
+        assert_eq!(
+            SigStack::try_from(stack(SS_ONSTACK | SS_AUTODISARM, MINSIGSTKSZ)),
+            Ok(enabled)
+        );
+        assert_eq!(enabled.to_raw(true).flags, SS_ONSTACK | SS_AUTODISARM);
+        assert_eq!(SigStack::Disable.to_raw(true).flags, SS_DISABLE);
+    }
+
+    #[test]
+    fn stack_t_round_trip() {
+        let uspace = MockUspace::new(1);
+        let stack = SigStack::Enable {
+            base: 0x8000,
+            size: MINSIGSTKSZ,
+            autodisarm: false,
+        };
+        uspace.write_stack_t(uspace.ptr(0), stack, false).unwrap();
+        assert_eq!(uspace.read_stack_t(uspace.cptr(0)), Ok(stack));
+        assert_eq!(
+            uspace.write_stack_t_opt(UserPtr::from(0), stack, true),
+            Ok(())
+        );
+        assert_eq!(uspace.checks.get(), 2);
+    }
+}
@@ -0,0 +1,356 @@
+use core::{ffi::c_long, time::Duration};
+
+use crate::{Error, UserResult};
+
+/// Number of nanoseconds in one second
+pub const NSEC_PER_SEC: i64 = 1_000_000_000;
+/// Number of microseconds in one second
+pub const USEC_PER_SEC: c_long = 1_000_000;
+
+/// `utimensat` sentinel: set the timestamp to the current time
+pub const UTIME_NOW: i64 = (1 << 30) - 1;
+/// `utimensat` sentinel: leave the timestamp unchanged
+pub const UTIME_OMIT: i64 = (1 << 30) - 2;
+
+/// `struct __kernel_timespec`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSpec {
+    /// Seconds
+    pub tv_sec: i64,
+    /// Nanoseconds, in `0..NSEC_PER_SEC` for a valid value
+    pub tv_nsec: i64,
+}
+
+impl TimeSpec {
+    /// Check the fields are non-negative and `tv_nsec` is below one second
+    pub fn validate(&self) -> UserResult<()> {
+        if self.tv_sec < 0 || !(0..NSEC_PER_SEC).contains(&self.tv_nsec) {
+            return Err(Error::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// Convert to a [`Duration`], the value must have been validated
+    pub fn to_duration(&self) -> Duration {
+        Duration::new(self.tv_sec as u64, self.tv_nsec as u32)
+    }
+}
+
+impl From<Duration> for TimeSpec {
+    fn from(d: Duration) -> Self {
+        Self {
+            tv_sec: d.as_secs() as i64,
+            tv_nsec: d.subsec_nanos() as i64,
+        }
+    }
+}
+
+/// `struct timeval`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimeVal {
+    /// Seconds
+    pub tv_sec: c_long,
+    /// Microseconds, in `0..USEC_PER_SEC` for a valid value
+    pub tv_usec: c_long,
+}
+
+impl TimeVal {
+    /// Check the fields are non-negative and `tv_usec` is below one second
+    pub fn validate(&self) -> UserResult<()> {
+        if self.tv_sec < 0 || !(0..USEC_PER_SEC).contains(&self.tv_usec) {
+            return Err(Error::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// Convert to a [`Duration`], the value must have been validated
+    pub fn to_duration(&self) -> Duration {
+        Duration::new(self.tv_sec as u64, self.tv_usec as u32 * 1000)
+    }
+}
+
+impl From<Duration> for TimeVal {
+    fn from(d: Duration) -> Self {
+        Self {
+            tv_sec: d.as_secs() as c_long,
+            tv_usec: d.subsec_micros() as c_long,
+        }
+    }
+}
+
+/// One timestamp of a `utimensat` times pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtimeSpec {
+    /// `UTIME_NOW`: use the current time
+    Now,
+    /// `UTIME_OMIT`: keep the existing timestamp
+    Omit,
+    /// An explicit timestamp
+    Set(TimeSpec),
+}
+
+impl TryFrom<TimeSpec> for UtimeSpec {
+    type Error = crate::Error;
+
+    /// Interpret the sentinels, `tv_sec` is ignored for them and may be
+    /// negative otherwise (timestamps before the epoch are allowed)
+    fn try_from(ts: TimeSpec) -> UserResult<Self> {
+        match ts.tv_nsec {
+            UTIME_NOW => Ok(Self::Now),
+            UTIME_OMIT => Ok(Self::Omit),
+            0..NSEC_PER_SEC => Ok(Self::Set(ts)),
+            _ => Err(Error::EINVAL),
+        }
+    }
+}
+
+/// `struct itimerspec`, as used by `timer_settime` and `timerfd_settime`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ITimerSpec {
+    /// Reload interval for periodic timers
+    pub it_interval: TimeSpec,
+    /// Time until the next expiration
+    pub it_value: TimeSpec,
+}
+
+impl ITimerSpec {
+    /// Validate both halves of the pair
+    pub fn validate(&self) -> UserResult<()> {
+        self.it_interval.validate()?;
+        self.it_value.validate()
+    }
+
+    /// Whether setting this value disarms the timer
+    ///
+    /// As on Linux, only a zero `it_value` disarms, `it_interval` is ignored.
+    pub fn is_disarm(&self) -> bool {
+        self.it_value == TimeSpec::default()
+    }
+}
+
+/// `struct itimerval`, as used by `setitimer` and `getitimer`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ITimerVal {
+    /// Reload interval for periodic timers
+    pub it_interval: TimeVal,
+    /// Time until the next expiration
+    pub it_value: TimeVal,
+}
+
+impl ITimerVal {
+    /// Validate both halves of the pair
+    pub fn validate(&self) -> UserResult<()> {
+        self.it_interval.validate()?;
+        self.it_value.validate()
+    }
+
+    /// Whether setting this value disarms the timer
+    ///
+    /// As on Linux, only a zero `it_value` disarms, `it_interval` is ignored.
+    pub fn is_disarm(&self) -> bool {
+        self.it_value == TimeVal::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserConstPtr, UserInOutPtr, UserSpaceAccess, mock::MockUspace};
+
+    const fn ts(tv_sec: i64, tv_nsec: i64) -> TimeSpec {
+        TimeSpec { tv_sec, tv_nsec }
+    }
+
+    #[test]
+    fn timespec_range() {
+        assert_eq!(ts(0, 0).validate(), Ok(()));
+        assert_eq!(ts(5, NSEC_PER_SEC - 1).validate(), Ok(()));
+        assert_eq!(ts(0, NSEC_PER_SEC).validate(), Err(Error::EINVAL));
+        assert_eq!(ts(0, -1).validate(), Err(Error::EINVAL));
+        assert_eq!(ts(-1, 0).validate(), Err(Error::EINVAL));
+        assert_eq!(ts(3, 250).to_duration(), Duration::new(3, 250));
+    }
+
+    #[test]
+    fn timeval_range() {
+        let tv = |tv_sec, tv_usec| TimeVal { tv_sec, tv_usec };
+        assert_eq!(tv(0, 0).validate(), Ok(()));
+        assert_eq!(tv(1, USEC_PER_SEC - 1).validate(), Ok(()));
+        assert_eq!(tv(0, USEC_PER_SEC).validate(), Err(Error::EINVAL));
+        assert_eq!(tv(0, -1).validate(), Err(Error::EINVAL));
+        assert_eq!(tv(-1, 0).validate(), Err(Error::EINVAL));
+        assert_eq!(tv(2, 7).to_duration(), Duration::new(2, 7000));
+        assert_eq!(TimeVal::from(Duration::new(2, 7999)), tv(2, 7));
+    }
+
+    #[test]
+    fn utime_sentinels() {
+        assert_eq!(UtimeSpec::try_from(ts(9, UTIME_NOW)), Ok(UtimeSpec::Now));
+        assert_eq!(UtimeSpec::try_from(ts(-9, UTIME_OMIT)), Ok(UtimeSpec::Omit));
+        // Timestamps before the epoch are allowed, a bad tv_nsec is not
+        assert_eq!(
+            UtimeSpec::try_from(ts(-9, 0)),
+            Ok(UtimeSpec::Set(ts(-9, 0)))
+        );
+        assert_eq!(UtimeSpec::try_from(ts(0, NSEC_PER_SEC)), Err(Error::EINVAL));
+        assert_eq!(
+            UtimeSpec::try_from(ts(0, UTIME_NOW + 1)),
+            Err(Error::EINVAL)
+        );
+    }
+
+    #[test]
+    fn reads_validate_the_user_value() {
+        let uspace = MockUspace::new(1);
+        uspace.put(0, ts(1, NSEC_PER_SEC));
+        assert_eq!(uspace.read_timespec(uspace.cptr(0)), Err(Error::EINVAL));
+        uspace.put(0, ts(1, 2));
+        assert_eq!(uspace.read_timespec(uspace.cptr(0)), Ok(ts(1, 2)));
+
+        uspace.put(
+            16,
+            TimeVal {
+                tv_sec: 1,
+                tv_usec: USEC_PER_SEC,
+            },
+        );
+        assert_eq!(uspace.read_timeval(uspace.cptr(16)), Err(Error::EINVAL));
+
+        uspace.write_timespec(uspace.ptr(32), ts(4, 5)).unwrap();
+        assert_eq!(uspace.get::<TimeSpec>(32), ts(4, 5));
+        let tv = TimeVal {
+            tv_sec: 6,
+            tv_usec: 7,
+        };
+        uspace.write_timeval(uspace.ptr(48), tv).unwrap();
+        assert_eq!(uspace.read_timeval(uspace.cptr(48)), Ok(tv));
+    }
+
+    #[test]
+    fn utimens_pairs() {
+        let uspace = MockUspace::new(1);
+        uspace.put(0, [ts(1, UTIME_OMIT), ts(2, 3)]);
+        assert_eq!(
+            uspace.read_utimens_pair(uspace.cptr(0)),
+            Ok([UtimeSpec::Omit, UtimeSpec::Set(ts(2, 3))])
+        );
+        // A null pointer means now only for the nullable flavor
+        assert_eq!(
+            uspace.read_utimens_pair_or_now(UserConstPtr::from(0)),
+            Ok([UtimeSpec::Now; 2])
+        );
+        assert_eq!(
+            uspace.read_utimens_pair(UserConstPtr::from(0)),
+            Err(Error::EFAULT)
+        );
+        uspace.put(16, ts(2, -1));
+        assert_eq!(
+            uspace.read_utimens_pair_or_now(uspace.cptr(0)),
+            Err(Error::EINVAL)
+        );
+    }
+
+    #[test]
+    fn itimer_pairs_validate_both_halves() {
+        let its = |interval, value| ITimerSpec {
+            it_interval: interval,
+            it_value: value,
+        };
+        assert_eq!(its(ts(0, 0), ts(1, 0)).validate(), Ok(()));
+        assert_eq!(
+            its(ts(0, NSEC_PER_SEC), ts(1, 0)).validate(),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(its(ts(1, 0), ts(0, -1)).validate(), Err(Error::EINVAL));
+
+        let tv = |tv_sec, tv_usec| TimeVal { tv_sec, tv_usec };
+        let itv = |interval, value| ITimerVal {
+            it_interval: interval,
+            it_value: value,
+        };
+        assert_eq!(itv(tv(0, 0), tv(0, 1)).validate(), Ok(()));
+        assert_eq!(
+            itv(tv(0, USEC_PER_SEC), tv(0, 1)).validate(),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(itv(tv(0, 1), tv(-1, 0)).validate(), Err(Error::EINVAL));
+    }
+
+    #[test]
+    fn only_a_zero_value_disarms() {
+        let its = |interval, value| ITimerSpec {
+            it_interval: interval,
+            it_value: value,
+        };
+        assert!(ITimerSpec::default().is_disarm());
+        assert!(its(ts(5, 0), ts(0, 0)).is_disarm());
+        assert!(!its(ts(0, 0), ts(0, 1)).is_disarm());
+        assert!(!its(ts(0, 0), ts(1, 0)).is_disarm());
+
+        let tv = |tv_sec, tv_usec| TimeVal { tv_sec, tv_usec };
+        let itv = |interval, value| ITimerVal {
+            it_interval: interval,
+            it_value: value,
+        };
+        assert!(ITimerVal::default().is_disarm());
+        assert!(itv(tv(0, 3), tv(0, 0)).is_disarm());
+        assert!(!itv(tv(0, 0), tv(0, 1)).is_disarm());
+    }
+
+    #[test]
+    fn timer_settime_round_trip() {
+        let uspace = MockUspace::new(1);
+        let new = ITimerSpec {
+            it_interval: ts(1, 0),
+            it_value: ts(0, 500),
+        };
+        let old = ITimerSpec {
+            it_interval: ts(0, 0),
+            it_value: ts(3, 0),
+        };
+        uspace.put(0, new);
+        assert_eq!(uspace.read_itimerspec(uspace.cptr(0)), Ok(new));
+        uspace.put(32, ts(0, NSEC_PER_SEC));
+        assert_eq!(uspace.read_itimerspec(uspace.cptr(16)), Err(Error::EINVAL));
+
+        // The old value goes back only when user space asked for it
+        let old_ptr = UserInOutPtr::from(uspace.ptr::<ITimerSpec>(64));
+        assert_eq!(old_ptr.write_back(&uspace, old), Ok(()));
+        assert_eq!(uspace.get::<ITimerSpec>(64), old);
+        assert_eq!(old_ptr.read_in(&uspace), Ok(Some(old)));
+        let none = UserInOutPtr::<ITimerSpec>::default();
+        assert_eq!(none.write_back(&uspace, old), Ok(()));
+        assert_eq!(none.read_in(&uspace), Ok(None));
+        let bad = UserInOutPtr::<ITimerSpec>::from(8);
+        assert_eq!(bad.write_back(&uspace, old), Err(Error::EFAULT));
+        // Null pointers and page zero never reach the backend
+        assert_eq!(uspace.checks.get(), 4);
+    }
+
+    #[test]
+    fn setitimer_round_trip() {
+        let uspace = MockUspace::new(1);
+        let itv = ITimerVal {
+            it_interval: TimeVal {
+                tv_sec: 0,
+                tv_usec: 10,
+            },
+            it_value: TimeVal {
+                tv_sec: 2,
+                tv_usec: 0,
+            },
+        };
+        uspace.write_itimerval(uspace.ptr(0), itv).unwrap();
+        assert_eq!(uspace.read_itimerval(uspace.cptr(0)), Ok(itv));
+        uspace.put(8, -1 as core::ffi::c_long);
+        assert_eq!(uspace.read_itimerval(uspace.cptr(0)), Err(Error::EINVAL));
+        UserInOutPtr::from(uspace.ptr::<ITimerVal>(0))
+            .write_back(&uspace, itv)
+            .unwrap();
+        assert_eq!(uspace.read_itimerval(uspace.cptr(0)), Ok(itv));
+    }
+}
@@ -0,0 +1,107 @@
+use alloc::vec::Vec;
+
+use crate::{Access, UserConstPtr, UserResult, UserSpaceAccess, check_region_batch};
+
+/// All-or-nothing copy of several user buffers into kernel memory
+///
+/// Every registered region is validated before any copy starts. If a copy
+/// still fails, every destination is zeroed so no partially captured input
+/// is observable, and the error is returned.
+pub struct CopyInTransaction<'a, 'b, A: UserSpaceAccess + ?Sized> {
+    uspace: &'a A,
+    copies: Vec<(UserConstPtr<u8>, &'b mut [u8])>,
+}
+
+impl<'a, 'b, A: UserSpaceAccess + ?Sized> CopyInTransaction<'a, 'b, A> {
+    /// Start an empty transaction
+    pub fn new(uspace: &'a A) -> Self {
+        Self {
+            uspace,
+            copies: Vec::new(),
+        }
+    }
+
+    /// Register a copy of `dest.len()` bytes from `src` into `dest`
+    pub fn add(mut self, src: UserConstPtr<u8>, dest: &'b mut [u8]) -> Self {
+        self.copies.push((src, dest));
+        self
+    }
+
+    /// Validate every region, then perform every copy
+    pub fn commit(mut self) -> UserResult<()> {
+        let regions = self
+            .copies
+            .iter()
+            .map(|(src, dest)| (src.address(), dest.len(), Access::READ))
+            .collect::<Vec<_>>();
+        check_region_batch(self.uspace, &regions)?;
+
+        let uspace = self.uspace;
+        let result = self
+            .copies
+            .iter_mut()
+            .try_for_each(|(src, dest)| uspace.read_slice_to(*src, dest));
+        if result.is_err() {
+            for (_, dest) in &mut self.copies {
+                dest.fill(0);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, UserSpaceAccess, mock::MockUspace};
+
+    #[test]
+    fn commit_copies_every_buffer() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, b"abcdef");
+        let (mut a, mut b) = ([0; 2], [0; 3]);
+        uspace
+            .copy_in_transaction()
+            .add(uspace.cptr(0), &mut a)
+            .add(uspace.cptr(3), &mut b)
+            .add(uspace.cptr(4096), &mut [])
+            .commit()
+            .unwrap();
+        assert_eq!((&a, &b), (b"ab", b"def"));
+    }
+
+    #[test]
+    fn invalid_region_copies_nothing() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        uspace.fill(0, b"abcd");
+        let (mut a, mut b) = ([0xff; 2], [0xff; 2]);
+        let res = uspace
+            .copy_in_transaction()
+            .add(uspace.cptr(0), &mut a)
+            .add(uspace.cptr(4096), &mut b)
+            .commit();
+        assert_eq!(res, Err(Error::EFAULT));
+        assert_eq!((a, b), ([0xff; 2], [0xff; 2]));
+    }
+
+    #[test]
+    fn failed_copy_zeroes_every_destination() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[0x5a; 40]);
+        let mut bufs = [[0xff; 8]; 5];
+        let [b0, b1, b2, b3, b4] = &mut bufs;
+        // The batch checks all five, then the third copy's own check fails
+        uspace.fault_after(5 + 2);
+        let res = uspace
+            .copy_in_transaction()
+            .add(uspace.cptr(0), b0)
+            .add(uspace.cptr(8), b1)
+            .add(uspace.cptr(16), b2)
+            .add(uspace.cptr(24), b3)
+            .add(uspace.cptr(32), b4)
+            .commit();
+        assert_eq!(res, Err(Error::EFAULT));
+        assert_eq!(bufs, [[0; 8]; 5]);
+        assert_eq!(uspace.checks.get(), 5 + 3);
+    }
+}
@@ -0,0 +1,90 @@
+use memory_addr::{VirtAddr, VirtAddrRange};
+
+use crate::{Access, Error, UserResult, UserSpaceRaw};
+
+/// Backend accepting every non-null range, for single-address-space builds
+/// where "user" code is trusted and shares the kernel address space
+///
+/// **This performs no protection at all.** Any address outside the null
+/// page is treated as accessible with any flags, kernel memory included,
+/// so it must never be used where user code is untrusted.
+///
+/// Only the checks that keep code paths identical to checked builds remain:
+/// zero-sized accesses succeed, misaligned and wrapping ranges fail, and
+/// ranges touching the first [`min_user_addr`](UserSpaceRaw::min_user_addr)
+/// bytes fail with `EFAULT`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UncheckedUspace;
+
+impl UserSpaceRaw for UncheckedUspace {
+    fn check_region_access(&self, range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        if range.start.as_usize() == 0 || range.end < range.start {
+            return Err(Error::EFAULT);
+        }
+        Ok(())
+    }
+
+    fn populate_region(&self, _range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        Ok(())
+    }
+
+    fn check_region_resident(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<bool> {
+        self.check_region_access(range, access_flags)?;
+        Ok(true)
+    }
+
+    /// The whole address space
+    fn user_addr_range(&self) -> VirtAddrRange {
+        VirtAddrRange::new(VirtAddr::from(0), VirtAddr::from(usize::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::Layout;
+
+    use super::*;
+    use crate::{AccessErrorKind, UserConstPtr, UserPtr, UserSpaceAccess, check_region};
+
+    #[test]
+    fn kernel_memory_is_accessed_directly() {
+        let mut value = [1u64, 2];
+        let addr = value.as_mut_ptr() as usize;
+        assert_eq!(UncheckedUspace.read(UserConstPtr::<u64>::from(addr)), Ok(1));
+        UncheckedUspace
+            .write(UserPtr::<u64>::from(addr + 8), 3)
+            .unwrap();
+        assert_eq!(value, [1, 3]);
+    }
+
+    #[test]
+    fn only_the_shape_of_a_range_is_checked() {
+        let check = |addr: usize, layout: Layout| {
+            check_region(
+                &UncheckedUspace,
+                VirtAddr::from(addr),
+                layout,
+                Access::WRITE,
+            )
+            .map(|_| ())
+            .map_err(|e| e.kind)
+        };
+        let word = Layout::new::<u64>();
+        assert_eq!(check(0x8000_0000, word), Ok(()));
+        assert_eq!(check(0, Layout::new::<()>()), Ok(()));
+        assert_eq!(check(0, word), Err(AccessErrorKind::NotMapped));
+        assert_eq!(check(0x8000_0004, word), Err(AccessErrorKind::Misaligned));
+        assert_eq!(
+            check(usize::MAX - 7, Layout::new::<[u64; 2]>()),
+            Err(AccessErrorKind::Overflow)
+        );
+        assert_eq!(
+            UncheckedUspace.read(UserConstPtr::<u64>::from(0x10)),
+            Err(Error::EFAULT)
+        );
+    }
+}
@@ -1,192 +1,3200 @@
 use core::{
     alloc::Layout,
-    ffi::c_char,
-    sync::atomic::{AtomicBool, Ordering},
+    mem::{ManuallyDrop, MaybeUninit},
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
 };
+#[cfg(feature = "alloc")]
+use core::{ffi::c_char, ops::Range};
 
-use alloc::{
-    string::{String, ToString},
-    vec::Vec,
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, rc::Rc, string::String, sync::Arc, vec, vec::Vec};
+use memory_addr::{MemoryAddr, PAGE_SIZE_4K, PhysAddr, VirtAddr, VirtAddrRange};
+
+#[cfg(feature = "debug-double-fetch")]
+use crate::DoubleFetch;
+#[cfg(all(feature = "struct-helpers", doc))]
+use crate::UserInOutPtr;
+use crate::{
+    Access, AccessErrorKind, AccessResult, Error, IoVec, Limits, MemoryType, PinnedRegion,
+    PrefaultHint, UserAccessError, UserCaptured, UserConstPtr, UserCopy, UserPtr, UserReadable,
+    UserResult, UserSliceRef, UserStrPtr, ValidatedRegion, assert_direct_map, copy_from_device,
+    copy_from_user_fallible, copy_to_device, copy_to_user_fallible, has_user_copy_backend, locate,
+    relax_user_access, slice_layout, try_access_user_memory, try_access_user_nofault,
+    try_access_user_range, user_range_of, user_slice, user_str, zeroize,
+};
+#[cfg(feature = "alloc")]
+use crate::{
+    AllocCharge, CopyInTransaction, ExecArgs, ExecArgsBuf, ExecBudget, ExecLimits, SecretBuf,
+    UserCapturedSlice, ValidationSession, capture_str_array, capture_str_array_into,
 };
-use axerrno::{LinuxError, LinuxResult};
-use memory_addr::{MemoryAddr, PAGE_SIZE_4K, VirtAddr, VirtAddrRange};
-use page_table_multiarch::MappingFlags;
+#[cfg(feature = "struct-helpers")]
+use crate::{
+    CLONE_ARGS_SIZE_VER0, CloneArgs, CpuMaskBuf, FdSetBuf, ITimerSpec, ITimerVal, RLimit64,
+    RawCloneArgs, RawSigAction, RawStack, SCHED_ATTR_SIZE_VER0, SIG_UNBLOCKABLE, SIGSET_SIZE,
+    SchedAttr, SigAction, SigStack, TimeSpec, TimeVal, UtimeSpec, check_cpu_set_out_size,
+    check_sigsetsize,
+};
+#[cfg(feature = "compat")]
+use crate::{CompatRLimit, CompatSigAction};
+#[cfg(feature = "debug-double-fetch")]
+use core::panic::Location;
 
-use crate::{UserConstPtr, UserPtr, UserReadable};
+/// Failure of an operation that may have partially completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialCopy {
+    /// Units (bytes or elements, per API) completed before the failure
+    pub done: usize,
+    /// Cause of the failure
+    pub error: Error,
+}
 
-#[percpu::def_percpu]
-static ACCESSING_USER_MEM: AtomicBool = AtomicBool::new(false);
+impl From<PartialCopy> for Error {
+    fn from(value: PartialCopy) -> Self {
+        value.error
+    }
+}
 
-/// Check if the current thread is accessing user memory
-pub fn is_accessing_user_memory() -> bool {
-    ACCESSING_USER_MEM.with_current(|v| v.load(Ordering::SeqCst))
+/// Failure of [`read_stable`](UserSpaceAccess::read_stable)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StableReadError {
+    /// A read failed
+    Access(Error),
+    /// The data changed between every pair of reads
+    Unstable,
 }
 
-/// Enable safe access to user memory within the closure
-pub fn access_user_memory<R>(f: impl FnOnce() -> R) -> R {
-    ACCESSING_USER_MEM.with_current(|v| {
-        v.store(true, Ordering::SeqCst);
-        let result = f();
-        v.store(false, Ordering::SeqCst);
-        result
-    })
+impl From<Error> for StableReadError {
+    fn from(value: Error) -> Self {
+        Self::Access(value)
+    }
+}
+
+impl From<StableReadError> for Error {
+    /// `EAGAIN` for unstable data
+    fn from(value: StableReadError) -> Self {
+        match value {
+            StableReadError::Access(error) => error,
+            StableReadError::Unstable => Error::EAGAIN,
+        }
+    }
+}
+
+/// Bytes compared per re-read by
+/// [`read_stable_bytes`](UserSpaceAccess::read_stable_bytes) and per copy by
+/// [`eq_constant_time`](UserSpaceAccess::eq_constant_time)
+const STABLE_CHUNK: usize = 64;
+
+/// End of the default [`UserSpaceRaw::user_addr_range`]
+pub const USER_ADDR_END: usize = if cfg!(target_arch = "x86_64") {
+    0x0000_7fff_ffff_f000
+} else if cfg!(target_arch = "aarch64") {
+    1 << 48
+} else if cfg!(target_arch = "riscv64") {
+    // Sv39
+    0x40_0000_0000
+} else if cfg!(target_pointer_width = "64") {
+    1 << 47
+} else {
+    0xc000_0000
+};
+
+/// [`UserSpaceRaw::user_addr_range`] with its start raised to
+/// [`UserSpaceRaw::min_user_addr`]
+pub(crate) fn effective_user_range<A: UserSpaceAccess + ?Sized>(uspace: &A) -> VirtAddrRange {
+    let range = uspace.user_addr_range();
+    let start = range.start.max(VirtAddr::from(uspace.min_user_addr()));
+    VirtAddrRange::new(start, range.end.max(start))
+}
+
+/// Whether the element of `layout` at `addr` is all zero bytes
+///
+/// # Safety
+///
+/// `addr` must be aligned for `layout` and the element readable.
+unsafe fn is_zero_element(addr: VirtAddr, layout: Layout) -> bool {
+    unsafe {
+        match (layout.size(), layout.align()) {
+            (1, _) => addr.as_ptr_of::<u8>().read_volatile() == 0,
+            (2, 2..) => addr.as_ptr_of::<u16>().read_volatile() == 0,
+            (4, 4..) => addr.as_ptr_of::<u32>().read_volatile() == 0,
+            (8, 8..) => addr.as_ptr_of::<u64>().read_volatile() == 0,
+            (size, _) => (0..size).all(|i| (addr + i).as_ptr_of::<u8>().read_volatile() == 0),
+        }
+    }
+}
+
+/// Size of the words byte scans read at once
+const WORD: usize = size_of::<usize>();
+
+/// Whether any byte of `word` is zero
+///
+/// Subtracting 1 from every byte borrows into the high bit only of bytes
+/// that were zero, or that follow a zero byte; masking out bytes whose high
+/// bit was already set leaves a nonzero result exactly when some byte is
+/// zero, on either endianness.
+const fn has_zero_byte(word: usize) -> bool {
+    word.wrapping_sub(BYTE_ONES) & !word & BYTE_HIGHS != 0
+}
+
+/// Word with every byte set to 1
+const BYTE_ONES: usize = usize::MAX / 0xff;
+
+/// Word with the high bit of every byte set
+const BYTE_HIGHS: usize = BYTE_ONES << 7;
+
+/// Flags passed to [`UserSpaceRaw::check_region_access`] for an access
+/// needing `flags`, adding [`Access::USER`] with `strict-user-flag`
+pub(crate) const fn check_flags(flags: Access) -> Access {
+    if cfg!(feature = "strict-user-flag") {
+        flags.union(Access::USER)
+    } else {
+        flags
+    }
 }
 
 /// Trait for validating and populating user space memory access
-pub trait UserSpaceAccess: Sized {
+///
+/// This is the object-safe part implemented by backends, so a task can hold
+/// e.g. an `Arc<dyn UserSpaceRaw>`. The helpers built on it are in
+/// [`UserSpaceAccess`], implemented for every `UserSpaceRaw`.
+pub trait UserSpaceRaw {
     /// Check if a memory region is accessible with given flags
-    fn check_region_access(
+    ///
+    /// Must only succeed if every page of `range` is mapped with at least
+    /// `access_flags`. With the `strict-user-flag` feature the crate's checks
+    /// (and the populates following them) always include
+    /// [`Access::USER`], which backends must then match against the
+    /// user bit of each mapping; otherwise the flags never contain `USER` and
+    /// backends must reject kernel mappings themselves.
+    ///
+    /// A failure means the range is not valid user memory for the access and
+    /// should be `EFAULT`.
+    fn check_region_access(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()>;
+
+    /// Populate a memory region making it accessible
+    ///
+    /// Called only after a successful check. Fails with `ENOMEM` when memory
+    /// for the pages (e.g. copy-on-write copies) cannot be allocated, and
+    /// with `EFAULT` when the mapping itself cannot back the access. The
+    /// crate propagates either unchanged to the caller of the copy.
+    fn populate_region(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()>;
+
+    /// Populate like [`populate_region`](Self::populate_region), but fail
+    /// with `EAGAIN` instead of blocking, e.g. on I/O or a sleeping lock
+    ///
+    /// Used by [`AccessHint::NonBlocking`] for callers holding a spinlock,
+    /// which drop it, populate normally and retry. Defaults to the blocking
+    /// hook, which is correct for backends that never block.
+    fn populate_region_nonblocking(
         &self,
         range: VirtAddrRange,
-        access_flags: MappingFlags,
-    ) -> LinuxResult<()>;
+        access_flags: Access,
+    ) -> UserResult<()> {
+        self.populate_region(range, access_flags)
+    }
 
-    /// Populate a memory region making it accessible
-    fn populate_region(&self, range: VirtAddrRange, access_flags: MappingFlags) -> LinuxResult<()>;
+    /// [`check_region_access`](Self::check_region_access) with a detailed
+    /// error
+    ///
+    /// Backends able to tell the cause apart should override it; the default
+    /// adapts the plain hook with [`UserAccessError::from_backend`].
+    fn check_region_access_detailed(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> AccessResult<()> {
+        self.check_region_access(range, access_flags)
+            .map_err(|e| UserAccessError::from_backend(range.start, access_flags, e))
+    }
+
+    /// [`populate_region`](Self::populate_region) with a detailed error
+    ///
+    /// The default adapts the plain hook like
+    /// [`check_region_access_detailed`](Self::check_region_access_detailed).
+    fn populate_region_detailed(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> AccessResult<()> {
+        self.populate_region(range, access_flags)
+            .map_err(|e| UserAccessError::from_backend(range.start, access_flags, e))
+    }
+
+    /// Check `range` for `access_flags` and populate it in one step
+    ///
+    /// Used by [`check_region`] with [`AccessHint::Populate`] and by the
+    /// page-stepping scans in place of separate
+    /// [`check_region_access_detailed`](Self::check_region_access_detailed)
+    /// and [`populate_region_detailed`](Self::populate_region_detailed)
+    /// calls, which the default makes in turn, adding [`Access::USER`] to
+    /// both under `strict-user-flag`. This is the intended place to
+    /// optimize validation of large ranges: a backend able to answer both
+    /// with one walk of its mappings should override it, keeping the errors
+    /// of the two hooks.
+    fn ensure_region(&self, range: VirtAddrRange, access_flags: Access) -> AccessResult<()> {
+        let flags = check_flags(access_flags);
+        self.check_region_access_detailed(range, flags)?;
+        self.populate_region_detailed(range, flags)
+    }
+
+    /// Get the validated `range` ready for a large transfer described by
+    /// `hint`
+    ///
+    /// Called by the chunked copies before copying at least
+    /// [`prefault_bytes`](Limits::prefault_bytes), so backends can batch
+    /// their page table work or start readahead instead of taking the pages
+    /// one fault at a time. A failure is ignored, leaving the copy to report
+    /// it. Defaults to [`populate_region`](Self::populate_region).
+    fn prefault(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+        _hint: PrefaultHint,
+    ) -> UserResult<()> {
+        self.populate_region(range, access_flags)
+    }
+
+    /// Check like [`check_region_access`](Self::check_region_access), and
+    /// also report whether the whole range is already populated
+    ///
+    /// Used by [`AccessHint::PopulateIfMissing`] to skip
+    /// [`populate_region`](Self::populate_region). Defaults to `false`, i.e.
+    /// residency unknown, so the populate call is always made.
+    fn check_region_resident(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<bool> {
+        self.check_region_access(range, access_flags)?;
+        Ok(false)
+    }
+
+    /// Check and populate several regions at once
+    ///
+    /// Every region is checked before any is populated. Backends guarding
+    /// their mappings with a lock can override this to handle the whole batch
+    /// under one acquisition; the default simply loops.
+    fn check_regions(&self, regions: &[(VirtAddrRange, Access)]) -> UserResult<()> {
+        for &(range, flags) in regions {
+            self.check_region_access(range, flags)?;
+        }
+        for &(range, flags) in regions {
+            self.populate_region(range, flags)?;
+        }
+        Ok(())
+    }
+
+    /// Validate the region of `layout` at `start` for `access_flags`,
+    /// populating it as `hint` says
+    ///
+    /// Backs [`check_region`] and its variants. An override must keep the
+    /// guarantees of the default: zero-sized layouts succeed without looking
+    /// at `start`; misaligned starts, ranges that wrap and ranges leaving
+    /// [`user_addr_range`](Self::user_addr_range) or starting below
+    /// [`min_user_addr`](Self::min_user_addr) fail; and on success the whole
+    /// range is accessible with `access_flags` (including `USER` under
+    /// `strict-user-flag`) and populated as `hint` asks.
+    fn check_region(
+        &self,
+        start: VirtAddr,
+        layout: Layout,
+        access_flags: Access,
+        hint: AccessHint,
+    ) -> AccessResult<()> {
+        check_region_default(self, start, layout, access_flags, hint)
+    }
+
+    /// Count the elements of `layout` at `start` before the first all-zero
+    /// one, checking every page read for `access_flags`
+    ///
+    /// Backs [`check_null_terminated`]. An override must return the same
+    /// count and fail in the same cases as the default: zero-sized elements
+    /// give 0, misaligned starts fail, more than `max` elements before the
+    /// terminator fail with [`TooLong`](AccessErrorKind::TooLong) without
+    /// reading further, and every byte up to and including the terminator
+    /// must be in user-accessible pages, each validated through
+    /// [`check_region`](Self::check_region) with `hint` before it is read.
+    /// The default validates pages with user access suspended, so that
+    /// populating them may block.
+    fn scan_null_terminated(
+        &self,
+        start: VirtAddr,
+        layout: Layout,
+        access_flags: Access,
+        max: usize,
+        hint: AccessHint,
+    ) -> AccessResult<usize> {
+        let size = layout.size();
+        let direct = self.direct_map();
+        scan_elements(
+            self,
+            start,
+            layout,
+            access_flags,
+            max,
+            hint,
+            |tagged, ahead, remaining| {
+                if size == 1 {
+                    return byte_scan_step(self, &[0], tagged, ahead, remaining);
+                }
+                let zero = if direct {
+                    unsafe { is_zero_element(tagged, layout) }
+                } else {
+                    is_zero_element_raw(self, tagged, size)?
+                };
+                Ok((!zero).then_some(1))
+            },
+        )
+    }
+
+    /// Granularity at which the backend tracks permissions, a power of two
+    ///
+    /// Page-stepping scans validate one page of this size at a time. Defaults
+    /// to 4K; backends using larger granules should override it.
+    fn page_size(&self) -> usize {
+        PAGE_SIZE_4K
+    }
+
+    /// Range of addresses that may hold user memory
+    ///
+    /// [`check_region`] and [`check_null_terminated`] reject any access not
+    /// fully inside it, or below [`min_user_addr`](Self::min_user_addr),
+    /// with `EFAULT` before consulting
+    /// [`check_region_access`](Self::check_region_access), so kernel
+    /// addresses never reach the backend. Defaults to the lower half of the
+    /// target's canonical address space.
+    fn user_addr_range(&self) -> VirtAddrRange {
+        VirtAddrRange::new(VirtAddr::from(0), VirtAddr::from(USER_ADDR_END))
+    }
+
+    /// Policy limits applied to user-supplied sizes
+    ///
+    /// Defaults to [`Limits::LINUX`].
+    fn limits(&self) -> &Limits {
+        &Limits::LINUX
+    }
+
+    /// Charge `bytes` of kernel memory about to be allocated for a size
+    /// chosen by user space
+    ///
+    /// Every helper allocating proportionally to user input calls it
+    /// through an [`AllocCharge`], and undoes it with
+    /// [`uncharge_kernel_alloc`](Self::uncharge_kernel_alloc) once it
+    /// returns, unless its result reports the charge as kept, like
+    /// [`ExecArgs::charged`]. Fail (typically with `ENOMEM`) to refuse the
+    /// allocation. Defaults to accepting everything.
+    fn charge_kernel_alloc(&self, bytes: usize) -> UserResult<()> {
+        let _ = bytes;
+        Ok(())
+    }
+
+    /// Undo a [`charge_kernel_alloc`](Self::charge_kernel_alloc)
+    fn uncharge_kernel_alloc(&self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// Truncate the length of a read or write to the `max_rw_count` of
+    /// [`limits`](Self::limits), as Linux does with `MAX_RW_COUNT`
+    fn clamp_rw_len(&self, len: usize) -> usize {
+        len.min(self.limits().max_rw_count)
+    }
+
+    /// Called with every error of the crate's region checks before it is
+    /// returned, e.g. for tracing
+    ///
+    /// With the `track-caller` feature the error records the call site of
+    /// the outermost crate entry point. Defaults to doing nothing.
+    fn on_access_error(&self, error: &UserAccessError) {
+        let _ = error;
+    }
+
+    /// Called by [`check_region`] and its variants after every successful
+    /// read-only check, with the call site of the crate entry point
+    ///
+    /// [`ValidationSession`](crate::ValidationSession) records the reads to
+    /// detect double fetches. Defaults to doing nothing.
+    #[cfg(feature = "debug-double-fetch")]
+    fn record_user_read(&self, _range: VirtAddrRange, _location: &'static Location<'static>) {}
+
+    /// Called when a [`ValidationSession`](crate::ValidationSession) sees a
+    /// read overlapping an earlier one, e.g. to log a warning
+    ///
+    /// Diagnostics only: some re-reads are legitimate. Sessions report a
+    /// few of them at most. Defaults to doing nothing.
+    #[cfg(feature = "debug-double-fetch")]
+    fn on_double_fetch(&self, _fetch: &DoubleFetch) {}
+
+    /// Called by [`read_stable`](UserSpaceAccess::read_stable) and
+    /// [`read_stable_bytes`](UserSpaceAccess::read_stable_bytes) before each
+    /// re-read of `range`
+    ///
+    /// [`ValidationSession`](crate::ValidationSession) neither reports nor
+    /// records the next read if it is of exactly `range`. Defaults to doing
+    /// nothing.
+    #[cfg(feature = "debug-double-fetch")]
+    fn expect_user_reread(&self, _range: VirtAddrRange) {}
+
+    /// Lowest address user memory may be accessed at, like `mmap_min_addr`
+    ///
+    /// Accesses below it fail with `EFAULT` regardless of the mappings, so a
+    /// mapped page zero never turns a kernel null-pointer bug into a read of
+    /// user-controlled data. Defaults to 4K; return 0 to allow page zero.
+    fn min_user_addr(&self) -> usize {
+        0x1000
+    }
+
+    /// Mapping generation of the address space
+    ///
+    /// Backends that support caching of validation results return a counter
+    /// they bump on every change that can revoke or downgrade a mapping
+    /// (unmapping, protecting, remapping), before the change takes effect for
+    /// accesses. Two equal values then guarantee that no such change happened
+    /// in between; caches such as [`ValidationSession`] and
+    /// [`ValidatedRegion`] only trust earlier results while the generation is
+    /// unchanged. The default returns a new value on every call, which
+    /// disables all caching.
+    fn generation(&self) -> u64 {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Identity of the address space behind the backend
+    ///
+    /// Two backends return the same value exactly when they access the same
+    /// address space, so that the `debug-aliasing` checks see overlapping
+    /// borrows made through different handles to it. Defaults to the address
+    /// of the backend; backends created per call for a shared address space
+    /// should return something stable such as their page table root.
+    fn space_id(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+
+    /// Error to stop a long access with, e.g. for a pending fatal signal
+    ///
+    /// Polled between pages by chunked copies and null-terminated scans.
+    /// Operations with partial results, such as
+    /// [`read_chunks`](UserSpaceAccess::read_chunks), report the bytes done
+    /// and the error in a [`PartialCopy`]; the others fail with the error.
+    /// A syscall that made progress returns the partial count, as Linux does
+    /// for `read` and `write`; otherwise it returns `EINTR`, or restarts as
+    /// its `ERESTARTSYS` would when the signal's handler allows it. Defaults
+    /// to never interrupting.
+    fn should_interrupt(&self) -> Option<Error> {
+        None
+    }
+
+    /// Check that the protection keys of the pages in `range` allow
+    /// `access_flags` under the current key rights, e.g. x86 `PKRU` or arm64
+    /// `POR_EL0`
+    ///
+    /// Called by [`check_region`](Self::check_region) after the mapping flags
+    /// passed; any error is reported as [`AccessErrorKind::PkeyDenied`].
+    /// Defaults to allowing everything.
+    fn check_pkey(&self, _range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        Ok(())
+    }
+
+    /// Extend a mapping that grows on demand, such as a `MAP_GROWSDOWN`
+    /// stack, so that it covers `range`, returning whether it grew
+    ///
+    /// Called once by [`check_region`](Self::check_region) when `range` is
+    /// found unmapped, which then checks again if this returns `true`.
+    /// Backends decide how far below the stack pointer growth is allowed.
+    /// Defaults to no growth.
+    fn try_grow_region(&self, _range: VirtAddrRange, _access_flags: Access) -> UserResult<bool> {
+        Ok(false)
+    }
+
+    /// Write `data` at `addr` even if the mapping is not writable, as
+    /// `FOLL_FORCE` does for debuggers
+    ///
+    /// Implementations break copy-on-write, make the pages writable for the
+    /// duration, or write through a kernel alias of the frames; the mapping
+    /// itself must stay as the task set it up. The range was checked to be
+    /// mapped. Defaults to failing with `EPERM`.
+    fn force_write_region(&self, _addr: VirtAddr, _data: &[u8]) -> UserResult<()> {
+        Err(Error::EPERM)
+    }
 
+    /// Perform the cache maintenance `kind` asks for after a write to `range`
+    ///
+    /// Called with [`SyncKind::Instruction`] after
+    /// [`write_forced`](UserSpaceAccess::write_forced), whose typical use is
+    /// patching code, and with whatever drivers ask for through
+    /// [`flush_user_range`](UserSpaceAccess::flush_user_range). Defaults to
+    /// doing nothing, which is enough on architectures with coherent caches.
+    fn sync_after_write(&self, _range: VirtAddrRange, _kind: SyncKind) {}
+
+    /// Strip the tag bits of a user address, such as the top byte under arm64
+    /// Top-Byte-Ignore or the bits x86 LAM masks
+    ///
+    /// Applied before any range check, while accesses still go through the
+    /// tagged address, which the hardware accepts. Defaults to the identity.
+    fn untag_addr(&self, addr: VirtAddr) -> VirtAddr {
+        addr
+    }
+
+    /// Copy `len` bytes from user `src` to kernel `dst`
+    ///
+    /// Every copy of the crate goes through this and
+    /// [`raw_copy_to_user`](Self::raw_copy_to_user), inside a user access
+    /// window. The default dereferences `src` through the installed
+    /// [`UserCopyBackend`](crate::UserCopyBackend), which needs user memory
+    /// reachable from the kernel's address space; backends where it is not,
+    /// e.g. with disjoint user and kernel address spaces, override both and
+    /// return `false` from [`direct_map`](Self::direct_map).
+    ///
+    /// # Safety
+    ///
+    /// `dst` must be valid for `len` bytes of writes, and `src` must have
+    /// been validated for `len` bytes of reads.
+    ///
+    /// A fault fails the whole call; bulk copies report partial progress up
+    /// to the start of the failed call only.
+    unsafe fn raw_copy_from_user(&self, dst: *mut u8, src: VirtAddr, len: usize) -> UserResult<()> {
+        unsafe { copy_from_user_fallible(dst, src.as_ptr(), len) }.map_err(|_| Error::EFAULT)
+    }
+
+    /// Copy `len` bytes from kernel `src` to user `dst`, see
+    /// [`raw_copy_from_user`](Self::raw_copy_from_user)
+    ///
+    /// # Safety
+    ///
+    /// `src` must be valid for `len` bytes of reads, and `dst` must have been
+    /// validated for `len` bytes of writes.
+    unsafe fn raw_copy_to_user(&self, dst: VirtAddr, src: *const u8, len: usize) -> UserResult<()> {
+        unsafe { copy_to_user_fallible(dst.as_mut_ptr(), src, len) }.map_err(|_| Error::EFAULT)
+    }
+
+    /// Whether user memory can be dereferenced directly from kernel context
+    ///
+    /// Helpers returning references into user memory, such as
+    /// [`read_slice`](UserSpaceAccess::read_slice), and the futex atomics
+    /// need it and panic otherwise; the copying helpers work either way.
+    /// Defaults to `true`.
+    fn direct_map(&self) -> bool {
+        true
+    }
+
+    /// Pin the pages of `range`, which was checked for `access_flags`, so
+    /// they are neither unmapped, swapped out nor migrated until
+    /// [`unpin_pages`](Self::unpin_pages)
+    ///
+    /// Pins nest: a page pinned twice stays pinned until unpinned twice. A
+    /// failing call must leave nothing pinned. Defaults to failing with
+    /// `EOPNOTSUPP`.
+    fn pin_pages(&self, _range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        Err(Error::EOPNOTSUPP)
+    }
+
+    /// Drop one pin of every page of `range`, taken by
+    /// [`pin_pages`](Self::pin_pages)
+    fn unpin_pages(&self, _range: VirtAddrRange) {}
+
+    /// Physical address backing user `vaddr`, called on pinned pages by
+    /// [`PinnedRegion::phys_segments`]
+    ///
+    /// Backends implementing [`pin_pages`](Self::pin_pages) should implement
+    /// it too. Defaults to `None`.
+    fn virt_to_phys(&self, _vaddr: VirtAddr) -> Option<PhysAddr> {
+        None
+    }
+
+    /// Memory type of the mappings covering `range`, which was checked
+    ///
+    /// Bulk copies use aligned, volatile, word-sized accesses for anything
+    /// but [`MemoryType::Normal`], so that user pointers into mapped device
+    /// registers or framebuffers do not take alignment faults. Backends
+    /// allowing such mappings should report the strictest type in `range`.
+    /// Defaults to `Normal`.
+    fn memory_type(&self, _range: VirtAddrRange) -> MemoryType {
+        MemoryType::Normal
+    }
+
+    /// Yield the CPU if a reschedule is due, like `cond_resched`
+    ///
+    /// Called every [`Limits::relax_pages`] pages of bulk copies and scans,
+    /// with the user access flag dropped for the call and set again after.
+    /// Defaults to doing nothing.
+    fn relax(&self) {}
+
+    /// Report the mapping flags of every page overlapping `range`, in order,
+    /// with `None` for unmapped pages
+    ///
+    /// The default probes each page with
+    /// [`check_region_access`](Self::check_region_access) for every single
+    /// flag, reporting `Some(Access::empty())` for pages only accepting
+    /// an empty flag set. Backends that can walk their mappings directly
+    /// should override it. Nothing is populated.
+    fn query_region(&self, range: VirtAddrRange, f: &mut dyn FnMut(VirtAddr, Option<Access>)) {
+        let user_range = effective_user_range(self);
+        let page_size = self.page_size();
+        let mut page = range.start.align_down(page_size);
+        while page < range.end {
+            let Some(page_range) = VirtAddrRange::try_from_start_size(page, page_size) else {
+                break;
+            };
+            let flags = if user_range.contains_range(page_range) {
+                let flags = [Access::READ, Access::WRITE, Access::EXECUTE]
+                    .into_iter()
+                    .filter(|&flag| {
+                        self.check_region_access(page_range, check_flags(flag))
+                            .is_ok()
+                    })
+                    .fold(Access::empty(), |acc, flag| acc | flag);
+                (!flags.is_empty()
+                    || self
+                        .check_region_access(page_range, check_flags(Access::empty()))
+                        .is_ok())
+                .then_some(flags)
+            } else {
+                None
+            };
+            f(page, flags);
+            page = page_range.end;
+        }
+    }
+}
+
+/// User memory helpers, implemented for every [`UserSpaceRaw`] including
+/// `dyn UserSpaceRaw`
+pub trait UserSpaceAccess: UserSpaceRaw {
     /// Read a value from user space
-    fn read<P, T>(&self, ptr: P) -> LinuxResult<T>
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn read<P, T>(&self, ptr: P) -> UserResult<T>
     where
         P: UserReadable<T>,
         T: Copy + 'static,
     {
-        ptr.get_as_ref(self).copied()
+        let range = check_user_bytes(self, ptr.user_address(), Layout::new::<T>(), Access::READ)?;
+        let mut val = MaybeUninit::<T>::uninit();
+        copy_in_chunked(self, range, val.as_mut_ptr().cast()).map_err(|e| e.error)?;
+        Ok(unsafe { val.assume_init() })
+    }
+
+    legacy_ref_fn! {
+        /// Read a null-terminated string from user space
+        ///
+        /// # Safety
+        ///
+        /// See [`UserReadable::get_as_ref`].
+        #[deprecated(note = "the string outlives the address space, use `read_cstr_into`")]
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        fn read_str<P: UserStrPtr>(&self, ptr: P) -> UserResult<&'static str> {
+            user_str(self, ptr.str_address(), self.limits().max_scan_len)
+        }
+    }
+
+    /// Copy the null-terminated UTF-8 string at `ptr` into a new string
+    ///
+    /// Copies and validates in the same pass, so the string returned holds
+    /// exactly the bytes the UTF-8 check passed, however user memory changes
+    /// meanwhile, and invalid input fails with `EILSEQ` as soon as it is
+    /// read. Accepts at most [`max_scan_len`](Limits::max_scan_len) bytes,
+    /// failing with `ENAMETOOLONG` beyond them.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn read_string<P: UserStrPtr>(&self, ptr: P) -> UserResult<String> {
+        copy_utf8_str(self, ptr.str_address(), self.limits().max_scan_len)
+    }
+
+    /// Copy the null-terminated string at `ptr` into `buf`, returning its
+    /// length without the terminator
+    ///
+    /// Reads one page at a time and stops at the first NUL, so nothing past
+    /// `buf.len()` bytes is touched. Fails with `ENAMETOOLONG` if `buf` fills
+    /// up first; the bytes are not required to be UTF-8.
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn read_cstr_into<P: UserStrPtr>(&self, ptr: P, buf: &mut [u8]) -> UserResult<usize> {
+        let page_size = self.page_size();
+        let mut addr = ptr.str_address().as_usize();
+        let mut len = 0;
+        let mut pages = 0;
+        while len < buf.len() {
+            let chunk = (page_size - (addr & (page_size - 1))).min(buf.len() - len);
+            let src = check_user_bytes(
+                self,
+                VirtAddr::from(addr),
+                slice_layout::<u8>(chunk)?,
+                Access::READ,
+            )?;
+            let dst = &mut buf[len..len + chunk];
+            if let Some(e) = self.should_interrupt() {
+                return Err(e);
+            }
+            relax_point(self, pages);
+            pages += 1;
+            try_access_user_memory(|window| {
+                if window.should_abort() {
+                    return Err(Error::EINTR);
+                }
+                window.expect(src, Access::READ);
+                let copied = unsafe { self.raw_copy_from_user(dst.as_mut_ptr(), src.start, chunk) };
+                if window.faulted() {
+                    return Err(Error::EFAULT);
+                }
+                copied
+            })?;
+            if let Some(nul) = dst.iter().position(|&b| b == 0) {
+                return Ok(len + nul);
+            }
+            len += chunk;
+            addr = addr.wrapping_add(chunk);
+        }
+        Err(Error::ENAMETOOLONG)
+    }
+
+    /// Count the elements at `ptr` before the first one `is_end` accepts,
+    /// checking and populating every page read
+    ///
+    /// For tables ending in a sentinel other than zero, such as 0xFF bytes,
+    /// newlines or an end entry. `is_end` only sees kernel copies of the
+    /// elements, never user memory. Fails with `EINVAL` once more than `max`
+    /// elements precede the end, and gives 0 for zero-sized `T`.
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn scan_terminated<T: UserCopy>(
+        &self,
+        ptr: UserConstPtr<T>,
+        is_end: impl Fn(&T) -> bool,
+        max: usize,
+    ) -> UserResult<usize> {
+        let direct = self.direct_map();
+        let scan = scan_elements(
+            self,
+            ptr.address(),
+            Layout::new::<T>(),
+            Access::READ,
+            max,
+            AccessHint::Populate,
+            |tagged, _, _| {
+                let mut val = MaybeUninit::<T>::uninit();
+                if direct {
+                    let src = tagged.as_ptr_of::<T>();
+                    unsafe { val.write(src.read_volatile()) };
+                } else {
+                    unsafe {
+                        self.raw_copy_from_user(val.as_mut_ptr().cast(), tagged, size_of::<T>())?
+                    };
+                }
+                let val = unsafe { val.assume_init() };
+                Ok((!is_end(&val)).then_some(1))
+            },
+        );
+        Ok(locate(self, scan)?)
+    }
+
+    /// Find the first `needle` in the `len` bytes at `ptr`
+    ///
+    /// Checks and populates pages only up to the one holding the match, so
+    /// a short search of a long buffer touches little of it. Fails with
+    /// `EFAULT` if a page before the match is inaccessible.
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn memchr(&self, ptr: UserConstPtr<u8>, len: usize, needle: u8) -> UserResult<Option<usize>> {
+        find_bytes(self, ptr, len, &[needle])
+    }
+
+    /// Like [`memchr`](Self::memchr), finding the first of either needle
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn memchr2(
+        &self,
+        ptr: UserConstPtr<u8>,
+        len: usize,
+        needle1: u8,
+        needle2: u8,
+    ) -> UserResult<Option<usize>> {
+        find_bytes(self, ptr, len, &[needle1, needle2])
+    }
+
+    /// Whether the null-terminated string at `ptr` equals `expected`
+    ///
+    /// Compares while scanning, reading at most `expected.len() + 1` bytes
+    /// and stopping at the first mismatch, without allocating. A user string
+    /// that is a strict prefix of `expected`, or that continues past it, is
+    /// not equal.
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn str_eq<P: UserStrPtr>(&self, ptr: P, expected: &str) -> UserResult<bool> {
+        compare_str(self, ptr.str_address(), expected.as_bytes(), true)
+    }
+
+    /// Whether the null-terminated string at `ptr` starts with `prefix`
+    ///
+    /// Like [`str_eq`](Self::str_eq), except that the user string may
+    /// continue past `prefix`; a user string shorter than `prefix` does not
+    /// start with it. Every string starts with an empty prefix.
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn str_starts_with<P: UserStrPtr>(&self, ptr: P, prefix: &str) -> UserResult<bool> {
+        compare_str(self, ptr.str_address(), prefix.as_bytes(), false)
+    }
+
+    /// Read a value from user space until two consecutive reads agree,
+    /// re-reading at most `max_retries + 1` times
+    ///
+    /// For structures read twice on purpose, such as a header whose length
+    /// was validated before the whole structure is read: checking the second
+    /// read against the value computations were based on catches a
+    /// concurrent change. Fails with [`StableReadError::Unstable`] if the
+    /// value keeps changing, which converts to `EAGAIN`; callers wanting
+    /// `EINVAL` map it themselves. This only makes the value itself
+    /// consistent: user memory that embedded pointers or lengths refer to
+    /// can still change after the call, and must be read once and trusted
+    /// from the kernel copy.
+    fn read_stable<T: UserCopy + PartialEq>(
+        &self,
+        ptr: UserConstPtr<T>,
+        max_retries: usize,
+    ) -> Result<T, StableReadError> {
+        let mut val = self.read(ptr)?;
+        for _ in 0..=max_retries {
+            #[cfg(feature = "debug-double-fetch")]
+            self.expect_user_reread(VirtAddrRange::from_start_size(
+                ptr.address(),
+                size_of::<T>(),
+            ));
+            let again = self.read(ptr)?;
+            if again == val {
+                return Ok(val);
+            }
+            val = again;
+        }
+        Err(StableReadError::Unstable)
+    }
+
+    /// Like [`read_stable`](Self::read_stable), for `buf.len()` bytes
+    ///
+    /// Re-reads in small chunks compared against `buf`, so nothing is
+    /// allocated. On success every byte of `buf` was unchanged over a full
+    /// re-read, which need not be a single atomic snapshot.
+    fn read_stable_bytes(
+        &self,
+        ptr: UserConstPtr<u8>,
+        buf: &mut [u8],
+        max_retries: usize,
+    ) -> Result<(), StableReadError> {
+        self.read_slice_to(ptr, buf)?;
+        let mut chunk = [0u8; STABLE_CHUNK];
+        for _ in 0..=max_retries {
+            let mut stable = true;
+            for (i, kernel) in buf.chunks_mut(STABLE_CHUNK).enumerate() {
+                let again = &mut chunk[..kernel.len()];
+                let at = ptr.offset(i * STABLE_CHUNK);
+                #[cfg(feature = "debug-double-fetch")]
+                self.expect_user_reread(VirtAddrRange::from_start_size(at.address(), again.len()));
+                self.read_slice_to(at, again)?;
+                if again != kernel {
+                    kernel.copy_from_slice(again);
+                    stable = false;
+                }
+            }
+            if stable {
+                return Ok(());
+            }
+        }
+        Err(StableReadError::Unstable)
+    }
+
+    /// Read a value from user space without populating, blocking or faulting
+    /// pages in
+    ///
+    /// Fails with `EFAULT` unless the value is resident and readable. Safe to
+    /// call from interrupt context and with interrupts disabled, e.g. for
+    /// sampling or oops dumps, provided the backend's
+    /// [`check_region_resident`](UserSpaceRaw::check_region_resident) takes no
+    /// locks; with a [`UserCopyBackend`](crate::UserCopyBackend) installed it
+    /// is not called at all.
+    fn read_nofault<T: UserCopy>(&self, ptr: UserConstPtr<T>) -> UserResult<T> {
+        let mut val = MaybeUninit::<T>::uninit();
+        let dst = unsafe {
+            core::slice::from_raw_parts_mut(val.as_mut_ptr().cast::<u8>(), size_of::<T>())
+        };
+        if read_bytes_nofault(self, ptr.address().as_usize(), dst)? < size_of::<T>() {
+            return Err(Error::EFAULT);
+        }
+        Ok(unsafe { val.assume_init() })
+    }
+
+    /// Like [`read_nofault`](Self::read_nofault), filling `buf` from `ptr` and
+    /// returning the number of bytes read
+    ///
+    /// Stops short at the first page that is not resident, failing with
+    /// `EFAULT` only if nothing could be read.
+    fn read_slice_nofault<T: UserCopy>(
+        &self,
+        ptr: UserConstPtr<T>,
+        buf: &mut [T],
+    ) -> UserResult<usize> {
+        let dst = unsafe {
+            core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), size_of_val(buf))
+        };
+        read_bytes_nofault(self, ptr.address().as_usize(), dst)
+    }
+
+    /// Like [`read_nofault`](Self::read_nofault), copying the null-terminated
+    /// string at `ptr` into `buf` and returning its length without the
+    /// terminator
+    ///
+    /// At most `buf.len()` bytes are read, so a small buffer caps the work; a
+    /// longer string, or one running into a page that is not resident, is
+    /// truncated rather than failed.
+    fn read_str_nofault<P: UserStrPtr>(&self, ptr: P, buf: &mut [u8]) -> UserResult<usize> {
+        let read = read_bytes_nofault(self, ptr.str_address().as_usize(), buf)?;
+        Ok(buf[..read].iter().position(|&b| b == 0).unwrap_or(read))
     }
 
-    /// Read a null-terminated string from user space
-    fn read_str(&self, ptr: UserConstPtr<c_char>) -> LinuxResult<&'static str> {
-        ptr.get_as_str(self)
+    legacy_ref_fn! {
+        /// Read a slice from user space
+        ///
+        /// # Safety
+        ///
+        /// See [`UserReadable::get_as_ref`].
+        #[deprecated(note = "the slice outlives the address space, use `read_vec` or `get_slice`")]
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        #[allow(deprecated, unused_unsafe)]
+        fn read_slice<P, T>(&self, ptr: P, len: usize) -> UserResult<&'static [T]>
+        where
+            P: UserReadable<T>,
+        {
+            unsafe { ptr.get_as_slice(self, len) }
+        }
     }
 
-    /// Read a slice from user space
-    fn read_slice<P, T>(&self, ptr: P, len: usize) -> LinuxResult<&'static [T]>
+    /// Copy `len` values from user space into a new vector
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn read_vec<P, T>(&self, ptr: P, len: usize) -> UserResult<Vec<T>>
     where
         P: UserReadable<T>,
+        T: Copy + 'static,
     {
-        ptr.get_as_slice(self, len)
+        let layout = slice_layout::<T>(len)?;
+        let range = check_user_bytes(self, ptr.user_address(), layout, Access::READ)?;
+        let mut buf = Vec::<T>::with_capacity(len);
+        copy_in_chunked(self, range, buf.as_mut_ptr().cast()).map_err(|e| e.error)?;
+        unsafe { buf.set_len(len) };
+        Ok(buf)
     }
 
     /// Read from user space into a kernel buffer using direct memory copy
-    fn read_slice_to<P, T>(&self, ptr: P, buf: &mut [T]) -> LinuxResult<()>
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn read_slice_to<P, T>(&self, ptr: P, buf: &mut [T]) -> UserResult<()>
     where
         P: UserReadable<T>,
         T: 'static,
     {
-        let user_slice = ptr.get_as_slice(self, buf.len())?;
-        unsafe {
-            core::ptr::copy_nonoverlapping(user_slice.as_ptr(), buf.as_mut_ptr(), buf.len());
+        let layout = slice_layout::<T>(buf.len())?;
+        let range = check_user_bytes(self, ptr.user_address(), layout, Access::READ)?;
+        copy_in_chunked(self, range, buf.as_mut_ptr().cast()).map_err(|e| e.error)
+    }
+
+    /// Read into `buf` from `ptr` with aligned, volatile, word-sized
+    /// accesses, for drivers that know the mapping is device or uncached
+    /// memory
+    ///
+    /// Unlike the other copies, faults are not recoverable.
+    fn read_slice_volatile<T: UserCopy>(
+        &self,
+        ptr: UserConstPtr<T>,
+        buf: &mut [T],
+    ) -> UserResult<()> {
+        let user_slice = ptr.get_slice(self, buf.len())?;
+        let range = user_range_of(user_slice.as_ptr(), user_slice.len());
+        let dst = buf.as_mut_ptr().cast::<u8>();
+        let src = user_slice.as_ptr().cast::<u8>();
+        copy_chunked(self, range, Access::READ, |off, len| unsafe {
+            copy_from_device(dst.add(off), src.add(off), len);
+            Ok(())
+        })
+        .map_err(|e| e.error)
+    }
+
+    /// Write `slice` to `ptr` like
+    /// [`read_slice_volatile`](Self::read_slice_volatile) reads
+    fn write_slice_volatile<T: UserCopy>(&self, ptr: UserPtr<T>, slice: &[T]) -> UserResult<()> {
+        let mut user_slice = ptr.get_mut_slice(self, slice.len())?;
+        let range = user_range_of(user_slice.as_ptr(), user_slice.len());
+        let dst = user_slice.as_mut_ptr().cast::<u8>();
+        let src = slice.as_ptr().cast::<u8>();
+        copy_chunked(
+            self,
+            range,
+            Access::READ | Access::WRITE,
+            |off, len| unsafe {
+                copy_to_device(dst.add(off), src.add(off), len);
+                Ok(())
+            },
+        )
+        .map_err(|e| e.error)
+    }
+
+    /// Copy `buf.len()` bytes from user `ptr` into `buf` a page at a time
+    ///
+    /// Polls [`should_interrupt`](UserSpaceRaw::should_interrupt) between
+    /// pages. On failure [`PartialCopy::done`] is the number of bytes copied.
+    fn read_chunks(&self, ptr: UserConstPtr<u8>, buf: &mut [u8]) -> Result<(), PartialCopy> {
+        let layout =
+            slice_layout::<u8>(buf.len()).map_err(|error| PartialCopy { done: 0, error })?;
+        let range = check_user_bytes(self, ptr.address(), layout, Access::READ)
+            .map_err(|error| PartialCopy { done: 0, error })?;
+        copy_in_chunked(self, range, buf.as_mut_ptr())
+    }
+
+    /// Copy `buf` to user `ptr` a page at a time
+    ///
+    /// Polls [`should_interrupt`](UserSpaceRaw::should_interrupt) between
+    /// pages. On failure [`PartialCopy::done`] is the number of bytes copied.
+    fn write_chunks(&self, ptr: UserPtr<u8>, buf: &[u8]) -> Result<(), PartialCopy> {
+        let layout =
+            slice_layout::<u8>(buf.len()).map_err(|error| PartialCopy { done: 0, error })?;
+        let range = check_user_bytes(self, ptr.address(), layout, Access::READ | Access::WRITE)
+            .map_err(|error| PartialCopy { done: 0, error })?;
+        copy_out_chunked(self, range, buf.as_ptr())
+    }
+
+    /// Copy `buf.len()` bytes from user `ptr` into `buf`, validating and
+    /// populating one window of pages at a time
+    ///
+    /// Unlike [`read_chunks`](Self::read_chunks), which populates the whole
+    /// range before copying, at most
+    /// [`stream_window_pages`](Limits::stream_window_pages) pages are
+    /// populated ahead of the copy, bounding the memory and latency a huge
+    /// transfer costs up front. On failure [`PartialCopy::done`] is the
+    /// number of bytes copied.
+    fn copy_from_user_streaming(
+        &self,
+        ptr: UserConstPtr<u8>,
+        buf: &mut [u8],
+    ) -> Result<(), PartialCopy> {
+        let dst = buf.as_mut_ptr();
+        stream_windows(
+            self,
+            ptr.address(),
+            buf.len(),
+            Access::READ,
+            |range, off| copy_in_chunked(self, range, unsafe { dst.add(off) }),
+        )
+    }
+
+    /// Copy `buf` to user `ptr` one window of pages at a time, like
+    /// [`copy_from_user_streaming`](Self::copy_from_user_streaming)
+    fn copy_to_user_streaming(&self, ptr: UserPtr<u8>, buf: &[u8]) -> Result<(), PartialCopy> {
+        stream_windows(
+            self,
+            ptr.address(),
+            buf.len(),
+            Access::READ | Access::WRITE,
+            |range, off| copy_out_chunked(self, range, unsafe { buf.as_ptr().add(off) }),
+        )
+    }
+
+    legacy_ref_fn! {
+        /// Get a mutable reference to user space data
+        ///
+        /// # Safety
+        ///
+        /// See [`UserPtr::get_as_mut`].
+        #[deprecated(note = "the reference outlives the address space, use `update`")]
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        #[allow(deprecated, unused_unsafe)]
+        fn raw_ptr<T>(&self, ptr: UserPtr<T>) -> UserResult<&'static mut T> {
+            unsafe { ptr.get_as_mut(self) }
         }
-        Ok(())
     }
 
-    /// Get a mutable reference to user space data
-    fn raw_ptr<T>(&self, ptr: UserPtr<T>) -> LinuxResult<&'static mut T> {
-        ptr.get_as_mut(self)
+    legacy_ref_fn! {
+        /// Get a mutable slice to user space data
+        ///
+        /// # Safety
+        ///
+        /// See [`UserPtr::get_as_mut`].
+        #[deprecated(note = "the slice outlives the address space, use `write_slice`")]
+        #[cfg_attr(feature = "track-caller", track_caller)]
+        #[allow(deprecated, unused_unsafe)]
+        fn raw_slice<T>(&self, ptr: UserPtr<T>, len: usize) -> UserResult<&'static mut [T]> {
+            unsafe { ptr.get_as_mut_slice(self, len) }
+        }
+    }
+
+    /// Copy `len` bytes of sensitive data, such as a key, into a buffer that
+    /// is zeroed when dropped
+    ///
+    /// Fails with `EINVAL` if `len` exceeds `max`. The copy goes straight
+    /// into the returned buffer; backends whose
+    /// [`raw_copy_from_user`](UserSpaceRaw::raw_copy_from_user) stages data
+    /// in a bounce buffer should scrub it too.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn read_sensitive(
+        &self,
+        ptr: UserConstPtr<u8>,
+        len: usize,
+        max: usize,
+    ) -> UserResult<SecretBuf> {
+        if len > max {
+            return Err(Error::EINVAL);
+        }
+        let mut buf = SecretBuf::zeroed(len);
+        self.read_slice_to(ptr, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Write sensitive `data` to `ptr`, then zero `data`, whether the write
+    /// succeeded or not
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn write_sensitive(&self, ptr: UserPtr<u8>, data: &mut [u8]) -> UserResult<()> {
+        let result = self.write_slice(ptr, data);
+        zeroize(data);
+        result
+    }
+
+    /// Compare the `len` user bytes at `ptr` with `kernel` in constant time
+    ///
+    /// The user bytes are copied through a small stack buffer, which is zeroed
+    /// afterwards, and every one of them is compared whether or not an
+    /// earlier one differed; a length mismatch is folded into the result
+    /// rather than returned early. Only `len`, which the caller chose, shapes
+    /// the timing.
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn eq_constant_time(
+        &self,
+        ptr: UserConstPtr<u8>,
+        len: usize,
+        kernel: &[u8],
+    ) -> UserResult<bool> {
+        let mut chunk = [0u8; STABLE_CHUNK];
+        let mut diff = (len != kernel.len()) as u8;
+        let mut off = 0;
+        let result = loop {
+            if off >= len {
+                break Ok(());
+            }
+            let user = &mut chunk[..STABLE_CHUNK.min(len - off)];
+            if let Err(error) = self.read_slice_to(ptr.offset(off), user) {
+                break Err(error);
+            }
+            for (i, &byte) in user.iter().enumerate() {
+                diff |= byte ^ kernel.get(off + i).copied().unwrap_or(0);
+            }
+            off += user.len();
+        };
+        zeroize(&mut chunk);
+        result.map(|()| core::hint::black_box(diff) == 0)
+    }
+
+    /// Copy the value at `ptr` into a guard that writes it back on
+    /// [`commit`](UserCaptured::commit)
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn capture<T: UserCopy>(&self, ptr: UserPtr<T>) -> UserResult<UserCaptured<'_, Self, T>> {
+        UserCaptured::new(self, ptr)
     }
 
-    /// Get a mutable slice to user space data
-    fn raw_slice<T>(&self, ptr: UserPtr<T>, len: usize) -> LinuxResult<&'static mut [T]> {
-        ptr.get_as_mut_slice(self, len)
+    /// Like [`capture`](Self::capture), for `len` values at `ptr`
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn capture_slice<T: UserCopy>(
+        &self,
+        ptr: UserPtr<T>,
+        len: usize,
+    ) -> UserResult<UserCapturedSlice<'_, Self, T>> {
+        UserCapturedSlice::new(self, ptr, len)
+    }
+
+    /// Read the value at `ptr`, let `f` modify it and write it back,
+    /// returning what `f` returns
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn update<T, R>(&self, ptr: UserPtr<T>, f: impl FnOnce(&mut T) -> R) -> UserResult<R>
+    where
+        T: Copy + 'static,
+    {
+        let mut val = self.read(ptr)?;
+        let ret = f(&mut val);
+        self.write(ptr, val)?;
+        Ok(ret)
     }
 
     /// Write a value to user space
-    fn write<T>(&self, ptr: UserPtr<T>, val: T) -> LinuxResult<()>
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn write<T>(&self, ptr: UserPtr<T>, val: T) -> UserResult<()>
     where
         T: 'static,
     {
-        ptr.get_as_mut(self).map(|v| *v = val)
+        let flags = Access::READ | Access::WRITE;
+        let range = check_user_bytes(self, ptr.address(), Layout::new::<T>(), flags)?;
+        let val = ManuallyDrop::new(val);
+        copy_out_chunked(self, range, (&*val as *const T).cast()).map_err(|e| e.error)
     }
 
     /// Write a slice to user space using direct memory copy
-    fn write_slice<T>(&self, ptr: UserPtr<T>, slice: &[T]) -> LinuxResult<()>
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn write_slice<T>(&self, ptr: UserPtr<T>, slice: &[T]) -> UserResult<()>
     where
         T: 'static,
     {
-        let user_slice = ptr.get_as_mut_slice(self, slice.len())?;
-        unsafe {
-            core::ptr::copy_nonoverlapping(slice.as_ptr(), user_slice.as_mut_ptr(), slice.len());
-        }
-        Ok(())
+        let layout = slice_layout::<T>(slice.len())?;
+        let range = check_user_bytes(self, ptr.address(), layout, Access::READ | Access::WRITE)?;
+        copy_out_chunked(self, range, slice.as_ptr().cast()).map_err(|e| e.error)
     }
 
     /// Read multiple strings from a null-terminated array of string pointers
-    fn read_str_array(&self, ptr: UserConstPtr<UserConstPtr<c_char>>) -> LinuxResult<Vec<String>> {
-        let mut strings = Vec::new();
-        let mut offset = 0;
-        if ptr.is_null() {
-            return Ok(strings);
+    ///
+    /// The strings are charged against the `exec` limits of
+    /// [`limits`](UserSpaceRaw::limits), failing with `E2BIG` beyond them.
+    #[cfg(feature = "alloc")]
+    fn read_str_array(&self, ptr: UserConstPtr<UserConstPtr<c_char>>) -> UserResult<Vec<String>> {
+        let mut charge = AllocCharge::new(self);
+        capture_str_array(
+            self,
+            ptr,
+            &mut ExecBudget::new(self.limits().exec),
+            &mut charge,
+        )
+    }
+
+    /// Like [`read_str_array`](Self::read_str_array), but append the strings
+    /// to `buf` and return the range of each instead of allocating per string
+    ///
+    /// Each string is followed by a NUL in `buf`; the bytes are not required
+    /// to be UTF-8. `buf` is only appended to, so ranges from earlier calls
+    /// stay valid, and it is left unchanged on failure. Strings are charged
+    /// against `limits` as by
+    /// [`capture_exec_args`](Self::capture_exec_args).
+    #[cfg(feature = "alloc")]
+    fn read_str_array_into(
+        &self,
+        ptr: UserConstPtr<UserConstPtr<c_char>>,
+        buf: &mut Vec<u8>,
+        limits: ExecLimits,
+    ) -> UserResult<Vec<Range<usize>>> {
+        let mut charge = AllocCharge::new(self);
+        capture_str_array_into(self, ptr, buf, &mut ExecBudget::new(limits), &mut charge)
+    }
+
+    /// Capture the `argv` and `envp` of an `execve` under one shared budget
+    ///
+    /// Strings, terminators and pointers of both arrays are charged against
+    /// `limits`, and any excess fails with `E2BIG` before the call commits to
+    /// anything. Null arrays are treated as empty. The copies stay charged
+    /// through [`charge_kernel_alloc`](UserSpaceRaw::charge_kernel_alloc) until the
+    /// caller uncharges [`ExecArgs::charged`].
+    #[cfg(feature = "alloc")]
+    fn capture_exec_args(
+        &self,
+        argv: UserConstPtr<UserConstPtr<c_char>>,
+        envp: UserConstPtr<UserConstPtr<c_char>>,
+        limits: ExecLimits,
+    ) -> UserResult<ExecArgs> {
+        let mut budget = ExecBudget::new(limits);
+        let mut charge = AllocCharge::new(self);
+        let argv = capture_str_array(self, argv, &mut budget, &mut charge)?;
+        let envp = capture_str_array(self, envp, &mut budget, &mut charge)?;
+        Ok(ExecArgs {
+            argv,
+            envp,
+            charged: charge.keep(),
+        })
+    }
+
+    /// Like [`capture_exec_args`](Self::capture_exec_args), but capture every
+    /// string into one buffer, keeping [`ExecArgsBuf::charged`] charged
+    #[cfg(feature = "alloc")]
+    fn capture_exec_args_into(
+        &self,
+        argv: UserConstPtr<UserConstPtr<c_char>>,
+        envp: UserConstPtr<UserConstPtr<c_char>>,
+        limits: ExecLimits,
+    ) -> UserResult<ExecArgsBuf> {
+        let mut budget = ExecBudget::new(limits);
+        let mut charge = AllocCharge::new(self);
+        let mut bytes = Vec::new();
+        let argv = capture_str_array_into(self, argv, &mut bytes, &mut budget, &mut charge)?;
+        let envp = capture_str_array_into(self, envp, &mut bytes, &mut budget, &mut charge)?;
+        Ok(ExecArgsBuf {
+            bytes,
+            argv,
+            envp,
+            charged: charge.keep(),
+        })
+    }
+
+    /// Read an iovec table of `count` segments and validate every segment
+    /// for `access_flags` in one batch
+    ///
+    /// Fails with `EINVAL` for more than the `max_iov` of
+    /// [`limits`](UserSpaceRaw::limits) segments or a total length above
+    /// `isize::MAX`.
+    #[cfg(feature = "alloc")]
+    fn import_iovec(
+        &self,
+        ptr: UserConstPtr<IoVec>,
+        count: usize,
+        access_flags: Access,
+    ) -> UserResult<Vec<IoVec>> {
+        if count > self.limits().max_iov {
+            return Err(Error::EINVAL);
         }
+        let mut charge = AllocCharge::new(self);
+        let bytes = count
+            .checked_mul(size_of::<IoVec>() + size_of::<(VirtAddr, usize, Access)>())
+            .ok_or(Error::EINVAL)?;
+        charge.charge(bytes)?;
+        let mut iovs = vec![IoVec::default(); count];
+        self.read_slice_to(ptr, &mut iovs)?;
 
-        loop {
-            let str_ptr = self.read(ptr.offset(offset))?;
-            if str_ptr.is_null() {
-                break;
-            }
-            strings.push(self.read_str(str_ptr)?.to_string());
-            offset += 1;
+        let mut total = 0usize;
+        let mut regions = Vec::with_capacity(count);
+        for iov in &iovs {
+            total = total
+                .checked_add(iov.iov_len)
+                .filter(|&t| t <= isize::MAX as usize)
+                .ok_or(Error::EINVAL)?;
+            regions.push((VirtAddr::from(iov.iov_base), iov.iov_len, access_flags));
         }
+        check_region_batch(self, &regions)?;
+        Ok(iovs)
+    }
 
-        Ok(strings)
+    /// Validate and populate the byte range `range` for `access_flags`,
+    /// returning a token for cheap accesses inside it
+    fn validate(
+        &self,
+        range: VirtAddrRange,
+        access_flags: Access,
+    ) -> UserResult<ValidatedRegion<'_, Self>> {
+        check_region(
+            self,
+            range.start,
+            slice_layout::<u8>(range.size())?,
+            access_flags,
+        )
+        .map_err(Into::into)
     }
-}
 
-/// Validate memory region alignment and accessibility
-pub fn check_region<A: UserSpaceAccess>(
-    uspace: &A,
-    start: VirtAddr,
-    layout: Layout,
-    access_flags: MappingFlags,
-) -> LinuxResult<()> {
-    let align = layout.align();
-    if start.as_usize() & (align - 1) != 0 {
-        return Err(LinuxError::EFAULT);
+    /// Read a value inside a validated region
+    fn read_in<T: Copy>(
+        &self,
+        region: &ValidatedRegion<'_, Self>,
+        ptr: UserConstPtr<T>,
+    ) -> UserResult<T> {
+        region.check(self, ptr.address(), Layout::new::<T>(), Access::READ)?;
+        let range = VirtAddrRange::from_start_size(ptr.address(), size_of::<T>());
+        let mut val = MaybeUninit::<T>::uninit();
+        copy_in_chunked(self, range, val.as_mut_ptr().cast()).map_err(|e| e.error)?;
+        Ok(unsafe { val.assume_init() })
     }
 
-    let range =
-        VirtAddrRange::try_from_start_size(start, layout.size()).ok_or(LinuxError::EFAULT)?;
-    uspace.check_region_access(range, access_flags)?;
-    uspace.populate_region(range, access_flags)?;
-    Ok(())
-}
+    /// Write a value inside a validated region
+    fn write_in<T>(
+        &self,
+        region: &ValidatedRegion<'_, Self>,
+        ptr: UserPtr<T>,
+        val: T,
+    ) -> UserResult<()> {
+        region.check(
+            self,
+            ptr.address(),
+            Layout::new::<T>(),
+            Access::READ | Access::WRITE,
+        )?;
+        let range = VirtAddrRange::from_start_size(ptr.address(), size_of::<T>());
+        let val = ManuallyDrop::new(val);
+        copy_out_chunked(self, range, (&*val as *const T).cast()).map_err(|e| e.error)
+    }
 
-/// Find the length of a null-terminated array in user space
-pub fn check_null_terminated<T: PartialEq + Default, A: UserSpaceAccess>(
-    uspace: &A,
-    start: VirtAddr,
-    access_flags: MappingFlags,
-) -> LinuxResult<usize> {
-    let align = Layout::new::<T>().align();
-    if start.as_usize() & (align - 1) != 0 {
-        return Err(LinuxError::EFAULT);
+    /// Borrow a slice inside a validated region for as long as `self`
+    fn slice_in<'a, T>(
+        &'a self,
+        region: &ValidatedRegion<'_, Self>,
+        ptr: UserConstPtr<T>,
+        len: usize,
+    ) -> UserResult<UserSliceRef<'a, T>> {
+        region.check(self, ptr.address(), slice_layout::<T>(len)?, Access::READ)?;
+        assert_direct_map(self);
+        Ok(UserSliceRef::new(unsafe {
+            user_slice(ptr.address().as_mut_ptr_of::<T>(), len)
+        }))
     }
 
-    let zero = T::default();
+    /// Check that `range` is executable user memory
+    ///
+    /// Only checks [`check_region_access`](UserSpaceRaw::check_region_access) for
+    /// `EXECUTE` and never populates: the kernel does not read code it
+    /// validates this way. Ranges leaving
+    /// [`user_addr_range`](UserSpaceRaw::user_addr_range) fail with `EFAULT`.
+    fn check_executable(&self, range: VirtAddrRange) -> UserResult<()> {
+        let range = VirtAddrRange::from_start_size(self.untag_addr(range.start), range.size());
+        if !effective_user_range(self).contains_range(range) {
+            return Err(Error::EFAULT);
+        }
+        self.check_region_access(range, check_flags(Access::EXECUTE))
+    }
 
-    let start_ptr = start.as_ptr_of::<T>();
+    /// Whether `range` is user memory accessible with `access_flags`
+    ///
+    /// Only asks [`check_region_access`](UserSpaceRaw::check_region_access): nothing
+    /// is populated or touched. An empty range is always accessible.
+    fn access_ok(&self, range: VirtAddrRange, access_flags: Access) -> bool {
+        if range.is_empty() {
+            return true;
+        }
+        let range = VirtAddrRange::from_start_size(self.untag_addr(range.start), range.size());
+        effective_user_range(self).contains_range(range)
+            && self
+                .check_region_access(range, check_flags(access_flags))
+                .is_ok()
+    }
 
-    access_user_memory(|| {
-        let mut len = 0;
-        let mut page = start.align_down_4k();
-        loop {
-            let ptr = unsafe { start_ptr.add(len) };
-            while ptr as usize >= page.as_ptr() as usize {
-                uspace.check_region_access(
-                    VirtAddrRange::from_start_size(page, PAGE_SIZE_4K),
-                    access_flags,
-                )?;
-                page += PAGE_SIZE_4K;
+    /// First address of `range` not accessible with `access_flags`, or `None`
+    /// if all of it is
+    ///
+    /// Probes one page at a time with
+    /// [`check_region_access`](UserSpaceRaw::check_region_access) and never populates.
+    fn first_invalid(&self, range: VirtAddrRange, access_flags: Access) -> Option<VirtAddr> {
+        let user_range = effective_user_range(self);
+        let page_size = self.page_size();
+        let mut addr = range.start;
+        while addr < range.end {
+            let end = addr
+                .align_down(page_size)
+                .as_usize()
+                .checked_add(page_size)
+                .map_or(range.end, |end| VirtAddr::from(end).min(range.end));
+            let chunk = VirtAddrRange::new(addr, end);
+            if !user_range.contains_range(chunk)
+                || self
+                    .check_region_access(chunk, check_flags(access_flags))
+                    .is_err()
+            {
+                return Some(addr);
             }
+            addr = end;
+        }
+        None
+    }
 
-            if unsafe { ptr.read_volatile() } == zero {
-                break;
-            }
-            len += 1;
+    /// Fill `out` with one residency byte per page of `range`, as `mincore`
+    /// does
+    ///
+    /// `range.start` must be page aligned (`EINVAL`), and every page must be
+    /// mapped (`ENOMEM`). Bit 0 of each byte is set for pages
+    /// [`check_region_resident`](UserSpaceRaw::check_region_resident) reports as
+    /// resident.
+    #[cfg(feature = "alloc")]
+    fn mincore_into(&self, range: VirtAddrRange, out: UserPtr<u8>) -> UserResult<()> {
+        let page_size = self.page_size();
+        if !range.start.is_aligned(page_size) {
+            return Err(Error::EINVAL);
         }
-        Ok(len)
-    })
-}
+        let count = (range.end.as_usize() - range.start.as_usize()).div_ceil(page_size);
+        let mut charge = AllocCharge::new(self);
+        charge.charge(count * (size_of::<VirtAddr>() + 1))?;
+        let mut mapped = true;
+        let mut pages = Vec::with_capacity(count);
+        self.query_region(range, &mut |page, flags| {
+            mapped &= flags.is_some();
+            pages.push(page);
+        });
+        if !mapped {
+            return Err(Error::ENOMEM);
+        }
+        let mut vec = Vec::with_capacity(pages.len());
+        for page in pages {
+            let page_range = VirtAddrRange::from_start_size(page, page_size);
+            let resident = self
+                .check_region_resident(page_range, check_flags(Access::empty()))
+                .map_err(|_| Error::ENOMEM)?;
+            vec.push(resident as u8);
+        }
+        self.write_slice(out, &vec)
+    }
 
-#[macro_export]
+    /// Start a validation session caching page checks, typically one per
+    /// syscall, see [`ValidationSession`] for when it must be invalidated
+    #[cfg(feature = "alloc")]
+    fn session(&self) -> ValidationSession<'_, Self> {
+        ValidationSession::new(self)
+    }
+
+    /// Start an all-or-nothing copy of several user buffers
+    #[cfg(feature = "alloc")]
+    fn copy_in_transaction<'b>(&self) -> CopyInTransaction<'_, 'b, Self> {
+        CopyInTransaction::new(self)
+    }
+
+    /// Read an extensible struct of `size` bytes into `dst`, like Linux's
+    /// `copy_struct_from_user`
+    ///
+    /// A shorter user struct is zero-extended. A longer one is accepted only
+    /// if every byte beyond `dst` is zero, and fails with `E2BIG` otherwise.
+    fn copy_struct_from_user(
+        &self,
+        dst: &mut [u8],
+        src: UserConstPtr<u8>,
+        size: usize,
+    ) -> UserResult<()> {
+        let ksize = dst.len();
+        if size > ksize {
+            let mut tail = src.offset(ksize);
+            let mut left = size - ksize;
+            let mut buf = [0u8; 256];
+            while left != 0 {
+                let chunk = &mut buf[..left.min(256)];
+                self.read_slice_to(tail, chunk)?;
+                if chunk.iter().any(|&b| b != 0) {
+                    return Err(Error::E2BIG);
+                }
+                tail = tail.offset(chunk.len());
+                left -= chunk.len();
+            }
+        }
+        let len = size.min(ksize);
+        self.read_slice_to(src, &mut dst[..len])?;
+        dst[len..].fill(0);
+        Ok(())
+    }
+
+    /// Read the machine word at `addr`, which need not be aligned
+    ///
+    /// Acts on the address space described by `self`, not necessarily the
+    /// current task's, which is what `PTRACE_PEEKDATA` needs. The copy goes
+    /// through [`raw_copy_from_user`](UserSpaceRaw::raw_copy_from_user),
+    /// whose default needs that address space reachable from the active page
+    /// table.
+    fn peek_word(&self, addr: usize) -> UserResult<usize> {
+        self.read(UserConstPtr::<[u8; size_of::<usize>()]>::from(addr))
+            .map(usize::from_ne_bytes)
+    }
+
+    /// Write the machine word at `addr`, which need not be aligned
+    ///
+    /// See [`peek_word`](Self::peek_word) for which address space is used.
+    fn poke_word(&self, addr: usize, val: usize) -> UserResult<()> {
+        self.write(
+            UserPtr::<[u8; size_of::<usize>()]>::from(addr),
+            val.to_ne_bytes(),
+        )
+    }
+
+    /// Read the futex word at `ptr` without blocking, as the futex fast path
+    /// does under a hash-bucket lock
+    ///
+    /// Fails with `EINVAL` if `ptr` is misaligned, and with `EAGAIN` if the
+    /// page cannot be populated without blocking or goes away during the
+    /// read; the caller then drops its lock, faults the page in with a plain
+    /// [`read`](Self::read) and retries. Other errors are final.
+    fn read_futex_value(&self, ptr: UserConstPtr<u32>) -> UserResult<u32> {
+        let addr = futex_word(self, ptr.address(), Access::READ)?;
+        try_access_user_nofault(user_range_of(addr, 1), Access::READ, || unsafe {
+            addr.read_volatile()
+        })
+        .map_err(|_| Error::EAGAIN)
+    }
+
+    /// Atomically replace the futex word at `ptr` with `new` if it holds
+    /// `old`, returning the value found
+    ///
+    /// Never blocks and fails like [`read_futex_value`](Self::read_futex_value).
+    fn futex_cmpxchg(&self, ptr: UserPtr<u32>, old: u32, new: u32) -> UserResult<u32> {
+        let flags = Access::READ | Access::WRITE;
+        let addr = futex_word(self, ptr.address(), flags)?;
+        try_access_user_nofault(user_range_of(addr, 1), flags, || unsafe {
+            AtomicU32::from_ptr(addr)
+                .compare_exchange(old, new, Ordering::SeqCst, Ordering::SeqCst)
+                .unwrap_or_else(|found| found)
+        })
+        .map_err(|_| Error::EAGAIN)
+    }
+
+    /// Write `val` at `ptr`, which need not be aligned, even into a mapping
+    /// that is not writable, as `PTRACE_POKETEXT` does
+    ///
+    /// Goes through [`force_write_region`](UserSpaceRaw::force_write_region),
+    /// then [`sync_after_write`](UserSpaceRaw::sync_after_write) for the
+    /// instruction cache.
+    /// See [`peek_word`](Self::peek_word) for which address space is used.
+    fn write_forced<T: UserCopy>(&self, ptr: UserPtr<T>, val: T) -> UserResult<()> {
+        self.write_slice_forced(ptr, &[val])
+    }
+
+    /// Like [`write_forced`](Self::write_forced), for a slice
+    fn write_slice_forced<T: UserCopy>(&self, ptr: UserPtr<T>, slice: &[T]) -> UserResult<()> {
+        let data =
+            unsafe { core::slice::from_raw_parts(slice.as_ptr().cast::<u8>(), size_of_val(slice)) };
+        if data.is_empty() {
+            return Ok(());
+        }
+        let range = VirtAddrRange::try_from_start_size(self.untag_addr(ptr.address()), data.len())
+            .filter(|range| effective_user_range(self).contains_range(*range))
+            .ok_or(Error::EFAULT)?;
+        self.check_region_access(range, check_flags(Access::empty()))?;
+        self.force_write_region(range.start, data)?;
+        self.sync_after_write(range, SyncKind::Instruction);
+        Ok(())
+    }
+
+    /// Request cache maintenance `kind` for user `range`, e.g. cleaning the
+    /// data cache after filling a buffer that is handed to a device
+    ///
+    /// Fails with `EFAULT` if `range` leaves
+    /// [`user_addr_range`](UserSpaceRaw::user_addr_range).
+    fn flush_user_range(&self, range: VirtAddrRange, kind: SyncKind) -> UserResult<()> {
+        let untagged = VirtAddrRange::from_start_size(self.untag_addr(range.start), range.size());
+        if !effective_user_range(self).contains_range(untagged) {
+            return Err(Error::EFAULT);
+        }
+        if !untagged.is_empty() {
+            self.sync_after_write(untagged, kind);
+        }
+        Ok(())
+    }
+
+    /// Check `range` for `flags` and pin its pages, like
+    /// `pin_user_pages`, so its contents can be accessed until the returned
+    /// region is dropped
+    ///
+    /// Fails with `EOPNOTSUPP` on backends without
+    /// [`pin_pages`](UserSpaceRaw::pin_pages). An empty range pins nothing.
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn pin_region(
+        &self,
+        range: VirtAddrRange,
+        flags: Access,
+    ) -> UserResult<PinnedRegion<'_, Self>> {
+        let layout = Layout::from_size_align(range.size(), 1).map_err(|_| Error::EFAULT)?;
+        check_region(self, range.start, layout, flags)?;
+        assert_direct_map(self);
+        if !range.is_empty() {
+            let untagged =
+                VirtAddrRange::from_start_size(self.untag_addr(range.start), range.size());
+            self.pin_pages(untagged, flags)?;
+        }
+        Ok(PinnedRegion::new(self, range, flags))
+    }
+
+    /// Transfer through a buffer described by a user `iovec`, as
+    /// `PTRACE_GETREGSET` and `PTRACE_SETREGSET` do
+    ///
+    /// The iovec is read once and its length clamped to `kernel_len`. `f` gets
+    /// the destination, already validated for `access_flags`, and returns the
+    /// number of bytes it transferred, which is written back to `iov_len`.
+    fn with_regset_iovec(
+        &self,
+        iov: UserPtr<IoVec>,
+        kernel_len: usize,
+        access_flags: Access,
+        f: impl FnOnce(UserPtr<u8>, usize) -> UserResult<usize>,
+    ) -> UserResult<()> {
+        let vec = self.read(iov)?;
+        let len = vec.iov_len.min(kernel_len);
+        let base = UserPtr::<u8>::from(vec.iov_base);
+        if len != 0 {
+            check_region(self, base.address(), slice_layout::<u8>(len)?, access_flags)?;
+        }
+        let done = f(base, len)?.min(len);
+        self.write(
+            iov,
+            IoVec {
+                iov_len: done,
+                ..vec
+            },
+        )
+    }
+
+    /// Read a `timespec`, rejecting negative fields and `tv_nsec` out of range
+    #[cfg(feature = "struct-helpers")]
+    fn read_timespec(&self, ptr: UserConstPtr<TimeSpec>) -> UserResult<TimeSpec> {
+        let ts = self.read(ptr)?;
+        ts.validate()?;
+        Ok(ts)
+    }
+
+    /// Read a `timeval`, rejecting negative fields and `tv_usec` out of range
+    #[cfg(feature = "struct-helpers")]
+    fn read_timeval(&self, ptr: UserConstPtr<TimeVal>) -> UserResult<TimeVal> {
+        let tv = self.read(ptr)?;
+        tv.validate()?;
+        Ok(tv)
+    }
+
+    /// Read the `[atime, mtime]` pair of `utimensat`, honoring `UTIME_NOW`
+    /// and `UTIME_OMIT`
+    #[cfg(feature = "struct-helpers")]
+    fn read_utimens_pair(&self, ptr: UserConstPtr<TimeSpec>) -> UserResult<[UtimeSpec; 2]> {
+        let [atime, mtime] = self.read(ptr.cast::<[TimeSpec; 2]>())?;
+        Ok([atime.try_into()?, mtime.try_into()?])
+    }
+
+    /// Like [`read_utimens_pair`](Self::read_utimens_pair), but a null pointer
+    /// means both timestamps are set to the current time
+    #[cfg(feature = "struct-helpers")]
+    fn read_utimens_pair_or_now(&self, ptr: UserConstPtr<TimeSpec>) -> UserResult<[UtimeSpec; 2]> {
+        if ptr.is_null() {
+            return Ok([UtimeSpec::Now; 2]);
+        }
+        self.read_utimens_pair(ptr)
+    }
+
+    /// Write a `timespec` to user space
+    #[cfg(feature = "struct-helpers")]
+    fn write_timespec(&self, ptr: UserPtr<TimeSpec>, ts: TimeSpec) -> UserResult<()> {
+        self.write(ptr, ts)
+    }
+
+    /// Write a `timeval` to user space
+    #[cfg(feature = "struct-helpers")]
+    fn write_timeval(&self, ptr: UserPtr<TimeVal>, tv: TimeVal) -> UserResult<()> {
+        self.write(ptr, tv)
+    }
+
+    /// Read an `itimerspec`, validating both the interval and the value
+    #[cfg(feature = "struct-helpers")]
+    fn read_itimerspec(&self, ptr: UserConstPtr<ITimerSpec>) -> UserResult<ITimerSpec> {
+        let its = self.read(ptr)?;
+        its.validate()?;
+        Ok(its)
+    }
+
+    /// Write an `itimerspec` to user space
+    ///
+    /// The old value of `timer_settime` may be left out with a null pointer,
+    /// pass it as a [`UserInOutPtr`] and use
+    /// [`write_back`](UserInOutPtr::write_back) instead.
+    #[cfg(feature = "struct-helpers")]
+    fn write_itimerspec(&self, ptr: UserPtr<ITimerSpec>, its: ITimerSpec) -> UserResult<()> {
+        self.write(ptr, its)
+    }
+
+    /// Read an `itimerval`, validating both the interval and the value
+    #[cfg(feature = "struct-helpers")]
+    fn read_itimerval(&self, ptr: UserConstPtr<ITimerVal>) -> UserResult<ITimerVal> {
+        let itv = self.read(ptr)?;
+        itv.validate()?;
+        Ok(itv)
+    }
+
+    /// Write an `itimerval` to user space
+    ///
+    /// Like [`write_itimerspec`](Self::write_itimerspec), the optional old
+    /// value of `setitimer` goes through [`UserInOutPtr`].
+    #[cfg(feature = "struct-helpers")]
+    fn write_itimerval(&self, ptr: UserPtr<ITimerVal>, itv: ITimerVal) -> UserResult<()> {
+        self.write(ptr, itv)
+    }
+
+    /// Read a signal set, failing with `EINVAL` unless `sigsetsize` matches the
+    /// kernel's
+    #[cfg(feature = "struct-helpers")]
+    fn read_sigset(&self, ptr: UserConstPtr<u8>, sigsetsize: usize) -> UserResult<u64> {
+        check_sigsetsize(sigsetsize)?;
+        self.read(ptr.cast::<[u8; SIGSET_SIZE]>())
+            .map(u64::from_ne_bytes)
+    }
+
+    /// Like [`read_sigset`](Self::read_sigset), but a null pointer yields
+    /// `None`
+    ///
+    /// `sigsetsize` is checked even for a null pointer, as Linux does.
+    #[cfg(feature = "struct-helpers")]
+    fn read_sigset_opt(&self, ptr: UserConstPtr<u8>, sigsetsize: usize) -> UserResult<Option<u64>> {
+        check_sigsetsize(sigsetsize)?;
+        if ptr.is_null() {
+            return Ok(None);
+        }
+        self.read_sigset(ptr, sigsetsize).map(Some)
+    }
+
+    /// Read a signal set to be blocked, clearing `SIGKILL` and `SIGSTOP`
+    #[cfg(feature = "struct-helpers")]
+    fn read_sigmask(&self, ptr: UserConstPtr<u8>, sigsetsize: usize) -> UserResult<u64> {
+        self.read_sigset(ptr, sigsetsize)
+            .map(|set| set & !SIG_UNBLOCKABLE)
+    }
+
+    /// Write a signal set, failing with `EINVAL` unless `sigsetsize` matches
+    /// the kernel's
+    #[cfg(feature = "struct-helpers")]
+    fn write_sigset(&self, ptr: UserPtr<u8>, sigsetsize: usize, set: u64) -> UserResult<()> {
+        check_sigsetsize(sigsetsize)?;
+        self.write(ptr.cast::<[u8; SIGSET_SIZE]>(), set.to_ne_bytes())
+    }
+
+    /// Like [`write_sigset`](Self::write_sigset), but does nothing for a null
+    /// pointer
+    #[cfg(feature = "struct-helpers")]
+    fn write_sigset_opt(&self, ptr: UserPtr<u8>, sigsetsize: usize, set: u64) -> UserResult<()> {
+        check_sigsetsize(sigsetsize)?;
+        if ptr.is_null() {
+            return Ok(());
+        }
+        self.write_sigset(ptr, sigsetsize, set)
+    }
+
+    /// Read a `struct sigaction`, rejecting unsupported flags and normalizing
+    /// the handler, restorer and mask
+    ///
+    /// A null `act` (query only) is expressed with
+    /// [`nullable!`](crate::nullable) at the call site.
+    #[cfg(feature = "struct-helpers")]
+    fn read_sigaction(&self, ptr: UserConstPtr<RawSigAction>) -> UserResult<SigAction> {
+        self.read(ptr)?.try_into()
+    }
+
+    /// Write a `struct sigaction` to user space
+    #[cfg(feature = "struct-helpers")]
+    fn write_sigaction(&self, ptr: UserPtr<RawSigAction>, act: SigAction) -> UserResult<()> {
+        self.write(ptr, act.into())
+    }
+
+    /// Write back the old action, doing nothing for a null `oldact`
+    #[cfg(feature = "struct-helpers")]
+    fn write_sigaction_opt(&self, ptr: UserPtr<RawSigAction>, act: SigAction) -> UserResult<()> {
+        if ptr.is_null() {
+            return Ok(());
+        }
+        self.write_sigaction(ptr, act)
+    }
+
+    /// Read a `stack_t` for `sigaltstack`, validating its mode and size
+    #[cfg(feature = "struct-helpers")]
+    fn read_stack_t(&self, ptr: UserConstPtr<RawStack>) -> UserResult<SigStack> {
+        self.read(ptr)?.try_into()
+    }
+
+    /// Write a `stack_t`, `on_stack` tells whether the thread currently runs
+    /// on the alternate stack
+    #[cfg(feature = "struct-helpers")]
+    fn write_stack_t(
+        &self,
+        ptr: UserPtr<RawStack>,
+        stack: SigStack,
+        on_stack: bool,
+    ) -> UserResult<()> {
+        self.write(ptr, stack.to_raw(on_stack))
+    }
+
+    /// Write back the old `stack_t`, doing nothing for a null `old_ss`
+    #[cfg(feature = "struct-helpers")]
+    fn write_stack_t_opt(
+        &self,
+        ptr: UserPtr<RawStack>,
+        stack: SigStack,
+        on_stack: bool,
+    ) -> UserResult<()> {
+        if ptr.is_null() {
+            return Ok(());
+        }
+        self.write_stack_t(ptr, stack, on_stack)
+    }
+
+    /// Read the first `nfds` bits of an `fd_set`, touching only
+    /// `ceil(nfds / 64)` words; a null pointer yields an empty set
+    #[cfg(feature = "struct-helpers")]
+    fn read_fd_set(&self, ptr: UserConstPtr<u64>, nfds: usize) -> UserResult<FdSetBuf> {
+        let mut set = FdSetBuf::new(nfds)?;
+        if !ptr.is_null() {
+            self.read_slice_to(ptr, set.words_mut())?;
+            set.mask_tail();
+        }
+        Ok(set)
+    }
+
+    /// Write an `fd_set` back, touching only the words covered by its `nfds`;
+    /// a null pointer is skipped
+    #[cfg(feature = "struct-helpers")]
+    fn write_fd_set(&self, ptr: UserPtr<u64>, set: &FdSetBuf) -> UserResult<()> {
+        if ptr.is_null() {
+            return Ok(());
+        }
+        self.write_slice(ptr, set.words())
+    }
+
+    /// Read a `sched_setaffinity` mask of `size` bytes into a mask of
+    /// `max_cpus` bits
+    ///
+    /// A shorter user mask is zero-extended and a longer one truncated, the
+    /// bytes beyond the kernel mask are never read.
+    #[cfg(feature = "struct-helpers")]
+    fn read_cpu_set(
+        &self,
+        ptr: UserConstPtr<u8>,
+        size: usize,
+        max_cpus: usize,
+    ) -> UserResult<CpuMaskBuf> {
+        let mut mask = CpuMaskBuf::new(max_cpus);
+        let len = size.min(mask.kernel_size());
+        self.read_slice_to(ptr, &mut mask.as_bytes_mut()[..len])?;
+        mask.mask_tail();
+        Ok(mask)
+    }
+
+    /// Write a mask for `sched_getaffinity`, returning the number of bytes
+    /// written, i.e. the syscall's return value
+    ///
+    /// Fails with `EINVAL` if `size` does not cover every kernel CPU or is not
+    /// a multiple of `sizeof(long)`, as Linux does.
+    #[cfg(feature = "struct-helpers")]
+    fn write_cpu_set(&self, ptr: UserPtr<u8>, size: usize, mask: &CpuMaskBuf) -> UserResult<usize> {
+        check_cpu_set_out_size(size, mask.max_cpus())?;
+        let len = size.min(mask.kernel_size());
+        self.write_slice(ptr, &mask.as_bytes()[..len])?;
+        Ok(len)
+    }
+
+    /// Read a `struct rlimit64`, rejecting a soft limit above the hard limit
+    #[cfg(feature = "struct-helpers")]
+    fn read_rlimit64(&self, ptr: UserConstPtr<RLimit64>) -> UserResult<RLimit64> {
+        let rlim = self.read(ptr)?;
+        rlim.validate()?;
+        Ok(rlim)
+    }
+
+    /// Write a `struct rlimit64` to user space
+    #[cfg(feature = "struct-helpers")]
+    fn write_rlimit64(&self, ptr: UserPtr<RLimit64>, rlim: RLimit64) -> UserResult<()> {
+        self.write(ptr, rlim)
+    }
+
+    /// Write back the old limit, doing nothing for a null `old_limit`
+    #[cfg(feature = "struct-helpers")]
+    fn write_rlimit64_opt(&self, ptr: UserPtr<RLimit64>, rlim: RLimit64) -> UserResult<()> {
+        if ptr.is_null() {
+            return Ok(());
+        }
+        self.write_rlimit64(ptr, rlim)
+    }
+
+    /// Read a 32-bit `struct rlimit`, widening it and validating as
+    /// [`read_rlimit64`](Self::read_rlimit64) does
+    #[cfg(feature = "compat")]
+    fn read_compat_rlimit(&self, ptr: UserConstPtr<CompatRLimit>) -> UserResult<RLimit64> {
+        let rlim = RLimit64::from(self.read(ptr)?);
+        rlim.validate()?;
+        Ok(rlim)
+    }
+
+    /// Write a 32-bit `struct rlimit`, clamping values that do not fit
+    #[cfg(feature = "compat")]
+    fn write_compat_rlimit(&self, ptr: UserPtr<CompatRLimit>, rlim: RLimit64) -> UserResult<()> {
+        self.write(ptr, rlim.into())
+    }
+
+    /// Read a versioned `struct sched_attr` for `sched_setattr`
+    ///
+    /// The struct's own `size` (zero meaning [`SCHED_ATTR_SIZE_VER0`]) must
+    /// equal `size_arg`. Sizes below `SCHED_ATTR_SIZE_VER0` or above a page,
+    /// and non-zero bytes beyond the kernel layout fail with `E2BIG`; unknown
+    /// flags fail with `EINVAL`.
+    #[cfg(feature = "struct-helpers")]
+    fn read_sched_attr(&self, ptr: UserConstPtr<u8>, size_arg: u32) -> UserResult<SchedAttr> {
+        let size = match self.read(ptr.cast::<[u8; 4]>()).map(u32::from_ne_bytes)? {
+            0 => SCHED_ATTR_SIZE_VER0,
+            size => size,
+        };
+        if size != size_arg {
+            return Err(Error::EINVAL);
+        }
+        if size < SCHED_ATTR_SIZE_VER0 || size as usize > self.page_size() {
+            return Err(Error::E2BIG);
+        }
+        let mut attr = SchedAttr::default();
+        self.copy_struct_from_user(attr.as_bytes_mut(), ptr, size as usize)?;
+        attr.size = size;
+        attr.validate(size)?;
+        Ok(attr)
+    }
+
+    /// Write a `struct sched_attr` for `sched_getattr`, truncated to the
+    /// user's `size`, returning the size recorded in the struct
+    ///
+    /// Sizes below [`SCHED_ATTR_SIZE_VER0`] or above a page fail with
+    /// `EINVAL`.
+    #[cfg(feature = "struct-helpers")]
+    fn write_sched_attr(&self, ptr: UserPtr<u8>, size: u32, attr: &SchedAttr) -> UserResult<u32> {
+        if size < SCHED_ATTR_SIZE_VER0 || size as usize > self.page_size() {
+            return Err(Error::EINVAL);
+        }
+        let size = size.min(SchedAttr::KERNEL_SIZE);
+        let attr = SchedAttr { size, ..*attr };
+        self.write_slice(ptr, &attr.as_bytes()[..size as usize])?;
+        Ok(size)
+    }
+
+    /// Read a versioned `struct clone_args` for `clone3`, including the
+    /// nested `set_tid` array
+    ///
+    /// Sizes below [`CLONE_ARGS_SIZE_VER0`] fail with `EINVAL`, sizes above a
+    /// page or non-zero bytes beyond the kernel layout with `E2BIG`. The
+    /// `set_tid` pointer is validated like any other user pointer and its
+    /// contents copied, so the result holds no reference to user memory.
+    #[cfg(feature = "struct-helpers")]
+    fn read_clone_args(&self, ptr: UserConstPtr<u8>, size: usize) -> UserResult<CloneArgs> {
+        if size < CLONE_ARGS_SIZE_VER0 {
+            return Err(Error::EINVAL);
+        }
+        if size > self.page_size() {
+            return Err(Error::E2BIG);
+        }
+        let mut raw = RawCloneArgs::default();
+        self.copy_struct_from_user(raw.as_bytes_mut(), ptr, size)?;
+        raw.validate(size)?;
+
+        let mut charge = AllocCharge::new(self);
+        charge.charge(raw.set_tid_size as usize * size_of::<i32>())?;
+        let mut args = CloneArgs::from_raw(&raw);
+        if !args.set_tid.is_empty() {
+            let set_tid = UserConstPtr::<i32>::from(raw.set_tid as usize);
+            self.read_slice_to(set_tid, &mut args.set_tid)?;
+        }
+        Ok(args)
+    }
+
+    /// Read a 32-bit `struct compat_sigaction`
+    #[cfg(feature = "compat")]
+    fn read_compat_sigaction(&self, ptr: UserConstPtr<CompatSigAction>) -> UserResult<SigAction> {
+        self.read(ptr)?.try_into()
+    }
+
+    /// Write a 32-bit `struct compat_sigaction` to user space
+    #[cfg(feature = "compat")]
+    fn write_compat_sigaction(
+        &self,
+        ptr: UserPtr<CompatSigAction>,
+        act: SigAction,
+    ) -> UserResult<()> {
+        self.write(ptr, act.try_into()?)
+    }
+
+    /// Write back the old 32-bit action, doing nothing for a null `oldact`
+    #[cfg(feature = "compat")]
+    fn write_compat_sigaction_opt(
+        &self,
+        ptr: UserPtr<CompatSigAction>,
+        act: SigAction,
+    ) -> UserResult<()> {
+        if ptr.is_null() {
+            return Ok(());
+        }
+        self.write_compat_sigaction(ptr, act)
+    }
+}
+
+impl<T: UserSpaceRaw + ?Sized> UserSpaceAccess for T {}
+
+/// Forward every [`UserSpaceRaw`] method through a pointer type
+macro_rules! forward_user_space_raw {
+    ($($ty:ty),*) => {$(
+        impl<A: UserSpaceRaw + ?Sized> UserSpaceRaw for $ty {
+            fn check_region_access(
+                &self,
+                range: VirtAddrRange,
+                access_flags: Access,
+            ) -> UserResult<()> {
+                (**self).check_region_access(range, access_flags)
+            }
+
+            fn populate_region(
+                &self,
+                range: VirtAddrRange,
+                access_flags: Access,
+            ) -> UserResult<()> {
+                (**self).populate_region(range, access_flags)
+            }
+
+            fn populate_region_nonblocking(
+                &self,
+                range: VirtAddrRange,
+                access_flags: Access,
+            ) -> UserResult<()> {
+                (**self).populate_region_nonblocking(range, access_flags)
+            }
+
+            fn check_region_access_detailed(
+                &self,
+                range: VirtAddrRange,
+                access_flags: Access,
+            ) -> AccessResult<()> {
+                (**self).check_region_access_detailed(range, access_flags)
+            }
+
+            fn populate_region_detailed(
+                &self,
+                range: VirtAddrRange,
+                access_flags: Access,
+            ) -> AccessResult<()> {
+                (**self).populate_region_detailed(range, access_flags)
+            }
+
+            fn ensure_region(&self, range: VirtAddrRange, access_flags: Access) -> AccessResult<()> {
+                (**self).ensure_region(range, access_flags)
+            }
+
+            fn prefault(
+                &self,
+                range: VirtAddrRange,
+                access_flags: Access,
+                hint: PrefaultHint,
+            ) -> UserResult<()> {
+                (**self).prefault(range, access_flags, hint)
+            }
+
+            fn check_region_resident(
+                &self,
+                range: VirtAddrRange,
+                access_flags: Access,
+            ) -> UserResult<bool> {
+                (**self).check_region_resident(range, access_flags)
+            }
+
+            fn check_regions(&self, regions: &[(VirtAddrRange, Access)]) -> UserResult<()> {
+                (**self).check_regions(regions)
+            }
+
+            fn check_region(
+                &self,
+                start: VirtAddr,
+                layout: Layout,
+                access_flags: Access,
+                hint: AccessHint,
+            ) -> AccessResult<()> {
+                (**self).check_region(start, layout, access_flags, hint)
+            }
+
+            fn scan_null_terminated(
+                &self,
+                start: VirtAddr,
+                layout: Layout,
+                access_flags: Access,
+                max: usize,
+                hint: AccessHint,
+            ) -> AccessResult<usize> {
+                (**self).scan_null_terminated(start, layout, access_flags, max, hint)
+            }
+
+            fn page_size(&self) -> usize {
+                (**self).page_size()
+            }
+
+            fn user_addr_range(&self) -> VirtAddrRange {
+                (**self).user_addr_range()
+            }
+
+            fn limits(&self) -> &Limits {
+                (**self).limits()
+            }
+
+            fn charge_kernel_alloc(&self, bytes: usize) -> UserResult<()> {
+                (**self).charge_kernel_alloc(bytes)
+            }
+
+            fn uncharge_kernel_alloc(&self, bytes: usize) {
+                (**self).uncharge_kernel_alloc(bytes)
+            }
+
+            fn clamp_rw_len(&self, len: usize) -> usize {
+                (**self).clamp_rw_len(len)
+            }
+
+            fn on_access_error(&self, error: &UserAccessError) {
+                (**self).on_access_error(error)
+            }
+
+            #[cfg(feature = "debug-double-fetch")]
+            fn record_user_read(&self, range: VirtAddrRange, location: &'static Location<'static>) {
+                (**self).record_user_read(range, location)
+            }
+
+            #[cfg(feature = "debug-double-fetch")]
+            fn on_double_fetch(&self, fetch: &DoubleFetch) {
+                (**self).on_double_fetch(fetch)
+            }
+
+            #[cfg(feature = "debug-double-fetch")]
+            fn expect_user_reread(&self, range: VirtAddrRange) {
+                (**self).expect_user_reread(range)
+            }
+
+            fn min_user_addr(&self) -> usize {
+                (**self).min_user_addr()
+            }
+
+            fn generation(&self) -> u64 {
+                (**self).generation()
+            }
+
+            fn space_id(&self) -> usize {
+                (**self).space_id()
+            }
+
+            fn query_region(
+                &self,
+                range: VirtAddrRange,
+                f: &mut dyn FnMut(VirtAddr, Option<Access>),
+            ) {
+                (**self).query_region(range, f)
+            }
+
+            fn should_interrupt(&self) -> Option<Error> {
+                (**self).should_interrupt()
+            }
+
+            fn relax(&self) {
+                (**self).relax()
+            }
+
+            fn memory_type(&self, range: VirtAddrRange) -> MemoryType {
+                (**self).memory_type(range)
+            }
+
+            unsafe fn raw_copy_from_user(
+                &self,
+                dst: *mut u8,
+                src: VirtAddr,
+                len: usize,
+            ) -> UserResult<()> {
+                unsafe { (**self).raw_copy_from_user(dst, src, len) }
+            }
+
+            unsafe fn raw_copy_to_user(
+                &self,
+                dst: VirtAddr,
+                src: *const u8,
+                len: usize,
+            ) -> UserResult<()> {
+                unsafe { (**self).raw_copy_to_user(dst, src, len) }
+            }
+
+            fn direct_map(&self) -> bool {
+                (**self).direct_map()
+            }
+
+            fn pin_pages(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+                (**self).pin_pages(range, access_flags)
+            }
+
+            fn unpin_pages(&self, range: VirtAddrRange) {
+                (**self).unpin_pages(range)
+            }
+
+            fn virt_to_phys(&self, vaddr: VirtAddr) -> Option<PhysAddr> {
+                (**self).virt_to_phys(vaddr)
+            }
+
+            fn untag_addr(&self, addr: VirtAddr) -> VirtAddr {
+                (**self).untag_addr(addr)
+            }
+
+            fn force_write_region(&self, addr: VirtAddr, data: &[u8]) -> UserResult<()> {
+                (**self).force_write_region(addr, data)
+            }
+
+            fn sync_after_write(&self, range: VirtAddrRange, kind: SyncKind) {
+                (**self).sync_after_write(range, kind)
+            }
+
+            fn check_pkey(&self, range: VirtAddrRange, access_flags: Access) -> UserResult<()> {
+                (**self).check_pkey(range, access_flags)
+            }
+
+            fn try_grow_region(
+                &self,
+                range: VirtAddrRange,
+                access_flags: Access,
+            ) -> UserResult<bool> {
+                (**self).try_grow_region(range, access_flags)
+            }
+        }
+    )*};
+}
+
+forward_user_space_raw!(&A);
+#[cfg(feature = "alloc")]
+forward_user_space_raw!(Box<A>, Rc<A>, Arc<A>);
+
+/// Validate `layout` at `addr` for `flags`, returning the byte range
+#[cfg_attr(feature = "track-caller", track_caller)]
+fn check_user_bytes<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    addr: VirtAddr,
+    layout: Layout,
+    flags: Access,
+) -> UserResult<VirtAddrRange> {
+    check_region(uspace, addr, layout, flags)?;
+    Ok(VirtAddrRange::try_from_start_size(addr, layout.size())
+        .unwrap_or(VirtAddrRange::new(addr, addr)))
+}
+
+/// Run `copy(range, offset)` over the `len` bytes at `addr` in windows of at
+/// most [`stream_window_pages`](Limits::stream_window_pages) pages, each
+/// validated for `flags` only once the copy reaches it
+fn stream_windows<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    addr: VirtAddr,
+    len: usize,
+    flags: Access,
+    mut copy: impl FnMut(VirtAddrRange, usize) -> Result<(), PartialCopy>,
+) -> Result<(), PartialCopy> {
+    let fail = |done, error| PartialCopy { done, error };
+    slice_layout::<u8>(len).map_err(|error| fail(0, error))?;
+    // A range wrapping past the top of the address space fails before any
+    // of it is copied
+    if len != 0 && VirtAddrRange::try_from_start_size(uspace.untag_addr(addr), len).is_none() {
+        return Err(fail(0, Error::EFAULT));
+    }
+    let page_size = uspace.page_size();
+    let window = uspace
+        .limits()
+        .stream_window_pages
+        .max(1)
+        .saturating_mul(page_size);
+    let mut done = 0;
+    while done < len {
+        if done > 0
+            && let Some(error) = uspace.should_interrupt()
+        {
+            return Err(fail(done, error));
+        }
+        let start = VirtAddr::from(addr.as_usize().wrapping_add(done));
+        let chunk = (window - (start.as_usize() & (page_size - 1))).min(len - done);
+        let layout = slice_layout::<u8>(chunk).map_err(|error| fail(done, error))?;
+        let range =
+            check_user_bytes(uspace, start, layout, flags).map_err(|error| fail(done, error))?;
+        copy(range, done).map_err(|e| fail(done + e.done, e.error))?;
+        done += chunk;
+    }
+    Ok(())
+}
+
+/// Copy the validated user `range` to kernel `dst` a page at a time through
+/// [`UserSpaceRaw::raw_copy_from_user`], or with volatile accesses for
+/// device memory
+fn copy_in_chunked<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    range: VirtAddrRange,
+    dst: *mut u8,
+) -> Result<(), PartialCopy> {
+    if range.is_empty() {
+        return Ok(());
+    }
+    let ty = uspace.memory_type(range);
+    copy_chunked(uspace, range, Access::READ, |off, len| unsafe {
+        let src = range.start + off;
+        if ty == MemoryType::Normal {
+            uspace
+                .raw_copy_from_user(dst.add(off), src, len)
+                .map_err(|_| 0)
+        } else {
+            copy_from_device(dst.add(off), src.as_ptr(), len);
+            Ok(())
+        }
+    })
+}
+
+/// Copy kernel `src` to the validated user `range`, like [`copy_in_chunked`]
+fn copy_out_chunked<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    range: VirtAddrRange,
+    src: *const u8,
+) -> Result<(), PartialCopy> {
+    if range.is_empty() {
+        return Ok(());
+    }
+    let ty = uspace.memory_type(range);
+    copy_chunked(
+        uspace,
+        range,
+        Access::READ | Access::WRITE,
+        |off, len| unsafe {
+            let dst = range.start + off;
+            if ty == MemoryType::Normal {
+                uspace
+                    .raw_copy_to_user(dst, src.add(off), len)
+                    .map_err(|_| 0)
+            } else {
+                copy_to_device(dst.as_mut_ptr(), src.add(off), len);
+                Ok(())
+            }
+        },
+    )
+}
+
+/// Page-stepping machinery of element scans such as
+/// [`UserSpaceRaw::scan_null_terminated`]
+///
+/// Walks the elements of `layout` from `start`, validating every page as
+/// `hint` says before `step` reads from it. `step` is given the tagged
+/// address of the next element, how many bytes from there lie in checked
+/// pages (at least one element), and how many more elements `max` allows,
+/// and returns `None` at the terminator, or else how many elements it
+/// passed over, at least one. Passing over more than `max` fails with
+/// [`TooLong`](AccessErrorKind::TooLong), and an error of `step` is
+/// reported as a fault. Zero-sized elements give 0.
+pub(crate) fn scan_elements<A: UserSpaceRaw + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    layout: Layout,
+    access_flags: Access,
+    max: usize,
+    hint: AccessHint,
+    mut step: impl FnMut(VirtAddr, usize, usize) -> UserResult<Option<usize>>,
+) -> AccessResult<usize> {
+    let size = layout.size();
+    // Every zero-sized value is its own terminator
+    if size == 0 {
+        return Ok(0);
+    }
+
+    let error = |addr, kind| UserAccessError::new(addr, kind, access_flags);
+    if start.as_usize() & (layout.align() - 1) != 0 {
+        return Err(error(start, AccessErrorKind::Misaligned));
+    }
+
+    // Pages are checked at the untagged address, elements read at the
+    // tagged one
+    let tagged = start;
+    let start = uspace.untag_addr(start);
+    let tag = tagged.as_usize().wrapping_sub(start.as_usize());
+    let user_range = effective_user_range(uspace);
+    let page_size = uspace.page_size();
+
+    try_access_user_memory(|window| {
+        let mut len = 0;
+        let mut addr = start;
+        let mut page = start.align_down(page_size);
+        let mut pages = 0;
+        loop {
+            // Every byte of the element must lie in a checked page, and none
+            // of the address arithmetic may wrap
+            let last = addr
+                .checked_add(size - 1)
+                .ok_or(error(addr, AccessErrorKind::Overflow))?;
+            while last >= page {
+                if window.should_abort() {
+                    return Err(error(page, AccessErrorKind::Other(Error::EINTR)));
+                }
+                if let Some(e) = uspace.should_interrupt() {
+                    return Err(error(page, AccessErrorKind::Other(e)));
+                }
+                relax_point(uspace, pages);
+                pages += 1;
+                let page_range = VirtAddrRange::try_from_start_size(page, page_size)
+                    .ok_or(error(page, AccessErrorKind::Overflow))?;
+                if !user_range.contains_range(page_range) {
+                    return Err(error(page, AccessErrorKind::NotMapped));
+                }
+                // A lazily mapped page must not fault inside the kernel
+                check_scanned_page(uspace, page_range, access_flags, hint)?;
+                page = page_range.end;
+                window.expect(VirtAddrRange::new(start, page), access_flags);
+            }
+
+            let tagged = VirtAddr::from(addr.as_usize().wrapping_add(tag));
+            let ahead = page.as_usize() - addr.as_usize();
+            let passed = step(tagged, ahead, max - len)
+                .map_err(|_| error(addr, AccessErrorKind::NotMapped))?;
+            if window.faulted() {
+                return Err(error(addr, AccessErrorKind::NotMapped));
+            }
+            let Some(passed) = passed else {
+                break;
+            };
+            if passed > max - len {
+                return Err(error(addr, AccessErrorKind::TooLong));
+            }
+            len += passed;
+            addr = addr
+                .checked_add(passed * size)
+                .ok_or(error(addr, AccessErrorKind::Overflow))?;
+        }
+        Ok(len)
+    })
+}
+
+/// Default of [`UserSpaceRaw::check_region`], for overrides that only add a
+/// fast path
+pub(crate) fn check_region_default<A: UserSpaceRaw + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    layout: Layout,
+    access_flags: Access,
+    hint: AccessHint,
+) -> AccessResult<()> {
+    if layout.size() == 0 {
+        return Ok(());
+    }
+
+    let start = uspace.untag_addr(start);
+    let error = |kind| UserAccessError::new(start, kind, access_flags);
+    let align = layout.align();
+    if start.as_usize() & (align - 1) != 0 {
+        return Err(error(AccessErrorKind::Misaligned));
+    }
+
+    // A range wrapping past the top of the address space is never valid, and
+    // must not reach backends that would see it as `end < start`
+    let range = VirtAddrRange::try_from_start_size(start, layout.size())
+        .ok_or(error(AccessErrorKind::Overflow))?;
+    if !effective_user_range(uspace).contains_range(range) {
+        return Err(error(AccessErrorKind::NotMapped));
+    }
+    let flags = check_flags(access_flags);
+    let resident = check_growing(uspace, range, access_flags, || match hint {
+        AccessHint::Populate => uspace.ensure_region(range, access_flags).map(|()| true),
+        AccessHint::NoPopulate => uspace
+            .check_region_access_detailed(range, flags)
+            .map(|()| false),
+        AccessHint::PopulateIfMissing | AccessHint::NonBlocking => uspace
+            .check_region_resident(range, flags)
+            .map_err(|e| UserAccessError::from_backend(start, access_flags, e)),
+    })?;
+    uspace
+        .check_pkey(range, access_flags)
+        .map_err(|_| error(AccessErrorKind::PkeyDenied))?;
+    match hint {
+        AccessHint::PopulateIfMissing if !resident => {
+            uspace.populate_region_detailed(range, flags)?
+        }
+        AccessHint::NonBlocking if !resident => uspace
+            .populate_region_nonblocking(range, flags)
+            .map_err(|e| UserAccessError::from_backend(start, access_flags, e))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Step of a [`scan_elements`] over bytes, stopping at any of `needles`
+///
+/// Bytes are read a word at a time while a whole aligned word lies in
+/// checked pages, and one at a time at the unaligned head, near page ends
+/// and in the word holding a match.
+fn byte_scan_step<A: UserSpaceRaw + ?Sized>(
+    uspace: &A,
+    needles: &[u8],
+    tagged: VirtAddr,
+    ahead: usize,
+    remaining: usize,
+) -> UserResult<Option<usize>> {
+    let direct = uspace.direct_map();
+    if direct && tagged.is_aligned(WORD) && remaining >= WORD && ahead >= WORD {
+        let word = unsafe { tagged.as_ptr_of::<usize>().read_volatile() };
+        if !needles
+            .iter()
+            .any(|&b| has_zero_byte(word ^ usize::from_ne_bytes([b; WORD])))
+        {
+            return Ok(Some(WORD));
+        }
+    }
+    let byte = read_scanned_byte(uspace, tagged)?;
+    Ok((!needles.contains(&byte)).then_some(1))
+}
+
+/// Incremental UTF-8 validation, fed one byte at a time
+#[derive(Debug, Clone, Copy)]
+struct Utf8Validator {
+    /// Continuation bytes still expected
+    need: u8,
+    /// Range the next continuation byte must be in
+    next: (u8, u8),
+}
+
+impl Utf8Validator {
+    const fn new() -> Self {
+        Self {
+            need: 0,
+            next: (0x80, 0xbf),
+        }
+    }
+
+    /// Feed the next byte, returning whether the input can still be valid
+    fn push(&mut self, byte: u8) -> bool {
+        if self.need > 0 {
+            if !(self.next.0..=self.next.1).contains(&byte) {
+                return false;
+            }
+            self.need -= 1;
+            self.next = (0x80, 0xbf);
+            return true;
+        }
+        // Second-byte ranges rule out overlong forms, surrogates and code
+        // points past U+10FFFF
+        let (need, next) = match byte {
+            0x00..=0x7f => return true,
+            0xc2..=0xdf => (1, (0x80, 0xbf)),
+            0xe0 => (2, (0xa0, 0xbf)),
+            0xed => (2, (0x80, 0x9f)),
+            0xe1..=0xef => (2, (0x80, 0xbf)),
+            0xf0 => (3, (0x90, 0xbf)),
+            0xf1..=0xf3 => (3, (0x80, 0xbf)),
+            0xf4 => (3, (0x80, 0x8f)),
+            _ => return false,
+        };
+        self.need = need;
+        self.next = next;
+        true
+    }
+
+    /// Whether the input fed so far ends on a character boundary
+    fn is_complete(&self) -> bool {
+        self.need == 0
+    }
+}
+
+/// Scan the null-terminated string at `start`, validating it as UTF-8 in
+/// the same pass and handing each run of accepted bytes to `sink`
+///
+/// Returns the length without the terminator, failing with `EILSEQ` at the
+/// first byte that cannot be valid UTF-8 and with `ENAMETOOLONG` beyond
+/// `max` bytes. Runs of ASCII are read a word at a time.
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub(crate) fn scan_utf8_str<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    max: usize,
+    mut sink: impl FnMut(&[u8]),
+) -> UserResult<usize> {
+    let direct = uspace.direct_map();
+    let mut utf8 = Utf8Validator::new();
+    let mut invalid = false;
+    let scan = scan_elements(
+        uspace,
+        start,
+        Layout::new::<u8>(),
+        Access::READ,
+        max,
+        AccessHint::Populate,
+        |tagged, ahead, remaining| {
+            if direct
+                && utf8.is_complete()
+                && tagged.is_aligned(WORD)
+                && remaining >= WORD
+                && ahead >= WORD
+            {
+                let word = unsafe { tagged.as_ptr_of::<usize>().read_volatile() };
+                if !has_zero_byte(word) && word & BYTE_HIGHS == 0 {
+                    sink(&word.to_ne_bytes());
+                    return Ok(Some(WORD));
+                }
+            }
+            let byte = read_scanned_byte(uspace, tagged)?;
+            if byte == 0 {
+                return Ok(None);
+            }
+            if !utf8.push(byte) {
+                invalid = true;
+                return Ok(None);
+            }
+            sink(&[byte]);
+            Ok(Some(1))
+        },
+    );
+    let len = locate(uspace, scan).map_err(|e| too_long_as(e, Error::ENAMETOOLONG))?;
+    if invalid || !utf8.is_complete() {
+        return Err(Error::EILSEQ);
+    }
+    Ok(len)
+}
+
+/// Copy the null-terminated UTF-8 string at `start` into a new string, as
+/// [`UserSpaceAccess::read_string`] with a bound of `max` bytes
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub(crate) fn copy_utf8_str<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    max: usize,
+) -> UserResult<String> {
+    let mut buf = Vec::new();
+    scan_utf8_str(uspace, start, max, |bytes| buf.extend_from_slice(bytes))?;
+    // Every byte kept was accepted by the validator
+    Ok(unsafe { String::from_utf8_unchecked(buf) })
+}
+
+/// Read the byte at `tagged`, in a page a [`scan_elements`] checked
+fn read_scanned_byte<A: UserSpaceRaw + ?Sized>(uspace: &A, tagged: VirtAddr) -> UserResult<u8> {
+    if uspace.direct_map() {
+        Ok(unsafe { tagged.as_ptr_of::<u8>().read_volatile() })
+    } else {
+        let mut byte = 0;
+        unsafe { uspace.raw_copy_from_user(&mut byte, tagged, 1)? };
+        Ok(byte)
+    }
+}
+
+/// Whether the user string at `start` starts with `expected`, and if
+/// `whole`, also ends right after it
+#[cfg_attr(feature = "track-caller", track_caller)]
+fn compare_str<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    expected: &[u8],
+    whole: bool,
+) -> UserResult<bool> {
+    let mut pos = 0;
+    let mut matched = false;
+    let scan = scan_elements(
+        uspace,
+        start,
+        Layout::new::<u8>(),
+        Access::READ,
+        expected.len(),
+        AccessHint::Populate,
+        |tagged, _, _| {
+            let Some(&want) = expected.get(pos) else {
+                matched = !whole || read_scanned_byte(uspace, tagged)? == 0;
+                return Ok(None);
+            };
+            let byte = read_scanned_byte(uspace, tagged)?;
+            if byte != want || byte == 0 {
+                return Ok(None);
+            }
+            pos += 1;
+            // A matched prefix needs nothing past its last byte
+            if !whole && pos == expected.len() {
+                matched = true;
+                return Ok(None);
+            }
+            Ok(Some(1))
+        },
+    );
+    locate(uspace, scan)?;
+    Ok(matched)
+}
+
+/// Offset of the first of `needles` in the `len` bytes at `ptr`
+#[cfg_attr(feature = "track-caller", track_caller)]
+fn find_bytes<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    ptr: UserConstPtr<u8>,
+    len: usize,
+    needles: &[u8],
+) -> UserResult<Option<usize>> {
+    if len == 0 {
+        return Ok(None);
+    }
+    // The last byte in range is scanned with no elements left, so passing
+    // over it means no match and the byte after it is never touched
+    let scan = scan_elements(
+        uspace,
+        ptr.address(),
+        Layout::new::<u8>(),
+        Access::READ,
+        len - 1,
+        AccessHint::Populate,
+        |tagged, ahead, remaining| byte_scan_step(uspace, needles, tagged, ahead, remaining),
+    );
+    match scan {
+        Err(e) if e.kind == AccessErrorKind::TooLong => Ok(None),
+        scan => Ok(Some(locate(uspace, scan)?)),
+    }
+}
+
+/// Whether the `size` bytes at `addr` are all zero, read through
+/// [`UserSpaceRaw::raw_copy_from_user`]
+fn is_zero_element_raw<A: UserSpaceRaw + ?Sized>(
+    uspace: &A,
+    addr: VirtAddr,
+    size: usize,
+) -> UserResult<bool> {
+    let mut buf = [0u8; 64];
+    let mut off = 0;
+    while off < size {
+        let len = (size - off).min(buf.len());
+        unsafe { uspace.raw_copy_from_user(buf.as_mut_ptr(), addr + off, len)? };
+        if buf[..len].iter().any(|&b| b != 0) {
+            return Ok(false);
+        }
+        off += len;
+    }
+    Ok(true)
+}
+
+/// Run `copy(offset, len)` over the validated user `range` one page at a
+/// time, polling [`UserSpaceRaw::should_interrupt`] and relaxing between
+/// pages
+///
+/// `copy` returns the bytes it managed before a fault as the error.
+fn copy_chunked<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    range: VirtAddrRange,
+    flags: Access,
+    mut copy: impl FnMut(usize, usize) -> Result<(), usize>,
+) -> Result<(), PartialCopy> {
+    let page_size = uspace.page_size();
+    let len = range.size();
+    let threshold = uspace.limits().prefault_bytes;
+    if threshold != 0 && len >= threshold {
+        let mut hint = PrefaultHint::SEQUENTIAL;
+        hint.set(PrefaultHint::WILL_WRITE, flags.contains(Access::WRITE));
+        let _ = uspace.prefault(range, flags, hint);
+    }
+    let mut done = 0;
+    let mut pages = 0;
+    while done < len {
+        if done > 0
+            && let Some(error) = uspace.should_interrupt()
+        {
+            return Err(PartialCopy { done, error });
+        }
+        relax_point(uspace, pages);
+        pages += 1;
+        let start = range.start.as_usize() + done;
+        let chunk = (page_size - (start & (page_size - 1))).min(len - done);
+        let page = VirtAddrRange::from_start_size(VirtAddr::from(start), chunk);
+        match try_access_user_range(page, flags, || copy(done, chunk)) {
+            Ok(Ok(())) => done += chunk,
+            Ok(Err(partial)) => {
+                return Err(PartialCopy {
+                    done: done + partial,
+                    error: Error::EFAULT,
+                });
+            }
+            Err(error) => return Err(PartialCopy { done, error }),
+        }
+    }
+    Ok(())
+}
+
+/// Run `check`, and once more if it found `range` unmapped and
+/// [`UserSpaceRaw::try_grow_region`] grew a mapping over it
+fn check_growing<A: UserSpaceRaw + ?Sized, T>(
+    uspace: &A,
+    range: VirtAddrRange,
+    access_flags: Access,
+    check: impl Fn() -> AccessResult<T>,
+) -> AccessResult<T> {
+    match check() {
+        Err(e) if e.kind == AccessErrorKind::NotMapped => {
+            match uspace.try_grow_region(range, access_flags) {
+                Ok(true) => check(),
+                Ok(false) => Err(e),
+                Err(grow) => Err(UserAccessError::from_backend(
+                    range.start,
+                    access_flags,
+                    grow,
+                )),
+            }
+        }
+        result => result,
+    }
+}
+
+/// Validate the page `range` of a null-terminated scan as `hint` says, with
+/// user access suspended so that populating it may block
+///
+/// Windows that must not block, such as nofault reads, validate the page in
+/// place instead, populating it only without blocking.
+fn check_scanned_page<A: UserSpaceRaw + ?Sized>(
+    uspace: &A,
+    range: VirtAddrRange,
+    access_flags: Access,
+    hint: AccessHint,
+) -> AccessResult<()> {
+    let layout = Layout::from_size_align(range.size(), 1).unwrap();
+    let mut checked = None;
+    relax_user_access(|| {
+        checked = Some(uspace.check_region(range.start, layout, access_flags, hint));
+    });
+    checked.unwrap_or_else(|| {
+        let hint = match hint {
+            AccessHint::NoPopulate => AccessHint::NoPopulate,
+            _ => AccessHint::NonBlocking,
+        };
+        uspace.check_region(range.start, layout, access_flags, hint)
+    })
+}
+
+/// Call [`UserSpaceRaw::relax`] if `pages` is a nonzero multiple of
+/// [`Limits::relax_pages`]
+fn relax_point<A: UserSpaceAccess + ?Sized>(uspace: &A, pages: usize) {
+    let every = uspace.limits().relax_pages;
+    if pages != 0 && pages.is_multiple_of(every) {
+        relax_user_access(|| uspace.relax());
+    }
+}
+
+/// Validate the futex word at `addr` for `flags` without blocking
+fn futex_word<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    addr: VirtAddr,
+    flags: Access,
+) -> UserResult<*mut u32> {
+    check_region_with_misaligned(
+        uspace,
+        addr,
+        Layout::new::<u32>(),
+        flags,
+        AccessHint::NonBlocking,
+        Error::EINVAL,
+    )?;
+    assert_direct_map(uspace);
+    Ok(addr.as_mut_ptr_of())
+}
+
+/// Copy user memory at `addr` into `dst` one page at a time without
+/// populating, stopping at the first page that cannot be read
+fn read_bytes_nofault<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    addr: usize,
+    dst: &mut [u8],
+) -> UserResult<usize> {
+    let len = dst.len();
+    let untagged = uspace.untag_addr(VirtAddr::from(addr)).as_usize();
+    VirtAddrRange::try_from_start_size(VirtAddr::from(untagged), len)
+        .filter(|range| effective_user_range(uspace).contains_range(*range))
+        .ok_or(Error::EFAULT)?;
+    let page_size = uspace.page_size();
+    let fallible = has_user_copy_backend();
+    let mut done = 0;
+    while done < len {
+        let start = addr.wrapping_add(done);
+        let chunk = (page_size - (start & (page_size - 1))).min(len - done);
+        let page = VirtAddrRange::from_start_size(VirtAddr::from(untagged + done), chunk);
+        if !fallible
+            && !uspace
+                .check_region_resident(page, check_flags(Access::READ))
+                .unwrap_or(false)
+        {
+            break;
+        }
+        let copied = try_access_user_nofault(page, Access::READ, || unsafe {
+            uspace.raw_copy_from_user(dst[done..].as_mut_ptr(), VirtAddr::from(start), chunk)
+        });
+        match copied {
+            Ok(Ok(())) => done += chunk,
+            _ => break,
+        }
+    }
+    if done == 0 && len > 0 {
+        return Err(Error::EFAULT);
+    }
+    Ok(done)
+}
+
+/// Cache maintenance requested through [`UserSpaceRaw::sync_after_write`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncKind {
+    /// Clean the data cache so devices see the written data
+    Data,
+    /// Make instruction fetches see the written data
+    Instruction,
+}
+
+/// Whether validating a region should also populate it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AccessHint {
+    /// Always call `populate_region` after the check
+    #[default]
+    Populate,
+    /// Populate only if `check_region_resident` does not report the range as
+    /// resident
+    PopulateIfMissing,
+    /// Only check, never populate, leaving the memory untouched
+    NoPopulate,
+    /// Like [`PopulateIfMissing`](Self::PopulateIfMissing), populating through
+    /// `populate_region_nonblocking` so the check fails with `EAGAIN` rather
+    /// than block
+    NonBlocking,
+}
+
+/// Validate memory region alignment and accessibility, and populate it,
+/// returning a token for cheap accesses inside the region
+///
+/// A zero-sized region always succeeds without consulting the backend, even
+/// for a null or unmapped `start`, as zero-byte copies do on Linux. Callers
+/// must then not derive references from `start`.
+///
+/// Errors from the backend are returned as they are, so an `ENOMEM` from
+/// [`UserSpaceRaw::populate_region`] is not reported as `EFAULT`.
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_region<'a, A: UserSpaceAccess + ?Sized>(
+    uspace: &'a A,
+    start: VirtAddr,
+    layout: Layout,
+    access_flags: Access,
+) -> AccessResult<ValidatedRegion<'a, A>> {
+    check_region_with(uspace, start, layout, access_flags, AccessHint::Populate)
+}
+
+/// Validate and populate several byte regions given as `(start, size, flags)`
+/// with one [`UserSpaceRaw::check_regions`] call
+///
+/// Empty regions are skipped. Regions that wrap or leave
+/// [`UserSpaceRaw::user_addr_range`] fail with `EFAULT` before the backend
+/// is consulted. A backend failure is reported at the start of the first
+/// region.
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_region_batch<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    regions: &[(VirtAddr, usize, Access)],
+) -> AccessResult<()> {
+    locate(uspace, try_check_region_batch(uspace, regions))
+}
+
+#[cfg(feature = "alloc")]
+fn try_check_region_batch<A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    regions: &[(VirtAddr, usize, Access)],
+) -> AccessResult<()> {
+    let user_range = effective_user_range(uspace);
+    let mut ranges = Vec::with_capacity(regions.len());
+    for &(start, size, flags) in regions {
+        if size == 0 {
+            continue;
+        }
+        let range = VirtAddrRange::try_from_start_size(start, size).ok_or(UserAccessError::new(
+            start,
+            AccessErrorKind::Overflow,
+            flags,
+        ))?;
+        if !user_range.contains_range(range) {
+            return Err(UserAccessError::new(
+                start,
+                AccessErrorKind::NotMapped,
+                flags,
+            ));
+        }
+        ranges.push((range, check_flags(flags)));
+    }
+    let Some(&(first, flags)) = ranges.first() else {
+        return Ok(());
+    };
+    // The batch hook cannot tell which region failed
+    uspace
+        .check_regions(&ranges)
+        .map_err(|e| UserAccessError::from_backend(first.start, flags, e))
+}
+
+/// Validate memory region alignment and accessibility without populating it
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_region_no_populate<'a, A: UserSpaceAccess + ?Sized>(
+    uspace: &'a A,
+    start: VirtAddr,
+    layout: Layout,
+    access_flags: Access,
+) -> AccessResult<ValidatedRegion<'a, A>> {
+    check_region_with(uspace, start, layout, access_flags, AccessHint::NoPopulate)
+}
+
+/// Validate memory region alignment and accessibility, populating it as
+/// `hint` says
+///
+/// See [`check_region`] for the handling of zero-sized regions.
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_region_with<'a, A: UserSpaceAccess + ?Sized>(
+    uspace: &'a A,
+    start: VirtAddr,
+    layout: Layout,
+    access_flags: Access,
+    hint: AccessHint,
+) -> AccessResult<ValidatedRegion<'a, A>> {
+    let generation = uspace.generation();
+    locate(
+        uspace,
+        uspace.check_region(start, layout, access_flags, hint),
+    )?;
+    let range = VirtAddrRange::try_from_start_size(start, layout.size())
+        .unwrap_or(VirtAddrRange::new(start, start));
+    #[cfg(feature = "debug-double-fetch")]
+    if access_flags == Access::READ && !range.is_empty() {
+        uspace.record_user_read(range, Location::caller());
+    }
+    Ok(ValidatedRegion::new(
+        uspace,
+        range,
+        access_flags,
+        generation,
+    ))
+}
+
+/// Like [`check_region_with`], failing with `on_misaligned` instead of
+/// `EFAULT` if `start` is not aligned to `layout`
+///
+/// For syscalls specified to return `EINVAL` on misalignment, such as futex.
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_region_with_misaligned<'a, A: UserSpaceAccess + ?Sized>(
+    uspace: &'a A,
+    start: VirtAddr,
+    layout: Layout,
+    access_flags: Access,
+    hint: AccessHint,
+    on_misaligned: Error,
+) -> UserResult<ValidatedRegion<'a, A>> {
+    check_region_with(uspace, start, layout, access_flags, hint)
+        .map_err(|e| misaligned_as(e, on_misaligned))
+}
+
+/// Errno of `error`, with misalignment reported as `on_misaligned`
+fn misaligned_as(error: UserAccessError, on_misaligned: Error) -> Error {
+    match error.kind {
+        AccessErrorKind::Misaligned => on_misaligned,
+        _ => error.into(),
+    }
+}
+
+/// Errno of `error`, with an overlong scan reported as `on_too_long`
+pub(crate) fn too_long_as(error: UserAccessError, on_too_long: Error) -> Error {
+    match error.kind {
+        AccessErrorKind::TooLong => on_too_long,
+        _ => error.into(),
+    }
+}
+
+/// Find the length of a null-terminated array in user space
+///
+/// Goes through [`UserSpaceRaw::scan_null_terminated`], so the terminator is
+/// an all-zero element, which `T::default()` is for the integer and pointer
+/// types this is used with. Accepts at most
+/// [`max_scan_len`](Limits::max_scan_len) elements before the terminator.
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_null_terminated<T: PartialEq + Default, A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    access_flags: Access,
+) -> AccessResult<usize> {
+    let max = uspace.limits().max_scan_len;
+    check_null_terminated_bounded::<T, A>(uspace, start, access_flags, max)
+}
+
+/// Like [`check_null_terminated`], failing with
+/// [`TooLong`](AccessErrorKind::TooLong) once more than `max` elements
+/// precede the terminator
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_null_terminated_bounded<T: PartialEq + Default, A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    access_flags: Access,
+    max: usize,
+) -> AccessResult<usize> {
+    check_null_terminated_with::<T, A>(uspace, start, access_flags, max, AccessHint::Populate)
+}
+
+/// Like [`check_null_terminated_bounded`], populating the pages read as
+/// `hint` says
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_null_terminated_with<T: PartialEq + Default, A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    access_flags: Access,
+    max: usize,
+    hint: AccessHint,
+) -> AccessResult<usize> {
+    locate(
+        uspace,
+        uspace.scan_null_terminated(start, Layout::new::<T>(), access_flags, max, hint),
+    )
+}
+
+/// Like [`check_null_terminated`], failing with `on_misaligned` instead of
+/// `EFAULT` if `start` is not aligned for `T`
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn check_null_terminated_misaligned<T: PartialEq + Default, A: UserSpaceAccess + ?Sized>(
+    uspace: &A,
+    start: VirtAddr,
+    access_flags: Access,
+    on_misaligned: Error,
+) -> UserResult<usize> {
+    check_null_terminated::<T, A>(uspace, start, access_flags)
+        .map_err(|e| misaligned_as(e, on_misaligned))
+}
+
+#[macro_export]
 macro_rules! nullable {
     (@impl ($($base:tt)*) . $method:ident ( $ptr:expr $(, $args:expr)* )) => {
         {
@@ -212,3 +3220,1417 @@ macro_rules! nullable {
     };
 }
 
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::{
+        RegionTableBuilder,
+        mock::{self, MockUspace, RW},
+    };
+
+    const WORD: usize = size_of::<usize>();
+
+    #[test]
+    fn peek_and_poke_unaligned_words() {
+        let uspace = MockUspace::new(2);
+        let addr = uspace.addr(4096 - WORD / 2).as_usize();
+        uspace.poke_word(addr, usize::MAX / 3).unwrap();
+        assert_eq!(uspace.peek_word(addr), Ok(usize::MAX / 3));
+        assert_eq!(
+            uspace.load(4096 - WORD / 2, WORD),
+            (usize::MAX / 3).to_ne_bytes()
+        );
+    }
+
+    #[test]
+    fn words_straddling_a_hole_fault() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        let addr = uspace.addr(4096 - WORD / 2).as_usize();
+        assert_eq!(uspace.peek_word(addr), Err(Error::EFAULT));
+        assert_eq!(uspace.poke_word(addr, 1), Err(Error::EFAULT));
+        assert_eq!(uspace.load(4096 - WORD / 2, WORD / 2), [0; WORD / 2]);
+    }
+
+    #[test]
+    fn kernel_addresses_never_reach_the_backend() {
+        let uspace = MockUspace::new(1);
+        let kernel = UserConstPtr::<u64>::from(USER_ADDR_END);
+        assert_eq!(uspace.read(kernel), Err(Error::EFAULT));
+        let straddling = UserConstPtr::<[u8; 16]>::from(USER_ADDR_END - 8);
+        assert_eq!(uspace.read(straddling), Err(Error::EFAULT));
+        assert_eq!(
+            uspace.read_cstr_into(UserConstPtr::<u8>::from(USER_ADDR_END), &mut [0; 8]),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(uspace.checks.get(), 0);
+    }
+
+    /// Near the top of the address space, 16 bytes wrap past zero
+    const TOP: usize = usize::MAX - 8;
+
+    #[test]
+    fn wrapping_region_is_rejected() {
+        let uspace = MockUspace::new(1).permissive();
+        let err = check_region(
+            &uspace,
+            VirtAddr::from(TOP),
+            Layout::new::<[u8; 16]>(),
+            Access::READ,
+        )
+        .unwrap_err();
+        assert_eq!(
+            (err.addr, err.kind),
+            (VirtAddr::from(TOP), AccessErrorKind::Overflow)
+        );
+        assert_eq!(
+            uspace.read(UserConstPtr::<[u8; 16]>::from(TOP)),
+            Err(Error::EFAULT)
+        );
+        let mut buf = [0; 16];
+        assert_eq!(
+            uspace.read_slice_to(UserConstPtr::<u8>::from(TOP), &mut buf),
+            Err(Error::EFAULT)
+        );
+        // The backend approving everything never saw the range
+        assert_eq!(uspace.checks.get(), 0);
+    }
+
+    #[test]
+    fn wrapping_scan_is_rejected() {
+        let uspace = MockUspace::new(1).permissive();
+        assert!(
+            check_null_terminated::<u8, _>(&uspace, VirtAddr::from(TOP), Access::READ).is_err()
+        );
+        assert!(
+            check_null_terminated::<u64, _>(&uspace, VirtAddr::from(TOP & !7), Access::READ)
+                .is_err()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn overflowing_slice_length_is_rejected() {
+        let uspace = MockUspace::new(1).permissive();
+        assert!(
+            uspace
+                .read_vec(UserConstPtr::<u64>::from(0x1000), usize::MAX / 4)
+                .is_err()
+        );
+        assert_eq!(uspace.checks.get(), 0);
+    }
+
+    #[test]
+    fn zero_sized_accesses_skip_the_backend() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        let unmapped = uspace.addr(4096);
+        for addr in [VirtAddr::from(0), unmapped] {
+            assert!(check_region(&uspace, addr, Layout::new::<()>(), Access::READ).is_ok());
+            let ptr = UserConstPtr::<u8>::from(addr.as_usize());
+            assert_eq!(uspace.read_slice_to(ptr, &mut []), Ok(()));
+            assert_eq!(
+                uspace.write_slice(UserPtr::<u8>::from(addr.as_usize()), &[]),
+                Ok(())
+            );
+            assert_eq!(
+                uspace.read(UserConstPtr::<()>::from(addr.as_usize())),
+                Ok(())
+            );
+            assert_eq!(
+                uspace.write(UserPtr::<()>::from(addr.as_usize()), ()),
+                Ok(())
+            );
+        }
+        assert_eq!(uspace.checks.get(), 0);
+        assert_eq!(uspace.populates.get(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn zero_length_vec_skips_the_backend() {
+        let uspace = MockUspace::new(1);
+        uspace.unmap(0);
+        assert_eq!(uspace.read_vec(uspace.cptr::<u32>(0), 0), Ok(Vec::new()));
+        assert_eq!(
+            uspace
+                .read_vec(UserConstPtr::<[u64; 0]>::from(8), 4)
+                .map(|v| v.len()),
+            Ok(4)
+        );
+        assert_eq!(uspace.checks.get(), 0);
+    }
+
+    #[test]
+    fn updates_write_the_modified_value_back() {
+        let uspace = MockUspace::new(1);
+        uspace.put(8, 41u32);
+        let ptr = uspace.ptr::<u32>(8);
+        assert_eq!(
+            uspace.update(ptr, |v| {
+                *v += 1;
+                *v * 2
+            }),
+            Ok(84)
+        );
+        assert_eq!(uspace.get::<u32>(8), 42);
+        #[cfg(feature = "alloc")]
+        assert_eq!(uspace.read_vec(uspace.cptr::<u32>(8), 1), Ok(vec![42]));
+
+        uspace.protect(0, Access::READ | Access::USER);
+        assert_eq!(uspace.update(ptr, |v| *v = 0), Err(Error::EFAULT));
+        assert_eq!(uspace.get::<u32>(8), 42);
+    }
+
+    /// Checks `check_null_terminated` makes for a string of 60K bytes, whose
+    /// terminator starts the sixteenth 4K page
+    fn scan_checks(page_size: usize) -> usize {
+        let len = 60 * 1024;
+        let uspace = MockUspace::with_page_size(0x10000 / page_size, page_size);
+        uspace.fill(0, &[b'a'; 60 * 1024]);
+        let found = check_null_terminated::<u8, _>(&uspace, uspace.addr(0), Access::READ);
+        assert_eq!(found, Ok(len));
+        uspace.checks.get()
+    }
+
+    #[test]
+    fn scans_validate_once_per_page() {
+        assert_eq!(scan_checks(0x10000), 1);
+        assert_eq!(scan_checks(0x4000), 4);
+        assert_eq!(scan_checks(0x1000), 16);
+    }
+
+    #[test]
+    fn hints_decide_whether_to_populate() {
+        let uspace = MockUspace::new(2);
+        uspace.unpopulate(1);
+        let layout = Layout::new::<[u8; 4096]>();
+        let check =
+            |off, hint| check_region_with(&uspace, uspace.addr(off), layout, Access::READ, hint);
+
+        check(0, AccessHint::PopulateIfMissing).unwrap();
+        assert_eq!(uspace.populates.get(), 0);
+        check(4096, AccessHint::NoPopulate).unwrap();
+        assert!(!uspace.is_populated(1));
+        check(4096, AccessHint::PopulateIfMissing).unwrap();
+        assert!(uspace.is_populated(1));
+        assert_eq!(uspace.populates.get(), 1);
+        check(0, AccessHint::Populate).unwrap();
+        assert_eq!(uspace.populates.get(), 2);
+        assert_eq!(uspace.checks.get(), 4);
+
+        uspace.unmap(0);
+        assert_eq!(
+            check_region_no_populate(&uspace, uspace.addr(0), layout, Access::READ)
+                .err()
+                .map(|e| e.kind),
+            Some(AccessErrorKind::NotMapped)
+        );
+    }
+
+    #[test]
+    fn queries_probe_without_populating() {
+        let uspace = MockUspace::new(3);
+        uspace.unpopulate(0);
+        uspace.protect(1, Access::READ);
+        assert!(uspace.access_ok(uspace.range(0, 3 * 4096), Access::READ));
+        assert!(!uspace.access_ok(uspace.range(8, 4096), Access::WRITE));
+        assert!(uspace.access_ok(uspace.range(4096, 0), Access::WRITE));
+        assert_eq!(
+            uspace.first_invalid(uspace.range(8, 3 * 4096 - 8), Access::READ),
+            None
+        );
+        assert_eq!(
+            uspace.first_invalid(uspace.range(8, 3 * 4096 - 8), Access::WRITE),
+            Some(uspace.addr(4096))
+        );
+        uspace.unmap(2);
+        assert_eq!(
+            uspace.first_invalid(uspace.range(4100, 8192), Access::READ),
+            Some(uspace.addr(8192))
+        );
+        assert!(!uspace.is_populated(0));
+        assert_eq!(uspace.populates.get(), 0);
+
+        // Kernel addresses fail without asking the backend
+        uspace.reset_counts();
+        let kernel = VirtAddrRange::from_start_size(VirtAddr::from(USER_ADDR_END), 8);
+        assert!(!uspace.access_ok(kernel, Access::READ));
+        assert_eq!(
+            uspace.first_invalid(kernel, Access::READ),
+            Some(kernel.start)
+        );
+        assert_eq!(uspace.checks.get(), 0);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn query_and_mincore_report_each_page() {
+        let uspace = MockUspace::new(4);
+        uspace.protect(1, Access::READ);
+        uspace.unpopulate(1);
+        uspace.unmap(2);
+        let mut pages = Vec::new();
+        uspace.query_region(uspace.range(8, 4 * 4096 - 16), &mut |page, flags| {
+            pages.push((page, flags))
+        });
+        let rw = Access::READ | Access::WRITE;
+        assert_eq!(
+            pages,
+            [
+                (uspace.addr(0), Some(rw)),
+                (uspace.addr(4096), Some(Access::READ)),
+                (uspace.addr(8192), None),
+                (uspace.addr(12288), Some(rw)),
+            ]
+        );
+
+        let out = uspace.ptr::<u8>(12288);
+        uspace.fill(12288, &[0xff; 2]);
+        uspace.mincore_into(uspace.range(0, 8192), out).unwrap();
+        assert_eq!(uspace.load(12288, 2), [1, 0]);
+        assert_eq!(
+            uspace.mincore_into(uspace.range(8, 4096), out),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(
+            uspace.mincore_into(uspace.range(4096, 8192), out),
+            Err(Error::ENOMEM)
+        );
+        assert!(!uspace.is_populated(1));
+    }
+
+    #[test]
+    #[cfg(feature = "strict-user-flag")]
+    fn supervisor_page_is_rejected() {
+        let uspace = MockUspace::new(2);
+        uspace.protect_supervisor(1, Access::READ | Access::WRITE);
+        assert_eq!(uspace.read(uspace.cptr::<u64>(0)), Ok(0));
+        assert_eq!(uspace.read(uspace.cptr::<u64>(4096)), Err(Error::EFAULT));
+        assert_eq!(uspace.write(uspace.ptr::<u64>(4096), 1), Err(Error::EFAULT));
+        let mut buf = [0; 16];
+        assert_eq!(
+            uspace.read_slice_to(uspace.cptr::<u8>(4088), &mut buf),
+            Err(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    fn page_zero_is_never_user_memory() {
+        let uspace = MockUspace::new(1);
+        let low = VirtAddrRange::from_start_size(VirtAddr::from(0x800), 8);
+        assert_eq!(
+            uspace.read(UserConstPtr::<u64>::from(0x800)),
+            Err(Error::EFAULT)
+        );
+        assert!(!uspace.access_ok(low, Access::READ));
+        assert_eq!(uspace.first_invalid(low, Access::READ), Some(low.start));
+        let straddling = VirtAddrRange::from_start_size(VirtAddr::from(0xff8), 16);
+        assert_eq!(
+            check_region(
+                &uspace,
+                straddling.start,
+                Layout::new::<[u64; 2]>(),
+                Access::READ
+            )
+            .err()
+            .map(|e| e.kind),
+            Some(AccessErrorKind::NotMapped)
+        );
+        assert_eq!(uspace.checks.get(), 0);
+    }
+
+    #[test]
+    fn misalignment_error_is_configurable() {
+        let uspace = MockUspace::new(1);
+        let layout = Layout::new::<u32>();
+        let region = |off, error| {
+            check_region_with_misaligned(
+                &uspace,
+                uspace.addr(off),
+                layout,
+                Access::READ,
+                AccessHint::NoPopulate,
+                error,
+            )
+            .err()
+        };
+        assert_eq!(region(2, Error::EINVAL), Some(Error::EINVAL));
+        assert_eq!(region(4, Error::EINVAL), None);
+        assert_eq!(
+            check_region(&uspace, uspace.addr(2), layout, Access::READ)
+                .err()
+                .map(|e| e.kind),
+            Some(AccessErrorKind::Misaligned)
+        );
+        assert_eq!(
+            check_null_terminated_misaligned::<u32, _>(
+                &uspace,
+                uspace.addr(2),
+                Access::READ,
+                Error::EINVAL
+            ),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(
+            check_null_terminated_misaligned::<u32, _>(
+                &uspace,
+                uspace.addr(4),
+                Access::READ,
+                Error::EINVAL
+            ),
+            Ok(0)
+        );
+        assert_eq!(uspace.checks.get(), 2);
+    }
+
+    #[test]
+    #[allow(deprecated, unused_unsafe)]
+    fn populate_enomem_is_not_efault() {
+        let uspace = MockUspace::new(1);
+        uspace.unpopulate(0);
+        uspace.populate_error.set(Some(Error::ENOMEM));
+        let ptr = uspace.cptr::<u8>(0);
+        assert_eq!(unsafe { uspace.read_slice(ptr, 16) }, Err(Error::ENOMEM));
+        assert_eq!(uspace.read_slice_to(ptr, &mut [0; 16]), Err(Error::ENOMEM));
+        assert_eq!(uspace.read(uspace.cptr::<u64>(0)), Err(Error::ENOMEM));
+
+        // A failed check is still a fault
+        uspace.unmap(0);
+        assert_eq!(uspace.read_slice_to(ptr, &mut [0; 16]), Err(Error::EFAULT));
+    }
+
+    /// Read and write through any `UserSpaceAccess`
+    fn round_trip<A: UserSpaceAccess + ?Sized>(uspace: &A, base: usize) {
+        uspace.write(UserPtr::<u32>::from(base), 0x1234).unwrap();
+        assert_eq!(uspace.read(UserConstPtr::<u32>::from(base)), Ok(0x1234));
+        let mut buf = [0; 4];
+        uspace
+            .read_slice_to(UserConstPtr::<u8>::from(base), &mut buf)
+            .unwrap();
+        assert_eq!(u32::from_ne_bytes(buf), 0x1234);
+    }
+
+    #[test]
+    fn dyn_backend_reads() {
+        let mock = MockUspace::new(1);
+        let uspace: &dyn UserSpaceRaw = &mock;
+        round_trip(uspace, mock.addr(0).as_usize());
+        assert_eq!(uspace.read(mock.cptr::<u64>(4096)), Err(Error::EFAULT));
+        assert_eq!(uspace.page_size(), 4096);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn boxed_dyn_backend_reads() {
+        let mock = MockUspace::new(1);
+        let base = mock.addr(0).as_usize();
+        let uspace: Box<dyn UserSpaceRaw> = Box::new(mock);
+        round_trip(&uspace, base);
+        round_trip(&*uspace, base);
+    }
+
+    #[cfg(feature = "alloc")]
+    /// Read the string at `ptr` through a backend taken by value
+    #[allow(deprecated, unused_unsafe)]
+    fn string_of<A: UserSpaceAccess>(uspace: A, ptr: UserConstPtr<c_char>) -> String {
+        uspace.read_string(ptr).unwrap()
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync, deprecated, unused_unsafe)]
+    fn smart_pointer_string_helpers() {
+        let mock = MockUspace::new(1);
+        mock.fill(0, b"hello\0");
+        let ptr = mock.cptr::<c_char>(0);
+        let uspace = Arc::new(mock);
+        assert_eq!(string_of(&uspace, ptr), "hello");
+        assert_eq!(string_of(uspace.clone(), ptr), "hello");
+        let by_ref: &Arc<MockUspace> = &uspace;
+        assert_eq!(unsafe { by_ref.read_str(ptr) }, Ok("hello"));
+        assert_eq!(unsafe { ptr.get_as_str(&uspace) }, Ok("hello"));
+
+        let rc = Rc::new(MockUspace::new(1));
+        rc.fill(0, b"rc\0");
+        assert_eq!(string_of(&rc, rc.cptr(0)), "rc");
+    }
+
+    /// Backend answering region checks and scans itself, for a fixed window
+    struct Hooked<'a> {
+        mock: &'a MockUspace,
+        window: VirtAddrRange,
+        scans: Cell<usize>,
+    }
+
+    impl UserSpaceRaw for Hooked<'_> {
+        fn check_region_access(&self, _: VirtAddrRange, _: Access) -> UserResult<()> {
+            unreachable!()
+        }
+
+        fn populate_region(&self, _: VirtAddrRange, _: Access) -> UserResult<()> {
+            unreachable!()
+        }
+
+        fn check_region(
+            &self,
+            start: VirtAddr,
+            layout: Layout,
+            access_flags: Access,
+            _hint: AccessHint,
+        ) -> AccessResult<()> {
+            let range = VirtAddrRange::from_start_size(start, layout.size());
+            if self.window.contains_range(range) {
+                Ok(())
+            } else {
+                Err(UserAccessError::new(
+                    start,
+                    AccessErrorKind::NotMapped,
+                    access_flags,
+                ))
+            }
+        }
+
+        fn scan_null_terminated(
+            &self,
+            start: VirtAddr,
+            layout: Layout,
+            access_flags: Access,
+            max: usize,
+            hint: AccessHint,
+        ) -> AccessResult<usize> {
+            self.scans.set(self.scans.get() + 1);
+            self.mock
+                .scan_null_terminated(start, layout, access_flags, max, hint)
+        }
+    }
+
+    #[test]
+    #[allow(deprecated, unused_unsafe)]
+    fn region_hooks_replace_the_page_checks() {
+        let mock = MockUspace::new(2);
+        mock.fill(16, b"hook\0");
+        let uspace = Hooked {
+            mock: &mock,
+            window: mock.range(0, 4096),
+            scans: Cell::new(0),
+        };
+        uspace.write(mock.ptr::<u64>(8), 3).unwrap();
+        assert_eq!(uspace.read(mock.cptr::<u64>(8)), Ok(3));
+        assert_eq!(uspace.read(mock.cptr::<u64>(4096)), Err(Error::EFAULT));
+        // Strings are validated while scanning, through the region hook
+        assert_eq!(unsafe { uspace.read_str(mock.cptr::<u8>(16)) }, Ok("hook"));
+        assert_eq!(uspace.scans.get(), 0);
+        let len = check_null_terminated::<u8, _>(&uspace, mock.addr(16), Access::READ);
+        assert_eq!(len.map_err(Error::from), Ok(4));
+        assert_eq!(uspace.scans.get(), 1);
+        assert_eq!(mock.checks.get(), 1);
+    }
+
+    #[test]
+    fn nofault_reads_stop_at_the_first_fault() {
+        let uspace = MockUspace::new(2);
+        uspace.fill(4088, b"abcdefgh\0");
+        assert_eq!(
+            uspace.read_nofault(uspace.cptr::<[u8; 4]>(4088)),
+            Ok(*b"abcd")
+        );
+        let populates = uspace.populates.get();
+
+        // Faults before the first byte fail, later ones cut the read short
+        mock::fault_copies_after(Some(0));
+        assert_eq!(
+            uspace.read_nofault(uspace.cptr::<u64>(4088)),
+            Err(Error::EFAULT)
+        );
+        mock::fault_copies_after(None);
+        let mut buf = [0; 16];
+        let ptr = uspace.cptr::<u8>(4088);
+        mock::fault_copies_after(Some(3));
+        assert_eq!(uspace.read_slice_nofault(ptr, &mut buf), Err(Error::EFAULT));
+        mock::fault_copies_after(Some(4));
+        let ptr = uspace.cptr::<u8>(4092);
+        assert_eq!(uspace.read_slice_nofault(ptr, &mut buf), Ok(4));
+        assert_eq!(
+            uspace.read_str_nofault(uspace.cptr::<u8>(4092), &mut buf),
+            Ok(4)
+        );
+        mock::fault_copies_after(None);
+
+        // Strings are truncated to the buffer, not failed
+        assert_eq!(
+            uspace.read_str_nofault(uspace.cptr::<u8>(4088), &mut buf[..4]),
+            Ok(4)
+        );
+        assert_eq!(
+            uspace.read_str_nofault(uspace.cptr::<u8>(4088), &mut buf),
+            Ok(8)
+        );
+        assert_eq!(&buf[..8], b"abcdefgh");
+        assert_eq!(uspace.populates.get(), populates);
+
+        // Ranges outside user space fail before any copy
+        let copies = mock::copies();
+        let high = UserConstPtr::<u64>::from(USER_ADDR_END - 4);
+        assert_eq!(uspace.read_nofault(high), Err(Error::EFAULT));
+        assert_eq!(mock::copies(), copies);
+    }
+
+    #[test]
+    fn futex_words_never_block() {
+        let uspace = MockUspace::new(2);
+        uspace.put(4096, 7u32);
+        uspace.unpopulate(1);
+        uspace.would_block.set(true);
+        let word = uspace.cptr::<u32>(4096);
+        assert_eq!(uspace.read_futex_value(word), Err(Error::EAGAIN));
+        assert!(!uspace.is_populated(1));
+        assert_eq!(
+            uspace.read_futex_value(uspace.cptr(4098)),
+            Err(Error::EINVAL)
+        );
+
+        // The caller faults the page in without its lock and retries
+        uspace.would_block.set(false);
+        assert_eq!(uspace.read(word), Ok(7));
+        uspace.would_block.set(true);
+        assert_eq!(uspace.read_futex_value(word), Ok(7));
+        let word = uspace.ptr::<u32>(4096);
+        assert_eq!(uspace.futex_cmpxchg(word, 1, 2), Ok(7));
+        assert_eq!(uspace.futex_cmpxchg(word, 7, 8), Ok(7));
+        assert_eq!(uspace.get::<u32>(4096), 8);
+
+        uspace.unmap(1);
+        assert_eq!(uspace.futex_cmpxchg(word, 8, 9), Err(Error::EFAULT));
+    }
+
+    #[test]
+    fn long_copies_stop_between_pages() {
+        let uspace = MockUspace::new(3);
+        let mut buf = [0; 8192];
+        assert_eq!(uspace.read_chunks(uspace.cptr::<u8>(100), &mut buf), Ok(()));
+        uspace.interrupt.set(Some(Error::EINTR));
+        let partial = |done| {
+            Err(PartialCopy {
+                done,
+                error: Error::EINTR,
+            })
+        };
+        assert_eq!(
+            uspace.read_chunks(uspace.cptr::<u8>(100), &mut buf),
+            partial(3996)
+        );
+        assert_eq!(uspace.write_chunks(uspace.ptr(4096), &buf), partial(4096));
+        assert_eq!(
+            uspace.read_slice_to(uspace.cptr::<u8>(100), &mut buf),
+            Err(Error::EINTR)
+        );
+        // Copies within one page are never interrupted
+        assert_eq!(
+            uspace.read_chunks(uspace.cptr::<u8>(100), &mut buf[..64]),
+            Ok(())
+        );
+        uspace.fill(4090, b"abcdefgh\0");
+        assert_eq!(
+            uspace.read_cstr_into(uspace.cptr::<u8>(4090), &mut [0; 16]),
+            Err(Error::EINTR)
+        );
+        uspace.interrupt.set(None);
+
+        // Progress is counted in whole raw copies
+        mock::fault_copies_after(Some(10));
+        assert_eq!(
+            uspace.write_chunks(uspace.ptr(4090), &buf),
+            Err(PartialCopy {
+                done: 6,
+                error: Error::EFAULT
+            })
+        );
+        mock::fault_copies_after(None);
+    }
+
+    #[test]
+    fn long_copies_relax_outside_the_window() {
+        let limits = Limits {
+            relax_pages: 2,
+            ..Limits::LINUX
+        };
+        let uspace = MockUspace::new(5).with_limits(limits);
+        let mut buf = [0; 5 * 4096];
+        uspace.read_chunks(uspace.cptr::<u8>(0), &mut buf).unwrap();
+        assert_eq!(uspace.relaxes.take(), [false; 2]);
+        uspace.write_chunks(uspace.ptr(100), &buf[..4096]).unwrap();
+        assert!(uspace.relaxes.take().is_empty());
+
+        // Scans relax the same way, restoring the window after
+        uspace.fill(0, &[1; 5 * 4096 - 1]);
+        let scan = check_null_terminated::<u8, _>(&uspace, uspace.addr(0), Access::READ);
+        assert_eq!(scan.map_err(|e| e.kind), Ok(5 * 4096 - 1));
+        assert_eq!(uspace.relaxes.take(), [false; 2]);
+
+        let uspace = MockUspace::new(5).with_limits(Limits {
+            relax_pages: 0,
+            ..Limits::LINUX
+        });
+        uspace.read_chunks(uspace.cptr::<u8>(0), &mut buf).unwrap();
+        assert!(uspace.relaxes.take().is_empty());
+    }
+
+    #[test]
+    fn large_copies_prefault_their_whole_range() {
+        let limits = Limits {
+            prefault_bytes: 2 * 4096,
+            ..Limits::LINUX
+        };
+        let uspace = MockUspace::new(4).with_limits(limits);
+        let mut buf = [0; 4 * 4096];
+        uspace
+            .read_chunks(uspace.cptr::<u8>(0), &mut buf[..4096])
+            .unwrap();
+        assert!(uspace.prefaults.take().is_empty());
+
+        uspace
+            .read_chunks(uspace.cptr::<u8>(100), &mut buf[..8192])
+            .unwrap();
+        let read = (uspace.range(100, 8192), PrefaultHint::SEQUENTIAL);
+        assert_eq!(uspace.prefaults.take(), [read]);
+        uspace.write_chunks(uspace.ptr(0), &buf).unwrap();
+        let written = (
+            uspace.range(0, 4 * 4096),
+            PrefaultHint::SEQUENTIAL | PrefaultHint::WILL_WRITE,
+        );
+        assert_eq!(uspace.prefaults.take(), [written]);
+
+        // A failed prefault leaves the copy to report faults itself
+        uspace.prefault_error.set(Some(Error::ENOMEM));
+        uspace.write_chunks(uspace.ptr(0), &buf).unwrap();
+        mock::fault_copies_after(Some(100));
+        assert_eq!(
+            uspace.read_chunks(uspace.cptr::<u8>(0), &mut buf),
+            Err(PartialCopy {
+                done: 0,
+                error: Error::EFAULT
+            })
+        );
+        mock::fault_copies_after(None);
+        assert_eq!(uspace.prefaults.take().len(), 2);
+
+        let uspace = MockUspace::new(4).with_limits(Limits {
+            prefault_bytes: 0,
+            ..Limits::LINUX
+        });
+        uspace.read_chunks(uspace.cptr::<u8>(0), &mut buf).unwrap();
+        assert!(uspace.prefaults.take().is_empty());
+    }
+
+    #[test]
+    fn streaming_copies_populate_one_window_at_a_time() {
+        let limits = Limits {
+            stream_window_pages: 2,
+            ..Limits::LINUX
+        };
+        let uspace = MockUspace::new(8).with_limits(limits);
+        uspace.fill(0, &[7; 8 * 4096]);
+        for page in 0..8 {
+            uspace.unpopulate(page);
+        }
+        let mut buf = [0; 8 * 4096];
+        assert_eq!(
+            uspace.copy_from_user_streaming(uspace.cptr::<u8>(0), &mut buf),
+            Ok(())
+        );
+        assert!(buf.iter().all(|&b| b == 7));
+        assert_eq!(uspace.populates.get(), 4);
+
+        // A fault stops at the window it is in, leaving later pages alone
+        for page in 0..8 {
+            uspace.unpopulate(page);
+        }
+        uspace.unmap(5);
+        assert_eq!(
+            uspace.copy_to_user_streaming(uspace.ptr::<u8>(100), &buf[..6 * 4096]),
+            Err(PartialCopy {
+                done: 4 * 4096 - 100,
+                error: Error::EFAULT
+            })
+        );
+        assert!((0..4).all(|page| uspace.is_populated(page)));
+        assert!((6..8).all(|page| !uspace.is_populated(page)));
+
+        // Ranges wrapping past the top fail before copying anything
+        let top = UserConstPtr::<u8>::from(TOP);
+        assert_eq!(
+            uspace.copy_from_user_streaming(top, &mut buf[..16]),
+            Err(PartialCopy {
+                done: 0,
+                error: Error::EFAULT
+            })
+        );
+    }
+
+    #[test]
+    fn unmapped_ranges_may_grow_once() {
+        let uspace = MockUspace::new(3);
+        uspace.unmap(0);
+        uspace.unmap(1);
+        assert_eq!(uspace.read(uspace.cptr::<u64>(4096)), Err(Error::EFAULT));
+        assert_eq!(uspace.grows.get(), 1);
+
+        // The stack may grow down to page 1 but not below it
+        uspace.grow_limit.set(Some(1));
+        uspace.write(uspace.ptr::<u64>(4096), 5).unwrap();
+        assert_eq!(uspace.grows.get(), 2);
+        assert_eq!(uspace.read(uspace.cptr::<u64>(4096)), Ok(5));
+        assert_eq!(uspace.grows.get(), 2);
+        assert_eq!(uspace.read(uspace.cptr::<u64>(4088)), Err(Error::EFAULT));
+        assert_eq!(uspace.grows.get(), 3);
+
+        // Ranges outside user space never try to grow
+        let high = UserConstPtr::<u64>::from(USER_ADDR_END);
+        assert_eq!(uspace.read(high), Err(Error::EFAULT));
+        assert_eq!(uspace.grows.get(), 3);
+    }
+
+    #[test]
+    fn forced_writes_patch_read_only_code() {
+        let uspace = MockUspace::new(2);
+        uspace.protect(0, Access::READ | Access::EXECUTE);
+        let insn = uspace.ptr::<u32>(0x101);
+        assert_eq!(uspace.write(uspace.ptr::<u8>(0x101), 1), Err(Error::EFAULT));
+        uspace.write_forced(insn, 0xcc90_90cc).unwrap();
+        assert_eq!(uspace.load(0x101, 4), 0xcc90_90ccu32.to_ne_bytes());
+        assert_eq!(
+            uspace.syncs.take(),
+            [(uspace.range(0x101, 4), SyncKind::Instruction)]
+        );
+        // The mapping keeps its protection
+        assert_eq!(uspace.write(uspace.ptr::<u8>(0x101), 1), Err(Error::EFAULT));
+
+        uspace.unmap(1);
+        assert_eq!(
+            uspace.write_slice_forced(uspace.ptr::<u8>(4094), &[0; 4]),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(
+            uspace.write_slice_forced(uspace.ptr::<u8>(4096), &[]),
+            Ok(())
+        );
+        assert!(uspace.syncs.take().is_empty());
+
+        // Backends without support refuse
+        let table = RegionTableBuilder::<1>::new()
+            .window(uspace.range(0, 4096), Access::READ | Access::USER)
+            .unwrap()
+            .build();
+        assert_eq!(table.write_forced(insn, 0), Err(Error::EPERM));
+    }
+
+    #[test]
+    fn tagged_pointers_are_untagged_for_checks_only() {
+        let uspace = MockUspace::new(2).tagged();
+        let tag = 0x2a << 56;
+        let tagged = |off| uspace.addr(off).as_usize() | tag;
+
+        uspace.write(UserPtr::<u32>::from(tagged(0)), 7).unwrap();
+        assert_eq!(mock::last_copy(), Some(VirtAddr::from(tagged(0))));
+        assert_eq!(uspace.read(UserConstPtr::<u32>::from(tagged(0))), Ok(7));
+        assert_eq!(mock::last_copy(), Some(VirtAddr::from(tagged(0))));
+
+        let start = VirtAddr::from(tagged(4096));
+        assert!(check_region(&uspace, start, Layout::new::<u64>(), Access::READ).is_ok());
+        let range = VirtAddrRange::from_start_size(start, 8);
+        assert!(uspace.access_ok(range, Access::READ));
+
+        // The tag does not hide an unmapped page
+        uspace.unmap(1);
+        assert_eq!(
+            uspace.read(UserConstPtr::<u32>::from(tagged(4096))),
+            Err(Error::EFAULT)
+        );
+        assert!(!uspace.access_ok(range, Access::READ));
+    }
+
+    #[test]
+    fn flushes_reach_the_backend_untagged() {
+        let uspace = MockUspace::new(2).tagged();
+        let tagged = VirtAddr::from(uspace.addr(0x10).as_usize() | 0x2a << 56);
+        let range = VirtAddrRange::from_start_size(tagged, 0x20);
+        uspace.flush_user_range(range, SyncKind::Data).unwrap();
+        assert_eq!(
+            uspace.syncs.take(),
+            [(uspace.range(0x10, 0x20), SyncKind::Data)]
+        );
+
+        // Empty ranges need no maintenance, ranges outside user space fail
+        let empty = VirtAddrRange::from_start_size(uspace.addr(0x10), 0);
+        assert_eq!(uspace.flush_user_range(empty, SyncKind::Data), Ok(()));
+        let high = VirtAddrRange::from_start_size(VirtAddr::from(USER_ADDR_END), 8);
+        assert_eq!(
+            uspace.flush_user_range(high, SyncKind::Instruction),
+            Err(Error::EFAULT)
+        );
+        assert!(uspace.syncs.take().is_empty());
+    }
+
+    #[test]
+    fn device_memory_is_copied_with_volatile_accesses() {
+        let uspace = MockUspace::new(1);
+        uspace.memory.set(MemoryType::Device);
+        let before = mock::copies();
+        let data: [u8; 13] = *b"device regs!\0";
+        uspace.write_slice(uspace.ptr::<u8>(3), &data).unwrap();
+        let mut buf = [0; 13];
+        uspace
+            .read_slice_to(uspace.cptr::<u8>(3), &mut buf)
+            .unwrap();
+        assert_eq!(buf, data);
+        // Neither copy went through the regular routines
+        assert_eq!(mock::copies(), before);
+
+        // The explicit pair does the same whatever the backend reports
+        uspace.memory.set(MemoryType::Normal);
+        uspace
+            .write_slice_volatile(uspace.ptr::<u32>(8), &[1, 2, 3])
+            .unwrap();
+        let mut words = [0; 3];
+        uspace
+            .read_slice_volatile(uspace.cptr::<u32>(8), &mut words)
+            .unwrap();
+        assert_eq!(words, [1, 2, 3]);
+        assert_eq!(mock::copies(), before);
+        uspace.unmap(0);
+        assert_eq!(
+            uspace.read_slice_volatile(uspace.cptr::<u32>(8), &mut words),
+            Err(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    fn indirect_spaces_copy_through_the_raw_hooks() {
+        let uspace = MockUspace::new(2).indirect();
+        uspace.fill(4090, b"split string\0");
+        uspace.write(uspace.ptr::<u64>(8), 42).unwrap();
+        assert_eq!(uspace.read(uspace.cptr::<u64>(8)), Ok(42));
+        let mut buf = [0; 16];
+        assert_eq!(
+            uspace.read_cstr_into(uspace.cptr::<u8>(4090), &mut buf),
+            Ok(12)
+        );
+        assert_eq!(&buf[..12], b"split string");
+        let start = uspace.addr(4090);
+        assert_eq!(
+            check_null_terminated::<u8, _>(&uspace, start, Access::READ).map_err(Error::from),
+            Ok(12)
+        );
+
+        // The tail of a longer struct is copied, not borrowed
+        let mut attr = [0; 8];
+        uspace.fill(16, &[0; 8]);
+        uspace
+            .copy_struct_from_user(&mut attr, uspace.cptr(8), 16)
+            .unwrap();
+        assert_eq!(attr, 42u64.to_ne_bytes());
+        uspace.fill(20, &[1]);
+        assert_eq!(
+            uspace.copy_struct_from_user(&mut attr, uspace.cptr(8), 16),
+            Err(Error::E2BIG)
+        );
+        assert!(uspace.raw_copies.get() > 0);
+        assert_eq!(mock::copies(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "not directly mapped")]
+    fn indirect_spaces_hand_out_no_references() {
+        let uspace = MockUspace::new(1).indirect();
+        let _ = uspace.cptr::<u64>(0).get_ref(&uspace);
+    }
+
+    #[test]
+    fn pkey_denies_writes_only() {
+        // Page 0 carries key 3, whose PKRU bits deny writes
+        let uspace = MockUspace::new(1);
+        uspace.write_denied.borrow_mut().push(0);
+        let start = uspace.addr(0);
+        let layout = Layout::new::<u64>();
+        assert!(check_region(&uspace, start, layout, Access::READ).is_ok());
+        assert_eq!(uspace.read(uspace.cptr::<u64>(0)), Ok(0));
+
+        let err = check_region(&uspace, start, layout, Access::WRITE).unwrap_err();
+        assert_eq!(err.kind, AccessErrorKind::PkeyDenied);
+        assert_eq!(err.errno(), Error::EFAULT);
+        assert_eq!(uspace.write(uspace.ptr::<u64>(0), 1), Err(Error::EFAULT));
+        assert_eq!(uspace.load(0, 8), [0; 8]);
+    }
+
+    #[test]
+    fn stable_reads_retry_until_two_agree() {
+        let uspace = MockUspace::new(1);
+        uspace.put(8, 5u32);
+        assert_eq!(uspace.read_stable(uspace.cptr::<u32>(8), 0), Ok(5));
+
+        // Changed between the first reads, then settled
+        uspace.race(8, 2);
+        assert_eq!(uspace.read_stable(uspace.cptr::<u32>(8), 2), Ok(7));
+        uspace.race(8, 3);
+        assert_eq!(
+            uspace.read_stable(uspace.cptr::<u32>(8), 1),
+            Err(StableReadError::Unstable)
+        );
+        assert_eq!(Error::from(StableReadError::Unstable), Error::EAGAIN);
+        assert_eq!(
+            uspace.read_stable(uspace.cptr::<u32>(4096), 1),
+            Err(StableReadError::Access(Error::EFAULT))
+        );
+    }
+
+    #[test]
+    fn stable_byte_reads_return_the_settled_bytes() {
+        let uspace = MockUspace::new(1);
+        let data: [u8; 200] = core::array::from_fn(|i| i as u8);
+        uspace.fill(0, &data);
+        let mut buf = [0; 200];
+        uspace.race(150, 1);
+        uspace
+            .read_stable_bytes(uspace.cptr::<u8>(0), &mut buf, 1)
+            .unwrap();
+        assert_eq!(buf[..150], data[..150]);
+        assert_eq!(buf[150], 151);
+
+        uspace.race(150, usize::MAX);
+        assert_eq!(
+            uspace.read_stable_bytes(uspace.cptr::<u8>(0), &mut buf, 3),
+            Err(StableReadError::Unstable)
+        );
+    }
+
+    #[cfg(feature = "debug-double-fetch")]
+    #[test]
+    fn stable_rereads_are_not_double_fetches() {
+        let uspace = MockUspace::new(1);
+        let session = uspace.session();
+        uspace.race(0, 1);
+        assert_eq!(session.read_stable(uspace.cptr::<u64>(0), 2), Ok(1));
+        let mut buf = [0; 100];
+        session
+            .read_stable_bytes(uspace.cptr::<u8>(1000), &mut buf, 2)
+            .unwrap();
+        assert!(uspace.fetches.borrow().is_empty());
+
+        // Plain reads of the same data still are
+        session.read(uspace.cptr::<u64>(0)).unwrap();
+        assert_eq!(uspace.fetches.borrow().len(), 1);
+    }
+
+    #[test]
+    fn constant_time_comparison_covers_every_byte() {
+        let uspace = MockUspace::new(1);
+        let secret: [u8; 150] = core::array::from_fn(|i| i as u8);
+        uspace.fill(0, &secret);
+        let ptr = uspace.cptr::<u8>(0);
+        assert_eq!(uspace.eq_constant_time(ptr, 150, &secret), Ok(true));
+        assert_eq!(uspace.eq_constant_time(ptr, 0, &[]), Ok(true));
+
+        // A difference in the last chunk, and length mismatches both ways
+        let mut other = secret;
+        other[149] ^= 1;
+        assert_eq!(uspace.eq_constant_time(ptr, 150, &other), Ok(false));
+        assert_eq!(uspace.eq_constant_time(ptr, 149, &secret), Ok(false));
+        assert_eq!(uspace.eq_constant_time(ptr, 150, &secret[..149]), Ok(false));
+        assert_eq!(uspace.eq_constant_time(ptr, 1, &[]), Ok(false));
+
+        assert_eq!(
+            uspace.eq_constant_time(uspace.cptr(4000), 150, &secret),
+            Err(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn hostile_inputs_fail_without_panicking() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[b'a'; 4096]);
+        let base = uspace.addr(0).as_usize();
+        let addrs = [0, 1, base + 4095, base + 4096, TOP, usize::MAX];
+        let lens = [4097, isize::MAX as usize, usize::MAX];
+        for addr in addrs {
+            let cptr = UserConstPtr::<u8>::from(addr);
+            assert!(uspace.read(UserConstPtr::<u64>::from(addr)).is_err());
+            assert!(uspace.read_string(cptr).is_err());
+            assert!(uspace.read_cstr_into(cptr, &mut [0; 64]).is_err());
+            assert!(uspace.str_eq(cptr, "a").is_err());
+            for len in lens {
+                assert!(uspace.read_vec(cptr, len).is_err());
+                assert!(
+                    uspace
+                        .read_vec(UserConstPtr::<u64>::from(addr), len)
+                        .is_err()
+                );
+                assert!(uspace.memchr(cptr, len, 0).is_err());
+            }
+        }
+
+        // Offsets wrap instead of overflowing, and still fail
+        for offset in [usize::MAX / 16, usize::MAX / 4] {
+            assert!(uspace.read(uspace.cptr::<u64>(0).offset(offset)).is_err());
+            assert!(uspace.read(uspace.cptr::<u8>(0).offset(offset)).is_err());
+        }
+
+        // Segment counts and lengths overflowing their sums
+        let iovs = uspace.cptr::<IoVec>(0);
+        assert_eq!(
+            uspace.import_iovec(iovs, usize::MAX, Access::READ),
+            Err(Error::EINVAL)
+        );
+        let huge = IoVec {
+            iov_base: base,
+            iov_len: usize::MAX,
+        };
+        uspace.write(uspace.ptr::<IoVec>(0), huge).unwrap();
+        uspace.write(uspace.ptr::<IoVec>(16), huge).unwrap();
+        assert_eq!(
+            uspace.import_iovec(iovs, 2, Access::READ),
+            Err(Error::EINVAL)
+        );
+    }
+
+    #[test]
+    fn zero_bytes_are_found_on_either_endianness() {
+        assert!(!has_zero_byte(usize::MAX));
+        assert!(!has_zero_byte(usize::from_ne_bytes([0x80; WORD])));
+        assert!(!has_zero_byte(usize::from_ne_bytes([0x01; WORD])));
+        for pos in 0..WORD {
+            for fill in [0x01, 0x80, 0xff, b'a'] {
+                let mut bytes = [fill; WORD];
+                bytes[pos] = 0;
+                assert!(has_zero_byte(usize::from_le_bytes(bytes)));
+                assert!(has_zero_byte(usize::from_be_bytes(bytes)));
+            }
+        }
+    }
+
+    #[test]
+    fn word_scans_stop_at_the_terminator_and_page_end() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        let scan = |off| {
+            check_null_terminated::<u8, _>(&uspace, uspace.addr(off), Access::READ)
+                .map_err(Error::from)
+        };
+        // Every head alignment and terminator position across a few words
+        for start in 0..WORD {
+            for nul in start..start + 3 * WORD {
+                uspace.fill(0, &[b'a'; 64]);
+                uspace.fill(nul, &[0]);
+                assert_eq!(scan(start), Ok(nul - start));
+            }
+        }
+
+        // A terminator in the page's last byte is found without touching the
+        // unmapped page, and a page without one runs into it
+        uspace.fill(0, &[0x80; 4096]);
+        uspace.fill(4095, &[0]);
+        for start in [0, 1, 4088, 4095] {
+            assert_eq!(scan(start), Ok(4095 - start));
+        }
+        uspace.fill(4095, &[1]);
+        assert_eq!(scan(0), Err(Error::EFAULT));
+        assert_eq!(scan(4090), Err(Error::EFAULT));
+    }
+
+    #[test]
+    fn word_scans_respect_the_bound() {
+        let uspace = MockUspace::new(1);
+        uspace.fill(0, &[b'a'; 32]);
+        for max in 0..20 {
+            let found =
+                check_null_terminated_bounded::<u8, _>(&uspace, uspace.addr(0), Access::READ, max);
+            assert_eq!(found.unwrap_err().kind, AccessErrorKind::TooLong);
+            assert_eq!(
+                check_null_terminated_bounded::<u8, _>(
+                    &uspace,
+                    uspace.addr(32 - max),
+                    Access::READ,
+                    max
+                )
+                .map_err(Error::from),
+                Ok(max)
+            );
+        }
+    }
+
+    #[test]
+    fn scans_populate_every_page_read() {
+        let uspace = MockUspace::new(4);
+        uspace.fill(0, &[b'a'; 3 * 4096]);
+        for page in 0..4 {
+            uspace.unpopulate(page);
+        }
+        let found = check_null_terminated::<u8, _>(&uspace, uspace.addr(100), Access::READ);
+        assert_eq!(found.map_err(Error::from), Ok(3 * 4096 - 100));
+        assert_eq!(uspace.populates.get(), 4);
+        assert!((0..4).all(|page| uspace.is_populated(page)));
+        // Populating may sleep, so it happens with user access suspended
+        assert_eq!(uspace.window_populates.get(), 0);
+
+        // A terminator early in the page leaves later pages alone
+        uspace.unpopulate(1);
+        uspace.unpopulate(2);
+        uspace.fill(4100, &[0]);
+        uspace.reset_counts();
+        let found = check_null_terminated::<u8, _>(&uspace, uspace.addr(0), Access::READ);
+        assert_eq!(found.map_err(Error::from), Ok(4100));
+        assert_eq!(uspace.populates.get(), 2);
+        assert!(uspace.is_populated(1));
+        assert!(!uspace.is_populated(2));
+
+        // A page that cannot be populated fails the scan
+        uspace.populate_error.set(Some(Error::ENOMEM));
+        uspace.fill(4100, b"a");
+        let found = check_null_terminated::<u8, _>(&uspace, uspace.addr(0), Access::READ);
+        assert_eq!(found.map_err(Error::from), Err(Error::ENOMEM));
+    }
+
+    #[test]
+    fn scans_populate_as_the_hint_says() {
+        let uspace = MockUspace::new(3);
+        uspace.fill(0, &[b'a'; 2 * 4096]);
+        uspace.unpopulate(1);
+        let scan = |hint| {
+            check_null_terminated_with::<u8, _>(&uspace, uspace.addr(0), Access::READ, 8192, hint)
+                .map_err(Error::from)
+        };
+        assert_eq!(scan(AccessHint::NoPopulate), Ok(8192));
+        assert_eq!(uspace.populates.get(), 0);
+        assert!(!uspace.is_populated(1));
+
+        // Only the missing page is populated
+        assert_eq!(scan(AccessHint::PopulateIfMissing), Ok(8192));
+        assert_eq!(uspace.populates.get(), 1);
+        assert!(uspace.is_populated(1));
+
+        uspace.unpopulate(1);
+        uspace.reset_counts();
+        uspace.would_block.set(true);
+        assert_eq!(scan(AccessHint::NonBlocking), Err(Error::EAGAIN));
+        assert_eq!(uspace.populates.get(), 0);
+        uspace.would_block.set(false);
+        assert_eq!(scan(AccessHint::Populate), Ok(8192));
+        assert_eq!(uspace.populates.get(), 3);
+        assert_eq!(uspace.window_populates.get(), 0);
+    }
+
+    #[test]
+    fn sentinel_scans_count_up_to_the_end_marker() {
+        let uspace = MockUspace::new(2);
+        uspace.fill(4090, &[1, 2, 3, 4, 5, 6, 7, 0xff]);
+        uspace.unpopulate(1);
+        let bytes = uspace.cptr::<u8>(4090);
+        assert_eq!(uspace.scan_terminated(bytes, |&b| b == 0xff, 16), Ok(7));
+        assert!(uspace.is_populated(1));
+        assert_eq!(
+            uspace.scan_terminated(bytes, |&b| b == 0xff, 6),
+            Err(Error::EINVAL)
+        );
+        assert_eq!(
+            uspace.scan_terminated(bytes, |&b| b == b'\n', usize::MAX),
+            Err(Error::EFAULT)
+        );
+
+        // An auxv-style table ends with an all-zero pair
+        for (i, pair) in [[6u64, 4096], [25, 0x1000], [0, 0]].iter().enumerate() {
+            uspace.put(16 * i, *pair);
+        }
+        let auxv = uspace.cptr::<[u64; 2]>(0);
+        assert_eq!(uspace.scan_terminated(auxv, |e| e[0] == 0, 8), Ok(2));
+        let empty = uspace.cptr::<[u64; 0]>(0);
+        assert_eq!(uspace.scan_terminated(empty, |_| false, 0), Ok(0));
+    }
+
+    #[test]
+    fn memchr_stops_at_the_page_of_the_match() {
+        let uspace = MockUspace::new(3);
+        uspace.fill(0, &[b'a'; 3 * 4096]);
+        uspace.fill(4100, b"\n");
+        uspace.fill(4200, b"\r");
+        uspace.unmap(2);
+        let ptr = uspace.cptr::<u8>(3);
+        assert_eq!(uspace.memchr(ptr, 3 * 4096 - 3, b'\n'), Ok(Some(4097)));
+        assert_eq!(uspace.checks.get(), 2);
+        assert_eq!(uspace.memchr2(ptr, 8000, b'\r', b'\n'), Ok(Some(4097)));
+        assert_eq!(uspace.memchr2(ptr, 8000, b'\r', b'x'), Ok(Some(4197)));
+
+        // No match within the range, even one byte before it
+        assert_eq!(uspace.memchr(ptr, 4097, b'\n'), Ok(None));
+        assert_eq!(uspace.memchr(ptr, 0, b'a'), Ok(None));
+        assert_eq!(uspace.memchr(ptr, 4093, b'z'), Ok(None));
+        // Searching into the unmapped page faults
+        assert_eq!(uspace.memchr(ptr, 3 * 4096 - 3, b'z'), Err(Error::EFAULT));
+
+        // Indirect spaces read byte by byte through the raw hooks
+        let uspace = MockUspace::new(1).indirect();
+        uspace.fill(0, b"key=value");
+        assert_eq!(uspace.memchr(uspace.cptr(0), 9, b'='), Ok(Some(3)));
+        assert!(uspace.raw_copies.get() > 0);
+    }
+
+    #[test]
+    fn user_strings_compare_without_allocating() {
+        let uspace = MockUspace::new(2);
+        uspace.fill(0, b"/proc/self\0\0");
+        let at = |off| uspace.cptr::<u8>(off);
+        assert_eq!(uspace.str_eq(at(0), "/proc/self"), Ok(true));
+        assert_eq!(uspace.str_eq(at(11), ""), Ok(true));
+        // Neither string may be a strict prefix of the other
+        assert_eq!(uspace.str_eq(at(0), "/proc/selfie"), Ok(false));
+        assert_eq!(uspace.str_eq(at(0), "/proc/"), Ok(false));
+        assert_eq!(uspace.str_eq(at(0), "/dev/"), Ok(false));
+
+        assert_eq!(uspace.str_starts_with(at(0), "/proc/"), Ok(true));
+        assert_eq!(uspace.str_starts_with(at(0), "/proc/self"), Ok(true));
+        assert_eq!(uspace.str_starts_with(at(0), "/proc/selfie"), Ok(false));
+        assert_eq!(uspace.str_starts_with(at(0), "/dev/"), Ok(false));
+        assert_eq!(uspace.str_starts_with(at(0), ""), Ok(true));
+        assert_eq!(uspace.str_starts_with(at(11), ""), Ok(true));
+        assert_eq!(uspace.str_starts_with(at(11), "/"), Ok(false));
+    }
+
+    #[test]
+    fn user_string_comparisons_read_only_what_they_need() {
+        let uspace = MockUspace::new(2);
+        uspace.unmap(1);
+        uspace.fill(4094, b"ab");
+        let ptr = uspace.cptr::<u8>(4094);
+        // A mismatch or a matched prefix ends the comparison before the
+        // unmapped page
+        assert_eq!(uspace.str_eq(ptr, "ax"), Ok(false));
+        assert_eq!(uspace.str_starts_with(ptr, "ab"), Ok(true));
+        assert_eq!(uspace.str_starts_with(ptr, "b"), Ok(false));
+        // Equality must see the terminator
+        assert_eq!(uspace.str_eq(ptr, "ab"), Err(Error::EFAULT));
+        assert_eq!(uspace.str_starts_with(ptr, "abc"), Err(Error::EFAULT));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn strings_are_validated_on_the_bytes_returned() {
+        let uspace = MockUspace::new(2);
+        uspace.fill(0, &[b'a'; 4096]);
+        uspace.fill(4096, b"bc\0");
+        // The first byte turns invalid while the second page is checked
+        let tamper = Some((2, 0, 0xff));
+
+        // Scanning and then validating sees different bytes each time
+        uspace.tamper.set(tamper);
+        let len = check_null_terminated::<u8, _>(&uspace, uspace.addr(0), Access::READ);
+        assert_eq!(len.map_err(Error::from), Ok(4098));
+        assert!(core::str::from_utf8(&uspace.load(0, 4098)).is_err());
+
+        // One pass copies exactly the bytes it validated
+        uspace.fill(0, b"a");
+        uspace.reset_counts();
+        uspace.tamper.set(tamper);
+        let s = uspace.read_string(uspace.cptr::<u8>(0)).unwrap();
+        assert_eq!(uspace.load(0, 1), [0xff]);
+        assert_eq!(s.len(), 4098);
+        assert!(s.starts_with("aa") && s.ends_with("abc"));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    #[allow(unused_unsafe)]
+    fn invalid_utf8_fails_at_the_first_bad_byte() {
+        let uspace = MockUspace::new(2);
+        uspace.fill(0, &[b'a'; 4096]);
+        uspace.fill(10, &[0xc3, b'(']);
+        let cptr = uspace.cptr::<u8>(0);
+        assert_eq!(uspace.read_string(cptr), Err(Error::EILSEQ));
+        assert_eq!(uspace.checks.get(), 1);
+        uspace.reset_counts();
+        assert_eq!(unsafe { cptr.get_as_str(&uspace) }, Err(Error::EILSEQ));
+        assert_eq!(uspace.checks.get(), 1);
+
+        // A sequence split across pages is still decoded as one
+        uspace.fill(10, b"aa");
+        uspace.fill(4095, "é".as_bytes());
+        uspace.fill(4097, b"\0");
+        let s = uspace.read_string(cptr).unwrap();
+        assert!(s.ends_with("aé"));
+        assert_eq!(
+            uspace.read_string(uspace.cptr::<u8>(4096)),
+            Err(Error::EILSEQ)
+        );
+    }
+
+    /// Validation results every `ensure_region` must give on a fresh
+    /// four-page mock holding a three-page string
+    fn ensure_region_contract(uspace: &MockUspace) {
+        uspace.fill(0, &[b'a'; 3 * 4096]);
+        for page in 0..4 {
+            uspace.unpopulate(page);
+        }
+        let mut buf = [0; 4 * 4096];
+        assert_eq!(uspace.read_slice_to(uspace.cptr::<u8>(0), &mut buf), Ok(()));
+        assert!((0..4).all(|page| uspace.is_populated(page)));
+        let found = check_null_terminated::<u8, _>(&uspace, uspace.addr(0), Access::READ);
+        assert_eq!(found.map_err(Error::from), Ok(3 * 4096));
+        assert_eq!(uspace.write(uspace.ptr::<u64>(8), 1), Ok(()));
+
+        uspace.protect(3, RW - Access::WRITE);
+        assert_eq!(
+            uspace.write(uspace.ptr::<u64>(3 * 4096), 1),
+            Err(Error::EFAULT)
+        );
+        assert_eq!(uspace.read(uspace.cptr::<u64>(3 * 4096)), Ok(0));
+        uspace.populate_error.set(Some(Error::ENOMEM));
+        assert_eq!(uspace.read(uspace.cptr::<u64>(0)), Err(Error::ENOMEM));
+        uspace.populate_error.set(None);
+        uspace.unmap(2);
+        assert_eq!(
+            uspace.read_slice_to(uspace.cptr::<u8>(0), &mut buf),
+            Err(Error::EFAULT)
+        );
+    }
+
+    #[test]
+    fn default_ensure_region_makes_both_calls() {
+        let uspace = MockUspace::new(4);
+        ensure_region_contract(&uspace);
+        assert_eq!(uspace.ensures.get(), 0);
+
+        // One call of each hook per page scanned or range copied
+        let uspace = MockUspace::new(4);
+        uspace.fill(0, &[b'a'; 3 * 4096]);
+        let found = check_null_terminated::<u8, _>(&uspace, uspace.addr(0), Access::READ);
+        assert_eq!(found.map_err(Error::from), Ok(3 * 4096));
+        assert_eq!((uspace.checks.get(), uspace.populates.get()), (4, 4));
+        uspace.reset_counts();
+        assert_eq!(
+            uspace.read_slice_to(uspace.cptr::<u8>(0), &mut [0; 8192]),
+            Ok(())
+        );
+        assert_eq!((uspace.checks.get(), uspace.populates.get()), (1, 1));
+    }
+
+    #[test]
+    fn overridden_ensure_region_walks_once() {
+        let uspace = MockUspace::new(4).combined();
+        ensure_region_contract(&uspace);
+
+        // One walk in place of each pair of calls
+        let uspace = MockUspace::new(4).combined();
+        uspace.fill(0, &[b'a'; 3 * 4096]);
+        let found = check_null_terminated::<u8, _>(&uspace, uspace.addr(0), Access::READ);
+        assert_eq!(found.map_err(Error::from), Ok(3 * 4096));
+        assert_eq!(uspace.ensures.get(), 4);
+        uspace.reset_counts();
+        assert_eq!(
+            uspace.read_slice_to(uspace.cptr::<u8>(0), &mut [0; 8192]),
+            Ok(())
+        );
+        assert_eq!(uspace.ensures.get(), 1);
+        assert_eq!((uspace.checks.get(), uspace.populates.get()), (0, 0));
+    }
+}
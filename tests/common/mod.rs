@@ -0,0 +1,140 @@
+//! Host address space for the integration tests and benchmarks, built on
+//! the public API only
+
+#![allow(dead_code)]
+
+use std::{
+    alloc::{Layout, alloc_zeroed, dealloc},
+    cell::Cell,
+    sync::Once,
+    vec::Vec,
+};
+
+use axuspace::{
+    Access, AccessStateBackend, Error, UserResult, UserSpaceRaw, set_access_state_backend,
+};
+use memory_addr::{VirtAddr, VirtAddrRange};
+
+pub const PAGE_SIZE: usize = 4096;
+
+thread_local! {
+    static FLAG: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Access flag of the calling thread, as test threads run concurrently
+struct ThreadState;
+
+impl AccessStateBackend for ThreadState {
+    fn set(&self) {
+        FLAG.set(true);
+    }
+
+    fn clear(&self) {
+        FLAG.set(false);
+    }
+
+    fn is_set(&self) -> bool {
+        FLAG.get()
+    }
+}
+
+/// Install the per-thread access flag
+pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| assert!(set_access_state_backend(&ThreadState)));
+}
+
+/// Heap pages standing in for a user address space, whose backend hooks
+/// each count as one acquisition of the address-space lock
+pub struct HostUspace {
+    base: *mut u8,
+    layout: Layout,
+    mapped: Vec<Cell<bool>>,
+    generation: Cell<u64>,
+    /// Lock acquisitions so far
+    pub locks: Cell<usize>,
+}
+
+impl HostUspace {
+    /// `pages` mapped, readable and writable pages
+    pub fn new(pages: usize) -> Self {
+        init();
+        let layout = Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap();
+        let base = unsafe { alloc_zeroed(layout) };
+        assert!(!base.is_null());
+        Self {
+            base,
+            layout,
+            mapped: (0..pages).map(|_| Cell::new(true)).collect(),
+            generation: Cell::new(0),
+            locks: Cell::new(0),
+        }
+    }
+
+    /// Address of byte `off`
+    pub fn addr(&self, off: usize) -> usize {
+        self.base as usize + off
+    }
+
+    /// Copy `data` to byte `off`
+    pub fn fill(&self, off: usize, data: &[u8]) {
+        assert!(off + data.len() <= self.layout.size());
+        unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), self.base.add(off), data.len()) };
+    }
+
+    /// Unmap page `page`
+    pub fn unmap(&self, page: usize) {
+        self.mapped[page].set(false);
+        self.generation.set(self.generation.get() + 1);
+    }
+
+    fn lock(&self) {
+        self.locks.set(self.locks.get() + 1);
+    }
+
+    fn check_mapped(&self, range: VirtAddrRange) -> UserResult<()> {
+        let base = self.base as usize;
+        if range.start.as_usize() < base || range.end.as_usize() > base + self.layout.size() {
+            return Err(Error::EFAULT);
+        }
+        let first = (range.start.as_usize() - base) / PAGE_SIZE;
+        let last = (range.end.as_usize() - base).div_ceil(PAGE_SIZE);
+        match self.mapped[first..last].iter().all(Cell::get) {
+            true => Ok(()),
+            false => Err(Error::EFAULT),
+        }
+    }
+}
+
+impl Drop for HostUspace {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.base, self.layout) };
+    }
+}
+
+impl UserSpaceRaw for HostUspace {
+    fn check_region_access(&self, range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        self.lock();
+        self.check_mapped(range)
+    }
+
+    fn populate_region(&self, range: VirtAddrRange, _access_flags: Access) -> UserResult<()> {
+        self.lock();
+        self.check_mapped(range)
+    }
+
+    fn check_regions(&self, regions: &[(VirtAddrRange, Access)]) -> UserResult<()> {
+        self.lock();
+        regions
+            .iter()
+            .try_for_each(|&(range, _)| self.check_mapped(range))
+    }
+
+    fn user_addr_range(&self) -> VirtAddrRange {
+        VirtAddrRange::from_start_size(VirtAddr::from(self.base as usize), self.layout.size())
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+}
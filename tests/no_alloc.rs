@@ -0,0 +1,74 @@
+//! The heap-free core of the crate, also run with `--no-default-features`
+
+mod common;
+
+use core::alloc::Layout;
+
+use axuspace::{
+    Access, Error, UserConstPtr, UserPtr, UserSpaceAccess, check_null_terminated, check_region,
+};
+use common::{HostUspace, PAGE_SIZE};
+use memory_addr::VirtAddr;
+
+#[test]
+fn values_round_trip() {
+    let uspace = HostUspace::new(1);
+    uspace
+        .write(UserPtr::<u64>::from(uspace.addr(8)), 42)
+        .unwrap();
+    assert_eq!(
+        uspace.read(UserConstPtr::<u64>::from(uspace.addr(8))),
+        Ok(42)
+    );
+}
+
+#[test]
+fn slices_copy_into_caller_buffers() {
+    let uspace = HostUspace::new(1);
+    uspace.fill(0, &[1, 2, 3, 4]);
+    let mut buf = [0u8; 4];
+    uspace
+        .read_slice_to(UserConstPtr::<u8>::from(uspace.addr(0)), &mut buf)
+        .unwrap();
+    assert_eq!(buf, [1, 2, 3, 4]);
+    uspace
+        .write_slice(UserPtr::<u8>::from(uspace.addr(16)), &buf)
+        .unwrap();
+    assert_eq!(
+        uspace.read(UserConstPtr::<[u8; 4]>::from(uspace.addr(16))),
+        Ok(buf)
+    );
+}
+
+#[test]
+fn strings_copy_into_caller_buffers() {
+    let uspace = HostUspace::new(1);
+    uspace.fill(0, b"/bin/sh\0");
+    let ptr = UserConstPtr::<u8>::from(uspace.addr(0));
+    let mut buf = [0; 16];
+    assert_eq!(uspace.read_cstr_into(ptr, &mut buf), Ok(7));
+    assert_eq!(&buf[..7], b"/bin/sh");
+    assert_eq!(
+        check_null_terminated::<u8, _>(&uspace, VirtAddr::from(uspace.addr(0)), Access::READ)
+            .map_err(Error::from),
+        Ok(7)
+    );
+}
+
+#[test]
+fn unmapped_pages_fault() {
+    let uspace = HostUspace::new(2);
+    uspace.unmap(1);
+    let layout = Layout::new::<u64>();
+    let start = VirtAddr::from(uspace.addr(PAGE_SIZE));
+    assert_eq!(
+        check_region(&uspace, start, layout, Access::READ)
+            .map(|_| ())
+            .map_err(Error::from),
+        Err(Error::EFAULT)
+    );
+    assert_eq!(
+        uspace.read(UserConstPtr::<u64>::from(start.as_usize())),
+        Err(Error::EFAULT)
+    );
+}
@@ -0,0 +1,168 @@
+//! The crate's accessors against the `PageTableUspace` reference backend,
+//! walking a real x86_64 page table that identity-maps host pages
+
+#![cfg(target_arch = "x86_64")]
+
+mod common;
+
+use std::{
+    alloc::{Layout, alloc_zeroed, dealloc},
+    cell::Cell,
+};
+
+use axuspace::{Access, Error, PageTableUspace, UserConstPtr, UserPtr, UserSpaceAccess};
+use common::PAGE_SIZE;
+use memory_addr::{PhysAddr, VirtAddr};
+use page_table_multiarch::{MappingFlags, PageSize, PagingHandler, x86_64::X64PageTable};
+
+const PAGE: Layout = match Layout::from_size_align(PAGE_SIZE, PAGE_SIZE) {
+    Ok(layout) => layout,
+    Err(_) => panic!(),
+};
+
+/// Page table frames from the host heap, identity mapped
+struct HostPaging;
+
+impl PagingHandler for HostPaging {
+    fn alloc_frame() -> Option<PhysAddr> {
+        let frame = unsafe { alloc_zeroed(PAGE) };
+        (!frame.is_null()).then(|| PhysAddr::from(frame as usize))
+    }
+
+    fn dealloc_frame(paddr: PhysAddr) {
+        unsafe { dealloc(paddr.as_usize() as *mut u8, PAGE) };
+    }
+
+    fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+        VirtAddr::from(paddr.as_usize())
+    }
+}
+
+const USER_RW: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::USER);
+
+/// Host pages and a page table mapping each at its own address with the
+/// given flags, `None` leaving it unmapped
+struct Mapped {
+    base: *mut u8,
+    layout: Layout,
+    table: X64PageTable<HostPaging>,
+}
+
+impl Mapped {
+    fn new(flags: &[Option<MappingFlags>]) -> Self {
+        common::init();
+        let layout = Layout::from_size_align(flags.len() * PAGE_SIZE, PAGE_SIZE).unwrap();
+        let base = unsafe { alloc_zeroed(layout) };
+        assert!(!base.is_null());
+        let mut table = X64PageTable::<HostPaging>::try_new().unwrap();
+        for (i, flags) in flags.iter().enumerate() {
+            if let Some(flags) = *flags {
+                let addr = base as usize + i * PAGE_SIZE;
+                table
+                    .map(
+                        VirtAddr::from(addr),
+                        PhysAddr::from(addr),
+                        PageSize::Size4K,
+                        flags,
+                    )
+                    .unwrap()
+                    .ignore();
+            }
+        }
+        Self {
+            base,
+            layout,
+            table,
+        }
+    }
+
+    fn addr(&self, off: usize) -> usize {
+        self.base as usize + off
+    }
+}
+
+impl Drop for Mapped {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.base, self.layout) };
+    }
+}
+
+#[test]
+fn user_pages_round_trip() {
+    let mapped = Mapped::new(&[Some(USER_RW), Some(USER_RW)]);
+    let uspace = PageTableUspace::new(&mapped.table);
+    // Across the page boundary
+    let off = PAGE_SIZE - 4;
+    uspace
+        .write(UserPtr::<[u8; 8]>::from(mapped.addr(off)), *b"abcdefg\0")
+        .unwrap();
+    assert_eq!(
+        uspace.read(UserConstPtr::<[u8; 8]>::from(mapped.addr(off))),
+        Ok(*b"abcdefg\0")
+    );
+    let mut buf = [0; 16];
+    assert_eq!(
+        uspace.read_cstr_into(UserConstPtr::<u8>::from(mapped.addr(off)), &mut buf),
+        Ok(7)
+    );
+}
+
+#[test]
+fn supervisor_pages_fault() {
+    let kernel = MappingFlags::READ | MappingFlags::WRITE;
+    let mapped = Mapped::new(&[Some(USER_RW), Some(kernel)]);
+    let uspace = PageTableUspace::new(&mapped.table);
+    assert_eq!(
+        uspace.read(UserConstPtr::<u64>::from(mapped.addr(PAGE_SIZE))),
+        Err(Error::EFAULT)
+    );
+    let mut buf = [0; 16];
+    assert_eq!(
+        uspace.read_slice_to(
+            UserConstPtr::<u8>::from(mapped.addr(PAGE_SIZE - 8)),
+            &mut buf
+        ),
+        Err(Error::EFAULT)
+    );
+}
+
+#[test]
+fn read_only_pages_reject_writes() {
+    let read_only = MappingFlags::READ | MappingFlags::USER;
+    let mapped = Mapped::new(&[Some(read_only)]);
+    let uspace = PageTableUspace::new(&mapped.table);
+    let addr = mapped.addr(0);
+    assert_eq!(uspace.read(UserConstPtr::<u32>::from(addr)), Ok(0));
+    assert_eq!(
+        uspace.write(UserPtr::<u32>::from(addr), 1),
+        Err(Error::EFAULT)
+    );
+}
+
+#[test]
+fn missing_pages_go_to_the_fault_handler() {
+    let mapped = Mapped::new(&[Some(USER_RW), None]);
+    let missing = mapped.addr(PAGE_SIZE);
+    let ptr = UserConstPtr::<u64>::from(missing);
+    assert_eq!(
+        PageTableUspace::new(&mapped.table).read(ptr),
+        Err(Error::EFAULT)
+    );
+
+    let faults = Cell::new(Vec::new());
+    let handler = |addr: VirtAddr, flags: Access| {
+        let mut seen = faults.take();
+        seen.push((addr.as_usize(), flags));
+        faults.set(seen);
+        Err(Error::ENOMEM)
+    };
+    let uspace = PageTableUspace::new(&mapped.table).with_fault_handler(&handler);
+    assert_eq!(uspace.read(ptr), Err(Error::ENOMEM));
+    assert_eq!(faults.take(), [(missing, Access::READ | Access::USER)]);
+
+    // A handler that does not map the page still faults
+    let uspace = PageTableUspace::new(&mapped.table).with_fault_handler(&|_, _| Ok(()));
+    assert_eq!(uspace.read(ptr), Err(Error::EFAULT));
+}
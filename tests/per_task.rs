@@ -0,0 +1,45 @@
+//! The per-task access state backend, which must follow the running task
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axuspace::{
+    CurrentTaskHooks, TaskAccessState, access_user_memory, is_accessing_user_memory,
+    set_current_task_hooks,
+};
+
+static TASKS: [TaskAccessState; 2] = [TaskAccessState::new(), TaskAccessState::new()];
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+/// A scheduler running one of [`TASKS`] at a time
+struct Scheduler;
+
+impl CurrentTaskHooks for Scheduler {
+    fn current_access_state(&self) -> &TaskAccessState {
+        &TASKS[CURRENT.load(Ordering::Relaxed)]
+    }
+}
+
+fn switch_to(task: usize) {
+    CURRENT.store(task, Ordering::Relaxed);
+}
+
+#[test]
+fn flag_follows_the_running_task() {
+    assert!(set_current_task_hooks(&Scheduler));
+    assert!(!set_current_task_hooks(&Scheduler));
+
+    access_user_memory(|| {
+        assert!(is_accessing_user_memory());
+        // Task 0 sleeps inside its window, task 1 runs without the flag
+        switch_to(1);
+        assert!(!is_accessing_user_memory());
+        access_user_memory(|| assert!(is_accessing_user_memory()));
+        assert!(!is_accessing_user_memory());
+        // Task 0 resumes, possibly elsewhere, and still has it
+        switch_to(0);
+        assert!(is_accessing_user_memory());
+    });
+    assert!(!is_accessing_user_memory());
+    switch_to(1);
+    assert!(!is_accessing_user_memory());
+}